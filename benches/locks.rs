@@ -0,0 +1,249 @@
+//! Throughput and tail latency of the locks under varying thread counts and critical section lengths.
+//!
+//! Run with `cargo bench --bench locks`, or e.g. `cargo bench --bench locks -- 'throughput/bakery'` for one lock.
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use rads::sync::cohort::{CohortLock, CohortN};
+use rads::sync::fast_lock::{FastLock, FastLockN};
+use rads::sync::filter::{FilterLock, FilterLockN};
+use rads::sync::lamports_bakery::{Bakery, BakeryN};
+use rads::sync::peterson::Peterson;
+use rads::sync::priority::{PriorityLock, PriorityN};
+use rads::sync::semaphore::StrongSemaphore;
+use rads::sync::tas::TasLock;
+use rads::sync::tournament::{NAry, NAryN};
+use rads::sync::{Mutex, NoStarveMutex};
+use std::hint::black_box;
+use std::sync::{Arc, Barrier};
+use std::time::{Duration, Instant};
+
+const THREADS: [usize; 3] = [2, 4, 8];
+/// Spins inside the critical section, from an empty one to one long enough for waiters to pile up
+const CS_LENS: [usize; 2] = [0, 200];
+
+/// Locks one thread's handle around the critical section it is given
+type Locker = Box<dyn FnMut(&mut dyn FnMut()) + Send>;
+
+/// Handle for each of `threads`, or None if the lock does not support as many
+type Lockers = fn(usize) -> Option<Vec<Locker>>;
+
+const LOCKS: [(&str, Lockers); 10] = [
+    ("peterson", |threads| {
+        (threads == 2).then(|| {
+            let (mut a, mut b) = Peterson::binary_mutex();
+            vec![
+                Box::new(move |cs: &mut dyn FnMut()| {
+                    let _guard = a.lock();
+                    cs()
+                }) as Locker,
+                Box::new(move |cs: &mut dyn FnMut()| {
+                    let _guard = b.lock();
+                    cs()
+                }),
+            ]
+        })
+    }),
+    ("bakery", |threads| {
+        let mu = Arc::new(Bakery::new(threads));
+        n_ary(
+            threads,
+            |n| BakeryN::new(n, &mu),
+            |mut h| {
+                Box::new(move |cs| {
+                    let _guard = h.lock();
+                    cs()
+                })
+            },
+        )
+    }),
+    ("filter", |threads| {
+        let mu = Arc::new(FilterLock::new(threads));
+        n_ary(
+            threads,
+            |n| FilterLockN::new(n, &mu),
+            |mut h| {
+                Box::new(move |cs| {
+                    let _guard = h.lock();
+                    cs()
+                })
+            },
+        )
+    }),
+    ("tournament", |threads| {
+        let mu = Arc::new(NAry::<Peterson>::new(threads));
+        n_ary(
+            threads,
+            |n| NAryN::new(n, &mu),
+            |mut h| {
+                Box::new(move |cs| {
+                    let _guard = h.lock();
+                    cs()
+                })
+            },
+        )
+    }),
+    ("cohort", |threads| {
+        let mu = Arc::new(CohortLock::new(2, 4));
+        n_ary(
+            threads,
+            |n| CohortN::new(n % 2, &mu),
+            |mut h| {
+                Box::new(move |cs| {
+                    let _guard = h.lock();
+                    cs()
+                })
+            },
+        )
+    }),
+    ("priority", |threads| {
+        let mu = Arc::new(PriorityLock::new(threads, 4));
+        n_ary(
+            threads,
+            |n| PriorityN::new(n, n % 2, &mu),
+            |mut h| {
+                Box::new(move |cs| {
+                    let _guard = h.lock();
+                    cs()
+                })
+            },
+        )
+    }),
+    ("ticket", |threads| {
+        let sem = Arc::new(StrongSemaphore::new(1));
+        n_ary(
+            threads,
+            |_| sem.clone(),
+            |sem| {
+                Box::new(move |cs| {
+                    let _permit = sem.acquire();
+                    cs()
+                })
+            },
+        )
+    }),
+    ("fast_lock", |threads| {
+        let mu = Arc::new(FastLock::new(threads));
+        n_ary(
+            threads,
+            |n| FastLockN::new(n, &mu),
+            |mut h| {
+                Box::new(move |cs| {
+                    let _guard = h.lock();
+                    cs()
+                })
+            },
+        )
+    }),
+    ("tas", |threads| {
+        let mu = TasLock::new();
+        n_ary(
+            threads,
+            |_| mu.clone(),
+            |mut h| {
+                Box::new(move |cs| {
+                    let _guard = h.lock();
+                    cs()
+                })
+            },
+        )
+    }),
+    ("std", |threads| {
+        let mu = Arc::new(std::sync::Mutex::new(()));
+        n_ary(
+            threads,
+            |_| mu.clone(),
+            |mu| {
+                Box::new(move |cs| {
+                    let _guard = mu.lock().unwrap();
+                    cs()
+                })
+            },
+        )
+    }),
+];
+
+fn n_ary<H>(
+    threads: usize,
+    handle: impl Fn(usize) -> H,
+    locker: impl Fn(H) -> Locker,
+) -> Option<Vec<Locker>> {
+    Some((0..threads).map(|n| locker(handle(n))).collect())
+}
+
+/// Runs `iters` critical sections of `cs_len` on each thread, and returns how long each waited for the lock, starting
+/// the clock once all threads are ready
+fn run(lockers: Vec<Locker>, iters: u64, cs_len: usize) -> (Duration, Vec<Duration>) {
+    let barrier = Arc::new(Barrier::new(lockers.len() + 1));
+    let ths: Vec<_> = lockers
+        .into_iter()
+        .map(|mut locker| {
+            let barrier = barrier.clone();
+            std::thread::spawn(move || {
+                barrier.wait();
+                let mut waits = Vec::with_capacity(iters as usize);
+                for _ in 0..iters {
+                    let start = Instant::now();
+                    locker(&mut || {
+                        waits.push(start.elapsed());
+                        for i in 0..cs_len {
+                            black_box(i);
+                        }
+                    });
+                }
+                waits
+            })
+        })
+        .collect();
+    barrier.wait();
+    let start = Instant::now();
+    let waits = ths.into_iter().flat_map(|th| th.join().unwrap()).collect();
+    (start.elapsed(), waits)
+}
+
+/// Lock acquisitions per second across all threads
+fn throughput(c: &mut Criterion) {
+    for cs_len in CS_LENS {
+        let mut group = c.benchmark_group(format!("throughput/cs_len={cs_len}"));
+        for threads in THREADS {
+            group.throughput(Throughput::Elements(threads as u64));
+            for (name, lockers) in LOCKS {
+                if lockers(threads).is_none() {
+                    continue;
+                }
+                group.bench_function(BenchmarkId::new(name, threads), |b| {
+                    b.iter_custom(|iters| run(lockers(threads).unwrap(), iters, cs_len).0)
+                });
+            }
+        }
+        group.finish();
+    }
+}
+
+/// 99th percentile of how long a thread waits for the lock, reported as the time per iteration
+fn tail_latency(c: &mut Criterion) {
+    for cs_len in CS_LENS {
+        let mut group = c.benchmark_group(format!("p99_wait/cs_len={cs_len}"));
+        for threads in THREADS {
+            for (name, lockers) in LOCKS {
+                if lockers(threads).is_none() {
+                    continue;
+                }
+                group.bench_function(BenchmarkId::new(name, threads), |b| {
+                    b.iter_custom(|iters| {
+                        let (_, mut waits) = run(lockers(threads).unwrap(), iters, cs_len);
+                        waits.sort_unstable();
+                        // Scaled, since criterion divides by the number of iterations
+                        waits[waits.len() * 99 / 100].mul_f64(iters as f64)
+                    })
+                });
+            }
+        }
+        group.finish();
+    }
+}
+
+criterion_group! {
+    name = benches;
+    config = Criterion::default().measurement_time(Duration::from_secs(2)).sample_size(20);
+    targets = throughput, tail_latency
+}
+criterion_main!(benches);
@@ -0,0 +1,201 @@
+use crate::dist_mutex::{check_sender, DistMutex, MutexMsg};
+use crate::order::lamport_clock::{LamportClock, TotalLamport};
+use crate::order::transport::Transport;
+use crate::order::{LogicalClock, Message, OrderError};
+use alloc::collections::BTreeSet;
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// Distributed mutual exclusion by a request queue that every process replicates, ordered by Lamport timestamp and
+/// then pid (Lamport).
+///
+/// A process broadcasts its timestamped request, which every process queues and replies to. It enters once its own
+/// request is first in its queue and it received a later message from every other process, since FIFO channels then
+/// rule out any earlier request in flight. Releasing broadcasts the removal of the request. Each critical section
+/// thus takes `3(n - 1)` messages, and processes enter in timestamp order, so every request is served eventually.
+///
+/// Requires FIFO channels, e.g. `MpscTransport`, or a `FifoChannel` over a transport that may reorder.
+///
+/// # Examples
+/// ```
+/// use rads::dist_mutex::lamport::LamportMutex;
+/// use rads::dist_mutex::DistMutex;
+/// use rads::order::transport::MpscTransport;
+///
+/// let mut ps: Vec<_> = MpscTransport::mesh(2).into_iter().map(LamportMutex::new).collect();
+/// ps[0].want_lock().unwrap();
+/// assert!(!ps[0].is_ready()); // until p1 replies
+/// ps[1].serve().unwrap();
+/// ps[0].serve().unwrap();
+/// assert!(ps[0].is_ready());
+/// ps[1].want_lock().unwrap();
+/// ps[0].serve().unwrap();
+/// ps[0].release().unwrap();
+/// while !ps[1].is_ready() {
+///     ps[1].serve().unwrap();
+/// }
+/// ```
+pub struct LamportMutex<T> {
+    transport: T,
+    clock: LamportClock,
+    /// Sequence number of the next message to each process
+    next_seq: Vec<u64>,
+    queue: BTreeSet<TotalLamport>,
+    /// Lamport timestamp of the latest message from each process
+    latest: Vec<usize>,
+    /// Own request, until released
+    request: Option<TotalLamport>,
+}
+
+impl<T> LamportMutex<T>
+where
+    T: Transport<Message<LamportClock, MutexMsg>>,
+{
+    pub fn new(transport: T) -> Self {
+        let (i, n_procs) = (transport.pid(), transport.n_procs());
+        Self {
+            transport,
+            clock: LamportClock::new(i, n_procs),
+            next_seq: vec![0; n_procs],
+            queue: BTreeSet::new(),
+            latest: vec![0; n_procs],
+            request: None,
+        }
+    }
+    pub fn clock(&self) -> &LamportClock {
+        &self.clock
+    }
+    /// Requests of every process queued here, in the order they enter
+    pub fn queue(&self) -> impl Iterator<Item = &TotalLamport> {
+        self.queue.iter()
+    }
+    pub fn into_inner(self) -> T {
+        self.transport
+    }
+
+    fn send_to(&mut self, pid: usize, msg: MutexMsg) -> Result<(), OrderError> {
+        let m = Message::new(self.pid(), self.next_seq[pid], self.clock, msg);
+        self.transport.send_to(pid, m)?;
+        self.next_seq[pid] += 1;
+        Ok(())
+    }
+    fn send_all(&mut self, msg: MutexMsg) -> Result<(), OrderError> {
+        let i = self.pid();
+        for pid in (0..self.next_seq.len()).filter(|&j| j != i) {
+            self.send_to(pid, msg.clone())?;
+        }
+        Ok(())
+    }
+
+    fn on_recv(
+        &mut self,
+        from: usize,
+        m: Message<LamportClock, MutexMsg>,
+    ) -> Result<(), OrderError> {
+        check_sender(from, m.from, self.next_seq.len())?;
+        self.clock = self.clock.merge(&m.clock);
+        self.latest[from] = m.clock.get();
+        match m.payload {
+            MutexMsg::Request(id) => {
+                self.queue.insert(id);
+                self.send_to(from, MutexMsg::Reply)?;
+            }
+            MutexMsg::Reply => {}
+            MutexMsg::Release(id) => {
+                self.queue.remove(&id);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<T> DistMutex for LamportMutex<T>
+where
+    T: Transport<Message<LamportClock, MutexMsg>>,
+{
+    fn pid(&self) -> usize {
+        self.transport.pid()
+    }
+    fn want_lock(&mut self) -> Result<(), OrderError> {
+        if self.request.is_some() {
+            return Err(OrderError::Other(anyhow::anyhow!(
+                "Expect to release before wanting the lock again"
+            )));
+        }
+        self.clock = self.clock.extend();
+        let id = self.clock.total();
+        self.queue.insert(id);
+        self.request = Some(id);
+        self.send_all(MutexMsg::Request(id))
+    }
+    fn is_ready(&self) -> bool {
+        let i = self.pid();
+        self.request.is_some_and(|id| {
+            self.queue.first() == Some(&id)
+                && (self.latest.iter().enumerate())
+                    .all(|(j, &clk)| j == i || TotalLamport::new(clk, j) > id)
+        })
+    }
+    fn release(&mut self) -> Result<(), OrderError> {
+        if let Some(id) = self.request.take() {
+            self.queue.remove(&id);
+            self.clock = self.clock.extend();
+            self.send_all(MutexMsg::Release(id))?;
+        }
+        Ok(())
+    }
+    fn serve(&mut self) -> Result<(), OrderError> {
+        let (from, m) = self.transport.recv()?;
+        self.on_recv(from, m)
+    }
+    fn try_serve(&mut self) -> Result<bool, OrderError> {
+        match self.transport.try_recv()? {
+            Some((from, m)) => self.on_recv(from, m).map(|_| true),
+            None => Ok(false),
+        }
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "std")]
+mod tests {
+    use crate::dist_mutex::lamport::LamportMutex;
+    use crate::dist_mutex::DistMutex;
+    use crate::order::transport::MpscTransport;
+
+    #[test]
+    fn mutual_exclusion() {
+        let ps = MpscTransport::mesh(4)
+            .into_iter()
+            .map(LamportMutex::new)
+            .collect();
+        crate::dist_mutex::tests::mutual_exclusion(ps, 20);
+    }
+
+    #[test]
+    fn timestamp_order() {
+        let mut ps: Vec<_> = MpscTransport::mesh(3)
+            .into_iter()
+            .map(LamportMutex::new)
+            .collect();
+        ps[2].want_lock().unwrap();
+        ps[0].serve().unwrap();
+        // Later than p2's request, which p0 saw
+        ps[0].want_lock().unwrap();
+        ps[1].serve().unwrap();
+        ps[1].serve().unwrap();
+        for _ in 0..3 {
+            ps[2].serve().unwrap();
+        }
+        assert!(ps[2].is_ready());
+        for _ in 0..2 {
+            ps[0].serve().unwrap();
+        }
+        // Replied to by all, but queued behind p2
+        assert!(!ps[0].is_ready());
+        ps[2].release().unwrap();
+        ps[0].serve().unwrap();
+        assert!(ps[0].is_ready());
+        assert!(ps[0].want_lock().is_err());
+    }
+}
@@ -0,0 +1,127 @@
+pub mod lamport;
+pub mod naimi_trehel;
+pub mod quorum;
+pub mod ricart_agrawala;
+pub mod token_ring;
+
+use crate::order::lamport_clock::TotalLamport;
+use crate::order::OrderError;
+
+/// Payload of a message between the permission-based `DistMutex`es, whose requests are ordered by Lamport timestamp.
+#[derive(Clone, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum MutexMsg {
+    /// Asks for the critical section, with the request's id
+    Request(TotalLamport),
+    /// Acknowledges or grants a request
+    Reply,
+    /// Withdraws the request of this id
+    Release(TotalLamport),
+}
+
+/// Mutual exclusion between processes that only share messages over a `Transport`, like `NoStarveMutex` between
+/// threads that share memory.
+///
+/// A process must keep handling messages even while it doesn't want the critical section, e.g. by `try_serve()` in its
+/// event loop, since other processes wait for its replies.
+pub trait DistMutex: Sized {
+    fn pid(&self) -> usize;
+
+    // Convenience function to request the critical section and handle messages until it may enter.
+    // &mut guarantees no double acquire within the same scope, at compile time
+    fn lock(&mut self) -> Result<DistGuard<'_, Self>, OrderError> {
+        self.want_lock()?;
+        while !self.is_ready() {
+            self.serve()?;
+        }
+        Ok(DistGuard(Some(self)))
+    }
+
+    // Requests the critical section. You can only be sure you may enter once `is_ready()`.
+    fn want_lock(&mut self) -> Result<(), OrderError>;
+
+    // Whether this process may enter the critical section, after `want_lock()`
+    fn is_ready(&self) -> bool;
+
+    // Leaves the critical section, letting the next process enter
+    fn release(&mut self) -> Result<(), OrderError>;
+
+    // Blocks until a message arrives, and handles it
+    fn serve(&mut self) -> Result<(), OrderError>;
+
+    // Like `serve()`, but returns false instead of blocking if no message arrived
+    fn try_serve(&mut self) -> Result<bool, OrderError>;
+}
+
+/// Critical section of a `DistMutex`, which releases when it drops, or by `unlock()` to handle errors of the transport.
+pub struct DistGuard<'a, M: DistMutex>(Option<&'a mut M>);
+
+impl<M: DistMutex> DistGuard<'_, M> {
+    pub fn unlock(mut self) -> Result<(), OrderError> {
+        self.0.take().unwrap().release()
+    }
+}
+
+impl<M: DistMutex> Drop for DistGuard<'_, M> {
+    fn drop(&mut self) {
+        if let Some(mu) = self.0.take() {
+            // Use `unlock()` to handle the error
+            let _ = mu.release();
+        }
+    }
+}
+
+/// Checks that a message of process `m_from` came from process `from` < `n_procs`
+fn check_sender(from: usize, m_from: usize, n_procs: usize) -> Result<(), OrderError> {
+    if from >= n_procs || m_from != from {
+        return Err(OrderError::Other(anyhow::anyhow!(
+            "Expect message of process {m_from} from process {from} < n_procs={n_procs}"
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+#[cfg(feature = "std")]
+mod tests {
+    use crate::dist_mutex::DistMutex;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Barrier};
+
+    /// Runs `n_locks` critical sections on each process, checking that no two overlap, and keeps serving the others
+    /// until all are done
+    pub(super) fn mutual_exclusion<M: DistMutex + Send + 'static>(ps: Vec<M>, n_locks: usize) {
+        let n_procs = ps.len();
+        let (inside, entered) = (Arc::new(AtomicUsize::new(0)), Arc::new(AtomicUsize::new(0)));
+        let (done, barrier) = (
+            Arc::new(AtomicUsize::new(0)),
+            Arc::new(Barrier::new(n_procs)),
+        );
+        let ths: Vec<_> = ps
+            .into_iter()
+            .map(|mut p| {
+                let (inside, entered) = (inside.clone(), entered.clone());
+                let (done, barrier) = (done.clone(), barrier.clone());
+                std::thread::spawn(move || {
+                    for _ in 0..n_locks {
+                        let guard = p.lock().unwrap();
+                        assert_eq!(inside.fetch_add(1, Ordering::SeqCst), 0);
+                        entered.fetch_add(1, Ordering::SeqCst);
+                        std::thread::yield_now();
+                        inside.fetch_sub(1, Ordering::SeqCst);
+                        guard.unlock().unwrap();
+                    }
+                    done.fetch_add(1, Ordering::SeqCst);
+                    while done.load(Ordering::SeqCst) < n_procs {
+                        if !p.try_serve().unwrap() {
+                            std::thread::yield_now();
+                        }
+                    }
+                    barrier.wait(); // before hanging up
+                })
+            })
+            .collect();
+        ths.into_iter().for_each(|th| th.join().unwrap());
+        assert_eq!(entered.load(Ordering::SeqCst), n_procs * n_locks);
+    }
+}
@@ -0,0 +1,227 @@
+use crate::dist_mutex::{check_sender, DistMutex};
+use crate::order::lamport_clock::LamportClock;
+use crate::order::transport::Transport;
+use crate::order::{LogicalClock, Message, OrderError};
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// Payload of a message between `NaimiTrehel` processes.
+#[derive(Clone, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum NaimiTrehelMsg {
+    /// Asks for the token on behalf of the process of this pid, forwarded along `last` pointers
+    Request(usize),
+    /// Privilege to enter the critical section
+    Token,
+}
+
+/// Distributed mutual exclusion by a token that processes request along a tree of probable owners, which each request
+/// reshapes (Naimi-Trehel).
+///
+/// Every process points by `last` to the process it thinks requested the token last, and the root of that tree, which
+/// points nowhere, is the last requester. A request travels along `last` pointers to the root, and every process it
+/// passes points to the requester from then on, which becomes the new root. The root hands over the token if idle, or
+/// else by `next` once it leaves, so that the `next` pointers queue the waiting processes. Each critical section takes
+/// `O(log n)` messages on average, and at most `n`.
+///
+/// # Examples
+/// ```
+/// use rads::dist_mutex::naimi_trehel::NaimiTrehel;
+/// use rads::dist_mutex::DistMutex;
+/// use rads::order::transport::MpscTransport;
+///
+/// let mut ps: Vec<_> = MpscTransport::mesh(2).into_iter().map(NaimiTrehel::new).collect();
+/// assert_eq!(ps[1].last(), Some(0));
+/// ps[1].want_lock().unwrap();
+/// ps[0].serve().unwrap(); // hands over the idle token
+/// ps[1].serve().unwrap();
+/// assert!(ps[1].is_ready());
+/// assert_eq!(ps[0].last(), Some(1));
+/// assert_eq!(ps[1].last(), None);
+/// ```
+pub struct NaimiTrehel<T> {
+    transport: T,
+    clock: LamportClock,
+    /// Sequence number of the next message to each process
+    next_seq: Vec<u64>,
+    /// Probable owner of the token, or none if this process requested it last
+    last: Option<usize>,
+    /// Process to hand over the token to once this one leaves
+    next: Option<usize>,
+    has_token: bool,
+    wants: bool,
+}
+
+impl<T> NaimiTrehel<T>
+where
+    T: Transport<Message<LamportClock, NaimiTrehelMsg>>,
+{
+    pub fn new(transport: T) -> Self {
+        let (i, n_procs) = (transport.pid(), transport.n_procs());
+        Self {
+            transport,
+            clock: LamportClock::new(i, n_procs),
+            next_seq: vec![0; n_procs],
+            // Process 0 starts as the root with the token
+            last: (i != 0).then_some(0),
+            next: None,
+            has_token: i == 0,
+            wants: false,
+        }
+    }
+    pub fn clock(&self) -> &LamportClock {
+        &self.clock
+    }
+    pub fn last(&self) -> Option<usize> {
+        self.last
+    }
+    pub fn next(&self) -> Option<usize> {
+        self.next
+    }
+    pub fn has_token(&self) -> bool {
+        self.has_token
+    }
+    pub fn into_inner(self) -> T {
+        self.transport
+    }
+
+    fn send_to(&mut self, pid: usize, msg: NaimiTrehelMsg) -> Result<(), OrderError> {
+        self.clock = self.clock.extend();
+        let m = Message::new(self.pid(), self.next_seq[pid], self.clock, msg);
+        self.transport.send_to(pid, m)?;
+        self.next_seq[pid] += 1;
+        Ok(())
+    }
+
+    fn on_recv(
+        &mut self,
+        from: usize,
+        m: Message<LamportClock, NaimiTrehelMsg>,
+    ) -> Result<(), OrderError> {
+        check_sender(from, m.from, self.next_seq.len())?;
+        self.clock = self.clock.merge(&m.clock);
+        match m.payload {
+            NaimiTrehelMsg::Request(j) => {
+                match self.last {
+                    Some(last) => self.send_to(last, NaimiTrehelMsg::Request(j))?,
+                    // Queues j behind this process
+                    None if self.wants => self.next = Some(j),
+                    None => {
+                        self.send_to(j, NaimiTrehelMsg::Token)?;
+                        self.has_token = false;
+                    }
+                }
+                self.last = Some(j);
+            }
+            NaimiTrehelMsg::Token => self.has_token = true,
+        }
+        Ok(())
+    }
+}
+
+impl<T> DistMutex for NaimiTrehel<T>
+where
+    T: Transport<Message<LamportClock, NaimiTrehelMsg>>,
+{
+    fn pid(&self) -> usize {
+        self.transport.pid()
+    }
+    fn want_lock(&mut self) -> Result<(), OrderError> {
+        if self.wants {
+            return Err(OrderError::Other(anyhow::anyhow!(
+                "Expect to release before wanting the lock again"
+            )));
+        }
+        if let Some(last) = self.last {
+            self.send_to(last, NaimiTrehelMsg::Request(self.pid()))?;
+        }
+        self.wants = true;
+        self.last = None;
+        Ok(())
+    }
+    fn is_ready(&self) -> bool {
+        self.wants && self.has_token
+    }
+    fn release(&mut self) -> Result<(), OrderError> {
+        self.wants = false;
+        // Keeps the token if the send failed, so that it isn't lost
+        if let Some(next) = self.next {
+            self.send_to(next, NaimiTrehelMsg::Token)?;
+            self.has_token = false;
+            self.next = None;
+        }
+        Ok(())
+    }
+    fn serve(&mut self) -> Result<(), OrderError> {
+        let (from, m) = self.transport.recv()?;
+        self.on_recv(from, m)
+    }
+    fn try_serve(&mut self) -> Result<bool, OrderError> {
+        match self.transport.try_recv()? {
+            Some((from, m)) => self.on_recv(from, m).map(|_| true),
+            None => Ok(false),
+        }
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "std")]
+mod tests {
+    use crate::dist_mutex::naimi_trehel::NaimiTrehel;
+    use crate::dist_mutex::DistMutex;
+    use crate::order::transport::MpscTransport;
+
+    #[test]
+    fn mutual_exclusion() {
+        let ps = MpscTransport::mesh(4)
+            .into_iter()
+            .map(NaimiTrehel::new)
+            .collect();
+        crate::dist_mutex::tests::mutual_exclusion(ps, 20);
+    }
+
+    #[test]
+    fn keeps_token_on_failed_send() {
+        let mut ps: Vec<_> = MpscTransport::mesh(2)
+            .into_iter()
+            .map(NaimiTrehel::new)
+            .collect();
+        ps[0].want_lock().unwrap();
+        ps[1].want_lock().unwrap();
+        ps[0].serve().unwrap();
+        assert_eq!(ps[0].next(), Some(1));
+        drop(ps.pop());
+        assert!(ps[0].release().is_err());
+        assert!(ps[0].has_token());
+        assert_eq!(ps[0].next(), Some(1));
+    }
+
+    #[test]
+    fn path_reversal() {
+        let mut ps: Vec<_> = MpscTransport::mesh(3)
+            .into_iter()
+            .map(NaimiTrehel::new)
+            .collect();
+        ps[1].want_lock().unwrap();
+        ps[0].serve().unwrap();
+        ps[2].want_lock().unwrap();
+        // Forwards to p1, which requested last
+        ps[0].serve().unwrap();
+        ps[1].serve().unwrap();
+        assert!(ps[1].is_ready());
+        ps[1].serve().unwrap();
+        assert_eq!(ps[1].next(), Some(2));
+        assert_eq!(
+            ps.iter().map(|p| p.last()).collect::<Vec<_>>(),
+            [Some(2), Some(2), None]
+        );
+        ps[1].release().unwrap();
+        assert!(!ps[1].has_token());
+        ps[2].serve().unwrap();
+        assert!(ps[2].is_ready());
+        ps[2].release().unwrap();
+        // Keeps the token while idle, so needs no messages to enter again
+        ps[2].want_lock().unwrap();
+        assert!(ps[2].is_ready());
+    }
+}
@@ -0,0 +1,187 @@
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// Set of quorums, every two of which intersect, so that a process that holds the permission of a whole quorum excludes
+/// any other, e.g. for Maekawa-style mutual exclusion or quorum replication.
+pub trait Coterie {
+    fn n_procs(&self) -> usize;
+
+    // Quorum of live processes that process `i` asks, or none if too many of them failed.
+    // `alive[j]` tells whether process j is up, for each of the `n_procs()` processes
+    fn quorum(&self, i: usize, alive: &[bool]) -> Option<Vec<usize>>;
+}
+
+fn check_alive(n_procs: usize, alive: &[bool]) {
+    assert_eq!(
+        alive.len(),
+        n_procs,
+        "Expect whether each of n_procs={n_procs} processes is alive"
+    );
+}
+
+/// Quorums of any majority of processes, which tolerate fewer than half of them failing, but take `n / 2 + 1`
+/// processes each.
+#[derive(Clone, Debug)]
+pub struct Majority {
+    n_procs: usize,
+}
+
+impl Majority {
+    pub fn new(n_procs: usize) -> Self {
+        assert!(n_procs > 0, "Expect at least 1 process");
+        Self { n_procs }
+    }
+}
+
+impl Coterie for Majority {
+    fn n_procs(&self) -> usize {
+        self.n_procs
+    }
+    fn quorum(&self, i: usize, alive: &[bool]) -> Option<Vec<usize>> {
+        check_alive(self.n_procs, alive);
+        // Starts at i, to spread the load
+        let n = self.n_procs;
+        let quorum: Vec<_> = (0..n)
+            .map(|j| (i + j) % n)
+            .filter(|&j| alive[j])
+            .take(n / 2 + 1)
+            .collect();
+        (quorum.len() > n / 2).then_some(quorum)
+    }
+}
+
+/// Quorums of root-to-leaf paths in a binary tree of the processes, in which the paths of both children replace a failed
+/// process (Agrawal-El Abbadi).
+///
+/// Process `k` is the parent of processes `2k + 1` and `2k + 2`. Without failures a quorum takes `log2(n) + 1`
+/// processes, and it grows by every failure along the path, up to every leaf if every inner process failed. Two quorums
+/// always intersect, since one includes the root or else both of its subtrees' quorums, and so on down the tree.
+///
+/// # Examples
+/// ```
+/// use rads::dist_mutex::quorum::{Coterie, TreeQuorum};
+///
+/// let tree = TreeQuorum::new(7);
+/// let mut alive = vec![true; 7];
+/// assert_eq!(tree.quorum(0, &alive), Some(vec![0, 1, 3]));
+/// alive[0] = false;
+/// assert_eq!(tree.quorum(0, &alive), Some(vec![1, 3, 2, 5]));
+/// alive[1] = false;
+/// alive[2] = false;
+/// assert_eq!(tree.quorum(0, &alive), Some(vec![3, 4, 5, 6]));
+/// alive[6] = false;
+/// assert_eq!(tree.quorum(0, &alive), None);
+/// ```
+#[derive(Clone, Debug)]
+pub struct TreeQuorum {
+    n_procs: usize,
+}
+
+impl TreeQuorum {
+    pub fn new(n_procs: usize) -> Self {
+        assert!(n_procs > 0, "Expect at least 1 process");
+        Self { n_procs }
+    }
+
+    /// Appends a quorum of the subtree of process `k` at `depth` to `quorum`, or returns false if there is none
+    fn subtree(
+        &self,
+        k: usize,
+        depth: u32,
+        i: usize,
+        alive: &[bool],
+        quorum: &mut Vec<usize>,
+    ) -> bool {
+        let children: Vec<_> = [2 * k + 1, 2 * k + 2]
+            .into_iter()
+            .filter(|&c| c < self.n_procs)
+            .collect();
+        let len = quorum.len();
+        if alive[k] {
+            quorum.push(k);
+            if children.is_empty() {
+                return true;
+            }
+            // Branches by the bits of i, to spread the load over the leaves
+            let first = (i >> depth) % children.len();
+            let found = (0..children.len())
+                .map(|c| children[(first + c) % children.len()])
+                .any(|c| self.subtree(c, depth + 1, i, alive, quorum));
+            if !found {
+                quorum.truncate(len);
+            }
+            found
+        } else {
+            let found = !children.is_empty()
+                && (children.iter()).all(|&c| self.subtree(c, depth + 1, i, alive, quorum));
+            if !found {
+                quorum.truncate(len);
+            }
+            found
+        }
+    }
+}
+
+impl Coterie for TreeQuorum {
+    fn n_procs(&self) -> usize {
+        self.n_procs
+    }
+    fn quorum(&self, i: usize, alive: &[bool]) -> Option<Vec<usize>> {
+        check_alive(self.n_procs, alive);
+        let mut quorum = vec![];
+        self.subtree(0, 0, i, alive, &mut quorum).then_some(quorum)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dist_mutex::quorum::{Coterie, Majority, TreeQuorum};
+    use alloc::vec;
+    use alloc::vec::Vec;
+
+    /// Checks that the quorums of every process intersect under every set of failures
+    fn intersect(coterie: &impl Coterie) {
+        let n = coterie.n_procs();
+        for failed in 0..1u32 << n {
+            let alive: Vec<_> = (0..n).map(|j| failed & (1 << j) == 0).collect();
+            let quorums: Vec<_> = (0..n).filter_map(|i| coterie.quorum(i, &alive)).collect();
+            for q in &quorums {
+                assert!(q.iter().all(|&j| alive[j]));
+                for r in &quorums {
+                    assert!(q.iter().any(|j| r.contains(j)), "{q:?} and {r:?}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn majority() {
+        intersect(&Majority::new(5));
+        assert_eq!(Majority::new(5).quorum(3, &[true; 5]), Some(vec![3, 4, 0]));
+        assert_eq!(
+            Majority::new(4).quorum(0, &[true, false, true, false]),
+            None
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Expect whether each of n_procs=5 processes is alive")]
+    fn too_few_alive() {
+        Majority::new(5).quorum(0, &[true; 3]);
+    }
+
+    #[test]
+    fn tree() {
+        for n in 1..=10 {
+            intersect(&TreeQuorum::new(n));
+        }
+        let tree = TreeQuorum::new(15);
+        let alive = [true; 15];
+        let leaves: Vec<_> = (0..8)
+            .map(|i| *tree.quorum(i, &alive).unwrap().last().unwrap())
+            .collect();
+        // Smaller than a majority, and spread over every leaf
+        assert!((0..8).all(|i| tree.quorum(i, &alive).unwrap().len() == 4));
+        assert!((7..15).all(|leaf| leaves.contains(&leaf)));
+    }
+}
@@ -0,0 +1,275 @@
+use crate::dist_mutex::{check_sender, DistMutex, MutexMsg};
+use crate::order::lamport_clock::{LamportClock, TotalLamport};
+use crate::order::transport::Transport;
+use crate::order::{LogicalClock, Message, OrderError};
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// Distributed mutual exclusion by asking every other process for permission, which it defers while it has priority
+/// (Ricart-Agrawala).
+///
+/// A process broadcasts its timestamped request, and enters once every other process replied. A process replies at
+/// once unless it is in the critical section or requested earlier, by Lamport timestamp and then pid, in which case it
+/// defers the reply until it leaves. Unlike `LamportMutex`, the deferred reply doubles as the release, so each critical
+/// section takes `2(n - 1)` messages, and no channel needs to be FIFO. `with_roucairol_carvalho()` takes even fewer
+/// if the same process enters repeatedly.
+///
+/// # Examples
+/// ```
+/// use rads::dist_mutex::ricart_agrawala::RicartAgrawala;
+/// use rads::dist_mutex::DistMutex;
+/// use rads::order::transport::MpscTransport;
+///
+/// let mut ps: Vec<_> = MpscTransport::mesh(2).into_iter().map(RicartAgrawala::new).collect();
+/// ps[0].want_lock().unwrap();
+/// ps[1].want_lock().unwrap();
+/// ps[0].serve().unwrap(); // defers p1, which requested later
+/// ps[1].serve().unwrap(); // replies to p0
+/// ps[0].serve().unwrap();
+/// assert!(ps[0].is_ready());
+/// assert!(!ps[1].is_ready());
+/// ps[0].release().unwrap(); // replies to p1
+/// ps[1].serve().unwrap();
+/// assert!(ps[1].is_ready());
+/// ```
+pub struct RicartAgrawala<T> {
+    transport: T,
+    clock: LamportClock,
+    /// Sequence number of the next message to each process
+    next_seq: Vec<u64>,
+    /// Own request, until released
+    request: Option<TotalLamport>,
+    /// Whether each process replied to the request, or still hasn't asked for its permission back
+    granted: Vec<bool>,
+    /// Whether each process waits for a reply until this process leaves the critical section
+    deferred: Vec<bool>,
+    /// Whether to keep permissions between requests
+    keep_granted: bool,
+}
+
+impl<T> RicartAgrawala<T>
+where
+    T: Transport<Message<LamportClock, MutexMsg>>,
+{
+    pub fn new(transport: T) -> Self {
+        let (i, n_procs) = (transport.pid(), transport.n_procs());
+        Self {
+            transport,
+            clock: LamportClock::new(i, n_procs),
+            next_seq: vec![0; n_procs],
+            request: None,
+            granted: vec![false; n_procs],
+            deferred: vec![false; n_procs],
+            keep_granted: false,
+        }
+    }
+    /// Keeps the permission of each process that replied until it requests (Roucairol-Carvalho), so that entering
+    /// again only asks the processes that requested since.
+    ///
+    /// A process that gives a permission back while requesting asks for it again, with the same request. Entering
+    /// repeatedly without contention then takes no messages, and at most `2(n - 1)` otherwise. Every process may
+    /// choose either, since the optimization only changes whom it asks.
+    pub fn with_roucairol_carvalho(mut self) -> Self {
+        self.keep_granted = true;
+        self
+    }
+    pub fn clock(&self) -> &LamportClock {
+        &self.clock
+    }
+    /// Number of processes waiting for this one to leave the critical section or get it first
+    pub fn n_deferred(&self) -> usize {
+        self.deferred.iter().filter(|&&d| d).count()
+    }
+    pub fn into_inner(self) -> T {
+        self.transport
+    }
+
+    fn send_to(&mut self, pid: usize, msg: MutexMsg) -> Result<(), OrderError> {
+        let m = Message::new(self.pid(), self.next_seq[pid], self.clock, msg);
+        self.transport.send_to(pid, m)?;
+        self.next_seq[pid] += 1;
+        Ok(())
+    }
+
+    fn on_recv(
+        &mut self,
+        from: usize,
+        m: Message<LamportClock, MutexMsg>,
+    ) -> Result<(), OrderError> {
+        check_sender(from, m.from, self.next_seq.len())?;
+        self.clock = self.clock.merge(&m.clock);
+        match m.payload {
+            MutexMsg::Request(id) => {
+                // In the critical section, or about to enter, or first in line
+                let defer = self.request.is_some_and(|own| self.is_ready() || own < id);
+                if defer {
+                    self.deferred[from] = true;
+                } else {
+                    self.send_to(from, MutexMsg::Reply)?;
+                    // Only ever kept between requests by Roucairol-Carvalho
+                    let was_granted = core::mem::replace(&mut self.granted[from], false);
+                    if let Some(own) = self.request.filter(|_| was_granted) {
+                        self.send_to(from, MutexMsg::Request(own))?;
+                    }
+                }
+            }
+            MutexMsg::Reply => self.granted[from] = true,
+            MutexMsg::Release(_) => {
+                return Err(OrderError::Other(anyhow::anyhow!(
+                    "Expect no release from process {from}, whose deferred reply releases instead"
+                )))
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<T> DistMutex for RicartAgrawala<T>
+where
+    T: Transport<Message<LamportClock, MutexMsg>>,
+{
+    fn pid(&self) -> usize {
+        self.transport.pid()
+    }
+    fn want_lock(&mut self) -> Result<(), OrderError> {
+        if self.request.is_some() {
+            return Err(OrderError::Other(anyhow::anyhow!(
+                "Expect to release before wanting the lock again"
+            )));
+        }
+        self.clock = self.clock.extend();
+        let id = self.clock.total();
+        self.request = Some(id);
+        if !self.keep_granted {
+            self.granted.fill(false);
+        }
+        let i = self.pid();
+        for pid in (0..self.granted.len()).filter(|&j| j != i) {
+            if !self.granted[pid] {
+                self.send_to(pid, MutexMsg::Request(id))?;
+            }
+        }
+        Ok(())
+    }
+    fn is_ready(&self) -> bool {
+        let i = self.pid();
+        self.request.is_some() && (self.granted.iter().enumerate()).all(|(j, &g)| j == i || g)
+    }
+    fn release(&mut self) -> Result<(), OrderError> {
+        if self.request.take().is_some() {
+            self.clock = self.clock.extend();
+            for pid in 0..self.deferred.len() {
+                if self.deferred[pid] {
+                    self.deferred[pid] = false;
+                    self.granted[pid] = false;
+                    self.send_to(pid, MutexMsg::Reply)?;
+                }
+            }
+        }
+        Ok(())
+    }
+    fn serve(&mut self) -> Result<(), OrderError> {
+        let (from, m) = self.transport.recv()?;
+        self.on_recv(from, m)
+    }
+    fn try_serve(&mut self) -> Result<bool, OrderError> {
+        match self.transport.try_recv()? {
+            Some((from, m)) => self.on_recv(from, m).map(|_| true),
+            None => Ok(false),
+        }
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "std")]
+mod tests {
+    use crate::dist_mutex::ricart_agrawala::RicartAgrawala;
+    use crate::dist_mutex::DistMutex;
+    use crate::order::transport::MpscTransport;
+
+    #[test]
+    fn mutual_exclusion() {
+        let ps = MpscTransport::mesh(4)
+            .into_iter()
+            .map(RicartAgrawala::new)
+            .collect();
+        crate::dist_mutex::tests::mutual_exclusion(ps, 20);
+    }
+
+    #[test]
+    fn roucairol_carvalho() {
+        let ps = MpscTransport::mesh(4)
+            .into_iter()
+            .map(|t| RicartAgrawala::new(t).with_roucairol_carvalho())
+            .collect();
+        crate::dist_mutex::tests::mutual_exclusion(ps, 20);
+
+        let mut ps: Vec<_> = MpscTransport::mesh(3)
+            .into_iter()
+            .map(|t| RicartAgrawala::new(t).with_roucairol_carvalho())
+            .collect();
+        ps[1].want_lock().unwrap();
+        ps[0].serve().unwrap();
+        ps[2].serve().unwrap();
+        for _ in 0..2 {
+            ps[1].serve().unwrap();
+        }
+        ps[1].release().unwrap();
+        // Still has every permission
+        ps[1].want_lock().unwrap();
+        assert!(ps[1].is_ready());
+        ps[1].release().unwrap();
+        assert!(!ps[0].try_serve().unwrap());
+
+        ps[2].want_lock().unwrap();
+        ps[1].serve().unwrap();
+        ps[0].serve().unwrap();
+        ps[0].want_lock().unwrap();
+        // Only asks p2, and is earlier than p1
+        ps[1].want_lock().unwrap();
+        assert!(!ps[1].is_ready());
+        // Gives p0's permission back, and asks again
+        ps[1].serve().unwrap();
+        for _ in 0..4 {
+            ps[2].serve().unwrap();
+        }
+        assert_eq!(ps[2].n_deferred(), 2);
+        ps[2].release().unwrap();
+        for _ in 0..3 {
+            ps[0].serve().unwrap();
+        }
+        assert!(ps[0].is_ready());
+        ps[1].serve().unwrap();
+        assert!(!ps[1].is_ready());
+        ps[0].release().unwrap();
+        ps[1].serve().unwrap();
+        assert!(ps[1].is_ready());
+    }
+
+    #[test]
+    fn defers_later_requests() {
+        let mut ps: Vec<_> = MpscTransport::mesh(3)
+            .into_iter()
+            .map(RicartAgrawala::new)
+            .collect();
+        ps[2].want_lock().unwrap();
+        ps[0].serve().unwrap();
+        // Later than p2's request, which p0 saw
+        ps[0].want_lock().unwrap();
+        ps[1].serve().unwrap();
+        ps[1].serve().unwrap();
+        for _ in 0..3 {
+            ps[2].serve().unwrap();
+        }
+        assert!(ps[2].is_ready());
+        assert_eq!(ps[2].n_deferred(), 1);
+        ps[0].serve().unwrap();
+        // Only p1 replied
+        assert!(!ps[0].is_ready());
+        ps[2].release().unwrap();
+        assert_eq!(ps[2].n_deferred(), 0);
+        ps[0].serve().unwrap();
+        assert!(ps[0].is_ready());
+        assert!(ps[0].want_lock().is_err());
+    }
+}
@@ -0,0 +1,255 @@
+use crate::dist_mutex::{check_sender, DistMutex};
+use crate::order::lamport_clock::LamportClock;
+use crate::order::transport::Transport;
+use crate::order::{LogicalClock, Message, OrderError};
+use alloc::boxed::Box;
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// Privilege to enter the critical section, of which one circulates around the ring.
+#[derive(Clone, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Token {
+    /// Times the token was regenerated, which supersedes every token of an earlier generation
+    pub generation: u64,
+    /// Process that regenerated the token, which breaks ties between concurrent regenerations
+    pub origin: usize,
+    /// Times the token passed on since it was generated
+    pub hops: u64,
+}
+
+impl Token {
+    fn id(&self) -> (u64, usize) {
+        (self.generation, self.origin)
+    }
+}
+
+type OnToken = Box<dyn FnMut(&Token) + Send>;
+
+/// Distributed mutual exclusion by a token that perpetually circulates around a ring of processes, the simplest and
+/// the baseline of token-based mutexes.
+///
+/// Process 0 starts with the token. A process that holds the token may enter, and passes it on to its successor once it
+/// leaves, or at once if it doesn't want to enter. Entering thus takes between 0 and `n` messages, and every process
+/// enters within one lap, but the token keeps circulating even while no process wants to enter.
+///
+/// If the transport may lose the token, detect it by `with_on_token()`, e.g. by a timeout longer than a lap since the
+/// token last passed, and `regenerate()` it. Tokens of an earlier generation are dropped wherever they turn up, but two
+/// processes may hold a token until then, so only regenerate once the token is surely lost.
+///
+/// # Examples
+/// ```
+/// use rads::dist_mutex::token_ring::TokenRing;
+/// use rads::dist_mutex::DistMutex;
+/// use rads::order::transport::MpscTransport;
+///
+/// let mut ps: Vec<_> = MpscTransport::mesh(3).into_iter().map(TokenRing::new).collect();
+/// ps[2].want_lock().unwrap();
+/// ps[0].try_serve().unwrap(); // passes on the token, which it doesn't want
+/// ps[1].serve().unwrap();
+/// assert!(!ps[2].is_ready());
+/// ps[2].serve().unwrap();
+/// assert!(ps[2].is_ready());
+/// ps[2].release().unwrap();
+/// assert!(!ps[2].holds_token());
+/// ```
+pub struct TokenRing<T> {
+    transport: T,
+    clock: LamportClock,
+    /// Sequence number of the next message to each process
+    next_seq: Vec<u64>,
+    token: Option<Token>,
+    wants: bool,
+    /// Generation and origin of the latest token seen
+    newest: (u64, usize),
+    /// Number of tokens dropped as superseded
+    n_stale: u64,
+    on_token: Option<OnToken>,
+}
+
+impl<T> TokenRing<T>
+where
+    T: Transport<Message<LamportClock, Token>>,
+{
+    pub fn new(transport: T) -> Self {
+        let (i, n_procs) = (transport.pid(), transport.n_procs());
+        Self {
+            transport,
+            clock: LamportClock::new(i, n_procs),
+            next_seq: vec![0; n_procs],
+            token: (i == 0).then_some(Token {
+                generation: 0,
+                origin: 0,
+                hops: 0,
+            }),
+            wants: false,
+            newest: (0, 0),
+            n_stale: 0,
+            on_token: None,
+        }
+    }
+    /// Calls `f` with every token that reaches this process, e.g. to reset a timeout that detects its loss
+    pub fn with_on_token<F: FnMut(&Token) + Send + 'static>(mut self, f: F) -> Self {
+        self.on_token = Some(Box::new(f));
+        self
+    }
+    pub fn clock(&self) -> &LamportClock {
+        &self.clock
+    }
+    pub fn holds_token(&self) -> bool {
+        self.token.is_some()
+    }
+    /// Number of tokens dropped since a later generation reached this process
+    pub fn n_stale(&self) -> u64 {
+        self.n_stale
+    }
+    /// Replaces a lost token by one of a later generation, which this process then holds
+    pub fn regenerate(&mut self) {
+        let i = self.pid();
+        self.newest = (self.newest.0 + 1, i);
+        self.token = Some(Token {
+            generation: self.newest.0,
+            origin: i,
+            hops: 0,
+        });
+    }
+    pub fn into_inner(self) -> T {
+        self.transport
+    }
+
+    /// Passes the token on to the successor, if held and not wanted
+    fn pass_idle(&mut self) -> Result<(), OrderError> {
+        let n_procs = self.next_seq.len();
+        if self.wants || n_procs == 1 {
+            return Ok(());
+        }
+        let Some(token) = self.token.as_ref() else {
+            return Ok(());
+        };
+        let token = Token {
+            hops: token.hops + 1,
+            ..token.clone()
+        };
+        let next = (self.pid() + 1) % n_procs;
+        self.clock = self.clock.extend();
+        let m = Message::new(self.pid(), self.next_seq[next], self.clock, token);
+        // Keeps the token if the send failed, so that it isn't lost
+        self.transport.send_to(next, m)?;
+        self.next_seq[next] += 1;
+        self.token = None;
+        Ok(())
+    }
+
+    fn on_recv(&mut self, from: usize, m: Message<LamportClock, Token>) -> Result<(), OrderError> {
+        check_sender(from, m.from, self.next_seq.len())?;
+        self.clock = self.clock.merge(&m.clock);
+        let token = m.payload;
+        // Or a duplicate of the one held
+        if token.id() < self.newest || (token.id() == self.newest && self.token.is_some()) {
+            self.n_stale += 1;
+            return Ok(());
+        }
+        self.newest = token.id();
+        if let Some(f) = self.on_token.as_mut() {
+            f(&token);
+        }
+        self.token = Some(token);
+        self.pass_idle()
+    }
+}
+
+impl<T> DistMutex for TokenRing<T>
+where
+    T: Transport<Message<LamportClock, Token>>,
+{
+    fn pid(&self) -> usize {
+        self.transport.pid()
+    }
+    fn want_lock(&mut self) -> Result<(), OrderError> {
+        if self.wants {
+            return Err(OrderError::Other(anyhow::anyhow!(
+                "Expect to release before wanting the lock again"
+            )));
+        }
+        self.wants = true;
+        Ok(())
+    }
+    fn is_ready(&self) -> bool {
+        self.wants && self.token.is_some()
+    }
+    fn release(&mut self) -> Result<(), OrderError> {
+        self.wants = false;
+        self.pass_idle()
+    }
+    fn serve(&mut self) -> Result<(), OrderError> {
+        self.pass_idle()?;
+        let (from, m) = self.transport.recv()?;
+        self.on_recv(from, m)
+    }
+    fn try_serve(&mut self) -> Result<bool, OrderError> {
+        self.pass_idle()?;
+        match self.transport.try_recv()? {
+            Some((from, m)) => self.on_recv(from, m).map(|_| true),
+            None => Ok(false),
+        }
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "std")]
+mod tests {
+    use crate::dist_mutex::token_ring::TokenRing;
+    use crate::dist_mutex::DistMutex;
+    use crate::order::transport::MpscTransport;
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::Arc;
+
+    #[test]
+    fn mutual_exclusion() {
+        let ps = MpscTransport::mesh(4)
+            .into_iter()
+            .map(TokenRing::new)
+            .collect();
+        crate::dist_mutex::tests::mutual_exclusion(ps, 20);
+    }
+
+    #[test]
+    fn keeps_token_on_failed_send() {
+        let mut ts = MpscTransport::mesh(2);
+        drop(ts.pop());
+        let mut p0 = TokenRing::new(ts.pop().unwrap());
+        assert!(p0.try_serve().is_err());
+        assert!(p0.holds_token());
+        p0.want_lock().unwrap();
+        assert!(p0.is_ready());
+    }
+
+    #[test]
+    fn regenerate() {
+        let seen = Arc::new(AtomicU64::new(0));
+        let mut ps: Vec<_> = MpscTransport::mesh(3)
+            .into_iter()
+            .map(|t| {
+                let seen = seen.clone();
+                TokenRing::new(t).with_on_token(move |token| {
+                    seen.store(token.generation, Ordering::SeqCst);
+                })
+            })
+            .collect();
+        // Suspects the token held by p0
+        ps[1].regenerate();
+        assert!(ps[1].holds_token());
+        ps[1].try_serve().unwrap();
+        ps[2].serve().unwrap();
+        assert_eq!(seen.load(Ordering::SeqCst), 1);
+        // Passes on the old one, then the new one
+        ps[0].serve().unwrap();
+        ps[1].want_lock().unwrap();
+        ps[1].serve().unwrap();
+        assert_eq!(ps[1].n_stale(), 1);
+        assert!(!ps[1].is_ready());
+        ps[1].serve().unwrap();
+        assert!(ps[1].is_ready());
+        assert_eq!(seen.load(Ordering::SeqCst), 1);
+    }
+}
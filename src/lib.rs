@@ -1,2 +1,14 @@
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+extern crate alloc;
+
+pub mod dist_mutex;
 pub mod order;
+pub mod sim;
+#[cfg(feature = "std")]
 pub mod sync;
+/// Only `Once` and `OnceCell` of `sync` remain without `std`
+#[cfg(not(feature = "std"))]
+pub mod sync {
+    pub mod once;
+    pub use once::{Once, OnceCell};
+}
@@ -0,0 +1,240 @@
+use crate::order::causal_delivery::CausalDelivery;
+use crate::order::transport::Transport;
+use crate::order::vector_clock::VectorClock;
+use crate::order::{Message, OrderError};
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// Payload of a broadcast, which is either the application's or a snapshot request.
+#[derive(Clone, Hash, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Payload<M> {
+    App(M),
+    Snapshot,
+}
+
+/// Local state of a process in a snapshot, along with the broadcasts in flight to it.
+#[derive(Clone, PartialEq, Eq)]
+#[cfg_attr(test, derive(Debug))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CausalSnapshot<M> {
+    pub pid: usize,
+    /// Clock of the snapshot request
+    pub request: VectorClock,
+    /// Number of broadcasts of each process delivered before the snapshot, including its own, i.e. sent by it
+    pub delivered: VectorClock,
+    /// Broadcasts sent before their sender's snapshot but delivered after this one, by sender
+    pub channels: Vec<Vec<Message<VectorClock, M>>>,
+}
+
+/// Whether every broadcast sent before the snapshot was delivered everywhere, given the local snapshots of all
+/// processes, so that their channel states are final.
+pub fn is_complete<M>(locals: &[CausalSnapshot<M>]) -> bool {
+    locals.iter().all(|l| {
+        locals.iter().filter(|s| s.pid != l.pid).all(|s| {
+            let seen = l.delivered.get(s.pid).unwrap_or_default() + l.channels[s.pid].len();
+            seen == s.delivered.get(s.pid).unwrap_or_default()
+        })
+    })
+}
+
+/// Snapshot over causal broadcast, without markers (Acharya-Badrinath).
+///
+/// The initiator broadcasts a snapshot request, and each process records how many broadcasts of each process it
+/// delivered when it delivers the request. Since delivery is causal, a broadcast sent after its sender's snapshot is
+/// delivered after the request everywhere, so the cut is consistent. A broadcast delivered after the request is in
+/// flight if its sender had yet to deliver the request, which its clock tells.
+///
+/// Only one snapshot is recorded at a time, so a new request replaces the previous snapshot.
+///
+/// # Examples
+/// ```
+/// use rads::order::acharya_badrinath::{is_complete, AcharyaBadrinath};
+/// use rads::order::transport::MpscTransport;
+///
+/// let mut ps: Vec<_> = MpscTransport::mesh(2).into_iter().map(AcharyaBadrinath::new).collect();
+/// ps[1].broadcast("before").unwrap();
+/// ps[0].request_snapshot().unwrap();
+/// // p0 records its state on delivering its own request, so p1's broadcast is in flight
+/// assert_eq!(ps[0].deliver().unwrap().payload, "before");
+/// assert_eq!(ps[1].deliver().unwrap().payload, "before");
+/// assert!(ps[1].try_deliver().unwrap().is_none());
+///
+/// let locals: Vec<_> = ps.iter().map(|p| p.snapshot().unwrap().clone()).collect();
+/// assert!(is_complete(&locals));
+/// assert_eq!(locals[0].channels[1][0].payload, "before");
+/// ```
+pub struct AcharyaBadrinath<T, M> {
+    inner: CausalDelivery<T, Payload<M>>,
+    /// Number of broadcasts of each process returned to the application, or recorded by a snapshot
+    delivered: VectorClock,
+    snapshot: Option<CausalSnapshot<M>>,
+}
+
+impl<T, M> AcharyaBadrinath<T, M>
+where
+    T: Transport<Message<VectorClock, Payload<M>>>,
+    M: Clone,
+{
+    pub fn new(transport: T) -> Self {
+        let (i, n_procs) = (transport.pid(), transport.n_procs());
+        Self {
+            inner: CausalDelivery::new(transport),
+            delivered: VectorClock::from_vec(i, vec![0; n_procs]),
+            snapshot: None,
+        }
+    }
+    pub fn pid(&self) -> usize {
+        self.inner.pid()
+    }
+    /// Number of broadcasts of each process delivered so far, including snapshot requests
+    pub fn delivered(&self) -> &VectorClock {
+        &self.delivered
+    }
+    /// Local snapshot of the latest request delivered, if any
+    pub fn snapshot(&self) -> Option<&CausalSnapshot<M>> {
+        self.snapshot.as_ref()
+    }
+    pub fn into_inner(self) -> T {
+        self.inner.into_inner()
+    }
+    /// Sends to every process, and delivers to self, returning the message's clock
+    pub fn broadcast(&mut self, payload: M) -> Result<VectorClock, OrderError> {
+        self.inner.broadcast(Payload::App(payload))
+    }
+    /// Broadcasts a snapshot request, which this process records once it delivers its own request
+    pub fn request_snapshot(&mut self) -> Result<VectorClock, OrderError> {
+        self.inner.broadcast(Payload::Snapshot)
+    }
+    /// Blocks until a message of the application can be delivered, recording any snapshot request before it
+    pub fn deliver(&mut self) -> Result<Message<VectorClock, M>, OrderError> {
+        loop {
+            let m = self.inner.deliver()?;
+            if let Some(m) = self.on_deliver(m) {
+                return Ok(m);
+            }
+        }
+    }
+    /// Like `deliver`, but returns None instead of blocking
+    pub fn try_deliver(&mut self) -> Result<Option<Message<VectorClock, M>>, OrderError> {
+        while let Some(m) = self.inner.try_deliver()? {
+            if let Some(m) = self.on_deliver(m) {
+                return Ok(Some(m));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Records a snapshot request, or the message's payload if it was in flight
+    fn on_deliver(
+        &mut self,
+        m: Message<VectorClock, Payload<M>>,
+    ) -> Option<Message<VectorClock, M>> {
+        let joined = self.delivered.join(&m.clock);
+        let prev = core::mem::replace(&mut self.delivered, joined);
+        let m = match m.payload {
+            Payload::App(payload) => Message::new(m.from, m.seq, m.clock, payload),
+            Payload::Snapshot => {
+                self.snapshot = Some(CausalSnapshot {
+                    pid: self.pid(),
+                    request: m.clock,
+                    delivered: prev,
+                    channels: vec![Vec::new(); self.delivered.len()],
+                });
+                return None;
+            }
+        };
+        if let Some(local) = self.snapshot.as_mut() {
+            let initiator = local.request.pid();
+            // The sender had yet to deliver the request when it sent the message
+            if m.clock.get(initiator) < local.request.get(initiator) {
+                local.channels[m.from].push(m.clone());
+            }
+        }
+        Some(m)
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "std")]
+mod tests {
+    use crate::order::acharya_badrinath::{is_complete, AcharyaBadrinath};
+    use crate::order::transport::MpscTransport;
+
+    #[test]
+    fn records_in_flight() {
+        let mut ps: Vec<_> = MpscTransport::mesh(3)
+            .into_iter()
+            .map(AcharyaBadrinath::new)
+            .collect();
+        ps[1].broadcast("a").unwrap();
+        ps[0].request_snapshot().unwrap();
+        assert_eq!(ps[1].deliver().unwrap().payload, "a");
+        ps[2].broadcast("b").unwrap();
+        assert_eq!(ps[2].deliver().unwrap().payload, "b");
+        assert_eq!(ps[2].deliver().unwrap().payload, "a");
+        assert!(ps[2].snapshot().is_none());
+
+        // p0 delivers its request first, then p1's broadcast, then p2's which it sent before its snapshot
+        assert_eq!(ps[0].deliver().unwrap().payload, "a");
+        assert_eq!(ps[0].deliver().unwrap().payload, "b");
+        assert_eq!(ps[1].deliver().unwrap().payload, "b");
+        assert_eq!(ps[1].try_deliver().unwrap(), None);
+        assert_eq!(ps[2].try_deliver().unwrap(), None);
+        let locals: Vec<_> = ps.iter().map(|p| p.snapshot().unwrap().clone()).collect();
+        let delivered: Vec<_> = locals.iter().map(|l| l.delivered.to_string()).collect();
+        assert_eq!(delivered, ["0:[0,0,0]", "1:[0,1,0]", "2:[0,1,1]"]);
+        assert_eq!(locals[0].channels.iter().map(Vec::len).sum::<usize>(), 2);
+        assert_eq!(locals[1].channels[2].len(), 1);
+        assert!(is_complete(&locals));
+
+        // After every snapshot, so not in flight
+        ps[2].broadcast("c").unwrap();
+        assert_eq!(ps[0].deliver().unwrap().payload, "c");
+        assert_eq!(ps[0].snapshot().unwrap(), &locals[0]);
+    }
+
+    #[test]
+    fn consistent_cut() {
+        const N_PROCS: usize = 3;
+        const N_MSGS: usize = 20;
+        let ths: Vec<_> = MpscTransport::mesh(N_PROCS)
+            .into_iter()
+            .map(|t| {
+                std::thread::spawn(move || {
+                    let mut p = AcharyaBadrinath::new(t);
+                    let mut n_delivered = 0;
+                    for k in 0..N_MSGS {
+                        if p.pid() == 0 && k == N_MSGS / 2 {
+                            p.request_snapshot().unwrap();
+                        }
+                        p.broadcast(k).unwrap();
+                        while p.try_deliver().unwrap().is_some() {
+                            n_delivered += 1;
+                        }
+                    }
+                    while n_delivered < N_PROCS * N_MSGS {
+                        p.deliver().unwrap();
+                        n_delivered += 1;
+                    }
+                    p.snapshot().unwrap().clone()
+                })
+            })
+            .collect();
+        let locals: Vec<_> = ths.into_iter().map(|th| th.join().unwrap()).collect();
+        assert!(is_complete(&locals));
+        for l in &locals {
+            for s in &locals {
+                let (seen, sent) = (
+                    l.delivered.get(s.pid).unwrap(),
+                    s.delivered.get(s.pid).unwrap(),
+                );
+                // Nothing delivered that was sent after its sender's snapshot
+                assert!(seen <= sent);
+                assert!(l.channels[s.pid]
+                    .iter()
+                    .all(|m| (seen..sent).contains(&(m.seq as usize))));
+            }
+        }
+    }
+}
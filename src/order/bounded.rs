@@ -0,0 +1,356 @@
+use crate::order::transport::Transport;
+use crate::order::{trace_event, GCClock, HasEvents, LogicalClock, OrdProcess, OrderError};
+use alloc::collections::VecDeque;
+
+/// What a full `BoundedEvents` does with a new event
+#[derive(Clone, Copy)]
+enum Policy<C> {
+    DropOldest,
+    /// Reclaims events seen by every process, by `GCClock::gc`, then rejects if none were
+    Gc(fn(&C, &C) -> bool),
+    Reject,
+}
+
+/// Event log of at most `capacity` events, so that long-running processes don't grow memory unboundedly.
+///
+/// It stores the events of a `BoundedProcess`, whereas `Process` keeps every event. Other process types can store
+/// theirs in one by implementing `HasEvents` with `push`, `last` and `as_slice`.
+///
+/// Events are kept in a ring buffer, which is rotated back into a contiguous slice when it wraps around.
+///
+/// # Examples
+/// ```
+/// use rads::order::bounded::BoundedEvents;
+/// use rads::order::lamport_clock::LamportClock;
+/// use rads::order::LogicalClock;
+///
+/// let mut log = BoundedEvents::drop_oldest(2);
+/// let mut e = LamportClock::new(0, 1);
+/// for _ in 0..3 {
+///     e = e.extend();
+///     log.push(e.clone()).unwrap();
+/// }
+/// assert_eq!((log.len(), log.n_evicted()), (2, 1));
+/// assert!(log.as_slice()[0] < log.as_slice()[1]);
+/// ```
+#[derive(Clone)]
+pub struct BoundedEvents<C> {
+    events: VecDeque<C>,
+    capacity: usize,
+    policy: Policy<C>,
+    n_evicted: usize,
+}
+
+impl<C: LogicalClock> BoundedEvents<C> {
+    fn new(capacity: usize, policy: Policy<C>) -> Self {
+        assert!(capacity > 0, "Expect capacity > 0");
+        Self {
+            // Twice the capacity, so that rotating the buffer is amortized over as many pushes
+            events: VecDeque::with_capacity(2 * capacity),
+            capacity,
+            policy,
+            n_evicted: 0,
+        }
+    }
+    /// Evicts the oldest event when full
+    pub fn drop_oldest(capacity: usize) -> Self {
+        Self::new(capacity, Policy::DropOldest)
+    }
+    /// Rejects new events when full
+    pub fn reject(capacity: usize) -> Self {
+        Self::new(capacity, Policy::Reject)
+    }
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+    pub fn len(&self) -> usize {
+        self.events.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+    pub fn is_full(&self) -> bool {
+        self.events.len() >= self.capacity
+    }
+    /// Number of events evicted so far
+    pub fn n_evicted(&self) -> usize {
+        self.n_evicted
+    }
+    pub fn last(&self) -> Option<&C> {
+        self.events.back()
+    }
+    /// Events from oldest to newest
+    pub fn as_slice(&self) -> &[C] {
+        let (front, back) = self.events.as_slices();
+        debug_assert!(back.is_empty());
+        front
+    }
+    /// Appends an event, making room by the eviction policy, or fails if it cannot
+    pub fn push(&mut self, e: C) -> Result<(), OrderError> {
+        self.check_room(&e)?;
+        if self.is_full() {
+            let n = self.n_evictable(&e);
+            self.evict(n);
+        }
+        self.events.push_back(e);
+        self.events.make_contiguous();
+        Ok(())
+    }
+
+    /// Fails unless `newest` can be pushed, without evicting anything
+    fn check_room(&self, newest: &C) -> Result<(), OrderError> {
+        if self.is_full() && self.n_evictable(newest) == 0 {
+            return Err(OrderError::Other(anyhow::anyhow!(
+                "Expect at most {} events, unless some can be evicted",
+                self.capacity
+            )));
+        }
+        Ok(())
+    }
+
+    fn n_evictable(&self, newest: &C) -> usize {
+        match self.policy {
+            Policy::DropOldest => self.events.len() + 1 - self.capacity,
+            Policy::Gc(gc) => self.events.iter().take_while(|c| gc(c, newest)).count(),
+            Policy::Reject => 0,
+        }
+    }
+
+    fn evict(&mut self, n: usize) {
+        self.events.drain(..n);
+        self.n_evicted += n;
+        #[cfg(feature = "tracing")]
+        tracing::debug!(target: "rads::order", evicted = n, "evict");
+    }
+}
+
+impl<C: GCClock> BoundedEvents<C> {
+    /// Reclaims events seen by every process when full, or rejects new events if none were
+    pub fn gc(capacity: usize) -> Self {
+        Self::new(capacity, Policy::Gc(C::gc))
+    }
+}
+
+/// Process that logs its events in a `BoundedEvents`.
+///
+/// `exec`, `send` and `recv` panic if an event cannot be logged, since recording an event cannot fail. The fallible
+/// methods, e.g. `try_send` or `send_via`, return the error instead, before sending.
+///
+/// # Examples
+/// ```
+/// use rads::order::bounded::{BoundedEvents, BoundedProcess};
+/// use rads::order::matrix_clock::MatrixClock;
+/// use rads::order::{HasEvents, OrdProcess};
+///
+/// let mut p0 = BoundedProcess::<MatrixClock>::new(0, 2, BoundedEvents::gc(2));
+/// let mut p1 = BoundedProcess::new(1, 2, BoundedEvents::gc(2));
+/// for _ in 0..10 {
+///     let mut msg = None;
+///     p0.send(|e| msg = Some(e));
+///     p1.recv(|| msg.take().unwrap());
+///     p1.send(|e| msg = Some(e));
+///     p0.recv(|| msg.take().unwrap());
+/// }
+/// assert!(p0.events().len() <= 2);
+/// assert!(p0.log().n_evicted() > 0);
+/// ```
+#[derive(Clone)]
+pub struct BoundedProcess<C> {
+    i: usize,
+    n_procs: usize,
+    log: BoundedEvents<C>,
+}
+
+impl<C: LogicalClock> BoundedProcess<C> {
+    pub fn new(i: usize, n_procs: usize, log: BoundedEvents<C>) -> Self {
+        assert!(
+            i < n_procs,
+            "Expect 0-based index of process {i} < n_procs={n_procs}"
+        );
+        Self { i, n_procs, log }
+    }
+    pub fn log(&self) -> &BoundedEvents<C> {
+        &self.log
+    }
+    pub fn log_mut(&mut self) -> &mut BoundedEvents<C> {
+        &mut self.log
+    }
+
+    fn next_event(&self) -> C {
+        self.last_event()
+            .unwrap_or(&C::new(self.i, self.n_procs))
+            .extend()
+    }
+    fn recv_event(&self, e_recv: &C) -> C {
+        self.last_event()
+            .unwrap_or(&C::new(self.i, self.n_procs))
+            .merge(e_recv)
+    }
+}
+
+impl<C: LogicalClock> HasEvents<C> for BoundedProcess<C> {
+    fn last_event(&self) -> Option<&C> {
+        self.log.last()
+    }
+    fn push_event(&mut self, e: C) {
+        if let Err(e) = self.log.push(e) {
+            panic!("{e}");
+        }
+    }
+    fn pid(&self) -> usize {
+        self.i
+    }
+    fn n_procs(&self) -> usize {
+        self.n_procs
+    }
+    fn events(&self) -> &[C] {
+        self.log.as_slice()
+    }
+}
+
+// Checks the log has room before recording, so that the fallible methods fail instead of panicking. `send_via`
+// goes through `try_send`
+impl<C: LogicalClock> OrdProcess<C> for BoundedProcess<C> {
+    fn try_exec<T, E, F>(&mut self, f: F) -> Result<T, OrderError>
+    where
+        E: Into<OrderError>,
+        F: FnOnce() -> Result<T, E>,
+    {
+        let e = self.next_event();
+        self.log.check_room(&e)?;
+        trace_event("exec", self.i, &e);
+        self.push_event(e);
+        f().map_err(Into::into)
+    }
+    fn try_send<E, F>(&mut self, send_fn: F) -> Result<(), OrderError>
+    where
+        E: Into<OrderError>,
+        F: FnOnce(C) -> Result<(), E>,
+    {
+        let e = self.next_event();
+        self.log.check_room(&e)?;
+        send_fn(e.clone()).map_err(Into::into)?;
+        trace_event("send", self.i, &e);
+        self.push_event(e);
+        Ok(())
+    }
+    // Drops the received clock if the log has no room for it
+    fn try_recv<E, F>(&mut self, recv_fn: F) -> Result<(), OrderError>
+    where
+        E: Into<OrderError>,
+        F: FnOnce() -> Result<C, E>,
+    {
+        let e_recv = recv_fn().map_err(Into::into)?;
+        self.log.check_room(&self.recv_event(&e_recv))?;
+        self.recv(|| e_recv);
+        Ok(())
+    }
+    fn send_all<T: Transport<C>>(&mut self, transport: &mut T) -> Result<(), OrderError> {
+        let e = self.next_event();
+        self.log.check_room(&e)?;
+        let (mut sent, mut res) = (false, Ok(()));
+        for pid in (0..self.n_procs).filter(|&j| j != self.i) {
+            match transport.send_to(pid, e.clone()) {
+                Ok(()) => sent = true,
+                Err(err) => res = res.and(Err(err)),
+            }
+        }
+        if sent {
+            trace_event("send_all", self.i, &e);
+            self.push_event(e);
+        }
+        res
+    }
+    // Drops the received clock if the log has no room for it
+    fn recv_via<T: Transport<C>>(&mut self, transport: &mut T) -> Result<usize, OrderError> {
+        let (from, e_recv) = transport.recv()?;
+        self.log.check_room(&self.recv_event(&e_recv))?;
+        Ok(self.recv_from(|| (from, e_recv)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::order::bounded::{BoundedEvents, BoundedProcess};
+    use crate::order::matrix_clock::MatrixClock;
+    use crate::order::vector_clock::VectorClock;
+    use crate::order::{HasEvents, LogicalClock, OrdProcess};
+
+    #[test]
+    fn drop_oldest_stays_contiguous() {
+        let mut p = BoundedProcess::new(0, 1, BoundedEvents::<VectorClock>::drop_oldest(3));
+        for n in 1..=20 {
+            p.exec(|| {});
+            let firsts: Vec<_> = p.events().iter().map(|e| e.get(0).unwrap()).collect();
+            // Clocks start at 1, so the n-th event is at n + 1
+            let lo = n.max(3) - 1;
+            assert_eq!(firsts, (lo..=n + 1).collect::<Vec<_>>());
+        }
+        assert_eq!(p.log().n_evicted(), 17);
+    }
+
+    #[test]
+    fn reject() {
+        let mut log = BoundedEvents::reject(2);
+        let e = VectorClock::new(0, 1);
+        log.push(e.extend()).unwrap();
+        log.push(e.extend().extend()).unwrap();
+        assert!(log.is_full());
+        assert!(log.push(e.extend().extend().extend()).is_err());
+        assert_eq!((log.len(), log.n_evicted()), (2, 0));
+    }
+
+    #[test]
+    fn gc_only_what_all_have_seen() {
+        let mut p0 = BoundedProcess::new(0, 2, BoundedEvents::<MatrixClock>::gc(3));
+        let mut p1 = BoundedProcess::new(1, 2, BoundedEvents::<MatrixClock>::gc(3));
+        let mut msg = None;
+        p0.exec(|| {});
+        p0.exec(|| {});
+        p0.send(|e| msg = Some(e));
+        // p1 has seen none, so none can be reclaimed
+        let e = p0.last_event().unwrap().extend();
+        assert!(p0.log_mut().push(e).is_err());
+
+        p1.recv(|| msg.take().unwrap());
+        p1.send(|e| msg = Some(e));
+        // Now p0 knows that p1 has seen all its events
+        p0.recv(|| msg.take().unwrap());
+        assert_eq!((p0.events().len(), p0.log().n_evicted()), (1, 3));
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn fallible_methods_fail_when_rejected() {
+        use crate::order::transport::{MpscTransport, Transport};
+        use crate::order::OrderError;
+
+        let mut ts = MpscTransport::mesh(2);
+        let mut p0 = BoundedProcess::new(0, 2, BoundedEvents::<VectorClock>::reject(1));
+        let mut p1 = BoundedProcess::new(1, 2, BoundedEvents::<VectorClock>::reject(1));
+        p0.send_via(&mut ts[0], 1).unwrap();
+        assert!(p0.send_via(&mut ts[0], 1).is_err());
+        assert!(p0.send_all(&mut ts[0]).is_err());
+        assert!(p0.try_exec(|| Ok::<_, OrderError>(())).is_err());
+        assert!(p0
+            .try_send(|_| -> Result<(), OrderError> { unreachable!() })
+            .is_err());
+        assert!(p0
+            .try_recv(|| Ok::<_, OrderError>(VectorClock::new(1, 2)))
+            .is_err());
+        assert_eq!(p0.events().len(), 1);
+        // Only the first send went out
+        p1.recv_via(&mut ts[1]).unwrap();
+        assert!(ts[1].try_recv().unwrap().is_none());
+        ts[0].send_to(1, VectorClock::new(0, 2)).unwrap();
+        assert!(p1.recv_via(&mut ts[1]).is_err());
+    }
+
+    #[test]
+    #[should_panic]
+    fn panics_when_rejected() {
+        let mut p = BoundedProcess::new(0, 1, BoundedEvents::<VectorClock>::reject(1));
+        p.exec(|| {});
+        p.exec(|| {});
+    }
+}
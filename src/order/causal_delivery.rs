@@ -0,0 +1,220 @@
+use crate::order::transport::Transport;
+use crate::order::vector_clock::VectorClock;
+use crate::order::{Message, OrderError};
+use alloc::collections::VecDeque;
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// Causal broadcast (Birman–Schiper–Stephenson) over any transport, so that if the broadcast of `m` happens before the
+/// broadcast of `n`, every process delivers `m` before `n`.
+///
+/// Each message carries the number of broadcasts of each process that its sender delivered, including its own. A
+/// received message is held back until its receiver delivered every broadcast the message depends on.
+///
+/// # Examples
+/// ```
+/// use rads::order::causal_delivery::CausalDelivery;
+/// use rads::order::transport::MpscTransport;
+///
+/// let mut ps: Vec<_> = MpscTransport::mesh(2).into_iter().map(CausalDelivery::new).collect();
+/// ps[0].broadcast("hello").unwrap();
+/// assert_eq!(ps[0].deliver().unwrap().payload, "hello"); // to self too
+/// let m = ps[1].deliver().unwrap();
+/// assert_eq!((m.from, m.payload), (0, "hello"));
+/// ```
+pub struct CausalDelivery<T, M> {
+    transport: T,
+    /// Number of broadcasts delivered of each process
+    delivered: VectorClock,
+    held: Vec<Message<VectorClock, M>>,
+    ready: VecDeque<Message<VectorClock, M>>,
+}
+
+impl<T, M> CausalDelivery<T, M>
+where
+    T: Transport<Message<VectorClock, M>>,
+    M: Clone,
+{
+    pub fn new(transport: T) -> Self {
+        let (i, n_procs) = (transport.pid(), transport.n_procs());
+        Self {
+            transport,
+            delivered: VectorClock::from_vec(i, vec![0; n_procs]),
+            held: Vec::new(),
+            ready: VecDeque::new(),
+        }
+    }
+    pub fn pid(&self) -> usize {
+        self.transport.pid()
+    }
+    /// Number of broadcasts delivered of each process
+    pub fn delivered(&self) -> &VectorClock {
+        &self.delivered
+    }
+    /// Number of messages received but held back for missing dependencies
+    pub fn n_held(&self) -> usize {
+        self.held.len()
+    }
+    pub fn into_inner(self) -> T {
+        self.transport
+    }
+    /// Sends to every process, and delivers to self at once, returning the message's clock.
+    ///
+    /// If sending to some process fails, still sends to the others and delivers to self, so that every process that
+    /// received the broadcast agrees on its place, and returns the first error.
+    pub fn broadcast(&mut self, payload: M) -> Result<VectorClock, OrderError> {
+        let i = self.pid();
+        let seq = self.delivered.get(i).unwrap_or_default();
+        let clock = self.delivered.advance(seq + 1);
+        let m = Message::new(i, seq as u64, clock.clone(), payload);
+        let sent = (0..self.transport.n_procs())
+            .filter(|&j| j != i)
+            .map(|pid| self.transport.send_to(pid, m.clone()))
+            .fold(Ok(()), Result::and);
+        self.delivered = clock.clone();
+        self.ready.push_back(m);
+        sent.map(|_| clock)
+    }
+    /// Blocks until a message can be delivered
+    pub fn deliver(&mut self) -> Result<Message<VectorClock, M>, OrderError> {
+        loop {
+            if let Some(m) = self.ready.pop_front() {
+                return Ok(m);
+            }
+            let (_, m) = self.transport.recv()?;
+            self.hold(m)?;
+        }
+    }
+    /// Like `deliver`, but returns None instead of blocking
+    pub fn try_deliver(&mut self) -> Result<Option<Message<VectorClock, M>>, OrderError> {
+        while self.ready.is_empty() {
+            match self.transport.try_recv()? {
+                Some((_, m)) => self.hold(m)?,
+                None => break,
+            }
+        }
+        Ok(self.ready.pop_front())
+    }
+
+    /// Holds back a received message, then readies every message whose dependencies were delivered
+    fn hold(&mut self, m: Message<VectorClock, M>) -> Result<(), OrderError> {
+        if m.clock.len() != self.delivered.len() || m.from >= self.delivered.len() {
+            return Err(OrderError::Other(anyhow::anyhow!(
+                "Expect clock of {} processes from a process among them, got {} processes from process {}",
+                self.delivered.len(),
+                m.clock.len(),
+                m.from
+            )));
+        }
+        self.held.push(m);
+        while let Some(idx) = self.held.iter().position(|m| self.is_deliverable(m)) {
+            let m = self.held.swap_remove(idx);
+            self.delivered = self.delivered.join(&m.clock);
+            self.ready.push_back(m);
+        }
+        Ok(())
+    }
+
+    /// Next broadcast of its sender, which only depends on delivered broadcasts of others
+    fn is_deliverable(&self, m: &Message<VectorClock, M>) -> bool {
+        m.clock
+            .iter()
+            .zip(self.delivered.iter())
+            .enumerate()
+            .all(|(k, (sent, seen))| match k == m.from {
+                true => *sent == seen + 1,
+                false => sent <= seen,
+            })
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "std")]
+mod tests {
+    use crate::order::causal_delivery::CausalDelivery;
+    use crate::order::transport::{MpscTransport, Transport};
+    use crate::order::vector_clock::VectorClock;
+    use crate::order::{LogicalClock, Message};
+
+    #[test]
+    fn holds_back() {
+        let mut ts = MpscTransport::<Message<VectorClock, &str>>::mesh(3);
+        let mut p2 = CausalDelivery::new(ts.pop().unwrap());
+        let mut p1 = CausalDelivery::new(ts.pop().unwrap());
+        let mut t0 = ts.pop().unwrap();
+
+        // p0 broadcasts, but p2 receives it last
+        let m1 = Message::new(0, 0, VectorClock::from_vec(0, vec![1, 0, 0]), "m1");
+        t0.send_to(1, m1.clone()).unwrap();
+        assert_eq!(p1.deliver().unwrap(), m1);
+        let clock = p1.broadcast("m2").unwrap();
+        assert!(clock == VectorClock::from_vec(1, vec![1, 1, 0]));
+        assert_eq!(p1.deliver().unwrap().payload, "m2");
+
+        assert_eq!(p2.try_deliver().unwrap(), None);
+        assert_eq!(p2.n_held(), 1);
+        t0.send_to(2, m1).unwrap();
+        assert_eq!(p2.deliver().unwrap().payload, "m1");
+        assert_eq!(p2.deliver().unwrap().payload, "m2");
+        assert_eq!(p2.n_held(), 0);
+        assert!(p2.delivered() == &VectorClock::from_vec(2, vec![1, 1, 0]));
+    }
+
+    #[test]
+    fn causal_order() {
+        const N_PROCS: usize = 3;
+        const N_MSGS: usize = 20;
+        let ths: Vec<_> = MpscTransport::mesh(N_PROCS)
+            .into_iter()
+            .map(|t| {
+                std::thread::spawn(move || {
+                    let mut p = CausalDelivery::new(t);
+                    let mut log = Vec::new();
+                    for k in 0..N_MSGS {
+                        p.broadcast(k).unwrap();
+                        while let Some(m) = p.try_deliver().unwrap() {
+                            log.push(m.clock);
+                        }
+                    }
+                    while log.len() < N_PROCS * N_MSGS {
+                        log.push(p.deliver().unwrap().clock);
+                    }
+                    log
+                })
+            })
+            .collect();
+        for th in ths {
+            let log = th.join().unwrap();
+            for (a, s) in log.iter().enumerate() {
+                for t in &log[a + 1..] {
+                    assert_ne!(t.partial_cmp(s), Some(std::cmp::Ordering::Less));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn broadcast_despite_failed_send() {
+        let mut ts = MpscTransport::mesh(3);
+        drop(ts.pop());
+        let mut p1 = CausalDelivery::new(ts.pop().unwrap());
+        let mut p0 = CausalDelivery::new(ts.pop().unwrap());
+        assert!(p0.broadcast("m1").is_err());
+        assert!(p0.broadcast("m2").is_err());
+        assert_eq!(p0.deliver().unwrap().payload, "m1");
+        assert_eq!(p0.deliver().unwrap().payload, "m2");
+        // Neither takes the place of the other
+        assert_eq!(p1.deliver().unwrap().payload, "m1");
+        assert_eq!(p1.deliver().unwrap().payload, "m2");
+    }
+
+    #[test]
+    fn rejects_foreign_clock() {
+        let mut ts = MpscTransport::mesh(2);
+        let mut p1 = CausalDelivery::new(ts.pop().unwrap());
+        ts[0]
+            .send_to(1, Message::new(0, 0, VectorClock::new(0, 3), ()))
+            .unwrap();
+        assert!(p1.deliver().is_err());
+    }
+}
@@ -1,72 +1,639 @@
-use crate::order::{HasEvents, LogicalClock, OrdProcess};
-use std::collections::HashMap;
+use crate::order::transport::Transport;
+use crate::order::{trace_event, HasEvents, LogicalClock, OrdProcess, OrderError};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Identifies a snapshot by its initiator and how many it initiated before, so that several can be in flight at once.
+#[derive(Clone, Copy, Hash, Eq, PartialEq, Ord, PartialOrd, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SnapshotId {
+    pub initiator: usize,
+    pub seq: usize,
+}
 
 #[derive(Clone, Hash, Eq, PartialEq)]
+#[cfg_attr(test, derive(Debug))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ChandyLamportClock {
     i: usize,
     clk: usize,
-    is_snapshot: bool,
+    /// Snapshot of the marker, if it is one
+    snapshot: Option<SnapshotId>,
+}
+impl ChandyLamportClock {
+    /// Snapshot of the marker, or None if it is a message
+    pub fn snapshot_id(&self) -> Option<SnapshotId> {
+        self.snapshot
+    }
+    fn marker(&self, id: SnapshotId) -> Self {
+        Self {
+            snapshot: Some(id),
+            ..self.clone()
+        }
+    }
 }
 impl LogicalClock for ChandyLamportClock {
     fn new(i: usize, _n_procs: usize) -> Self {
         Self {
             i,
             clk: 0,
-            is_snapshot: false,
+            snapshot: None,
         }
     }
     fn extend(&self) -> Self {
         Self {
             i: self.i,
             clk: self.clk + 1,
-            is_snapshot: false,
+            snapshot: None,
         }
     }
     fn merge(&self, _other: &Self) -> Self {
         self.extend()
     }
+    fn observe(&self, _other: &Self) -> Self {
+        self.clone()
+    }
+    fn fmt_clock(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self.snapshot {
+            Some(id) => write!(
+                f,
+                "{}:{} (marker {}.{})",
+                self.i, self.clk, id.initiator, id.seq
+            ),
+            None => write!(f, "{}:{}", self.i, self.clk),
+        }
+    }
+}
+
+/// Local state of a process in a snapshot, along with the state of its incoming channels.
+#[derive(Clone, PartialEq, Eq)]
+#[cfg_attr(test, derive(Debug))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LocalSnapshot<S = ()> {
+    /// Number of events before the snapshot, i.e. its prefix of `events()`
+    pub n_events: usize,
+    /// State of the application when the snapshot was recorded
+    pub state: S,
+    /// Messages in flight on each incoming channel by sender, i.e. received after the snapshot but before the marker
+    /// on that channel
+    pub channels: Vec<Vec<ChandyLamportClock>>,
+    /// Whether the marker arrived on each incoming channel, which stops recording it
+    recorded: Vec<bool>,
+    /// Snapshots whose regions merged with this one, including its own
+    merged: BTreeSet<SnapshotId>,
+    /// Latest snapshot completed before this one was recorded, whose events its delta leaves out
+    base: Option<SnapshotId>,
+    /// Number of events before the base snapshot, i.e. the watermark that its delta starts from
+    pub since: usize,
+}
+
+impl<S> LocalSnapshot<S> {
+    /// Snapshot `id` of process `i` after `n_events`, which stops recording the channel from `from` if its marker
+    /// arrived
+    fn new(
+        id: SnapshotId,
+        i: usize,
+        n: usize,
+        n_events: usize,
+        from: Option<usize>,
+        state: S,
+    ) -> Self {
+        Self {
+            n_events,
+            state,
+            channels: vec![Vec::new(); n],
+            recorded: (0..n).map(|j| j == i || Some(j) == from).collect(),
+            merged: BTreeSet::from([id]),
+            base: None,
+            since: 0,
+        }
+    }
+    /// Latest snapshot completed before this one was recorded, if any
+    pub fn base(&self) -> Option<SnapshotId> {
+        self.base
+    }
+    /// Whether the marker arrived on every incoming channel, so that the channel states are final
+    pub fn is_complete(&self) -> bool {
+        self.recorded.iter().all(|&r| r)
+    }
+}
+
+/// Part of a global snapshot contributed by one process, e.g. to send to a `Collector`.
+#[derive(Clone, PartialEq, Eq)]
+#[cfg_attr(test, derive(Debug))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LocalState<S = ()> {
+    pub pid: usize,
+    /// State of the application at the snapshot
+    pub state: S,
+    /// Events before the snapshot
+    pub events: Vec<ChandyLamportClock>,
+    /// Clock of the matching send of each event that is a receive
+    pub received: Vec<Option<ChandyLamportClock>>,
+    /// Messages in flight on each incoming channel by sender
+    pub channels: Vec<Vec<ChandyLamportClock>>,
+    /// Every snapshot this local state belongs to, which is more than one if concurrent snapshots merged
+    pub merged: Vec<SnapshotId>,
+}
+
+impl<S> LocalState<S> {
+    /// Extends the local state of the base snapshot of `delta` into that of its snapshot
+    pub fn apply(&mut self, delta: LocalDelta<S>) -> Result<(), OrderError> {
+        if delta.pid != self.pid || delta.since != self.events.len() {
+            return Err(OrderError::Other(anyhow::anyhow!(
+                "Expect a delta of process {} since event {}, got process {} since event {}",
+                self.pid,
+                self.events.len(),
+                delta.pid,
+                delta.since
+            )));
+        }
+        self.events.extend(delta.events);
+        self.received.extend(delta.received);
+        self.channels = delta.channels;
+        self.merged = delta.merged;
+        self.state = delta.state;
+        Ok(())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<S: serde::Serialize + serde::de::DeserializeOwned> LocalState<S> {
+    /// Local state as bytes, e.g. to ship it to a `Collector` or write it to disk as a checkpoint
+    pub fn to_bytes(&self) -> Result<Vec<u8>, OrderError> {
+        to_bytes(self)
+    }
+    /// Inverse of `to_bytes`
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, OrderError> {
+        let local: Self = from_bytes(bytes)?;
+        local.validate()?;
+        Ok(local)
+    }
+    fn validate(&self) -> Result<(), OrderError> {
+        if self.received.len() != self.events.len() || self.events.iter().any(|e| e.i != self.pid) {
+            return Err(OrderError::Other(anyhow::anyhow!(
+                "Invalid local state of process {} with {} events",
+                self.pid,
+                self.events.len()
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Part of a local state since the latest snapshot completed before it, which is cheaper to ship than the whole
+/// local state of a long-running process.
+///
+/// # Examples
+/// ```
+/// use rads::order::chandy_lamport::ChandyLamportProc;
+/// use rads::order::OrdProcess;
+///
+/// let mut p = ChandyLamportProc::new(0, 1);
+/// p.exec(|| {});
+/// let a = p.global_snapshot(|_| {});
+/// p.exec(|| {});
+/// let b = p.global_snapshot(|_| {});
+///
+/// let delta = p.local_delta(b).unwrap();
+/// assert_eq!((delta.base, delta.events.len()), (Some(a), 1));
+/// let mut local = p.local_state(a).unwrap();
+/// local.apply(delta).unwrap();
+/// assert!(local == p.local_state(b).unwrap());
+/// ```
+#[derive(Clone, PartialEq, Eq)]
+#[cfg_attr(test, derive(Debug))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LocalDelta<S = ()> {
+    pub pid: usize,
+    /// State of the application at the snapshot, which is captured whole
+    pub state: S,
+    /// Snapshot whose local state this delta extends, or None if it extends the initial state
+    pub base: Option<SnapshotId>,
+    /// Number of events before the base snapshot
+    pub since: usize,
+    /// Events after the base snapshot but before this one
+    pub events: Vec<ChandyLamportClock>,
+    /// Clock of the matching send of each event that is a receive
+    pub received: Vec<Option<ChandyLamportClock>>,
+    /// Messages in flight on each incoming channel by sender, which are never shared with the base snapshot
+    pub channels: Vec<Vec<ChandyLamportClock>>,
+    /// Every snapshot this local state belongs to
+    pub merged: Vec<SnapshotId>,
+}
+
+#[cfg(feature = "serde")]
+impl<S: serde::Serialize + serde::de::DeserializeOwned> LocalDelta<S> {
+    /// Delta as bytes, e.g. to ship it to a `Collector` or append it to a checkpoint on disk
+    pub fn to_bytes(&self) -> Result<Vec<u8>, OrderError> {
+        to_bytes(self)
+    }
+    /// Inverse of `to_bytes`
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, OrderError> {
+        let delta: Self = from_bytes(bytes)?;
+        if delta.received.len() != delta.events.len()
+            || delta.events.iter().any(|e| e.i != delta.pid)
+        {
+            return Err(OrderError::Other(anyhow::anyhow!(
+                "Invalid delta of process {} with {} events",
+                delta.pid,
+                delta.events.len()
+            )));
+        }
+        Ok(delta)
+    }
+}
+
+/// Local states of every process in the same snapshot.
+///
+/// # Examples
+/// ```
+/// use rads::order::chandy_lamport::{ChandyLamportProc, GlobalSnapshot};
+/// use rads::order::OrdProcess;
+///
+/// let mut ps = [ChandyLamportProc::new(0, 2), ChandyLamportProc::new(1, 2)];
+/// let (mut to_p1, mut to_p0) = (Vec::new(), Vec::new());
+/// ps[0].send(|e| to_p1.push(e));
+/// let id = ps[0].global_snapshot(|e| to_p1.push(e));
+/// for e in to_p1 {
+///     ps[1].recv_and_forward(|| (0, e), |e| to_p0.push(e));
+/// }
+/// for e in to_p0 {
+///     ps[0].recv_from(|| (1, e));
+/// }
+///
+/// let global = GlobalSnapshot::collect(&ps, id).unwrap();
+/// assert!(global.is_consistent());
+/// assert_eq!(global.locals[1].events.len(), 1);
+/// ```
+#[derive(Clone, PartialEq, Eq)]
+#[cfg_attr(test, derive(Debug))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GlobalSnapshot<S = ()> {
+    pub id: SnapshotId,
+    /// Local state of every process by pid
+    pub locals: Vec<LocalState<S>>,
+}
+
+impl<S: Clone> GlobalSnapshot<S> {
+    /// Assembles the local states of snapshot `id` from every process, ordered by pid, once it completed at each
+    pub fn collect(procs: &[ChandyLamportProc<S>], id: SnapshotId) -> Result<Self, OrderError> {
+        let mut collector = Collector::new(procs.len());
+        for p in procs {
+            let local = p
+                .local_state(id)
+                .filter(|_| p.is_complete(id))
+                .ok_or_else(|| {
+                    OrderError::Other(anyhow::anyhow!(
+                        "Expect process {} to have completed snapshot {}.{}",
+                        p.i,
+                        id.initiator,
+                        id.seq
+                    ))
+                })?;
+            if let Some(global) = collector.add(id, local)? {
+                return Ok(global);
+            }
+        }
+        Err(OrderError::Other(anyhow::anyhow!(
+            "Expect at least 1 process"
+        )))
+    }
+}
+
+impl<S> GlobalSnapshot<S> {
+    /// Whether no process received a message that was sent after the sender's snapshot, and every channel only holds
+    /// messages sent before it
+    pub fn is_consistent(&self) -> bool {
+        // Events are counted from 1, so the k-th event of a process has clock k
+        let sent_before = |e: &ChandyLamportClock| {
+            self.locals
+                .get(e.i)
+                .is_some_and(|l| e.clk <= l.events.len())
+        };
+        self.locals.iter().all(|l| {
+            l.received.iter().flatten().all(sent_before)
+                && l.channels
+                    .iter()
+                    .enumerate()
+                    .all(|(j, ms)| ms.iter().all(|e| e.i == j && sent_before(e)))
+        })
+    }
+
+    /// Number of messages in flight on every channel
+    pub fn n_in_flight(&self) -> usize {
+        self.locals
+            .iter()
+            .flat_map(|l| &l.channels)
+            .map(Vec::len)
+            .sum()
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<S: serde::Serialize + serde::de::DeserializeOwned> GlobalSnapshot<S> {
+    /// Global snapshot as bytes, e.g. to write it to disk as a checkpoint
+    pub fn to_bytes(&self) -> Result<Vec<u8>, OrderError> {
+        to_bytes(self)
+    }
+    /// Inverse of `to_bytes`, which expects the local state of every process by pid
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, OrderError> {
+        let global: Self = from_bytes(bytes)?;
+        let n_procs = global.locals.len();
+        for (i, l) in global.locals.iter().enumerate() {
+            if l.pid != i || l.channels.len() != n_procs {
+                return Err(OrderError::Other(anyhow::anyhow!(
+                    "Expect the local state of process {i} of {n_procs}, got process {}",
+                    l.pid
+                )));
+            }
+            l.validate()?;
+        }
+        Ok(global)
+    }
 }
 
-pub struct ChandyLamportProc {
+#[cfg(feature = "serde")]
+fn to_bytes<T: serde::Serialize>(t: &T) -> Result<Vec<u8>, OrderError> {
+    serde_json::to_vec(t).map_err(|e| OrderError::Other(e.into()))
+}
+
+#[cfg(feature = "serde")]
+fn from_bytes<T: serde::de::DeserializeOwned>(bytes: &[u8]) -> Result<T, OrderError> {
+    serde_json::from_slice(bytes).map_err(|e| OrderError::Other(e.into()))
+}
+
+/// Assembles global snapshots from local states that processes report, e.g. over a transport, in any order.
+///
+/// Local states that belong to several merged snapshots merge their global snapshots into one, identified by the
+/// oldest.
+pub struct Collector<S = ()> {
+    n_procs: usize,
+    pending: BTreeMap<SnapshotId, Vec<Option<LocalState<S>>>>,
+    completed: BTreeSet<SnapshotId>,
+    /// Snapshots merged into an older one
+    merged_into: BTreeMap<SnapshotId, SnapshotId>,
+}
+
+impl<S> Collector<S> {
+    pub fn new(n_procs: usize) -> Self {
+        Self {
+            n_procs,
+            pending: BTreeMap::new(),
+            completed: BTreeSet::new(),
+            merged_into: BTreeMap::new(),
+        }
+    }
+    /// Adds a process's local state of snapshot `id`, returning the global snapshot once every process reported
+    pub fn add(
+        &mut self,
+        id: SnapshotId,
+        local: LocalState<S>,
+    ) -> Result<Option<GlobalSnapshot<S>>, OrderError> {
+        let (pid, n_procs) = (local.pid, self.n_procs);
+        if pid >= n_procs {
+            return Err(OrderError::Other(anyhow::anyhow!(
+                "Expect 0-based index of process {pid} < n_procs={n_procs}"
+            )));
+        }
+        let roots: BTreeSet<_> = core::iter::once(id)
+            .chain(local.merged.iter().copied())
+            .map(|id| self.root(id))
+            .collect();
+        let reported = |j: usize| {
+            roots
+                .iter()
+                .filter_map(|r| self.pending.get(r))
+                .filter(|locals| locals[j].is_some())
+                .count()
+        };
+        if roots.iter().any(|r| self.completed.contains(r))
+            || reported(pid) > 0
+            || (0..n_procs).any(|j| reported(j) > 1)
+        {
+            return Err(OrderError::Other(anyhow::anyhow!(
+                "Expect process {pid} to report snapshot {}.{} once",
+                id.initiator,
+                id.seq
+            )));
+        }
+
+        let root = *roots.first().expect("Expect at least `id`");
+        let mut locals: Vec<_> = (0..n_procs).map(|_| None).collect();
+        for r in &roots {
+            let merged = self.pending.remove(r).unwrap_or_default();
+            for (slot, l) in locals.iter_mut().zip(merged) {
+                *slot = slot.take().or(l);
+            }
+            if *r != root {
+                self.merged_into.insert(*r, root);
+            }
+        }
+        locals[pid] = Some(local);
+        if locals.iter().any(Option::is_none) {
+            self.pending.insert(root, locals);
+            return Ok(None);
+        }
+        self.completed.insert(root);
+        Ok(Some(GlobalSnapshot {
+            id: root,
+            locals: locals.into_iter().flatten().collect(),
+        }))
+    }
+    /// Number of snapshots that some process has yet to report
+    pub fn n_pending(&self) -> usize {
+        self.pending.len()
+    }
+    /// Whether every process reported its local state of snapshot `id`, or of one it merged with
+    pub fn is_complete(&self, id: SnapshotId) -> bool {
+        self.completed.contains(&self.root(id))
+    }
+
+    /// Oldest snapshot that `id` merged into
+    fn root(&self, mut id: SnapshotId) -> SnapshotId {
+        while let Some(&older) = self.merged_into.get(&id) {
+            id = older;
+        }
+        id
+    }
+}
+
+pub struct ChandyLamportProc<S = ()> {
     i: usize,
     n: usize,
     events: Vec<ChandyLamportClock>,
-    snapshots: HashMap<ChandyLamportClock, usize>,
+    /// Clock of the matching send of each event that is a receive
+    received: Vec<Option<ChandyLamportClock>>,
+    /// Number of snapshots this process initiated
+    n_initiated: usize,
+    snapshots: BTreeMap<SnapshotId, LocalSnapshot<S>>,
+    on_complete: Option<OnComplete<S>>,
+    /// Captures the state of the application whenever a local snapshot is recorded
+    capture: Capture<S>,
+    /// Whether concurrent snapshots merge into the one in progress
+    merging: bool,
 }
-impl ChandyLamportProc {
-    pub fn snapshots(&self) -> Vec<(ChandyLamportClock, &[ChandyLamportClock])> {
+
+type OnComplete<S> = Box<dyn FnMut(SnapshotId, LocalState<S>) + Send>;
+type Capture<S> = Box<dyn Fn() -> S + Send>;
+impl<S: Clone> ChandyLamportProc<S> {
+    /// Local snapshots by id, oldest initiator and sequence first, along with the application's state in each
+    pub fn snapshots(&self) -> Vec<(SnapshotId, &LocalSnapshot<S>)> {
+        self.snapshots.iter().map(|(k, v)| (*k, v)).collect()
+    }
+    /// Local snapshot of `id`, or of the snapshot it merged into
+    pub fn snapshot(&self, id: SnapshotId) -> Option<&LocalSnapshot<S>> {
         self.snapshots
-            .iter()
-            .map(|(k, v)| (k.clone(), &self.events[..*v]))
-            .collect()
+            .get(&id)
+            .or_else(|| self.snapshots.values().find(|l| l.merged.contains(&id)))
+    }
+    /// Copy of the local state in snapshot `id`, which outlives the process
+    pub fn local_state(&self, id: SnapshotId) -> Option<LocalState<S>> {
+        let local = self.snapshot(id)?;
+        Some(LocalState {
+            pid: self.i,
+            state: local.state.clone(),
+            events: self.events[..local.n_events].to_vec(),
+            received: self.received[..local.n_events].to_vec(),
+            channels: local.channels.clone(),
+            merged: local.merged.iter().copied().collect(),
+        })
+    }
+    /// Events of snapshot `id` since its base snapshot, i.e. the latest this process completed before recording it
+    pub fn local_delta(&self, id: SnapshotId) -> Option<LocalDelta<S>> {
+        let local = self.snapshot(id)?;
+        let events = local.since..local.n_events;
+        Some(LocalDelta {
+            pid: self.i,
+            state: local.state.clone(),
+            base: local.base,
+            since: local.since,
+            events: self.events[events.clone()].to_vec(),
+            received: self.received[events].to_vec(),
+            channels: local.channels.clone(),
+            merged: local.merged.iter().copied().collect(),
+        })
     }
 }
 
 impl ChandyLamportProc {
-    fn new(i: usize, n: usize) -> Self {
+    pub fn new(i: usize, n: usize) -> Self {
         Self {
             i,
             n,
             events: Vec::new(),
-            snapshots: HashMap::new(),
+            received: Vec::new(),
+            n_initiated: 0,
+            snapshots: BTreeMap::new(),
+            on_complete: None,
+            capture: Box::new(|| ()),
+            merging: false,
+        }
+    }
+    /// Records the state of the application that `f` captures in every local snapshot, along with the events
+    /// before it.
+    ///
+    /// `f` runs whenever this process records a local snapshot, i.e. before any event after it, so it must see the
+    /// state that the events so far left behind, e.g. through an `Arc<Mutex<_>>` shared with the application.
+    ///
+    /// # Examples
+    /// ```
+    /// use rads::order::chandy_lamport::ChandyLamportProc;
+    /// use rads::order::OrdProcess;
+    /// use std::sync::{Arc, Mutex};
+    ///
+    /// let balance = Arc::new(Mutex::new(100));
+    /// let state = balance.clone();
+    /// let mut p = ChandyLamportProc::new(0, 1).with_state(move || *state.lock().unwrap());
+    /// p.exec(|| *balance.lock().unwrap() -= 30);
+    /// let id = p.global_snapshot(|_| {});
+    /// p.exec(|| *balance.lock().unwrap() -= 30);
+    /// assert_eq!(p.snapshot(id).unwrap().state, 70);
+    /// assert_eq!(p.local_state(id).unwrap().state, 70);
+    /// ```
+    pub fn with_state<S: Clone, F: Fn() -> S + Send + 'static>(self, f: F) -> ChandyLamportProc<S> {
+        assert!(
+            self.snapshots.is_empty() && self.on_complete.is_none(),
+            "Expect to capture state before any snapshot or on_complete"
+        );
+        ChandyLamportProc {
+            i: self.i,
+            n: self.n,
+            events: self.events,
+            received: self.received,
+            n_initiated: self.n_initiated,
+            snapshots: BTreeMap::new(),
+            on_complete: None,
+            capture: Box::new(f),
+            merging: self.merging,
+        }
+    }
+}
+
+impl<S: Clone> ChandyLamportProc<S> {
+    /// Merges snapshots initiated concurrently into one (Spezialetti-Kearns), instead of recording a local snapshot
+    /// for each.
+    ///
+    /// The first marker to arrive decides which snapshot's region this process joins, and only that marker is
+    /// forwarded. Markers of any other snapshot that arrive before this local snapshot completes close their channel,
+    /// and merge their snapshot into it. Every process must merge, so that each channel carries one marker per
+    /// snapshot.
+    pub fn with_merging(mut self) -> Self {
+        self.merging = true;
+        self
+    }
+    /// Calls `f` with the local state of every snapshot once it completes, e.g. to upload a checkpoint or report it
+    /// to a `Collector`
+    pub fn with_on_complete<F: FnMut(SnapshotId, LocalState<S>) + Send + 'static>(
+        mut self,
+        f: F,
+    ) -> Self {
+        self.on_complete = Some(Box::new(f));
+        self
+    }
+    /// Whether this process received the marker of snapshot `id` on every incoming channel
+    pub fn is_complete(&self, id: SnapshotId) -> bool {
+        self.snapshot(id).is_some_and(LocalSnapshot::is_complete)
+    }
+    // Records the local snapshot of a new snapshot, then sends its marker to all other processes
+    // If merging, returns the snapshot in progress without sending anything instead
+    // Expects to take a function that sends clock to all other processes in a lossless FIFO channel
+    pub fn global_snapshot<F: FnOnce(ChandyLamportClock)>(&mut self, send_fn: F) -> SnapshotId {
+        if let Some(id) = self.in_progress(None) {
+            return id;
         }
+        let id = SnapshotId {
+            initiator: self.i,
+            seq: self.n_initiated,
+        };
+        self.n_initiated += 1;
+        let marker = self.record(id, None);
+        trace_event("snapshot", self.i, &marker);
+        send_fn(marker);
+        id
     }
+    // Like `recv_from`, but forwards a marker to all other processes the first time it is received, so that the
+    // snapshot propagates beyond the initiator's neighbours
     // Expects to take a function that sends clock to all other processes in a lossless FIFO channel
-    pub fn global_snapshot<F: Fn(ChandyLamportClock)>(&self, send_fn: F) {
-        let e = self
-            .last_event()
-            .unwrap_or(&ChandyLamportClock::new(self.i, self.n))
-            .clone();
-        send_fn(e);
+    pub fn recv_and_forward<R, F>(&mut self, recv_fn: R, send_fn: F) -> usize
+    where
+        R: FnOnce() -> (usize, ChandyLamportClock),
+        F: FnOnce(ChandyLamportClock),
+    {
+        let (from, e_recv) = recv_fn();
+        if let Some(marker) = self.on_recv(from, e_recv) {
+            send_fn(marker);
+        }
+        from
     }
 }
 
-impl HasEvents<ChandyLamportClock> for ChandyLamportProc {
+impl<S: Clone> HasEvents<ChandyLamportClock> for ChandyLamportProc<S> {
     fn last_event(&self) -> Option<&ChandyLamportClock> {
         self.events.last()
     }
     fn push_event(&mut self, e: ChandyLamportClock) {
-        self.events.push(e)
+        self.events.push(e);
+        self.received.push(None);
     }
     fn pid(&self) -> usize {
         self.i
@@ -77,108 +644,826 @@ impl HasEvents<ChandyLamportClock> for ChandyLamportProc {
     fn events(&self) -> &[ChandyLamportClock] {
         self.events.as_slice()
     }
+    fn sender(&self, idx: usize) -> Option<usize> {
+        self.received.get(idx)?.as_ref().map(|e| e.i)
+    }
 }
 
-impl OrdProcess<ChandyLamportClock> for ChandyLamportProc {
+// `recv` and `recv_from` cannot forward markers, use `recv_and_forward` or `recv_via` instead
+impl<S: Clone> OrdProcess<ChandyLamportClock> for ChandyLamportProc<S> {
     fn recv<F: FnOnce() -> ChandyLamportClock>(&mut self, recv_fn: F) {
         let e_recv = recv_fn();
-        if !e_recv.is_snapshot {
+        self.on_recv(e_recv.i, e_recv);
+    }
+    fn recv_from<F: FnOnce() -> (usize, ChandyLamportClock)>(&mut self, recv_fn: F) -> usize {
+        let (from, e_recv) = recv_fn();
+        self.on_recv(from, e_recv);
+        from
+    }
+    // Forwards a marker received for the first time to all other processes over the transport
+    fn recv_via<T: Transport<ChandyLamportClock>>(
+        &mut self,
+        transport: &mut T,
+    ) -> Result<usize, OrderError> {
+        let m = transport.recv()?;
+        let (i, n) = (self.i, self.n);
+        let mut res = Ok(());
+        let from = self.recv_and_forward(
+            || m,
+            |marker| {
+                for j in (0..n).filter(|&j| j != i) {
+                    if let (Err(err), true) = (transport.send_to(j, marker.clone()), res.is_ok()) {
+                        res = Err(err);
+                    }
+                }
+            },
+        );
+        res.map(|()| from)
+    }
+}
+
+impl<S: Clone> ChandyLamportProc<S> {
+    /// Records the local snapshot, which stops recording the channel from `from` if its marker arrived, and returns
+    /// the marker to send
+    fn record(&mut self, id: SnapshotId, from: Option<usize>) -> ChandyLamportClock {
+        let state = (self.capture)();
+        let mut local = LocalSnapshot::new(id, self.i, self.n, self.events.len(), from, state);
+        // The watermark of the latest completed snapshot, which never exceeds the events so far
+        if let Some((base, l)) = self
+            .snapshots
+            .iter()
+            .filter(|(_, l)| l.is_complete())
+            .max_by_key(|(_, l)| l.n_events)
+        {
+            (local.base, local.since) = (Some(*base), l.n_events);
+        }
+        self.snapshots.insert(id, local);
+        self.notify_if_complete(id);
+        self.last_event()
+            .unwrap_or(&ChandyLamportClock::new(self.i, self.n))
+            .marker(id)
+    }
+    /// Oldest snapshot that concurrent ones merge into, if merging and this process is yet to complete it
+    ///
+    /// A marker on a channel that already carried one is of a later snapshot, so it won't merge into that one.
+    fn in_progress(&self, from: Option<usize>) -> Option<SnapshotId> {
+        self.snapshots
+            .iter()
+            .filter(|(_, l)| self.merging && !l.is_complete())
+            .filter(|(_, l)| from.is_none_or(|j| !l.recorded[j]))
+            .min_by_key(|(_, l)| l.n_events)
+            .map(|(id, _)| *id)
+    }
+    fn notify_if_complete(&mut self, id: SnapshotId) {
+        if self.on_complete.is_none() || !self.is_complete(id) {
+            return;
+        }
+        let local = self.local_state(id).expect("Expect a recorded snapshot");
+        if let Some(f) = self.on_complete.as_mut() {
+            f(id, local);
+        }
+    }
+    // Returns the marker to forward if it was received for the first time
+    fn on_recv(&mut self, from: usize, e_recv: ChandyLamportClock) -> Option<ChandyLamportClock> {
+        let Some(id) = e_recv.snapshot else {
+            // In flight on every channel still being recorded
+            for local in self.snapshots.values_mut() {
+                if !local.recorded[from] {
+                    local.channels[from].push(e_recv.clone());
+                }
+            }
             let e = self
                 .last_event()
                 .unwrap_or(&ChandyLamportClock::new(self.pid(), self.n_procs()))
                 .merge(&e_recv);
             self.push_event(e);
-        } else if let std::collections::hash_map::Entry::Vacant(e) = self.snapshots.entry(e_recv) {
-            e.insert(self.events.len());
-            // TODO broadcast to all
+            *self.received.last_mut().expect("Expect the receive event") = Some(e_recv);
+            return None;
+        };
+        if let Some(local) = self.snapshots.get_mut(&id) {
+            local.recorded[from] = true;
+            self.notify_if_complete(id);
+            return None;
         }
+        let Some(current) = self.in_progress(Some(from)) else {
+            trace_event("marker", self.i, &e_recv);
+            return Some(self.record(id, Some(from)));
+        };
+        // Concurrent snapshot, whose region borders this one's
+        trace_event("merge", self.i, &e_recv);
+        if let Some(local) = self.snapshots.get_mut(&current) {
+            local.merged.insert(id);
+            local.recorded[from] = true;
+        }
+        self.notify_if_complete(current);
+        None
     }
 }
 
+/// Repeatedly takes snapshots initiated by process 0 until `is_stable` holds in one (Chandy-Lamport), e.g. to detect
+/// termination, deadlock or token loss, returning that snapshot.
+///
+/// Drives every process from one thread by delivering whatever arrived at each in turn, forwarding markers and
+/// calling `on_recv` with the process, its transport and the sender of every other message, so that the application
+/// reacts to it. The application only acts on receipt, so once a snapshot completes without delivering any message,
+/// nothing will ever change, and None is returned if the property still doesn't hold.
+///
+/// The property must be stable, i.e. hold forever once it holds, so that it holds when detected, since a snapshot
+/// only records a state the processes could have passed through.
+///
+/// # Examples
+/// ```
+/// use rads::order::chandy_lamport::{detect_stable, ChandyLamportProc};
+/// use rads::order::transport::MpscTransport;
+/// use rads::order::{HasEvents, OrdProcess};
+///
+/// let mut ts = MpscTransport::mesh(3);
+/// let mut ps: Vec<_> = (0..3).map(|i| ChandyLamportProc::new(i, 3)).collect();
+/// // Passes a token around the ring until it wore out
+/// let mut hops = 10;
+/// ps[0].send_via(&mut ts[0], 1).unwrap();
+/// let on_recv = |p: &mut ChandyLamportProc, t: &mut MpscTransport<_>, _| {
+///     hops -= 1;
+///     match hops {
+///         0 => Ok(()),
+///         _ => p.send_via(t, (p.pid() + 1) % 3),
+///     }
+/// };
+/// // Terminated once nothing is in flight, since processes only send on receipt
+/// let global = detect_stable(&mut ps, &mut ts, on_recv, |g| g.n_in_flight() == 0).unwrap().unwrap();
+/// assert_eq!(hops, 0);
+/// assert!(global.locals.iter().zip(&ps).all(|(l, p)| l.events == p.events()));
+/// ```
+pub fn detect_stable<S, T, F, P>(
+    procs: &mut [ChandyLamportProc<S>],
+    transports: &mut [T],
+    mut on_recv: F,
+    is_stable: P,
+) -> Result<Option<GlobalSnapshot<S>>, OrderError>
+where
+    S: Clone,
+    T: Transport<ChandyLamportClock>,
+    F: FnMut(&mut ChandyLamportProc<S>, &mut T, usize) -> Result<(), OrderError>,
+    P: Fn(&GlobalSnapshot<S>) -> bool,
+{
+    if procs.is_empty() || procs.len() != transports.len() {
+        return Err(OrderError::Other(anyhow::anyhow!(
+            "Expect a transport for each of at least 1 process, got {} for {}",
+            transports.len(),
+            procs.len()
+        )));
+    }
+    loop {
+        let mut res = Ok(());
+        let id = procs[0].global_snapshot(|marker| res = send_others(&mut transports[0], marker));
+        res?;
+        let mut n_delivered = 0;
+        while !procs.iter().all(|p| p.is_complete(id)) {
+            let mut delivered = false;
+            for (p, t) in procs.iter_mut().zip(transports.iter_mut()) {
+                let Some((from, e)) = t.try_recv()? else {
+                    continue;
+                };
+                delivered = true;
+                let is_marker = e.snapshot_id().is_some();
+                let mut res = Ok(());
+                p.recv_and_forward(|| (from, e), |marker| res = send_others(t, marker));
+                res?;
+                if !is_marker {
+                    n_delivered += 1;
+                    on_recv(p, t, from)?;
+                }
+            }
+            if !delivered {
+                return Err(OrderError::Other(anyhow::anyhow!(
+                    "Expect the markers of snapshot {}.{} on every channel",
+                    id.initiator,
+                    id.seq
+                )));
+            }
+        }
+        let global = GlobalSnapshot::collect(procs, id)?;
+        if is_stable(&global) {
+            return Ok(Some(global));
+        }
+        if n_delivered == 0 {
+            return Ok(None);
+        }
+    }
+}
+
+fn send_others<T: Transport<ChandyLamportClock>>(
+    t: &mut T,
+    marker: ChandyLamportClock,
+) -> Result<(), OrderError> {
+    let i = t.pid();
+    (0..t.n_procs())
+        .filter(|&j| j != i)
+        .try_for_each(|j| t.send_to(j, marker.clone()))
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::order::chandy_lamport::ChandyLamportProc;
+    use crate::order::chandy_lamport::{
+        detect_stable, ChandyLamportClock, ChandyLamportProc, Collector, GlobalSnapshot,
+        LocalSnapshot, SnapshotId,
+    };
+    use crate::order::transport::{MpscTransport, Transport};
     use crate::order::{HasEvents, OrdProcess};
+    use std::sync::{Arc, Mutex};
+
+    /// Processes connected all-to-all, driven step by step from one thread
+    struct Mesh<S = ()> {
+        ps: Vec<ChandyLamportProc<S>>,
+        ts: Vec<MpscTransport<ChandyLamportClock>>,
+    }
+
+    impl Mesh {
+        fn new(n: usize) -> Self {
+            Self {
+                ps: (0..n).map(|i| ChandyLamportProc::new(i, n)).collect(),
+                ts: MpscTransport::mesh(n),
+            }
+        }
+        fn merging(n: usize) -> Self {
+            Self {
+                ps: (0..n)
+                    .map(|i| ChandyLamportProc::new(i, n).with_merging())
+                    .collect(),
+                ts: MpscTransport::mesh(n),
+            }
+        }
+    }
+
+    impl<S: Clone> Mesh<S> {
+        fn snapshot(&mut self, i: usize) -> SnapshotId {
+            let t = &mut self.ts[i];
+            let n = self.ps.len();
+            self.ps[i].global_snapshot(|e| {
+                (0..n)
+                    .filter(|&j| j != i)
+                    .for_each(|j| t.send_to(j, e.clone()).unwrap())
+            })
+        }
+        fn send(&mut self, from: usize, to: usize) {
+            self.ps[from].send_via(&mut self.ts[from], to).unwrap();
+        }
+        fn recv(&mut self, i: usize) -> usize {
+            self.ps[i].recv_via(&mut self.ts[i]).unwrap()
+        }
+        /// Receives until nothing is in flight to process i
+        fn drain(&mut self, i: usize) {
+            self.drain_with(i, |_| {});
+        }
+        /// Like `drain`, but calls `f` after receiving each message that is not a marker
+        fn drain_with<F: FnMut(&ChandyLamportClock)>(&mut self, i: usize, mut f: F) {
+            while let Some(msg) = self.ts[i].try_recv().unwrap() {
+                let (t, n) = (&mut self.ts[i], self.ps.len());
+                let is_marker = msg.1.snapshot_id().is_some();
+                let from = self.ps[i].recv_and_forward(
+                    || msg,
+                    |m| {
+                        (0..n)
+                            .filter(|&j| j != i)
+                            .for_each(|j| t.send_to(j, m.clone()).unwrap())
+                    },
+                );
+                assert_ne!(from, i);
+                if !is_marker {
+                    f(self.ps[i].last_event().unwrap());
+                }
+            }
+        }
+        /// Local snapshot of process i in the snapshot initiated by `initiator`
+        fn local(&self, i: usize, initiator: usize) -> &LocalSnapshot<S> {
+            let mut ss = self.ps[i].snapshots();
+            ss.retain(|(id, _)| id.initiator == initiator);
+            assert_eq!(ss.len(), 1);
+            ss[0].1
+        }
+        /// Number of events of every process in the snapshot initiated by `initiator`
+        fn cut(&self, initiator: usize) -> Vec<usize> {
+            (0..self.ps.len())
+                .map(|i| self.local(i, initiator).n_events)
+                .collect()
+        }
+        /// Number of messages in flight on every channel in the snapshot initiated by `initiator`
+        fn n_in_flight(&self, initiator: usize) -> usize {
+            (0..self.ps.len())
+                .flat_map(|i| &self.local(i, initiator).channels)
+                .map(Vec::len)
+                .sum()
+        }
+    }
+
+    /// Whether every receive in the cut has its send in the cut, by (receiver, idx, sender, idx)
+    fn is_consistent(cut: &[usize], msgs: &[(usize, usize, usize, usize)]) -> bool {
+        msgs.iter()
+            .all(|&(to, recv, from, send)| cut[to] <= recv || cut[from] > send)
+    }
 
     #[test]
     fn wont_snapshot_before_send_after_recv() {
-        let (tx1, rx1) = std::sync::mpsc::channel();
-        let (tx2, rx2) = std::sync::mpsc::channel();
-        let tx = Vec::from([tx1, tx2]);
-
-        let p1 = std::thread::spawn({
-            let tx = tx.clone();
-            move || {
-                let mut p = ChandyLamportProc::new(0, 2);
-                assert!(p.snapshots().is_empty());
-
-                p.global_snapshot(|e| tx.iter().for_each(|tx| tx.send(e.clone()).unwrap())); // snapshots on start
-                assert_eq!(p.snapshots().len(), 1);
-                assert!(p.snapshots().last().unwrap().1.is_empty()); // hence snapshot is empty
-
-                p.send(|e| tx[0].send(e).unwrap());
-                assert_eq!(p.snapshots().len(), 1); // no new snapshot
-                assert!(p.snapshots().last().unwrap().1.is_empty()); // old snapshot
-            }
+        let mut ts = MpscTransport::mesh(2);
+        let mut t1 = ts.pop().unwrap();
+        let mut t0 = ts.pop().unwrap();
+
+        let p0 = std::thread::spawn(move || {
+            let mut p = ChandyLamportProc::new(0, 2);
+            assert!(p.snapshots().is_empty());
+
+            p.global_snapshot(|e| t0.send_to(1, e).unwrap()); // snapshots on start
+            assert_eq!(p.snapshots().len(), 1);
+            assert_eq!(p.snapshots().last().unwrap().1.n_events, 0); // hence snapshot is empty
+
+            p.send_via(&mut t0, 1).unwrap();
+            assert_eq!(p.snapshots().len(), 1); // no new snapshot
+            assert_eq!(p.snapshots().last().unwrap().1.n_events, 0); // old snapshot
+            assert_eq!(p.recv_via(&mut t0).unwrap(), 1); // forwarded marker
+            assert_eq!(p.snapshots().len(), 1);
         });
 
-        let p2 = std::thread::spawn({
-            move || {
-                let mut p = ChandyLamportProc::new(1, 2);
-                assert!(p.snapshots().is_empty());
+        let p1 = std::thread::spawn(move || {
+            let mut p = ChandyLamportProc::new(1, 2);
+            assert!(p.snapshots().is_empty());
 
-                p.recv(|| rx2.recv().unwrap()); // should be snapshot
-                assert_eq!(p.snapshots().len(), 1);
-                assert_eq!(p.snapshots().last().unwrap().1.len(), 1); // immediately snapshot 1st recv
+            p.recv_via(&mut t1).unwrap(); // should be snapshot
+            assert_eq!(p.snapshots().len(), 1);
+            assert_eq!(p.snapshots().last().unwrap().1.n_events, 0); // before the send after it
 
-                p.recv(|| rx2.recv().unwrap()); // should be recv
-                assert_eq!(p.snapshots().len(), 1); // no new snapshot
-                assert_eq!(p.snapshots().last().unwrap().1.len(), 1); // old snapshot
-            }
+            p.recv_via(&mut t1).unwrap(); // should be recv
+            assert_eq!(p.snapshots().len(), 1); // no new snapshot
+            assert_eq!(p.snapshots().last().unwrap().1.n_events, 0); // old snapshot
+            assert_eq!(p.events().len(), 1);
         });
 
+        p0.join().unwrap();
         p1.join().unwrap();
-        p2.join().unwrap();
     }
 
     #[test]
     fn snapshot_after_send_before_recv() {
-        // 1 snapshot
+        let mut m = Mesh::new(3);
+        m.snapshot(0);
 
-        // 2 send
-        // 2 recv (snapshot)
+        m.send(1, 2);
+        assert_eq!(m.recv(1), 0); // snapshot, forwarded to 2
 
-        // 3 recv (snapshot)
-        // 3 recv
+        assert_eq!(m.recv(2), 0); // snapshot
+        assert_eq!(m.recv(2), 1); // in flight, since sent before 1's snapshot but received after 2's
+        assert_eq!(m.recv(2), 1); // forwarded marker, ignored
+        m.drain(0);
 
-        // in flight?
+        assert_eq!(m.cut(0), [0, 1, 0]);
+        assert_eq!(m.ps[2].events().len(), 1);
+        // Recorded as p2's channel state, since the marker from p1 came after it
+        let sent = m.ps[1].events()[0].clone();
+        assert_eq!(m.local(2, 0).channels[1], [sent]);
+        assert_eq!(m.n_in_flight(0), 1);
     }
 
     #[test]
     fn snapshot_after_send_recv() {
-        // 2 send
-        // 2 recv (snapshot)
+        let mut m = Mesh::new(3);
+        m.send(1, 2);
+        assert_eq!(m.recv(2), 1);
+
+        m.snapshot(0);
 
-        // 1 snapshot
+        assert_eq!(m.recv(1), 0); // snapshot
+        assert_eq!(m.recv(2), 0); // snapshot
+        assert_eq!(m.recv(2), 1); // forwarded marker, ignored
+        m.drain(0);
+        m.drain(1);
 
-        // 3 recv (snapshot)
-        // 3 recv
+        // Forwarded beyond the initiator's neighbours, yet only once per process
+        assert_eq!(m.cut(0), [0, 1, 1]);
+        assert!(m.ps.iter().all(|p| p.snapshots().len() == 1));
+        assert_eq!(m.n_in_flight(0), 0);
+    }
+
+    /// Two snapshots whose cuts are incomparable
+    fn concurrent_snapshots() -> (Mesh, [(usize, usize, usize, usize); 2]) {
+        let mut m = Mesh::new(3);
+        m.snapshot(0);
+        m.snapshot(2);
+        m.ps[0].exec(|| {});
+        m.ps[0].exec(|| {});
+        m.send(0, 1);
+        m.drain(0);
+        m.drain(1);
+        m.send(1, 2);
+        m.ps[2].exec(|| {});
+        m.drain(2);
+        (0..3).for_each(|i| m.drain(i));
+        // p0's 3rd event sent to p1's 1st, and p1's 2nd to p2's 2nd
+        (m, [(1, 0, 0, 2), (2, 1, 1, 1)])
     }
 
     #[test]
     fn union_snapshots() {
-        // send 1 - recv 1 - send 1 - recv 1
-        // send n - recv n
-        // send 1 - recv 1 - send 1 - recv 1
-        // send n - recv n
+        let (m, msgs) = concurrent_snapshots();
+        let (a, b) = (m.cut(0), m.cut(2));
+        assert_eq!((a.clone(), b.clone()), (vec![0, 0, 1], vec![3, 0, 0]));
+        // p0's send to p1 is after the first cut, but in flight in the second
+        assert_eq!((m.n_in_flight(0), m.n_in_flight(2)), (0, 1));
+        assert!(is_consistent(&a, &msgs) && is_consistent(&b, &msgs));
 
-        // all program order
-        // all recv with send
+        let union: Vec<_> = a.iter().zip(&b).map(|(a, b)| *a.max(b)).collect();
+        assert!(is_consistent(&union, &msgs));
     }
 
     #[test]
     fn intersection_snapshots() {
-        // all program order
-        // all recv with send
+        let (m, msgs) = concurrent_snapshots();
+        let (a, b) = (m.cut(0), m.cut(2));
+        let intersection: Vec<_> = a.iter().zip(&b).map(|(a, b)| *a.min(b)).collect();
+        assert_eq!(intersection, [0, 0, 0]);
+        assert!(is_consistent(&intersection, &msgs));
+        // Unlike a cut that includes a receive but not its send
+        assert!(!is_consistent(&[2, 1, 0], &msgs));
+    }
+
+    #[test]
+    fn snapshots_in_flight_at_once() {
+        let mut m = Mesh::new(2);
+        let a = m.snapshot(0);
+        m.send(0, 1);
+        let b = m.snapshot(0);
+        assert_eq!((a.seq, b.seq), (0, 1));
+
+        assert_eq!(m.recv(1), 0); // a
+        m.send(1, 0);
+        assert_eq!(m.recv(1), 0); // after a, but before b
+        assert_eq!(m.recv(1), 0); // b
+        m.drain(0);
+
+        let (p0, p1) = (&m.ps[0], &m.ps[1]);
+        assert_eq!(
+            p0.snapshots().iter().map(|s| s.0).collect::<Vec<_>>(),
+            [a, b]
+        );
+        assert_eq!(p1.snapshot(a).unwrap().n_events, 0);
+        assert_eq!(p1.snapshot(b).unwrap().n_events, 2);
+        // p1's send is after its local snapshot of a, but before that of b, which p0 received after its own
+        assert!(p0.snapshot(a).unwrap().channels[1].is_empty());
+        assert_eq!(p0.snapshot(b).unwrap().channels[1].len(), 1);
+        assert_eq!(p0.events().len(), 2);
+    }
+
+    #[test]
+    fn collects_global_snapshot() {
+        let (m, _) = concurrent_snapshots();
+        let id = |initiator| SnapshotId { initiator, seq: 0 };
+        for initiator in [0, 2] {
+            let global = GlobalSnapshot::collect(&m.ps, id(initiator)).unwrap();
+            let cut: Vec<_> = global.locals.iter().map(|l| l.events.len()).collect();
+            assert_eq!(cut, m.cut(initiator));
+            assert_eq!(global.n_in_flight(), m.n_in_flight(initiator));
+            assert!(global.is_consistent());
+        }
+        assert_eq!(m.ps[1].sender(0), Some(0));
+        assert_eq!(m.ps[0].sender(0), None);
+        assert!(GlobalSnapshot::collect(&m.ps, id(1)).is_err());
+
+        // p1 received p0's 3rd event, which is after p0's local state in the first snapshot
+        let mut global = GlobalSnapshot::collect(&m.ps, id(0)).unwrap();
+        global.locals[1].events = m.ps[1].events()[..1].to_vec();
+        global.locals[1].received = vec![m.ps[1].received[0].clone()];
+        assert!(!global.is_consistent());
+    }
+
+    #[test]
+    fn collector() {
+        let (m, _) = concurrent_snapshots();
+        let ids = [0, 2].map(|initiator| SnapshotId { initiator, seq: 0 });
+        let mut c = Collector::new(3);
+        // Reported in any order, interleaved between snapshots
+        for (pid, id) in [(2, ids[0]), (0, ids[1]), (0, ids[0]), (1, ids[1])] {
+            assert!(c
+                .add(id, m.ps[pid].local_state(id).unwrap())
+                .unwrap()
+                .is_none());
+        }
+        assert_eq!(c.n_pending(), 2);
+        assert!(c.add(ids[0], m.ps[2].local_state(ids[0]).unwrap()).is_err());
+
+        let global = c
+            .add(ids[0], m.ps[1].local_state(ids[0]).unwrap())
+            .unwrap()
+            .unwrap();
+        assert_eq!(global, GlobalSnapshot::collect(&m.ps, ids[0]).unwrap());
+        assert_eq!(c.n_pending(), 1);
+    }
+
+    #[test]
+    fn reports_once_complete() {
+        let (tx, rx) = std::sync::mpsc::channel();
+        let mut m = Mesh::new(3);
+        for p in &mut m.ps {
+            let (p_new, tx) = (ChandyLamportProc::new(p.i, 3), tx.clone());
+            *p = p_new.with_on_complete(move |id, local| tx.send((id, local)).unwrap());
+        }
+        let mut c = Collector::new(3);
+
+        let id = m.snapshot(1);
+        m.send(1, 0);
+        assert_eq!(m.recv(0), 1); // marker
+        assert!(!m.ps[0].is_complete(id)); // yet to hear from p2
+        assert!(rx.try_recv().is_err());
+        assert_eq!(m.recv(2), 1); // marker, complete once p0 forwarded it
+        assert_eq!(m.recv(2), 0);
+        assert!(m.ps[2].is_complete(id));
+        (0..3).for_each(|i| m.drain(i));
+
+        for (id, local) in rx.try_iter() {
+            assert!(!c.is_complete(id));
+            if let Some(global) = c.add(id, local).unwrap() {
+                assert_eq!(global, GlobalSnapshot::collect(&m.ps, id).unwrap());
+                assert_eq!(global.n_in_flight(), 0);
+            }
+        }
+        assert!(c.is_complete(id) && c.n_pending() == 0);
+        assert!(c.add(id, m.ps[0].local_state(id).unwrap()).is_err());
+    }
+
+    #[test]
+    fn applies_deltas() {
+        let mut m = Mesh::new(3);
+        let mut ids = Vec::new();
+        for round in 0..4 {
+            m.send(round % 3, (round + 1) % 3);
+            ids.push(m.snapshot(round % 3));
+            m.send((round + 2) % 3, round % 3);
+            for _ in 0..2 {
+                (0..3).for_each(|i| m.drain(i));
+            }
+        }
+        for p in &m.ps {
+            let mut local = p.local_state(ids[0]).unwrap();
+            for w in ids.windows(2) {
+                let delta = p.local_delta(w[1]).unwrap();
+                assert_eq!(delta.base, Some(w[0]));
+                assert_eq!(
+                    delta.events.len(),
+                    p.snapshot(w[1]).unwrap().n_events - local.events.len()
+                );
+                local.apply(delta).unwrap();
+                assert_eq!(local, p.local_state(w[1]).unwrap());
+            }
+            // Out of order
+            assert!(local.apply(p.local_delta(ids[1]).unwrap()).is_err());
+        }
+        assert_eq!(m.ps[0].local_delta(ids[0]).unwrap().base, None);
+    }
+
+    #[test]
+    fn delta_skips_incomplete_base() {
+        let mut m = Mesh::new(2);
+        let a = m.snapshot(0);
+        m.send(0, 1);
+        let b = m.snapshot(0);
+        for _ in 0..2 {
+            (0..2).for_each(|i| m.drain(i));
+        }
+        assert!(m.ps[0].is_complete(a) && m.ps[0].is_complete(b));
+        // a was yet to complete when b was recorded
+        let delta = m.ps[0].local_delta(b).unwrap();
+        assert_eq!((delta.base, delta.since, delta.events.len()), (None, 0, 1));
+        let c = m.snapshot(1);
+        for _ in 0..2 {
+            (0..2).for_each(|i| m.drain(i));
+        }
+        assert_eq!(m.ps[0].local_delta(c).unwrap().base, Some(b));
+        assert_eq!(m.ps[1].snapshot(c).unwrap().since, 1);
+    }
+
+    #[test]
+    fn captures_state() {
+        // Each message moves a coin from its sender to its receiver
+        let coins = Arc::new(Mutex::new(vec![10; 3]));
+        let mut m = Mesh {
+            ps: (0..3)
+                .map(|i| {
+                    let coins = coins.clone();
+                    ChandyLamportProc::new(i, 3).with_state(move || coins.lock().unwrap()[i])
+                })
+                .collect(),
+            ts: MpscTransport::mesh(3),
+        };
+        let transfer = |m: &mut Mesh<usize>, from: usize, to: usize| {
+            coins.lock().unwrap()[from] -= 1;
+            m.send(from, to);
+        };
+        transfer(&mut m, 0, 1);
+        transfer(&mut m, 1, 2);
+        let id = m.snapshot(1);
+        transfer(&mut m, 2, 0);
+        transfer(&mut m, 0, 2);
+        for _ in 0..2 {
+            for i in 0..3 {
+                m.drain_with(i, |_| coins.lock().unwrap()[i] += 1);
+            }
+        }
+
+        let global = GlobalSnapshot::collect(&m.ps, id).unwrap();
+        let states: Vec<_> = m.ps.iter().map(|p| p.snapshots()[0].1.state).collect();
+        assert_eq!(
+            states,
+            global.locals.iter().map(|l| l.state).collect::<Vec<_>>()
+        );
+        assert_eq!(states[1], 9); // sent to p2, yet to receive from p0
+
+        // Coins are conserved, counting those in flight
+        assert_eq!(states.iter().sum::<usize>() + global.n_in_flight(), 30);
+        assert!(global.is_consistent());
+        assert_eq!(coins.lock().unwrap().iter().sum::<usize>(), 30);
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn to_bytes() {
+        use crate::order::chandy_lamport::{LocalDelta, LocalState};
+        let (m, _) = concurrent_snapshots();
+        let id = SnapshotId {
+            initiator: 2,
+            seq: 0,
+        };
+        let global = GlobalSnapshot::collect(&m.ps, id).unwrap();
+        assert_eq!(
+            GlobalSnapshot::from_bytes(&global.to_bytes().unwrap()).unwrap(),
+            global
+        );
+        let local = m.ps[0].local_state(id).unwrap();
+        assert_eq!(
+            LocalState::from_bytes(&local.to_bytes().unwrap()).unwrap(),
+            local
+        );
+        let delta = m.ps[0].local_delta(id).unwrap();
+        assert_eq!(
+            LocalDelta::from_bytes(&delta.to_bytes().unwrap()).unwrap(),
+            delta
+        );
+        let snapshot = m.ps[0].snapshot(id).unwrap();
+        let json = serde_json::to_string(snapshot).unwrap();
+        assert_eq!(
+            &serde_json::from_str::<LocalSnapshot>(&json).unwrap(),
+            snapshot
+        );
+
+        // Out of order, or with events of another process
+        let mut swapped = global.clone();
+        swapped.locals.swap(0, 1);
+        assert!(GlobalSnapshot::<()>::from_bytes(&swapped.to_bytes().unwrap()).is_err());
+        let mut forged = local.clone();
+        forged.pid = 1;
+        assert!(LocalState::<()>::from_bytes(&forged.to_bytes().unwrap()).is_err());
+        assert!(LocalState::<()>::from_bytes(b"[]").is_err());
+    }
+
+    #[test]
+    fn detects_token_loss() {
+        // Tokens held by each process, of which p2 drops every one it receives after its 5th
+        let tokens = Arc::new(Mutex::new(vec![2, 1, 0]));
+        let mut ps: Vec<_> = (0..3)
+            .map(|i| {
+                let tokens = tokens.clone();
+                ChandyLamportProc::new(i, 3).with_state(move || tokens.lock().unwrap()[i])
+            })
+            .collect();
+        let mut ts = MpscTransport::mesh(3);
+        let pass = |p: &mut ChandyLamportProc<usize>, t: &mut MpscTransport<_>| {
+            tokens.lock().unwrap()[p.pid()] -= 1;
+            p.send_via(t, (p.pid() + 1) % 3)
+        };
+        for i in [0, 0, 1] {
+            pass(&mut ps[i], &mut ts[i]).unwrap();
+        }
+        let mut n_received = 0;
+        let on_recv = |p: &mut ChandyLamportProc<usize>, t: &mut MpscTransport<_>, _| {
+            if p.pid() == 2 {
+                n_received += 1;
+                if n_received > 5 {
+                    return Ok(());
+                }
+            }
+            tokens.lock().unwrap()[p.pid()] += 1;
+            pass(p, t)
+        };
+        let n_tokens = |g: &GlobalSnapshot<usize>| {
+            g.locals.iter().map(|l| l.state).sum::<usize>() + g.n_in_flight()
+        };
+        let global = detect_stable(&mut ps, &mut ts, on_recv, |g| n_tokens(g) == 0)
+            .unwrap()
+            .unwrap();
+        assert!(global.is_consistent());
+        assert_eq!(n_received, 8);
+        assert!(ps[0].snapshots().len() > 1);
+        // Every earlier snapshot still counted some token
+        for (id, _) in ps[0].snapshots() {
+            let g = GlobalSnapshot::collect(&ps, id).unwrap();
+            assert_eq!(n_tokens(&g) == 0, id == global.id);
+        }
+    }
+
+    #[test]
+    fn detect_stable_stops_once_quiescent() {
+        let mut ps: Vec<_> = (0..2).map(|i| ChandyLamportProc::new(i, 2)).collect();
+        let mut ts = MpscTransport::mesh(2);
+        ps[0].send_via(&mut ts[0], 1).unwrap();
+        let mut n_received = 0;
+        let on_recv = |_: &mut ChandyLamportProc, _: &mut MpscTransport<_>, from| {
+            assert_eq!(from, 0);
+            n_received += 1;
+            Ok(())
+        };
+        assert!(detect_stable(&mut ps, &mut ts, on_recv, |_| false)
+            .unwrap()
+            .is_none());
+        assert_eq!(n_received, 1);
+        // The first snapshot delivered the message, and the second nothing
+        assert_eq!(ps[1].snapshots().len(), 2);
+        let on_recv = |_: &mut ChandyLamportProc, _: &mut MpscTransport<_>, _| Ok(());
+        assert!(detect_stable(&mut ps, &mut ts[..1], on_recv, |_| true).is_err());
+    }
+
+    #[test]
+    fn merges_concurrent_snapshots() {
+        let mut m = Mesh::merging(3);
+        let a = m.snapshot(0);
+        let b = m.snapshot(2);
+        assert_eq!(m.snapshot(0), a); // already in progress
+        m.send(0, 1);
+        m.send(1, 2);
+        for _ in 0..2 {
+            (0..3).for_each(|i| m.drain(i));
+        }
+
+        // p1 joined the region of the first marker to arrive, then merged the other
+        assert!(m.ps.iter().all(|p| p.snapshots().len() == 1));
+        assert!(m.ps.iter().all(|p| p.is_complete(a) && p.is_complete(b)));
+        assert_eq!(m.ps[1].snapshots()[0].0, a);
+        let global = GlobalSnapshot::collect(&m.ps, b).unwrap();
+        assert_eq!(global, GlobalSnapshot::collect(&m.ps, a).unwrap());
+        assert_eq!(global.id, a);
+        assert!(global.is_consistent());
+        assert!(global.locals.iter().all(|l| l.merged == [a, b]));
+
+        let mut c = Collector::new(3);
+        assert!(c.add(b, m.ps[2].local_state(b).unwrap()).unwrap().is_none());
+        assert!(c.add(a, m.ps[0].local_state(a).unwrap()).unwrap().is_none());
+        assert_eq!(c.n_pending(), 1);
+        assert!(c.add(a, m.ps[2].local_state(a).unwrap()).is_err());
+        let merged = c.add(b, m.ps[1].local_state(b).unwrap()).unwrap();
+        assert_eq!(merged, Some(global));
+        assert!(c.is_complete(a) && c.is_complete(b));
+    }
+
+    #[test]
+    fn merging_keeps_later_snapshots_apart() {
+        let mut ps: Vec<_> = (0..3)
+            .map(|i| ChandyLamportProc::new(i, 3).with_merging())
+            .collect();
+        // FIFO per channel, by (to, from), but not across channels
+        type Msg = (usize, usize, ChandyLamportClock);
+        let mut q: Vec<Msg> = Vec::new();
+        fn deliver(ps: &mut [ChandyLamportProc], q: &mut Vec<Msg>, to: usize, from: usize) {
+            let idx = q
+                .iter()
+                .position(|&(t, f, _)| (t, f) == (to, from))
+                .unwrap();
+            let (_, _, msg) = q.remove(idx);
+            ps[to].recv_and_forward(
+                || (from, msg),
+                |m| {
+                    (0..3)
+                        .filter(|&j| j != to)
+                        .for_each(|j| q.push((j, to, m.clone())))
+                },
+            );
+        }
+        let a =
+            ps[0].global_snapshot(|m| [1, 2].into_iter().for_each(|j| q.push((j, 0, m.clone()))));
+        deliver(&mut ps, &mut q, 2, 0);
+        deliver(&mut ps, &mut q, 1, 0);
+        deliver(&mut ps, &mut q, 2, 1);
+        assert!(ps[2].is_complete(a));
+        let b =
+            ps[2].global_snapshot(|m| [0, 1].into_iter().for_each(|j| q.push((j, 2, m.clone()))));
+        assert_ne!(a, b);
+
+        deliver(&mut ps, &mut q, 0, 2); // a
+        deliver(&mut ps, &mut q, 0, 2); // b, overtaking a from p1
+        assert!(!ps[0].is_complete(a));
+        assert_eq!(ps[0].snapshots().len(), 2);
+        while let Some(&(to, from, _)) = q.first() {
+            deliver(&mut ps, &mut q, to, from);
+        }
+        for p in &ps {
+            let ids: Vec<_> = p.snapshots().iter().map(|s| s.0).collect();
+            assert_eq!(ids, [a, b]);
+            assert!(p.is_complete(a) && p.is_complete(b));
+            assert_eq!(p.local_state(b).unwrap().merged, [b]);
+        }
     }
 }
@@ -0,0 +1,382 @@
+use crate::order::{trace_event, HasEvents, LogicalClock, OrdProcess, OrderError, Process};
+use core::marker::PhantomData;
+use std::collections::BTreeSet;
+use std::sync::mpsc::{Receiver, RecvTimeoutError, Sender};
+use std::sync::Mutex;
+use std::thread::Scope;
+use std::time::Duration;
+
+/// Runs N processes on their own threads, connected all-to-all, then collects their event logs.
+///
+/// Processes may join and leave while the cluster runs. A joining process gets the next pid and runs the same
+/// closure, and every member is told of joins and departures. Clocks grow as they merge with clocks of processes that
+/// joined since.
+///
+/// # Examples
+/// ```
+/// use rads::order::cluster::Cluster;
+/// use rads::order::vector_clock::VectorClock;
+///
+/// let logs = Cluster::<VectorClock>::new(2).spawn(|pid, p| match pid {
+///     0 => p.send_to(1).unwrap(),
+///     _ => assert_eq!(p.recv().unwrap(), 0),
+/// });
+/// assert!(logs[0][0] < logs[1][0]);
+/// ```
+///
+/// A process joins, and learns of the process that let it join leaving.
+/// ```
+/// use rads::order::cluster::Cluster;
+/// use rads::order::vector_clock::VectorClock;
+///
+/// let logs = Cluster::<VectorClock>::new(1).spawn(|pid, p| match pid {
+///     0 => {
+///         let joiner = p.join().unwrap();
+///         p.send_to(joiner).unwrap();
+///         p.leave();
+///     }
+///     _ => {
+///         assert_eq!(p.members().collect::<Vec<_>>(), [0, 1]);
+///         assert_eq!(p.recv().unwrap(), 0);
+///         assert!(p.recv().is_err());
+///         assert_eq!(p.members().collect::<Vec<_>>(), [1]);
+///     }
+/// });
+/// assert!(logs[0][0] < logs[1][0]);
+/// ```
+pub struct Cluster<C> {
+    n_procs: usize,
+    _clock: PhantomData<C>,
+}
+
+/// Message between members of a `Cluster`
+enum Envelope<C> {
+    Clock(C),
+    Joined(usize),
+    Left(usize),
+}
+
+/// Sending end of the channel to a process, which is None once it left
+type Link<C> = Option<Sender<(usize, Envelope<C>)>>;
+
+/// Channels to every process that ever joined, and its event log once it finished
+struct Registry<C> {
+    senders: Mutex<Vec<Link<C>>>,
+    logs: Mutex<Vec<Option<std::thread::Result<Vec<C>>>>>,
+}
+
+type Body<'env, C> = dyn Fn(usize, &mut Handle<'_, 'env, C>) + Sync + 'env;
+
+/// How often a blocked `recv` checks whether any other member is left
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+impl<C: LogicalClock + Send> Cluster<C> {
+    pub fn new(n_procs: usize) -> Self {
+        Self {
+            n_procs,
+            _clock: PhantomData,
+        }
+    }
+    /// Runs `f` on every process, including those that join later, returning each event log by pid once all finished
+    ///
+    /// Panics if any process panicked.
+    pub fn spawn<F>(self, f: F) -> Vec<Vec<C>>
+    where
+        F: Fn(usize, &mut Handle<'_, '_, C>) + Sync,
+    {
+        let registry = Registry {
+            senders: Mutex::new(Vec::new()),
+            logs: Mutex::new(Vec::new()),
+        };
+        let body: &Body<C> = &f;
+        std::thread::scope(|s| {
+            let mut senders = registry.lock();
+            let rxs: Vec<_> = (0..self.n_procs)
+                .map(|_| registry.register(&mut senders).1)
+                .collect();
+            drop(senders);
+            for (pid, rx) in rxs.into_iter().enumerate() {
+                let members = (0..self.n_procs).collect();
+                run(s, &registry, body, pid, rx, members);
+            }
+        });
+        let logs = registry
+            .logs
+            .into_inner()
+            .unwrap_or_else(|e| e.into_inner());
+        logs.into_iter()
+            .map(|log| match log.expect("Expect every process to finish") {
+                Ok(log) => log,
+                Err(e) => std::panic::resume_unwind(e),
+            })
+            .collect()
+    }
+}
+
+impl<C> Registry<C> {
+    /// Adds a channel to a new process, returning its pid and the receiving end
+    fn register(&self, senders: &mut Vec<Link<C>>) -> (usize, Receiver<(usize, Envelope<C>)>) {
+        let (tx, rx) = std::sync::mpsc::channel();
+        senders.push(Some(tx));
+        self.logs
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(None);
+        (senders.len() - 1, rx)
+    }
+    fn lock(&self) -> std::sync::MutexGuard<'_, Vec<Link<C>>> {
+        // A panicking process never holds the lock while it runs `f`
+        self.senders.lock().unwrap_or_else(|e| e.into_inner())
+    }
+    fn send(&self, from: usize, to: usize, e: Envelope<C>) -> Result<(), OrderError> {
+        let senders = self.lock();
+        let tx = senders
+            .get(to)
+            .and_then(Option::as_ref)
+            .ok_or(OrderError::Disconnected)?;
+        tx.send((from, e)).map_err(|_| OrderError::Disconnected)
+    }
+    /// Whether any process other than `pid` has not left yet
+    fn has_others(&self, pid: usize) -> bool {
+        self.lock()
+            .iter()
+            .enumerate()
+            .any(|(j, tx)| j != pid && tx.is_some())
+    }
+    /// Announces that `pid` left to every other member, unless it already did
+    fn leave(&self, pid: usize) {
+        let mut senders = self.lock();
+        if senders[pid].take().is_some() {
+            for tx in senders.iter().flatten() {
+                let _ = tx.send((pid, Envelope::Left(pid))); // unless it finished
+            }
+        }
+    }
+}
+
+/// Spawns process `pid` on the scope
+fn run<'scope, 'env, C: LogicalClock + Send>(
+    scope: &'scope Scope<'scope, 'env>,
+    registry: &'env Registry<C>,
+    body: &'env Body<'env, C>,
+    pid: usize,
+    rx: Receiver<(usize, Envelope<C>)>,
+    members: BTreeSet<usize>,
+) {
+    scope.spawn(move || {
+        let res = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            let mut h = Handle {
+                process: Process::new(pid, pid.max(members.last().copied().unwrap_or(0)) + 1),
+                rx,
+                members,
+                registry,
+                scope,
+                body,
+            };
+            body(pid, &mut h);
+            h.process.snapshot()
+        }));
+        registry.logs.lock().unwrap_or_else(|e| e.into_inner())[pid] = Some(res);
+    });
+}
+
+/// Process of a `Cluster`, with its channels to every other member.
+///
+/// Leaves the cluster when dropped.
+pub struct Handle<'scope, 'env, C: LogicalClock> {
+    process: Process<C>,
+    rx: Receiver<(usize, Envelope<C>)>,
+    /// Members this process was told of, including itself until it leaves
+    members: BTreeSet<usize>,
+    registry: &'env Registry<C>,
+    scope: &'scope Scope<'scope, 'env>,
+    body: &'env Body<'env, C>,
+}
+
+impl<C: LogicalClock + Send> Handle<'_, '_, C> {
+    pub fn pid(&self) -> usize {
+        self.process.pid()
+    }
+    /// Number of processes this process knows of, including those that left
+    pub fn n_procs(&self) -> usize {
+        self.process
+            .n_procs()
+            .max(self.members.last().map_or(0, |j| j + 1))
+    }
+    /// Pids of the members this process was told of
+    pub fn members(&self) -> impl Iterator<Item = usize> + '_ {
+        self.members.iter().copied()
+    }
+    pub fn events(&self) -> &[C] {
+        self.process.events()
+    }
+    pub fn exec<F: FnOnce()>(&mut self, f: F) {
+        self.process.exec(f)
+    }
+    pub fn send_to(&mut self, pid: usize) -> Result<(), OrderError> {
+        if !self.members.contains(&pid) {
+            return Err(OrderError::Other(anyhow::anyhow!(
+                "Expect process {pid} to be a member"
+            )));
+        }
+        let (i, registry) = (self.pid(), self.registry);
+        self.process
+            .try_send(|e| registry.send(i, pid, Envelope::Clock(e)))
+    }
+    /// Sends one clock to every other member, i.e. a single send event. The event is recorded if any member was sent
+    /// the clock, even if sending to another failed.
+    pub fn send_all(&mut self) -> Result<(), OrderError> {
+        let i = self.pid();
+        let e = self
+            .process
+            .last_event()
+            .unwrap_or(&C::new(i, self.process.n_procs()))
+            .extend();
+        let (mut sent, mut res) = (false, Ok(()));
+        for j in self.members().filter(|&j| j != i) {
+            match self.registry.send(i, j, Envelope::Clock(e.clone())) {
+                Ok(()) => sent = true,
+                Err(err) => res = res.and(Err(err)),
+            }
+        }
+        if sent {
+            trace_event("send_all", i, &e);
+            self.process.push_event(e);
+        }
+        res
+    }
+    /// Blocks until any process sends, returning its pid, or fails once every other member left
+    pub fn recv(&mut self) -> Result<usize, OrderError> {
+        loop {
+            match self.rx.recv_timeout(POLL_INTERVAL) {
+                Ok((from, Envelope::Clock(e))) => return Ok(self.process.recv_from(|| (from, e))),
+                Ok((_, Envelope::Joined(pid))) => {
+                    self.members.insert(pid);
+                }
+                Ok((_, Envelope::Left(pid))) => {
+                    self.members.remove(&pid);
+                }
+                Err(RecvTimeoutError::Timeout) if self.registry.has_others(self.pid()) => {}
+                Err(_) => return Err(OrderError::Disconnected),
+            }
+        }
+    }
+    /// Starts a new process running the same closure, announcing it to every member, and returns its pid
+    pub fn join(&mut self) -> Result<usize, OrderError> {
+        let i = self.pid();
+        if !self.members.contains(&i) {
+            return Err(OrderError::Other(anyhow::anyhow!(
+                "Expect process {i} to be a member to let others join"
+            )));
+        }
+        let mut senders = self.registry.lock();
+        let (pid, rx) = self.registry.register(&mut senders);
+        // Announced while locked, so that every member hears of it before any message from it
+        let members: BTreeSet<_> = (0..=pid).filter(|&j| senders[j].is_some()).collect();
+        for j in members.iter().filter(|&&j| j != i && j != pid) {
+            if let Some(tx) = &senders[*j] {
+                let _ = tx.send((i, Envelope::Joined(pid))); // unless it finished
+            }
+        }
+        drop(senders);
+        self.members.insert(pid);
+        run(self.scope, self.registry, self.body, pid, rx, members);
+        Ok(pid)
+    }
+    /// Announces to every other member that this process left, after which it can no longer send or receive
+    pub fn leave(&mut self) {
+        self.registry.leave(self.pid());
+        self.members.clear();
+    }
+}
+
+impl<C: LogicalClock> Drop for Handle<'_, '_, C> {
+    fn drop(&mut self) {
+        self.registry.leave(self.process.pid());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::order::cluster::Cluster;
+    use crate::order::matrix_clock::MatrixClock;
+    use crate::order::vector_clock::VectorClock;
+    use crate::order::OrderError;
+
+    #[test]
+    fn mock_scheduler() {
+        let logs = Cluster::<VectorClock>::new(3).spawn(|pid, p| match pid {
+            0 => {
+                p.exec(|| {});
+                p.send_to(1).unwrap();
+            }
+            1 => {
+                assert_eq!(p.recv().unwrap(), 0);
+                p.send_to(2).unwrap();
+            }
+            _ => assert_eq!(p.recv().unwrap(), 1),
+        });
+        assert_eq!(logs.iter().map(Vec::len).collect::<Vec<_>>(), [2, 2, 1]);
+        assert!(logs[0].iter().all(|s| s < &logs[2][0]));
+        assert!(logs[1].iter().all(|s| s < &logs[2][0]));
+    }
+
+    #[test]
+    fn any_clock() {
+        let logs = Cluster::<MatrixClock>::new(2).spawn(|pid, p| {
+            assert_eq!((p.pid(), p.n_procs()), (pid, 2));
+            p.send_to(1 - pid).unwrap();
+            p.recv().unwrap();
+            assert_eq!(p.events().len(), 2);
+        });
+        assert!(logs[0][0] < logs[1][1]);
+        assert!(logs[1][0] < logs[0][1]);
+    }
+
+    #[test]
+    fn send_all() {
+        let logs = Cluster::<VectorClock>::new(3).spawn(|pid, p| match pid {
+            0 => p.send_all().unwrap(),
+            _ => assert_eq!(p.recv().unwrap(), 0),
+        });
+        assert_eq!(logs[0].len(), 1);
+        assert!(logs[0][0] < logs[1][0] && logs[0][0] < logs[2][0]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn propagates_panic() {
+        Cluster::<VectorClock>::new(2).spawn(|pid, _| assert_eq!(pid, 0));
+    }
+
+    #[test]
+    fn membership() {
+        let logs = Cluster::<VectorClock>::new(2).spawn(|pid, p| match pid {
+            0 => {
+                p.exec(|| {});
+                assert_eq!(p.join().unwrap(), 2);
+                p.send_to(2).unwrap();
+                assert_eq!(p.recv().unwrap(), 2);
+            }
+            1 => {
+                // Told of the joiner before its message
+                assert_eq!(p.recv().unwrap(), 2);
+                assert_eq!(p.members().collect::<Vec<_>>(), [0, 1, 2]);
+                assert!(p.send_to(3).is_err());
+                p.leave();
+                assert!(p.send_to(0).is_err());
+                assert!(matches!(p.recv(), Err(OrderError::Disconnected)));
+            }
+            _ => {
+                assert_eq!((p.n_procs(), p.events().len()), (3, 0));
+                assert_eq!(p.recv().unwrap(), 0);
+                p.send_all().unwrap();
+            }
+        });
+        assert_eq!(logs.iter().map(Vec::len).collect::<Vec<_>>(), [3, 1, 2]);
+        // Clocks of the initial processes grew to merge with the joiner's
+        assert!(logs[0][1] < logs[2][0]);
+        assert!(logs[2][1] < logs[0][2] && logs[2][1] < logs[1][0]);
+        assert_eq!((logs[0][1].len(), logs[1][0].len()), (2, 3));
+    }
+}
@@ -0,0 +1,193 @@
+use crate::order::{CausalOrd, LogicalClock};
+use alloc::vec::Vec;
+
+/// Whether the cut, given by the last event of each process in it by pid, is consistent, i.e. left-closed under
+/// happened before: no event in the cut depends on an event after the cut.
+///
+/// A process without events in the cut has its initial clock `LogicalClock::new` as its last event. Event `e` of
+/// process `i` depends on an event after the cut on process `j` iff `cut[j].extend() <= e`, which is exact for clocks
+/// that characterize happened before, e.g. vector clocks. Other clocks, e.g. Lamport clocks, may reject consistent
+/// cuts, but never accept inconsistent ones.
+///
+/// # Examples
+/// ```
+/// use rads::order::cut::is_consistent;
+/// use rads::order::vector_clock::VectorClock;
+/// use rads::order::{HasEvents, LogicalClock, OrdProcess, Process};
+///
+/// let mut p0 = Process::<VectorClock>::new(0, 2);
+/// let mut p1 = Process::<VectorClock>::new(1, 2);
+/// let mut msg = None;
+/// p0.send(|e| msg = Some(e));
+/// p1.recv(|| msg.unwrap());
+/// assert!(is_consistent(&[p0.events()[0].clone(), p1.events()[0].clone()]));
+/// // Receives a message that is yet to be sent
+/// assert!(!is_consistent(&[VectorClock::new(0, 2), p1.events()[0].clone()]));
+/// ```
+pub fn is_consistent<E: LogicalClock + CausalOrd>(cut: &[E]) -> bool {
+    (0..cut.len()).all(|i| is_closed(cut, i))
+}
+
+/// Largest consistent cut below `cut`, given each process's events by pid and the number of them in `cut`.
+///
+/// Returns the number of events of each process in the cut, as in `ChandyLamportProc`'s snapshots. Consistent cuts
+/// are closed under intersection, so every consistent cut below `cut` is also below this one.
+///
+/// # Examples
+/// ```
+/// use rads::order::cut::max_consistent_cut;
+/// use rads::order::vector_clock::VectorClock;
+/// use rads::order::{HasEvents, OrdProcess, Process};
+///
+/// let mut p0 = Process::<VectorClock>::new(0, 2);
+/// let mut p1 = Process::<VectorClock>::new(1, 2);
+/// let mut msg = None;
+/// p0.exec(|| {});
+/// p0.send(|e| msg = Some(e));
+/// p1.exec(|| {});
+/// p1.recv(|| msg.unwrap());
+/// // p1's receive needs p0's send, so the cut stops before it
+/// assert_eq!(max_consistent_cut(&[p0.events(), p1.events()], &[1, 2]), [1, 1]);
+/// ```
+pub fn max_consistent_cut<E: LogicalClock + CausalOrd>(
+    histories: &[&[E]],
+    cut: &[usize],
+) -> Vec<usize> {
+    assert_eq!(
+        histories.len(),
+        cut.len(),
+        "Expect the events of each process in the cut"
+    );
+    let mut cut: Vec<_> = cut
+        .iter()
+        .zip(histories)
+        .map(|(&n, h)| n.min(h.len()))
+        .collect();
+    let mut frontier = frontier(histories, &cut);
+    // Each retreat only makes others more likely to retreat, so repeat until none does
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for i in 0..cut.len() {
+            while !is_closed(&frontier, i) {
+                cut[i] -= 1;
+                frontier[i] = last(histories, &cut, i);
+                changed = true;
+            }
+        }
+    }
+    cut
+}
+
+/// Last event of each process in the cut
+fn frontier<E: LogicalClock>(histories: &[&[E]], cut: &[usize]) -> Vec<E> {
+    (0..cut.len()).map(|i| last(histories, cut, i)).collect()
+}
+
+fn last<E: LogicalClock>(histories: &[&[E]], cut: &[usize], i: usize) -> E {
+    match cut[i] {
+        0 => E::new(i, cut.len()),
+        n => histories[i][n - 1].clone(),
+    }
+}
+
+/// Whether the last event of process `i` only depends on events in the cut
+fn is_closed<E: LogicalClock + CausalOrd>(cut: &[E], i: usize) -> bool {
+    cut.iter()
+        .enumerate()
+        .filter(|&(j, _)| j != i)
+        .all(|(_, e)| e.extend().partial_cmp(&cut[i]).is_none_or(|o| o.is_gt()))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::order::cut::{frontier, is_consistent, max_consistent_cut};
+    use crate::order::tests::random_history;
+    use crate::order::vector_clock::VectorClock;
+
+    /// Events of each process in a random run, by pid
+    fn histories(seed: u64) -> Vec<Vec<VectorClock>> {
+        let n_procs = 3;
+        let events = random_history::<VectorClock>(seed, n_procs, 15);
+        // Skips the initial clock of each process
+        (0..n_procs)
+            .map(|i| {
+                let h = events[n_procs..].iter().filter(|e| e.pid() == i);
+                h.cloned().collect()
+            })
+            .collect()
+    }
+
+    /// Whether no event after the cut happens before an event in it, by brute force
+    fn is_left_closed(histories: &[&[VectorClock]], cut: &[usize]) -> bool {
+        histories.iter().zip(cut).all(|(h, &n)| {
+            h[..n].iter().all(|e| {
+                histories.iter().zip(cut).all(|(g, &m)| {
+                    g[m..]
+                        .iter()
+                        .all(|f| f.partial_cmp(e).is_none_or(|o| o.is_gt()))
+                })
+            })
+        })
+    }
+
+    /// Every cut of the histories
+    fn cuts(histories: &[&[VectorClock]]) -> Vec<Vec<usize>> {
+        histories.iter().fold(vec![vec![]], |cuts, h| {
+            cuts.into_iter()
+                .flat_map(|c| {
+                    (0..=h.len()).map(move |n| {
+                        let mut c = c.clone();
+                        c.push(n);
+                        c
+                    })
+                })
+                .collect()
+        })
+    }
+
+    #[test]
+    fn consistent_iff_left_closed() {
+        let (mut n_consistent, mut n_inconsistent) = (0, 0);
+        for seed in 0..5 {
+            let hs = histories(seed);
+            let hs: Vec<_> = hs.iter().map(Vec::as_slice).collect();
+            for cut in cuts(&hs) {
+                let consistent = is_consistent(&frontier(&hs, &cut));
+                assert_eq!(consistent, is_left_closed(&hs, &cut), "{cut:?}");
+                match consistent {
+                    true => n_consistent += 1,
+                    false => n_inconsistent += 1,
+                }
+            }
+        }
+        assert!(n_consistent > 0 && n_inconsistent > 0);
+    }
+
+    #[test]
+    fn max_consistent_cut_is_greatest() {
+        for seed in 0..5 {
+            let hs = histories(seed);
+            let hs: Vec<_> = hs.iter().map(Vec::as_slice).collect();
+            let all = cuts(&hs);
+            for cut in &all {
+                let max = max_consistent_cut(&hs, cut);
+                assert!(is_left_closed(&hs, &max));
+                assert!(max.iter().zip(cut).all(|(m, c)| m <= c));
+                let below = |c: &Vec<usize>| c.iter().zip(cut).all(|(a, b)| a <= b);
+                for c in all.iter().filter(|c| below(c) && is_left_closed(&hs, c)) {
+                    assert!(c.iter().zip(&max).all(|(a, b)| a <= b), "{c:?} > {max:?}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn clamps_to_history() {
+        let hs = histories(0);
+        let hs: Vec<_> = hs.iter().map(Vec::as_slice).collect();
+        let lens: Vec<_> = hs.iter().map(|h| h.len()).collect();
+        assert_eq!(max_consistent_cut(&hs, &[usize::MAX; 3]), lens);
+        assert!(is_consistent::<VectorClock>(&[]));
+    }
+}
@@ -0,0 +1,163 @@
+use crate::order::{CausalOrd, LogicalClock};
+use core::cmp::Ordering;
+
+/// Epoch Clock restarts the history of an inner clock whenever the epoch (a.k.a. term) changes, e.g. after a leader
+/// election.
+///
+/// Events are ordered by epoch first, then by the inner clock. Merging with a clock from a newer epoch adopts that
+/// epoch and discards the local history, while clocks from an older epoch are stale and only count as a local event.
+///
+/// # Examples
+/// ```
+/// use rads::order::LogicalClock;
+/// use rads::order::epoch_clock::EpochClock;
+/// use rads::order::vector_clock::VectorClock;
+///
+/// let e1 = EpochClock::<VectorClock>::new(0, 2).extend();
+/// let f1 = EpochClock::<VectorClock>::new(1, 2).next_epoch();
+/// assert!(e1 < f1);
+/// let e2 = e1.merge(&f1);
+/// assert_eq!(e2.epoch(), 1);
+/// assert!(f1 < e2);
+/// ```
+#[derive(Clone, Hash, PartialEq, Eq)]
+#[cfg_attr(test, derive(Debug))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct EpochClock<C> {
+    i: usize,
+    n_procs: usize,
+    epoch: usize,
+    clk: C,
+}
+
+impl<C: LogicalClock> EpochClock<C> {
+    pub fn epoch(&self) -> usize {
+        self.epoch
+    }
+    pub fn inner(&self) -> &C {
+        &self.clk
+    }
+    /// Starts the next epoch with a fresh inner clock
+    pub fn next_epoch(&self) -> Self {
+        Self {
+            i: self.i,
+            n_procs: self.n_procs,
+            epoch: self.epoch + 1,
+            clk: C::new(self.i, self.n_procs),
+        }
+    }
+}
+
+impl<C: LogicalClock> LogicalClock for EpochClock<C> {
+    fn new(i: usize, n_procs: usize) -> Self {
+        Self {
+            i,
+            n_procs,
+            epoch: 0,
+            clk: C::new(i, n_procs),
+        }
+    }
+    fn extend(&self) -> Self {
+        Self {
+            clk: self.clk.extend(),
+            ..self.clone()
+        }
+    }
+    fn merge(&self, other: &Self) -> Self {
+        let clk = match self.epoch.cmp(&other.epoch) {
+            // History restarted, forget everything from older epochs
+            Ordering::Less => C::new(self.i, self.n_procs).merge(&other.clk),
+            Ordering::Equal => self.clk.merge(&other.clk),
+            // Stale clock, nothing to learn from it
+            Ordering::Greater => self.clk.extend(),
+        };
+        Self {
+            epoch: self.epoch.max(other.epoch),
+            clk,
+            ..self.clone()
+        }
+    }
+    fn observe(&self, other: &Self) -> Self {
+        let clk = match self.epoch.cmp(&other.epoch) {
+            Ordering::Less => C::new(self.i, self.n_procs).observe(&other.clk),
+            Ordering::Equal => self.clk.observe(&other.clk),
+            Ordering::Greater => self.clk.clone(),
+        };
+        Self {
+            epoch: self.epoch.max(other.epoch),
+            clk,
+            ..self.clone()
+        }
+    }
+    fn fmt_clock(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "epoch {} ", self.epoch)?;
+        self.clk.fmt_clock(f)
+    }
+    fn byte_size(&self) -> usize {
+        core::mem::size_of::<Self>() - core::mem::size_of::<C>() + self.clk.byte_size()
+    }
+}
+
+impl<C: LogicalClock + PartialOrd> PartialOrd for EpochClock<C> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        match self.epoch.cmp(&other.epoch) {
+            Ordering::Equal => self.clk.partial_cmp(&other.clk),
+            ord => Some(ord),
+        }
+    }
+}
+
+impl<C: LogicalClock + CausalOrd> CausalOrd for EpochClock<C> {}
+
+#[cfg(test)]
+mod tests {
+    use crate::order::epoch_clock::EpochClock;
+    use crate::order::vector_clock::VectorClock;
+    use crate::order::LogicalClock;
+
+    #[test]
+    fn next_epoch_resets() {
+        let e1 = EpochClock::<VectorClock>::new(0, 2).extend().extend();
+        let e2 = e1.next_epoch();
+        assert_eq!(e2.epoch(), 1);
+        assert_eq!(e2.inner(), &VectorClock::new(0, 2));
+        assert!(e1 < e2);
+    }
+
+    #[test]
+    fn partial_ord() {
+        let e1 = EpochClock::<VectorClock>::new(0, 2);
+        let f1 = EpochClock::<VectorClock>::new(1, 2);
+        assert_eq!(e1.partial_cmp(&f1), None); // concurrent within the same epoch
+        let f2 = f1.merge(&e1);
+        assert!(e1 < f2);
+
+        // Any event of a newer epoch is ordered after older epochs
+        let e2 = e1.extend().extend().next_epoch();
+        assert!(f2 < e2);
+        assert!(f1 < e2);
+    }
+
+    #[test]
+    fn merge_across_epochs() {
+        let e1 = EpochClock::<VectorClock>::new(0, 2).extend();
+        let f1 = EpochClock::<VectorClock>::new(1, 2).next_epoch();
+
+        // Adopt newer epoch, dropping own history
+        let e2 = e1.merge(&f1);
+        assert_eq!(e2.epoch(), 1);
+        assert_eq!(e2.inner(), &VectorClock::new(0, 2).merge(f1.inner()));
+        assert!(f1 < e2);
+
+        // Stale clocks only extend own history
+        let f2 = f1.merge(&e1);
+        assert_eq!(f2.epoch(), 1);
+        assert_eq!(f2.inner(), &f1.inner().extend());
+        assert!(f1 < f2);
+    }
+
+    #[test]
+    fn causal_ord_laws() {
+        crate::order::tests::assert_causal_ord_laws::<EpochClock<VectorClock>>();
+    }
+}
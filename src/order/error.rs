@@ -0,0 +1,54 @@
+/// Failure of a channel or user code mid-protocol, instead of panicking.
+#[derive(Debug)]
+pub enum OrderError {
+    /// The other end of the channel hung up
+    Disconnected,
+    /// Any other failure, e.g. of a transport or user code
+    Other(anyhow::Error),
+}
+
+impl core::fmt::Display for OrderError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            OrderError::Disconnected => write!(f, "Channel disconnected"),
+            OrderError::Other(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for OrderError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            OrderError::Disconnected => None,
+            OrderError::Other(e) => Some(e.as_ref()),
+        }
+    }
+}
+
+impl From<anyhow::Error> for OrderError {
+    fn from(e: anyhow::Error) -> Self {
+        OrderError::Other(e)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T> From<std::sync::mpsc::SendError<T>> for OrderError {
+    fn from(_: std::sync::mpsc::SendError<T>) -> Self {
+        OrderError::Disconnected
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<std::sync::mpsc::RecvError> for OrderError {
+    fn from(_: std::sync::mpsc::RecvError) -> Self {
+        OrderError::Disconnected
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<std::io::Error> for OrderError {
+    fn from(e: std::io::Error) -> Self {
+        OrderError::Other(e.into())
+    }
+}
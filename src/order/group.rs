@@ -0,0 +1,202 @@
+use crate::order::transport::{MpscTransport, Transport};
+use crate::order::{LogicalClock, OrdProcess, OrderError, Process};
+use alloc::boxed::Box;
+use core::cell::RefCell;
+use std::panic::AssertUnwindSafe;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// How often a process waiting to receive checks whether another panicked
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+type Body<'a, C> = Box<dyn FnOnce(&mut Process<C>, &Network<C>) + Send + 'a>;
+
+/// Runs a different closure per process on its own thread, with channels wired up all-to-all, then collects their
+/// event logs.
+///
+/// Unlike `Cluster`, where every process runs the same closure, the i-th closure added runs process i.
+///
+/// # Examples
+/// ```
+/// use rads::order::group::ProcessGroup;
+/// use rads::order::vector_clock::VectorClock;
+/// use rads::order::OrdProcess;
+///
+/// let logs = ProcessGroup::<VectorClock>::new()
+///     .with_process(|p, net| {
+///         p.exec(|| {});
+///         net.send_to(p, 1).unwrap();
+///     })
+///     .with_process(|p, net| assert_eq!(net.recv(p).unwrap(), 0))
+///     .run();
+/// assert!(logs[0][1] < logs[1][0]);
+/// ```
+pub struct ProcessGroup<'a, C> {
+    bodies: Vec<Body<'a, C>>,
+}
+
+impl<C: LogicalClock> Default for ProcessGroup<'_, C> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a, C: LogicalClock> ProcessGroup<'a, C> {
+    pub fn new() -> Self {
+        Self { bodies: Vec::new() }
+    }
+    /// Adds the next process, which runs `f`
+    pub fn with_process<F>(mut self, f: F) -> Self
+    where
+        F: FnOnce(&mut Process<C>, &Network<C>) + Send + 'a,
+    {
+        self.bodies.push(Box::new(f));
+        self
+    }
+    /// Runs every process, returning each event log by pid once all finished
+    ///
+    /// Panics if any process panicked, once the others return. Processes waiting to receive then fail with
+    /// `OrderError::Disconnected`, instead of waiting forever.
+    pub fn run(self) -> Vec<Vec<C>>
+    where
+        C: Send,
+    {
+        let n_procs = self.bodies.len();
+        let failed = Arc::new(AtomicBool::new(false));
+        std::thread::scope(|s| {
+            let ths: Vec<_> = MpscTransport::mesh(n_procs)
+                .into_iter()
+                .zip(self.bodies)
+                .enumerate()
+                .map(|(pid, (transport, f))| {
+                    let net = Network {
+                        transport: RefCell::new(transport),
+                        failed: failed.clone(),
+                    };
+                    s.spawn(move || {
+                        let mut p = Process::new(pid, n_procs);
+                        if let Err(e) =
+                            std::panic::catch_unwind(AssertUnwindSafe(|| f(&mut p, &net)))
+                        {
+                            net.failed.store(true, Ordering::SeqCst);
+                            std::panic::resume_unwind(e);
+                        }
+                        p.snapshot()
+                    })
+                })
+                .collect();
+            ths.into_iter()
+                .map(|th| th.join().unwrap_or_else(|e| std::panic::resume_unwind(e)))
+                .collect()
+        })
+    }
+}
+
+/// Channels of a process in a `ProcessGroup` to every other process.
+pub struct Network<C> {
+    transport: RefCell<MpscTransport<C>>,
+    /// Whether any process of the group panicked
+    failed: Arc<AtomicBool>,
+}
+
+impl<C: LogicalClock> Network<C> {
+    pub fn send_to(&self, p: &mut Process<C>, pid: usize) -> Result<(), OrderError> {
+        p.send_via(&mut *self.transport.borrow_mut(), pid)
+    }
+    /// Sends one clock to every other process
+    pub fn send_all(&self, p: &mut Process<C>) -> Result<(), OrderError> {
+        p.send_all(&mut *self.transport.borrow_mut())
+    }
+    /// Blocks until any process sends, returning its pid, or fails once another process panicked
+    pub fn recv(&self, p: &mut Process<C>) -> Result<usize, OrderError> {
+        loop {
+            if let Some(m) = self.transport.borrow_mut().recv_timeout(POLL_INTERVAL)? {
+                return Ok(p.recv_from(|| m));
+            }
+            if self.failed.load(Ordering::SeqCst) {
+                return Err(OrderError::Disconnected);
+            }
+        }
+    }
+    /// Like `recv`, but returns None instead of blocking
+    pub fn try_recv(&self, p: &mut Process<C>) -> Result<Option<usize>, OrderError> {
+        let m = self.transport.borrow_mut().try_recv()?;
+        Ok(m.map(|m| p.recv_from(|| m)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::order::group::ProcessGroup;
+    use crate::order::lamport_clock::LamportClock;
+    use crate::order::vector_clock::VectorClock;
+    use crate::order::{HasEvents, OrdProcess};
+
+    #[test]
+    fn mock_scheduler() {
+        let logs = ProcessGroup::<VectorClock>::new()
+            .with_process(|p, net| {
+                p.exec(|| {});
+                net.send_to(p, 1).unwrap();
+            })
+            .with_process(|p, net| {
+                assert_eq!(net.recv(p).unwrap(), 0);
+                net.send_to(p, 2).unwrap();
+            })
+            .with_process(|p, net| {
+                let from = loop {
+                    if let Some(from) = net.try_recv(p).unwrap() {
+                        break from;
+                    }
+                };
+                assert_eq!(from, 1);
+                assert_eq!(p.events().len(), 1);
+            })
+            .run();
+        assert_eq!(logs.iter().map(Vec::len).collect::<Vec<_>>(), [2, 2, 1]);
+        assert!(logs[0].iter().all(|s| s < &logs[2][0]));
+    }
+
+    #[test]
+    fn borrows_from_caller() {
+        let n_msgs = 3;
+        let logs = ProcessGroup::<LamportClock>::new()
+            .with_process(|p, net| (0..n_msgs).for_each(|_| net.send_all(p).unwrap()))
+            .with_process(|p, net| (0..n_msgs).for_each(|_| assert_eq!(net.recv(p).unwrap(), 0)))
+            .with_process(|p, net| (0..n_msgs).for_each(|_| assert_eq!(net.recv(p).unwrap(), 0)))
+            .run();
+        assert_eq!(logs.iter().map(Vec::len).collect::<Vec<_>>(), [3, 3, 3]);
+        assert!(logs[0][2] < logs[2][2]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn propagates_panic() {
+        ProcessGroup::<VectorClock>::new()
+            .with_process(|_, _| panic!())
+            .run();
+    }
+
+    #[test]
+    #[should_panic(expected = "p0 failed")]
+    fn propagates_panic_to_waiting() {
+        ProcessGroup::<VectorClock>::new()
+            .with_process(|_, _| panic!("p0 failed"))
+            .with_process(|p, net| {
+                net.recv(p).unwrap();
+            })
+            .run();
+    }
+
+    #[test]
+    #[should_panic(expected = "p0 failed")]
+    fn propagates_panic_despite_live_peers() {
+        // p1 and p2 could still send to each other, so only the panic ends their wait
+        ProcessGroup::<VectorClock>::new()
+            .with_process(|_, _| panic!("p0 failed"))
+            .with_process(|p, net| assert!(net.recv(p).is_err()))
+            .with_process(|p, net| assert!(net.recv(p).is_err()))
+            .run();
+    }
+}
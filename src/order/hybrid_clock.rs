@@ -0,0 +1,197 @@
+/// Hybrid Logical Clock (HLC) timestamps events close to physical time, while still respecting happens before.
+///
+/// More precisely, `s` happens before `t` implies `s < t`, and the logical part of a timestamp never exceeds physical
+/// time by more than the configured max drift. Messages from further in the future are rejected, so that one faulty
+/// clock cannot drag everyone's timestamps along.
+///
+/// # Examples
+/// ```
+/// use rads::order::hybrid_clock::HybridClock;
+///
+/// let mut p0 = HybridClock::new(|| 100, 10);
+/// let mut p1 = HybridClock::new(|| 90, 10);
+/// let s = p0.now();
+/// let t = p1.update(s).unwrap();
+/// assert!(s < t);
+/// assert_eq!(t.physical(), 100);
+/// let mut p2 = HybridClock::new(|| 80, 10);
+/// assert!(p2.update(s).is_err()); // 20 ahead of p2
+/// ```
+#[derive(Clone, Copy, Default, Hash, PartialEq, Eq, PartialOrd, Ord, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct HybridTimestamp {
+    l: u64,
+    c: u64,
+}
+
+impl HybridTimestamp {
+    /// Largest physical time seen
+    pub fn physical(&self) -> u64 {
+        self.l
+    }
+    /// Number of events since physical time last moved
+    pub fn logical(&self) -> u64 {
+        self.c
+    }
+}
+
+/// Source of physical time, e.g. milliseconds since UNIX epoch.
+pub trait PhysicalTime {
+    fn now(&self) -> u64;
+}
+
+impl<F: Fn() -> u64> PhysicalTime for F {
+    fn now(&self) -> u64 {
+        self()
+    }
+}
+
+/// System time in milliseconds since UNIX epoch.
+#[cfg(feature = "std")]
+pub struct WallClock;
+
+#[cfg(feature = "std")]
+impl PhysicalTime for WallClock {
+    fn now(&self) -> u64 {
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .expect("System time before UNIX epoch")
+            .as_millis() as u64
+    }
+}
+
+/// Received timestamp is too far ahead of local physical time.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct DriftError {
+    pub remote: u64,
+    pub local: u64,
+    pub max_drift: u64,
+}
+
+impl core::fmt::Display for DriftError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "Remote time {} is ahead of local time {} by more than {}",
+            self.remote, self.local, self.max_drift
+        )
+    }
+}
+
+pub struct HybridClock<P> {
+    last: HybridTimestamp,
+    physical: P,
+    max_drift: u64,
+}
+
+impl<P: PhysicalTime> HybridClock<P> {
+    pub fn new(physical: P, max_drift: u64) -> Self {
+        Self {
+            last: HybridTimestamp::default(),
+            physical,
+            max_drift,
+        }
+    }
+    /// Latest timestamp issued
+    pub fn last(&self) -> HybridTimestamp {
+        self.last
+    }
+    /// Timestamps a local or send event
+    pub fn now(&mut self) -> HybridTimestamp {
+        let pt = self.physical.now();
+        let prev = self.last;
+        self.last = if pt > prev.l {
+            HybridTimestamp { l: pt, c: 0 }
+        } else {
+            HybridTimestamp {
+                l: prev.l,
+                c: prev.c + 1,
+            }
+        };
+        self.last
+    }
+    /// Timestamps a receive event, unless the received timestamp drifted too far ahead of local physical time
+    pub fn update(&mut self, msg: HybridTimestamp) -> Result<HybridTimestamp, DriftError> {
+        let pt = self.physical.now();
+        if msg.l > pt.saturating_add(self.max_drift) {
+            return Err(DriftError {
+                remote: msg.l,
+                local: pt,
+                max_drift: self.max_drift,
+            });
+        }
+        let prev = self.last;
+        let l = prev.l.max(msg.l).max(pt);
+        let c = match (l == prev.l, l == msg.l) {
+            (true, true) => prev.c.max(msg.c) + 1,
+            (true, false) => prev.c + 1,
+            (false, true) => msg.c + 1,
+            (false, false) => 0,
+        };
+        self.last = HybridTimestamp { l, c };
+        Ok(self.last)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::order::hybrid_clock::{DriftError, HybridClock};
+    use std::cell::Cell;
+
+    #[test]
+    fn tracks_physical_time() {
+        let pt = Cell::new(10);
+        let mut hlc = HybridClock::new(|| pt.get(), 5);
+        let s = hlc.now();
+        assert_eq!((s.physical(), s.logical()), (10, 0));
+        let t = hlc.now(); // physical time stood still
+        assert_eq!((t.physical(), t.logical()), (10, 1));
+        pt.set(12);
+        let u = hlc.now();
+        assert_eq!((u.physical(), u.logical()), (12, 0));
+        assert!(s < t && t < u);
+    }
+
+    #[test]
+    fn update_respects_happens_before() {
+        let mut p0 = HybridClock::new(|| 20, 5);
+        let pt1 = Cell::new(18);
+        let mut p1 = HybridClock::new(|| pt1.get(), 5);
+
+        let s = p0.now();
+        let t = p1.update(s).unwrap();
+        assert!(s < t);
+        assert_eq!((t.physical(), t.logical()), (20, 1));
+
+        // Equal physical parts take the max logical part
+        let s2 = p0.now();
+        let t2 = p1.update(s2).unwrap();
+        assert_eq!((t2.physical(), t2.logical()), (20, 2));
+
+        // Physical time overtakes
+        pt1.set(30);
+        let t3 = p1.update(s2).unwrap();
+        assert_eq!((t3.physical(), t3.logical()), (30, 0));
+        assert_eq!(p1.last(), t3);
+    }
+
+    #[test]
+    fn rejects_drift() {
+        let mut p0 = HybridClock::new(|| 100, 10);
+        let mut p1 = HybridClock::new(|| 89, 10);
+        let before = p1.now();
+        let s = p0.now();
+        assert_eq!(
+            p1.update(s),
+            Err(DriftError {
+                remote: 100,
+                local: 89,
+                max_drift: 10
+            })
+        );
+        assert_eq!(p1.last(), before); // unchanged
+
+        let mut p2 = HybridClock::new(|| 90, 10);
+        assert!(p2.update(s).is_ok()); // exactly at max drift
+    }
+}
@@ -0,0 +1,145 @@
+use core::cmp::Ordering;
+#[cfg(feature = "std")]
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Interval Clock bounds the true physical time of an event within `[earliest, latest]` nanoseconds, like TrueTime.
+///
+/// More precisely, events `s < t` if and only if their intervals do not overlap, and `s` ends before `t` starts. Any
+/// overlapping intervals are incomparable (None) since either could have happened first.
+///
+/// # Examples
+/// ```
+/// use rads::order::interval_clock::IntervalClock;
+///
+/// let s = IntervalClock::new(10, 20);
+/// let t = IntervalClock::new(21, 30);
+/// assert!(s < t);
+/// let u = IntervalClock::new(15, 25);
+/// assert!(s.partial_cmp(&u) == None);
+/// ```
+#[derive(Clone, Copy, Hash, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct IntervalClock {
+    earliest: u64,
+    latest: u64,
+}
+
+impl IntervalClock {
+    pub fn new(earliest: u64, latest: u64) -> Self {
+        assert!(
+            earliest <= latest,
+            "Expect earliest={earliest} <= latest={latest}"
+        );
+        Self { earliest, latest }
+    }
+    pub fn earliest(&self) -> u64 {
+        self.earliest
+    }
+    pub fn latest(&self) -> u64 {
+        self.latest
+    }
+}
+
+impl PartialOrd for IntervalClock {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        if self == other {
+            Some(Ordering::Equal)
+        } else if self.latest < other.earliest {
+            Some(Ordering::Less)
+        } else if self.earliest > other.latest {
+            Some(Ordering::Greater)
+        } else {
+            None
+        }
+    }
+}
+
+/// Source of physical time with bounded uncertainty.
+pub trait Uncertainty {
+    /// Interval guaranteed to contain the true current time
+    fn now(&self) -> IntervalClock;
+
+    /// Blocks until `ts` has definitely passed, i.e. every event after this is ordered after `ts`.
+    ///
+    /// Called before making a write with commit timestamp `ts` visible, so that any later read sees it.
+    #[cfg(feature = "std")]
+    fn commit_wait(&self, ts: &IntervalClock) {
+        loop {
+            let now = self.now();
+            if now.earliest > ts.latest {
+                return;
+            }
+            std::thread::sleep(Duration::from_nanos(ts.latest - now.earliest + 1));
+        }
+    }
+}
+
+/// System time, trusted to be within `epsilon` of the true time.
+#[cfg(feature = "std")]
+pub struct SystemUncertainty {
+    epsilon: Duration,
+}
+
+#[cfg(feature = "std")]
+impl SystemUncertainty {
+    pub fn new(epsilon: Duration) -> Self {
+        Self { epsilon }
+    }
+}
+
+#[cfg(feature = "std")]
+impl Uncertainty for SystemUncertainty {
+    fn now(&self) -> IntervalClock {
+        let t = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("System time before UNIX epoch")
+            .as_nanos() as u64;
+        let eps = self.epsilon.as_nanos() as u64;
+        IntervalClock::new(t.saturating_sub(eps), t.saturating_add(eps))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::order::interval_clock::IntervalClock;
+
+    #[test]
+    fn partial_ord() {
+        let s = IntervalClock::new(10, 20);
+        assert_eq!(s.partial_cmp(&s), Some(std::cmp::Ordering::Equal));
+        let t = IntervalClock::new(21, 30);
+        assert_eq!(s.partial_cmp(&t), Some(std::cmp::Ordering::Less));
+        assert_eq!(t.partial_cmp(&s), Some(std::cmp::Ordering::Greater));
+
+        // Any overlap, even on the boundary, is incomparable
+        let u = IntervalClock::new(20, 21);
+        assert_eq!(s.partial_cmp(&u), None);
+        assert_eq!(u.partial_cmp(&t), None);
+        let v = IntervalClock::new(12, 18);
+        assert_eq!(s.partial_cmp(&v), None);
+        assert_eq!(v.partial_cmp(&s), None);
+    }
+
+    #[test]
+    #[should_panic]
+    fn invalid_interval() {
+        IntervalClock::new(2, 1);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn commit_wait() {
+        use crate::order::interval_clock::{SystemUncertainty, Uncertainty};
+        use std::time::{Duration, Instant};
+
+        let eps = Duration::from_millis(20);
+        let tt = SystemUncertainty::new(eps);
+        let start = Instant::now();
+        let ts = tt.now();
+        assert_eq!(ts.latest() - ts.earliest(), 2 * eps.as_nanos() as u64);
+
+        tt.commit_wait(&ts);
+        assert!(start.elapsed() >= 2 * eps);
+        assert!(ts < tt.now());
+    }
+}
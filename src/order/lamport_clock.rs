@@ -0,0 +1,175 @@
+use crate::order::{CausalOrd, LogicalClock};
+use core::cmp::Ordering;
+
+/// Lamport Clock is a single counter, so that if event `s` happens before `t` then `s < t`.
+///
+/// Unlike the vector clock, the converse does not hold: `s < t` may be concurrent events. Only events of different
+/// processes with equal counters are known to be concurrent (None).
+///
+/// # Examples
+/// ```
+/// use rads::order::LogicalClock;
+/// use rads::order::lamport_clock::LamportClock;
+///
+/// let e1 = LamportClock::new(0, 2);
+/// let e2 = e1.extend();
+/// assert!(e1 < e2);
+/// let f1 = LamportClock::new(1, 2);
+/// assert!(e1.partial_cmp(&f1) == None);
+/// let f2 = f1.merge(&e2);
+/// assert!(e2 < f2);
+/// ```
+#[derive(Clone, Copy, Hash, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LamportClock {
+    i: usize,
+    clk: usize,
+}
+
+impl LamportClock {
+    pub fn pid(&self) -> usize {
+        self.i
+    }
+    pub fn get(&self) -> usize {
+        self.clk
+    }
+    pub fn total(&self) -> TotalLamport {
+        TotalLamport {
+            clk: self.clk,
+            pid: self.i,
+        }
+    }
+}
+
+/// Unique event id ordered by Lamport clock, then pid, e.g. for total order broadcast or request queues of distributed
+/// mutual exclusion.
+///
+/// # Examples
+/// ```
+/// use rads::order::LogicalClock;
+/// use rads::order::lamport_clock::{LamportClock, TotalLamport};
+///
+/// let e1 = LamportClock::new(0, 2).total();
+/// let f1 = LamportClock::new(1, 2).total();
+/// assert!(e1 < f1);
+/// assert_eq!(f1, TotalLamport::new(1, 1));
+/// ```
+// Field order matters for the derived Ord
+#[derive(Clone, Copy, Hash, PartialEq, Eq, PartialOrd, Ord, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TotalLamport {
+    clk: usize,
+    pid: usize,
+}
+
+impl TotalLamport {
+    pub fn new(clk: usize, pid: usize) -> Self {
+        Self { clk, pid }
+    }
+    pub fn clk(&self) -> usize {
+        self.clk
+    }
+    pub fn pid(&self) -> usize {
+        self.pid
+    }
+}
+
+impl From<LamportClock> for TotalLamport {
+    fn from(c: LamportClock) -> Self {
+        c.total()
+    }
+}
+
+impl LogicalClock for LamportClock {
+    fn new(i: usize, _n_procs: usize) -> Self {
+        Self { i, clk: 1 }
+    }
+    fn extend(&self) -> Self {
+        Self {
+            i: self.i,
+            clk: self.clk + 1,
+        }
+    }
+    fn merge(&self, other: &Self) -> Self {
+        Self {
+            i: self.i,
+            clk: self.clk.max(other.clk) + 1,
+        }
+    }
+    fn observe(&self, other: &Self) -> Self {
+        Self {
+            i: self.i,
+            clk: self.clk.max(other.clk),
+        }
+    }
+    fn fmt_clock(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}:{}", self.i, self.clk)
+    }
+}
+
+impl PartialOrd for LamportClock {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        match self.clk.cmp(&other.clk) {
+            Ordering::Equal if self.i != other.i => None,
+            ord => Some(ord),
+        }
+    }
+}
+
+impl CausalOrd for LamportClock {}
+
+#[cfg(test)]
+mod tests {
+    use crate::order::lamport_clock::{LamportClock, TotalLamport};
+    use crate::order::{tests::assert_causal_ord_laws, LogicalClock};
+
+    #[test]
+    fn partial_ord() {
+        let e1 = LamportClock::new(0, 2);
+        assert_eq!(e1.partial_cmp(&e1), Some(std::cmp::Ordering::Equal));
+        let e2 = e1.extend();
+        assert_eq!(e1.partial_cmp(&e2), Some(std::cmp::Ordering::Less));
+        let f1 = LamportClock::new(1, 2);
+        assert_eq!(e1.partial_cmp(&f1), None);
+        assert_eq!(e2.partial_cmp(&f1), Some(std::cmp::Ordering::Greater)); // though concurrent
+        let f2 = f1.merge(&e2);
+        assert_eq!(f2.get(), 3);
+        assert_eq!(e2.partial_cmp(&f2), Some(std::cmp::Ordering::Less));
+        assert_eq!(f1.observe(&e2).get(), 2);
+    }
+
+    #[test]
+    fn total_order() {
+        let e1 = LamportClock::new(0, 3);
+        let f1 = LamportClock::new(1, 3);
+        let g2 = LamportClock::new(2, 3).merge(&f1);
+        let e3 = e1.merge(&g2);
+        let mut ids = [e3, g2, f1, e1].map(TotalLamport::from);
+        ids.sort();
+        assert_eq!(
+            ids,
+            [
+                TotalLamport::new(1, 0),
+                TotalLamport::new(1, 1),
+                TotalLamport::new(2, 2),
+                TotalLamport::new(3, 0)
+            ]
+        );
+        assert_eq!((ids[3].clk(), ids[3].pid()), (3, 0));
+
+        // Consistent with happens before
+        let history = crate::order::tests::random_history::<LamportClock>(0, 4, 100);
+        for s in &history {
+            for t in &history {
+                if s < t {
+                    assert!(s.total() < t.total());
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn causal_ord_laws() {
+        assert_causal_ord_laws::<LamportClock>();
+    }
+}
@@ -1,12 +1,20 @@
-use crate::order::{pairwise_max, CausalOrd, GCClock, HasEvents, LogicalClock, OrdProcess};
-use std::cmp::Ordering;
-use std::collections::VecDeque;
+use crate::order::{
+    pairwise_max, parse_list, split_pid, CausalOrd, GCClock, HasEvents, LogicalClock, OrdProcess,
+    Tombstone,
+};
+use alloc::collections::VecDeque;
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::cmp::Ordering;
 
 #[derive(Clone, Default)]
-#[cfg_attr(test, derive(Debug))]
+#[cfg_attr(any(test, feature = "proptest"), derive(Debug))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MatrixClock {
     i: usize,
-    clk: Vec<Vec<usize>>,
+    // Rows are shared between events, so that an event log of m events costs O(m * n) instead of O(m * n^2)
+    clk: Vec<Arc<Vec<usize>>>,
 }
 
 impl GCClock for MatrixClock {
@@ -23,13 +31,13 @@ impl LogicalClock for MatrixClock {
             i,
             clk: (0..n_procs)
                 .map(|j| {
-                    if i == j {
+                    Arc::new(if i == j {
                         // So that p0 is not comparable to p1
                         (0..n_procs).map(|j| usize::from(i == j)).collect()
                     } else {
                         // i don't know anything about other processes
                         vec![0; n_procs]
-                    }
+                    })
                 })
                 .collect(),
         }
@@ -37,29 +45,200 @@ impl LogicalClock for MatrixClock {
 
     fn extend(&self) -> Self {
         let mut c = self.clone();
-        c.clk[self.i][self.i] += 1;
+        Arc::make_mut(&mut c.clk[self.i])[self.i] += 1;
         c
     }
 
     fn merge(&self, other: &Self) -> Self {
+        let mut c = self.observe(other);
+        // Receive event > previous event
+        Arc::make_mut(&mut c.clk[self.i])[self.i] += 1;
+        c
+    }
+
+    fn observe(&self, other: &Self) -> Self {
+        if self.clk.len() != other.clk.len() {
+            // Either knows of processes that joined since the other last heard
+            let n = self.clk.len().max(other.clk.len());
+            return self.resized(n).observe(&other.resized(n));
+        }
         let mut c = Self {
             i: self.i,
             clk: self
                 .clk
                 .iter()
                 .zip(&other.clk)
-                // Take max of what everyone has seen
-                .map(|(u, v)| pairwise_max(u.iter(), v.iter()).collect())
+                // Take max of what everyone has seen, sharing the row if either has seen it all
+                .map(|(u, v)| {
+                    if Arc::ptr_eq(u, v) || u.iter().zip(v.iter()).all(|(s, t)| s >= t) {
+                        u.clone()
+                    } else if u.iter().zip(v.iter()).all(|(s, t)| s <= t) {
+                        v.clone()
+                    } else {
+                        Arc::new(pairwise_max(u.iter(), v.iter()).collect())
+                    }
+                })
                 .collect(),
         };
         // I have seen max of what everyone has seen
-        c.clk[self.i] = (0..self.clk.len())
-            .map(|col| c.clk.iter().fold(0, |acc, vi| vi[col].max(acc)))
-            .collect();
-        // Receive event > previous event
-        c.clk[self.i][self.i] += 1;
+        c.clk[self.i] = Arc::new(
+            (0..self.clk.len())
+                .map(|col| c.clk.iter().fold(0, |acc, vi| vi[col].max(acc)))
+                .collect(),
+        );
         c
     }
+    fn fmt_clock(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        core::fmt::Display::fmt(self, f)
+    }
+    // Counts shared rows as if they were not
+    fn byte_size(&self) -> usize {
+        let n = self.clk.len();
+        let row = core::mem::size_of::<Arc<Vec<usize>>>()
+            + core::mem::size_of::<Vec<usize>>()
+            + n * core::mem::size_of::<usize>();
+        core::mem::size_of::<Self>() + n * row
+    }
+}
+
+impl MatrixClock {
+    /// Pads rows and columns of processes that joined later, which this process has seen no events of
+    fn resized(&self, n: usize) -> Self {
+        let row = |r: &Arc<Vec<usize>>| match r.len() < n {
+            true => Arc::new(
+                r.iter()
+                    .copied()
+                    .chain(core::iter::repeat(0))
+                    .take(n)
+                    .collect(),
+            ),
+            false => r.clone(),
+        };
+        Self {
+            i: self.i,
+            clk: (0..n)
+                .map(|j| self.clk.get(j).map_or_else(|| Arc::new(vec![0; n]), row))
+                .collect(),
+        }
+    }
+    /// Number of processes this clock knows of
+    pub fn len(&self) -> usize {
+        self.clk.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.clk.is_empty()
+    }
+    /// What this process knows of process `pid`'s vector clock
+    pub fn get(&self, pid: usize) -> Option<&[usize]> {
+        self.clk.get(pid).map(|r| r.as_slice())
+    }
+    pub fn iter(&self) -> impl Iterator<Item = &[usize]> {
+        self.clk.iter().map(|r| r.as_slice())
+    }
+    /// Whether this clock has seen at least everything `other` has seen, i.e. `self >= other`
+    pub fn dominates(&self, other: &Self) -> bool {
+        self >= other
+    }
+    /// Marks this process as retired, to be compacted out of clocks after its final event
+    pub fn retire(&self) -> Tombstone {
+        Tombstone {
+            pid: self.i,
+            last: self.clk[self.i][self.i],
+        }
+    }
+    /// Removes rows and columns of retired processes, renumbering the rest (see [`Tombstone::renumber`]).
+    ///
+    /// None if this process retired or has not seen the final event of every retired process.
+    pub fn compact(&self, retired: &[Tombstone]) -> Option<Self> {
+        let i = Tombstone::renumber(self.i, retired)?;
+        if !retired
+            .iter()
+            .all(|t| self.clk[self.i].get(t.pid) == Some(&t.last))
+        {
+            return None;
+        }
+        let live = |j: &usize| retired.iter().all(|t| t.pid != *j);
+        Some(Self {
+            i,
+            clk: (0..self.clk.len())
+                .filter(live)
+                .map(|r| {
+                    Arc::new(
+                        (0..self.clk.len())
+                            .filter(live)
+                            .map(|c| self.clk[r][c])
+                            .collect(),
+                    )
+                })
+                .collect(),
+        })
+    }
+    /// Number of events of each process that this process has seen but the process of `other` has not, skipping
+    /// processes without any
+    pub fn diff(&self, other: &Self) -> Vec<(usize, usize)> {
+        if self.clk.len() != other.clk.len() {
+            let n = self.clk.len().max(other.clk.len());
+            return self.resized(n).diff(&other.resized(n));
+        }
+        self.clk[self.i]
+            .iter()
+            .zip(other.clk[other.i].iter())
+            .enumerate()
+            .filter(|(_, (s, t))| s > t)
+            .map(|(pid, (s, t))| (pid, s - t))
+            .collect()
+    }
+}
+
+/// Formats as `<pid>:[[<row>,..],..]`, e.g. `0:[[1,0],[0,0]]`
+impl core::fmt::Display for MatrixClock {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}:[", self.i)?;
+        for (j, row) in self.clk.iter().enumerate() {
+            if j > 0 {
+                write!(f, ",")?;
+            }
+            write!(f, "[")?;
+            for (k, v) in row.iter().enumerate() {
+                if k > 0 {
+                    write!(f, ",")?;
+                }
+                write!(f, "{v}")?;
+            }
+            write!(f, "]")?;
+        }
+        write!(f, "]")
+    }
+}
+
+impl core::str::FromStr for MatrixClock {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (i, clk) = split_pid(s)?;
+        let clk = clk.trim();
+        let rows = clk
+            .strip_prefix('[')
+            .and_then(|s| s.strip_suffix(']'))
+            .ok_or_else(|| anyhow::anyhow!("Expect matrix in [[..],..], got {clk:?}"))?;
+        let clk = rows
+            .trim()
+            .split_inclusive(']')
+            .map(|row| parse_list(row.trim_start_matches(|c: char| c == ',' || c.is_whitespace())))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let n_procs = clk.len();
+        anyhow::ensure!(
+            i < n_procs,
+            "Expect 0-based index of process {i} < n_procs={n_procs}"
+        );
+        anyhow::ensure!(
+            clk.iter().all(|row| row.len() == n_procs),
+            "Expect {n_procs}x{n_procs} matrix"
+        );
+        Ok(Self {
+            i,
+            clk: clk.into_iter().map(Arc::new).collect(),
+        })
+    }
 }
 
 impl CausalOrd for MatrixClock {}
@@ -67,13 +246,14 @@ impl CausalOrd for MatrixClock {}
 impl PartialOrd for MatrixClock {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
         if self.clk.len() != other.clk.len() {
-            return None;
+            let n = self.clk.len().max(other.clk.len());
+            return self.resized(n).partial_cmp(&other.resized(n));
         }
-        use std::cmp::Ordering::{Equal, Greater, Less};
+        use core::cmp::Ordering::{Equal, Greater, Less};
         self.clk
             .iter()
-            .flatten()
-            .zip(other.clk.iter().flatten())
+            .flat_map(|r| r.iter())
+            .zip(other.clk.iter().flat_map(|r| r.iter()))
             .try_fold(Equal, |acc, (s, t)| match (acc, s.cmp(t)) {
                 (Less, Greater) | (Greater, Less) => None,
                 (_, Less) | (Less, _) => Some(Less),
@@ -83,8 +263,13 @@ impl PartialOrd for MatrixClock {
     }
 }
 
+// Missing entries are zero, like `partial_cmp`
 impl PartialEq<Self> for MatrixClock {
     fn eq(&self, other: &Self) -> bool {
+        if self.clk.len() != other.clk.len() {
+            let n = self.clk.len().max(other.clk.len());
+            return self.resized(n).clk == other.resized(n).clk;
+        }
         self.clk == other.clk
     }
 }
@@ -105,9 +290,11 @@ impl GCProcess {
     }
     pub fn gc(&mut self) -> Vec<MatrixClock> {
         let Some(latest) = self.events.back() else {
-            return Vec::new()
+            return Vec::new();
         };
         let i = self.events.partition_point(|c| c.gc(latest));
+        #[cfg(feature = "tracing")]
+        tracing::debug!(target: "rads::order", pid = self.i, reclaimed = i, "gc");
         self.events.drain(..i).collect()
     }
 }
@@ -175,6 +362,115 @@ mod tests {
         assert_eq!(ps[0].events().len(), 1); // recv event only
     }
 
+    #[test]
+    fn grows_on_merge() {
+        // p2 joined after p0 and p1 started
+        let e2 = MatrixClock::new(0, 2).extend();
+        let g1 = MatrixClock::new(2, 3);
+        let g2 = g1.merge(&e2);
+        assert_eq!(g2.len(), 3);
+        assert_eq!(g2.get(0), Some(&[2, 0, 0][..]));
+        assert_eq!(g2.get(2), Some(&[2, 0, 2][..]));
+        assert!(e2 < g2 && e2.partial_cmp(&g1).is_none());
+        assert_eq!(e2.observe(&g1).len(), 3);
+    }
+
+    #[test]
+    fn eq_despite_length() {
+        let e2 = MatrixClock::new(0, 2).extend();
+        let e3 = e2.resized(3);
+        assert_eq!(e2.partial_cmp(&e3), Some(std::cmp::Ordering::Equal));
+        assert!(e2 == e3);
+        assert!(e2.dominates(&e3) && e3.dominates(&e2));
+        assert!(e2 != MatrixClock::new(0, 3));
+    }
+
+    #[test]
+    fn accessors() {
+        let e1 = MatrixClock::new(0, 2).extend();
+        let f1 = MatrixClock::new(1, 2).merge(&e1);
+        assert_eq!(f1.len(), 2);
+        assert_eq!(f1.get(0), Some([2, 0].as_slice())); // what 1 knows 0 has seen
+        assert_eq!(f1.get(1), Some([2, 2].as_slice()));
+        assert_eq!(f1.get(2), None);
+        assert_eq!(f1.iter().count(), 2);
+
+        assert!(f1.dominates(&e1));
+        assert!(f1.dominates(&f1));
+        assert!(!e1.dominates(&f1));
+    }
+
+    #[test]
+    fn observe_does_not_extend() {
+        let e1 = MatrixClock::new(0, 2).extend();
+        let f1 = MatrixClock::new(1, 2);
+        let m = f1.observe(&e1);
+        assert_eq!(m.get(0), Some([2, 0].as_slice()));
+        assert_eq!(m.get(1), Some([2, 1].as_slice())); // own entry unchanged
+        assert_eq!(m.observe(&e1), m);
+        assert!(m < f1.merge(&e1));
+    }
+
+    #[test]
+    fn retire_and_compact() {
+        let e1 = MatrixClock::new(0, 3);
+        let f2 = MatrixClock::new(1, 3).merge(&e1);
+        let retired = [f2.retire()];
+        assert_eq!(MatrixClock::new(2, 3).compact(&retired), None); // not seen f2
+        assert_eq!(f2.compact(&retired), None);
+
+        let g2 = MatrixClock::new(2, 3).merge(&f2);
+        let g3 = g2.compact(&retired).unwrap();
+        assert_eq!(g2.to_string(), "2:[[1,0,0],[1,2,0],[1,2,2]]");
+        assert_eq!(g3.to_string(), "1:[[1,0],[1,2]]");
+        assert!(g3 < g3.extend());
+    }
+
+    #[test]
+    fn shares_rows() {
+        let e1 = MatrixClock::new(0, 3);
+        let e2 = e1.extend();
+        assert!(!std::sync::Arc::ptr_eq(&e1.clk[0], &e2.clk[0])); // own row copied
+        assert!(std::sync::Arc::ptr_eq(&e1.clk[1], &e2.clk[1]));
+        assert!(std::sync::Arc::ptr_eq(&e1.clk[2], &e2.clk[2]));
+
+        let f1 = MatrixClock::new(1, 3);
+        let f2 = f1.merge(&e2);
+        assert!(std::sync::Arc::ptr_eq(&e2.clk[0], &f2.clk[0])); // learnt from e2
+        assert!(std::sync::Arc::ptr_eq(&f1.clk[2], &f2.clk[2])); // nothing new
+        assert_eq!(f2.to_string(), "1:[[2,0,0],[2,2,0],[0,0,0]]");
+        assert_eq!(e2.to_string(), "0:[[2,0,0],[0,0,0],[0,0,0]]"); // unchanged
+    }
+
+    #[test]
+    fn diff() {
+        let e2 = MatrixClock::new(0, 2).extend();
+        let f1 = MatrixClock::new(1, 2);
+        let f2 = f1.merge(&e2);
+        assert_eq!(e2.diff(&f1), vec![(0, 2)]);
+        assert_eq!(f1.diff(&e2), vec![(1, 1)]);
+        assert_eq!(f2.diff(&e2), vec![(1, 2)]);
+        assert_eq!(e2.diff(&f2), vec![]);
+        // p2 joined after f1
+        let g1 = MatrixClock::new(2, 3);
+        assert_eq!(g1.diff(&f1), vec![(2, 1)]);
+        assert_eq!(f2.diff(&g1), vec![(0, 2), (1, 2)]);
+    }
+
+    #[test]
+    fn display_from_str() {
+        let f2 = MatrixClock::new(1, 2).merge(&MatrixClock::new(0, 2).extend());
+        assert_eq!(f2.to_string(), "1:[[2,0],[2,2]]");
+        let parsed = "1:[[2,0],[2,2]]".parse::<MatrixClock>().unwrap();
+        assert_eq!(parsed, f2);
+        assert_eq!(parsed.to_string(), f2.to_string());
+        assert_eq!(" 1: [ [2, 0], [2, 2] ]".parse::<MatrixClock>().unwrap(), f2);
+
+        assert!("2:[[2,0],[2,2]]".parse::<MatrixClock>().is_err()); // unknown pid
+        assert!("0:[[2,0],[2]]".parse::<MatrixClock>().is_err()); // not square
+        assert!("0:[2,0]".parse::<MatrixClock>().is_err());
+    }
+
     #[test]
     fn partial_ord() {
         let e1 = MatrixClock::new(0, 2);
@@ -199,4 +495,9 @@ mod tests {
         assert_eq!(f2.partial_cmp(&f1), Some(std::cmp::Ordering::Greater));
         assert_eq!(f2.partial_cmp(&f2), Some(std::cmp::Ordering::Equal));
     }
+
+    #[test]
+    fn causal_ord_laws() {
+        crate::order::tests::assert_causal_ord_laws::<MatrixClock>();
+    }
 }
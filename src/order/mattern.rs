@@ -0,0 +1,329 @@
+use crate::order::transport::Transport;
+use crate::order::vector_clock::VectorClock;
+use crate::order::{trace_event, HasEvents, LogicalClock, OrdProcess, OrderError};
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// Future vector time announced by the initiator of a snapshot, which defines the cut: events whose clock reached
+/// the initiator's entry are after the cut (red), all others before it (white).
+#[derive(Clone, Hash, Eq, PartialEq)]
+#[cfg_attr(test, derive(Debug))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Cut {
+    pub initiator: usize,
+    pub at: VectorClock,
+}
+
+impl Cut {
+    /// Whether event or message `e` is after the cut, i.e. causally after the initiator's snapshot
+    pub fn is_after(&self, e: &VectorClock) -> bool {
+        e.get(self.initiator).unwrap_or(0) >= self.at.get(self.initiator).unwrap_or(0)
+    }
+}
+
+/// Local state of a process at the cut, along with the messages in flight across it.
+#[derive(Clone, PartialEq, Eq)]
+#[cfg_attr(test, derive(Debug))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MatternSnapshot {
+    /// Number of events before the cut, i.e. its prefix of `events()`
+    pub n_events: usize,
+    /// Number of messages sent before the cut
+    pub n_sent: usize,
+    /// Number of messages received before the cut
+    pub n_received: usize,
+    /// Messages sent before the cut but received after it, by sender
+    pub channels: Vec<Vec<VectorClock>>,
+}
+
+impl MatternSnapshot {
+    /// Number of messages recorded in flight so far
+    pub fn n_in_flight(&self) -> usize {
+        self.channels.iter().map(Vec::len).sum()
+    }
+}
+
+/// Whether every message sent before the cut has been received, i.e. the channel states of the local snapshots of all
+/// processes are final.
+pub fn is_complete<'a, I: IntoIterator<Item = &'a MatternSnapshot>>(locals: I) -> bool {
+    let (n_sent, n_received) = locals.into_iter().fold((0, 0), |(s, r), l| {
+        (s + l.n_sent, r + l.n_received + l.n_in_flight())
+    });
+    n_sent == n_received
+}
+
+/// Process that takes a snapshot at the cut defined by a vector time, without markers or FIFO channels (Mattern).
+///
+/// The initiator announces the vector time of its next event. Each process records its local state right before its
+/// first event at or after that time, e.g. on receiving a message sent after the cut. Messages sent before the cut but
+/// received after it are in flight, and counting messages on both sides of the cut tells when all have arrived.
+///
+/// Every process must learn the cut before the initiator's next event, e.g. by acknowledging the announcement, since a
+/// process cannot tell that a message is after a cut it doesn't know of. Processes that never hear from the initiator
+/// again won't record their state, so the initiator would usually `send_all` after the cut.
+///
+/// # Examples
+/// ```
+/// use rads::order::mattern::{is_complete, MatternProc};
+/// use rads::order::OrdProcess;
+///
+/// let mut p0 = MatternProc::new(0, 2);
+/// let mut p1 = MatternProc::new(1, 2);
+/// let (mut before, mut after) = (None, None);
+/// p1.send(|e| before = Some(e));
+/// // p0 announces the cut, which p1 acknowledges before p0 moves on
+/// p1.on_cut(p0.initiate());
+/// p0.send(|e| after = Some(e));
+/// p1.recv(|| after.unwrap());
+/// assert_eq!(p1.snapshot().unwrap().n_events, 1);
+/// assert!(!is_complete([p0.snapshot().unwrap(), p1.snapshot().unwrap()]));
+///
+/// p0.recv(|| before.unwrap());
+/// assert_eq!(p0.snapshot().unwrap().channels[1].len(), 1);
+/// assert!(is_complete([p0.snapshot().unwrap(), p1.snapshot().unwrap()]));
+/// ```
+#[derive(Clone)]
+pub struct MatternProc {
+    i: usize,
+    n: usize,
+    events: Vec<VectorClock>,
+    cut: Option<Cut>,
+    snapshot: Option<MatternSnapshot>,
+    // Messages sent and received so far, which are before any cut that is yet to come
+    n_sent: usize,
+    n_received: usize,
+}
+
+impl MatternProc {
+    pub fn new(i: usize, n: usize) -> Self {
+        assert!(i < n, "Expect 0-based index of process {i} < n_procs={n}");
+        Self {
+            i,
+            n,
+            events: Vec::new(),
+            cut: None,
+            snapshot: None,
+            n_sent: 0,
+            n_received: 0,
+        }
+    }
+    /// Announces a cut at this process' next event, returning it to send to every other process.
+    ///
+    /// The next event must wait until every process learnt of the cut.
+    pub fn initiate(&mut self) -> Cut {
+        let cut = Cut {
+            initiator: self.i,
+            at: self.next_event(),
+        };
+        self.on_cut(cut.clone());
+        cut
+    }
+    /// Learns of a cut announced by its initiator, replacing any previous snapshot
+    pub fn on_cut(&mut self, cut: Cut) {
+        self.cut = Some(cut);
+        self.snapshot = None;
+    }
+    pub fn cut(&self) -> Option<&Cut> {
+        self.cut.as_ref()
+    }
+    /// Local snapshot, once this process reached the cut
+    pub fn snapshot(&self) -> Option<&MatternSnapshot> {
+        self.snapshot.as_ref()
+    }
+
+    fn next_event(&self) -> VectorClock {
+        self.last_event()
+            .unwrap_or(&VectorClock::new(self.i, self.n))
+            .extend()
+    }
+}
+
+impl HasEvents<VectorClock> for MatternProc {
+    fn last_event(&self) -> Option<&VectorClock> {
+        self.events.last()
+    }
+    // Records the local snapshot before the first event after the cut
+    fn push_event(&mut self, e: VectorClock) {
+        if self.snapshot.is_none() && self.cut.as_ref().is_some_and(|cut| cut.is_after(&e)) {
+            self.snapshot = Some(MatternSnapshot {
+                n_events: self.events.len(),
+                n_sent: self.n_sent,
+                n_received: self.n_received,
+                channels: vec![Vec::new(); self.n],
+            });
+            trace_event("snapshot", self.i, &e);
+        }
+        self.events.push(e);
+    }
+    fn pid(&self) -> usize {
+        self.i
+    }
+    fn n_procs(&self) -> usize {
+        self.n
+    }
+    fn events(&self) -> &[VectorClock] {
+        self.events.as_slice()
+    }
+}
+
+// Overrides every send to count messages, and every receive to record messages in flight
+impl OrdProcess<VectorClock> for MatternProc {
+    fn send<F: FnOnce(VectorClock)>(&mut self, send_fn: F) {
+        let e = self.next_event();
+        trace_event("send", self.i, &e);
+        self.push_event(e.clone());
+        self.n_sent += 1;
+        send_fn(e);
+    }
+    fn recv<F: FnOnce() -> VectorClock>(&mut self, recv_fn: F) {
+        let e_recv = recv_fn();
+        self.recv_from(|| (e_recv.pid(), e_recv));
+    }
+    fn recv_from<F: FnOnce() -> (usize, VectorClock)>(&mut self, recv_fn: F) -> usize {
+        let (from, e_recv) = recv_fn();
+        let before_cut = !self.cut.as_ref().is_some_and(|cut| cut.is_after(&e_recv));
+        let e = self
+            .last_event()
+            .unwrap_or(&VectorClock::new(self.i, self.n))
+            .merge(&e_recv);
+        trace_event("recv", self.i, &e);
+        self.push_recv_event(e, from);
+        self.n_received += 1;
+        if let (true, Some(local)) = (before_cut, self.snapshot.as_mut()) {
+            local.channels[from].push(e_recv);
+        }
+        from
+    }
+    fn try_send<E, F>(&mut self, send_fn: F) -> Result<(), OrderError>
+    where
+        E: Into<OrderError>,
+        F: FnOnce(VectorClock) -> Result<(), E>,
+    {
+        let e = self.next_event();
+        send_fn(e.clone()).map_err(Into::into)?;
+        trace_event("send", self.i, &e);
+        self.push_event(e);
+        self.n_sent += 1;
+        Ok(())
+    }
+    fn send_all<T: Transport<VectorClock>>(&mut self, transport: &mut T) -> Result<(), OrderError> {
+        let e = self.next_event();
+        let (mut n_sent, mut res) = (0, Ok(()));
+        for pid in (0..self.n).filter(|&j| j != self.i) {
+            match transport.send_to(pid, e.clone()) {
+                Ok(()) => n_sent += 1,
+                Err(err) => res = res.and(Err(err)),
+            }
+        }
+        if n_sent > 0 {
+            trace_event("send_all", self.i, &e);
+            self.push_event(e);
+            self.n_sent += n_sent;
+        }
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::order::mattern::{is_complete, MatternProc};
+    use crate::order::vector_clock::VectorClock;
+    use crate::order::{HasEvents, OrdProcess};
+    use alloc::vec::Vec;
+
+    // Sends from `i`, returning the message to deliver later
+    fn send(ps: &mut [MatternProc], i: usize) -> VectorClock {
+        let mut msg = None;
+        ps[i].send(|e| msg = Some(e));
+        msg.unwrap()
+    }
+
+    fn announce(ps: &mut [MatternProc], initiator: usize) {
+        let cut = ps[initiator].initiate();
+        for p in ps.iter_mut().filter(|p| p.pid() != initiator) {
+            p.on_cut(cut.clone());
+        }
+    }
+
+    fn complete(ps: &[MatternProc]) -> bool {
+        let locals: Option<Vec<_>> = ps.iter().map(MatternProc::snapshot).collect();
+        locals.is_some_and(is_complete)
+    }
+
+    // No event after the cut happens before an event before it
+    fn is_consistent(ps: &[MatternProc]) -> bool {
+        fn split(p: &MatternProc) -> (&[VectorClock], &[VectorClock]) {
+            p.events().split_at(p.snapshot().unwrap().n_events)
+        }
+        ps.iter().all(|p| {
+            split(p).0.iter().all(|e| {
+                ps.iter().all(|q| {
+                    split(q)
+                        .1
+                        .iter()
+                        .all(|f| f.partial_cmp(e).is_none_or(|o| o.is_gt()))
+                })
+            })
+        })
+    }
+
+    #[test]
+    fn records_in_flight() {
+        let mut ps: Vec<_> = (0..3).map(|i| MatternProc::new(i, 3)).collect();
+        let m02 = send(&mut ps, 0);
+        let m12 = send(&mut ps, 1);
+        announce(&mut ps, 0);
+        let r01 = send(&mut ps, 0);
+        let r02 = send(&mut ps, 0);
+        assert_eq!(ps[0].snapshot().unwrap().n_events, 1);
+        assert!(ps[1].snapshot().is_none());
+
+        ps[1].recv(|| r01);
+        ps[2].recv(|| m12);
+        assert!(ps[2].snapshot().is_none());
+        ps[2].recv(|| r02);
+        ps[2].recv(|| m02.clone());
+        assert_eq!(ps[1].snapshot().unwrap().n_events, 1);
+        assert_eq!(ps[2].snapshot().unwrap().n_events, 1);
+        assert_eq!(ps[2].snapshot().unwrap().channels[0], [m02]);
+        assert!(complete(&ps));
+        assert!(is_consistent(&ps));
+    }
+
+    #[test]
+    fn waits_for_in_flight() {
+        let mut ps: Vec<_> = (0..2).map(|i| MatternProc::new(i, 2)).collect();
+        let m10 = send(&mut ps, 1);
+        announce(&mut ps, 0);
+        let r01 = send(&mut ps, 0);
+        ps[1].recv(|| r01);
+        // Sent after the cut, so neither counted nor in flight
+        let r10 = send(&mut ps, 1);
+        ps[0].recv(|| r10);
+        assert!(!complete(&ps));
+        ps[0].recv(|| m10);
+        assert!(complete(&ps));
+        assert_eq!(ps[0].snapshot().unwrap().n_in_flight(), 1);
+        assert!(is_consistent(&ps));
+    }
+
+    #[test]
+    fn repeated_snapshots() {
+        let mut ps: Vec<_> = (0..2).map(|i| MatternProc::new(i, 2)).collect();
+        for round in 0..3 {
+            let initiator = round % 2;
+            let m = send(&mut ps, 1);
+            announce(&mut ps, initiator);
+            let r = send(&mut ps, initiator);
+            ps[1 - initiator].recv(|| r);
+            assert!(!complete(&ps));
+            ps[0].recv(|| m);
+            assert!(complete(&ps));
+            assert!(is_consistent(&ps));
+        }
+        assert_eq!(
+            ps[0].snapshot().unwrap().n_sent + ps[1].snapshot().unwrap().n_sent,
+            5
+        );
+    }
+}
@@ -0,0 +1,70 @@
+/// Wire shape shared by the protocols, bundling a clock `E` and user payload `T` with where it came from.
+///
+/// `seq` numbers the messages of the channel from `from` to the receiver, starting at 0, so that the receiver can
+/// detect gaps and restore FIFO order.
+///
+/// # Examples
+/// ```
+/// use rads::order::{LogicalClock, Message};
+/// use rads::order::lamport_clock::LamportClock;
+///
+/// let m = Message::new(0, 0, LamportClock::new(0, 2), "hello");
+/// assert_eq!((m.from, m.seq, m.payload), (0, 0, "hello"));
+/// let (clock, payload) = m.into_parts();
+/// assert_eq!((clock.get(), payload), (1, "hello"));
+/// ```
+#[derive(Clone, Hash, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Message<E, T> {
+    pub from: usize,
+    pub seq: u64,
+    pub clock: E,
+    pub payload: T,
+}
+
+impl<E, T> Message<E, T> {
+    pub fn new(from: usize, seq: u64, clock: E, payload: T) -> Self {
+        Self {
+            from,
+            seq,
+            clock,
+            payload,
+        }
+    }
+    /// Clock and payload, dropping the sender and sequence number
+    pub fn into_parts(self) -> (E, T) {
+        (self.clock, self.payload)
+    }
+    /// Same envelope with another payload, e.g. after decoding it
+    pub fn map<U, F: FnOnce(T) -> U>(self, f: F) -> Message<E, U> {
+        Message {
+            from: self.from,
+            seq: self.seq,
+            clock: self.clock,
+            payload: f(self.payload),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::order::vector_clock::VectorClock;
+    use crate::order::{LogicalClock, Message};
+
+    #[test]
+    fn map() {
+        let m = Message::new(1, 3, VectorClock::new(1, 2), "42");
+        let n = m.clone().map(|p| p.parse::<usize>().unwrap());
+        assert_eq!((n.from, n.seq, n.payload), (1, 3, 42));
+        assert!(n.clock == m.clock);
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn serde_round_trip() {
+        let m = Message::new(1, 3, VectorClock::new(1, 2), vec![1u8, 2]);
+        let json = serde_json::to_string(&m).unwrap();
+        let n: Message<VectorClock, Vec<u8>> = serde_json::from_str(&json).unwrap();
+        assert_eq!(m, n);
+    }
+}
@@ -0,0 +1,324 @@
+use crate::order::matrix_clock::{GCProcess, MatrixClock};
+use crate::order::transport::Transport;
+use crate::order::{HasEvents, LogicalClock, OrdProcess, OrderError};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::marker::PhantomData;
+
+/// Counters of a run of one process, e.g. to analyze the performance of a protocol built on the crate.
+#[derive(Clone, Default, PartialEq, Eq, Debug)]
+pub struct Metrics {
+    pub n_exec: usize,
+    pub n_send: usize,
+    pub n_recv: usize,
+    /// Messages sent to each process, where the receiver is known
+    pub sent_to: Vec<usize>,
+    /// Messages received from each process, where the sender is known
+    pub recv_from: Vec<usize>,
+    /// Size of the latest clock in bytes
+    pub clock_bytes: usize,
+    pub max_clock_bytes: usize,
+    /// Events garbage collected
+    pub gc_reclaimed: usize,
+    /// Most messages held back at once, e.g. by a delivery layer
+    pub max_queue_depth: usize,
+}
+
+impl Metrics {
+    pub fn new(n_procs: usize) -> Self {
+        Self {
+            sent_to: vec![0; n_procs],
+            recv_from: vec![0; n_procs],
+            ..Default::default()
+        }
+    }
+    pub fn record_clock<C: LogicalClock>(&mut self, e: &C) {
+        self.clock_bytes = e.byte_size();
+        self.max_clock_bytes = self.max_clock_bytes.max(self.clock_bytes);
+    }
+    pub fn record_gc(&mut self, reclaimed: usize) {
+        self.gc_reclaimed += reclaimed;
+    }
+    pub fn record_queue_depth(&mut self, depth: usize) {
+        self.max_queue_depth = self.max_queue_depth.max(depth);
+    }
+}
+
+/// Process that records `Metrics` of every event, while delegating to the inner process.
+///
+/// # Examples
+/// ```
+/// use rads::order::metrics::Metered;
+/// use rads::order::transport::MpscTransport;
+/// use rads::order::vector_clock::VectorClock;
+/// use rads::order::{OrdProcess, Process};
+///
+/// let mut ts = MpscTransport::mesh(2);
+/// let mut p0 = Metered::new(Process::<VectorClock>::new(0, 2));
+/// let mut p1 = Metered::new(Process::<VectorClock>::new(1, 2));
+/// p0.exec(|| {});
+/// p0.send_via(&mut ts[0], 1).unwrap();
+/// p1.recv_via(&mut ts[1]).unwrap();
+/// assert_eq!((p0.metrics().n_exec, p0.metrics().sent_to[1]), (1, 1));
+/// assert_eq!(p1.metrics().recv_from[0], 1);
+/// ```
+pub struct Metered<P, E> {
+    inner: P,
+    metrics: Metrics,
+    _event: PhantomData<E>,
+}
+
+impl<E: LogicalClock, P: OrdProcess<E>> Metered<P, E> {
+    pub fn new(inner: P) -> Self {
+        let metrics = Metrics::new(inner.n_procs());
+        Self {
+            inner,
+            metrics,
+            _event: PhantomData,
+        }
+    }
+    pub fn inner(&self) -> &P {
+        &self.inner
+    }
+    pub fn metrics(&self) -> &Metrics {
+        &self.metrics
+    }
+    /// To record what the process cannot see, e.g. queue depths
+    pub fn metrics_mut(&mut self) -> &mut Metrics {
+        &mut self.metrics
+    }
+    pub fn into_parts(self) -> (P, Metrics) {
+        (self.inner, self.metrics)
+    }
+    fn record_last(&mut self) {
+        if let Some(e) = self.inner.last_event() {
+            self.metrics.record_clock(e);
+        }
+    }
+}
+
+impl Metered<GCProcess, MatrixClock> {
+    pub fn gc(&mut self) -> Vec<MatrixClock> {
+        let reclaimed = self.inner.gc();
+        self.metrics.record_gc(reclaimed.len());
+        reclaimed
+    }
+}
+
+impl<E: LogicalClock, P: OrdProcess<E>> HasEvents<E> for Metered<P, E> {
+    fn last_event(&self) -> Option<&E> {
+        self.inner.last_event()
+    }
+    fn push_event(&mut self, e: E) {
+        self.inner.push_event(e)
+    }
+    fn push_recv_event(&mut self, e: E, from: usize) {
+        self.inner.push_recv_event(e, from)
+    }
+    fn pid(&self) -> usize {
+        self.inner.pid()
+    }
+    fn n_procs(&self) -> usize {
+        self.inner.n_procs()
+    }
+    fn events(&self) -> &[E] {
+        self.inner.events()
+    }
+    fn sender(&self, idx: usize) -> Option<usize> {
+        self.inner.sender(idx)
+    }
+}
+
+// Delegates to the inner process, so that its overrides still apply
+impl<E: LogicalClock, P: OrdProcess<E>> OrdProcess<E> for Metered<P, E> {
+    fn exec<F: FnOnce()>(&mut self, f: F) {
+        self.inner.exec(f);
+        self.metrics.n_exec += 1;
+        self.record_last();
+    }
+    fn send<F: FnOnce(E)>(&mut self, send_fn: F) {
+        self.inner.send(send_fn);
+        self.metrics.n_send += 1;
+        self.record_last();
+    }
+    fn recv<F: FnOnce() -> E>(&mut self, recv_fn: F) {
+        self.inner.recv(recv_fn);
+        self.metrics.n_recv += 1;
+        self.record_last();
+    }
+    fn recv_from<F: FnOnce() -> (usize, E)>(&mut self, recv_fn: F) -> usize {
+        let from = self.inner.recv_from(recv_fn);
+        self.metrics.n_recv += 1;
+        if let Some(n) = self.metrics.recv_from.get_mut(from) {
+            *n += 1;
+        }
+        self.record_last();
+        from
+    }
+    fn try_exec<T, Err, F>(&mut self, f: F) -> Result<T, OrderError>
+    where
+        Err: Into<OrderError>,
+        F: FnOnce() -> Result<T, Err>,
+    {
+        let res = self.inner.try_exec(f);
+        self.metrics.n_exec += 1; // even if f failed
+        self.record_last();
+        res
+    }
+    fn try_send<Err, F>(&mut self, send_fn: F) -> Result<(), OrderError>
+    where
+        Err: Into<OrderError>,
+        F: FnOnce(E) -> Result<(), Err>,
+    {
+        self.inner.try_send(send_fn)?;
+        self.metrics.n_send += 1;
+        self.record_last();
+        Ok(())
+    }
+    fn try_recv<Err, F>(&mut self, recv_fn: F) -> Result<(), OrderError>
+    where
+        Err: Into<OrderError>,
+        F: FnOnce() -> Result<E, Err>,
+    {
+        self.inner.try_recv(recv_fn)?;
+        self.metrics.n_recv += 1;
+        self.record_last();
+        Ok(())
+    }
+    fn send_via<T: Transport<E>>(
+        &mut self,
+        transport: &mut T,
+        pid: usize,
+    ) -> Result<(), OrderError> {
+        self.inner.send_via(transport, pid)?;
+        self.metrics.n_send += 1;
+        if let Some(n) = self.metrics.sent_to.get_mut(pid) {
+            *n += 1;
+        }
+        self.record_last();
+        Ok(())
+    }
+    fn recv_via<T: Transport<E>>(&mut self, transport: &mut T) -> Result<usize, OrderError> {
+        let from = self.inner.recv_via(transport)?;
+        self.metrics.n_recv += 1;
+        if let Some(n) = self.metrics.recv_from.get_mut(from) {
+            *n += 1;
+        }
+        self.record_last();
+        Ok(from)
+    }
+    fn send_all<T: Transport<E>>(&mut self, transport: &mut T) -> Result<(), OrderError> {
+        let n_events = self.inner.events().len();
+        let res = self.inner.send_all(transport);
+        if self.inner.events().len() > n_events {
+            self.metrics.n_send += 1;
+            self.record_last();
+        }
+        if res.is_ok() {
+            let i = self.pid();
+            for (_, n) in self
+                .metrics
+                .sent_to
+                .iter_mut()
+                .enumerate()
+                .filter(|(j, _)| *j != i)
+            {
+                *n += 1;
+            }
+        }
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::order::matrix_clock::{GCProcess, MatrixClock};
+    use crate::order::metrics::Metered;
+    use crate::order::{LogicalClock, OrdProcess};
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn counts_per_peer() {
+        use crate::order::metrics::Metrics;
+        use crate::order::transport::MpscTransport;
+        use crate::order::vector_clock::VectorClock;
+        use crate::order::{OrderError, Process};
+
+        let mut ts = MpscTransport::mesh(3);
+        let mut ps: Vec<_> = (0..3)
+            .map(|i| Metered::new(Process::<VectorClock>::new(i, 3)))
+            .collect();
+        ps[0].exec(|| {});
+        ps[0].send_all(&mut ts[0]).unwrap();
+        ps[0].send_via(&mut ts[0], 2).unwrap();
+        ps[1].recv_via(&mut ts[1]).unwrap();
+        ps[2].recv_via(&mut ts[2]).unwrap();
+        ps[2].recv_via(&mut ts[2]).unwrap();
+        let _ = ps[2].try_exec(|| Err::<(), _>(OrderError::Disconnected));
+
+        let (_, m0) = ps.remove(0).into_parts();
+        assert_eq!(
+            m0,
+            Metrics {
+                n_exec: 1,
+                n_send: 2,
+                n_recv: 0,
+                sent_to: vec![0, 1, 2],
+                recv_from: vec![0; 3],
+                clock_bytes: VectorClock::new(0, 3).byte_size(),
+                max_clock_bytes: VectorClock::new(0, 3).byte_size(),
+                gc_reclaimed: 0,
+                max_queue_depth: 0,
+            }
+        );
+        let m1 = ps[0].metrics();
+        assert_eq!((m1.n_recv, &m1.recv_from), (1, &vec![1, 0, 0]));
+        let m2 = ps[1].metrics();
+        assert_eq!(
+            (m2.n_exec, m2.n_recv, &m2.recv_from),
+            (1, 2, &vec![2, 0, 0])
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn delegates_recv_via() {
+        use crate::order::chandy_lamport::ChandyLamportProc;
+        use crate::order::transport::{MpscTransport, Transport};
+
+        let mut ts = MpscTransport::mesh(3);
+        let mut p0 = ChandyLamportProc::new(0, 3);
+        let mut p1 = Metered::new(ChandyLamportProc::new(1, 3));
+        p0.global_snapshot(|marker| {
+            for j in 1..3 {
+                ts[0].send_to(j, marker.clone()).unwrap();
+            }
+        });
+        assert_eq!(p1.recv_via(&mut ts[1]).unwrap(), 0);
+        assert_eq!(p1.metrics().recv_from, vec![1, 0, 0]);
+        // p1 forwarded the marker, as its own `recv_via` does
+        let froms: Vec<_> = core::iter::from_fn(|| ts[2].try_recv().unwrap())
+            .map(|(from, _)| from)
+            .collect();
+        assert_eq!(froms, [0, 1]);
+    }
+
+    #[test]
+    fn gc_and_queue_depth() {
+        let mut p0 = Metered::new(GCProcess::new(0, 2));
+        let mut p1 = Metered::new(GCProcess::new(1, 2));
+        p0.exec(|| {});
+        let mut msg = None;
+        p0.send(|e| msg = Some(e));
+        p1.recv(|| msg.take().unwrap());
+        p1.send(|e| msg = Some(e));
+        p0.recv(|| msg.take().unwrap());
+        assert_eq!(p0.gc().len(), 2);
+        assert_eq!(p0.metrics().gc_reclaimed, 2);
+        assert_eq!(p0.metrics().clock_bytes, MatrixClock::new(0, 2).byte_size());
+
+        p0.metrics_mut().record_queue_depth(3);
+        p0.metrics_mut().record_queue_depth(1);
+        assert_eq!(p0.metrics().max_queue_depth, 3);
+    }
+}
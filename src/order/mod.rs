@@ -1,14 +1,90 @@
+pub mod acharya_badrinath;
+pub mod bounded;
+pub mod causal_delivery;
+#[cfg(feature = "std")]
 pub mod chandy_lamport;
+#[cfg(feature = "std")]
+pub mod cluster;
+pub mod cut;
+pub mod epoch_clock;
+mod error;
+#[cfg(feature = "std")]
+pub mod group;
+pub mod hybrid_clock;
+pub mod interval_clock;
+pub mod lamport_clock;
 pub mod matrix_clock;
+pub mod mattern;
+mod message;
+pub mod metrics;
+mod process;
+pub mod ses;
+#[cfg(any(feature = "proptest", test))]
+pub mod strategy;
+pub mod total_order;
+#[cfg(all(feature = "std", feature = "serde"))]
+pub mod trace;
+pub mod transport;
 pub mod vector_clock;
+pub mod version_vector;
+#[cfg(all(feature = "std", feature = "serde"))]
+pub mod wal;
+
+use alloc::vec::Vec;
+pub use error::OrderError;
+pub use message::Message;
+pub use process::Process;
+use transport::Transport;
 
 // PartialOrd because not all clocks are comparable
 pub trait CausalOrd: PartialOrd {}
 
+/// Extends a causal order into a deterministic total order, e.g. for total order broadcast.
+///
+/// Events that happen before another are still ordered before it. Concurrent events are ordered by a tie-break such
+/// as the process id.
+#[derive(Clone, Debug)]
+pub struct TotalOrder<C>(pub C);
+
+/// Final clock entry `last` of a retired process `pid`, so that its entries can be compacted out of clocks.
+///
+/// Compacting is only safe past the tombstone frontier, i.e. once a clock has seen the final event of every retired
+/// process. Every live process must then compact with the same tombstones, since the remaining pids are renumbered.
+#[derive(Clone, Copy, Hash, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Tombstone<T = usize> {
+    pub pid: usize,
+    pub last: T,
+}
+
+impl<T> Tombstone<T> {
+    /// Pid of process `pid` after compacting the retired processes, or None if it retired
+    pub fn renumber(pid: usize, retired: &[Tombstone<T>]) -> Option<usize> {
+        if retired.iter().any(|t| t.pid == pid) {
+            return None;
+        }
+        Some(pid - retired.iter().filter(|t| t.pid < pid).count())
+    }
+}
+
 pub trait LogicalClock: Clone {
     fn new(i: usize, n_procs: usize) -> Self;
     fn extend(&self) -> Self;
     fn merge(&self, other: &Self) -> Self;
+    // Learns what `other` has seen without creating an event, e.g. for passive observers that never send.
+    // Defaults to `merge()`, which also counts an event of this process, since only the clock knows its components.
+    // Every clock of this crate takes the pairwise max instead
+    fn observe(&self, other: &Self) -> Self {
+        self.merge(other)
+    }
+    // Size of the clock in bytes, including heap allocations, e.g. to measure the overhead of messages
+    fn byte_size(&self) -> usize {
+        core::mem::size_of_val(self)
+    }
+    // Writes the clock for diagnostics, e.g. traces. Defaults to the type name
+    fn fmt_clock(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str(core::any::type_name::<Self>())
+    }
 }
 
 pub trait GCClock: LogicalClock {
@@ -18,9 +94,17 @@ pub trait GCClock: LogicalClock {
 pub trait HasEvents<Event: LogicalClock> {
     fn last_event(&self) -> Option<&Event>;
     fn push_event(&mut self, e: Event);
+    // Records a receive event along with the sender's pid, which is dropped unless overridden
+    fn push_recv_event(&mut self, e: Event, _from: usize) {
+        self.push_event(e)
+    }
     fn pid(&self) -> usize;
     fn n_procs(&self) -> usize;
     fn events(&self) -> &[Event];
+    // Pid of the process that sent the `idx`-th event, if known
+    fn sender(&self, _idx: usize) -> Option<usize> {
+        None
+    }
 }
 
 pub trait OrdProcess<Event>: HasEvents<Event>
@@ -33,6 +117,7 @@ where
             .last_event()
             .unwrap_or(&Event::new(self.pid(), self.n_procs()))
             .extend();
+        trace_event("exec", self.pid(), &e);
         self.push_event(e);
         f();
     }
@@ -43,6 +128,7 @@ where
             .last_event()
             .unwrap_or(&Event::new(self.pid(), self.n_procs()))
             .extend();
+        trace_event("send", self.pid(), &e);
         self.push_event(e.clone());
         send_fn(e);
     }
@@ -54,14 +140,476 @@ where
             .last_event()
             .unwrap_or(&Event::new(self.pid(), self.n_procs()))
             .merge(&e_recv);
+        trace_event("recv", self.pid(), &e);
         self.push_event(e);
     }
+    // Like `recv`, but also records the sender's pid, which it returns
+    // Expects a function that receives the sender's pid and clock from any other process in a lossless FIFO channel
+    fn recv_from<F: FnOnce() -> (usize, Event)>(&mut self, recv_fn: F) -> usize {
+        let (from, e_recv) = recv_fn();
+        let e = self
+            .last_event()
+            .unwrap_or(&Event::new(self.pid(), self.n_procs()))
+            .merge(&e_recv);
+        trace_event("recv", self.pid(), &e);
+        self.push_recv_event(e, from);
+        from
+    }
+    // Like `exec`, but propagates the error of the function. The event is recorded even if the function fails.
+    fn try_exec<T, E, F>(&mut self, f: F) -> Result<T, OrderError>
+    where
+        E: Into<OrderError>,
+        F: FnOnce() -> Result<T, E>,
+    {
+        let e = self
+            .last_event()
+            .unwrap_or(&Event::new(self.pid(), self.n_procs()))
+            .extend();
+        trace_event("exec", self.pid(), &e);
+        self.push_event(e);
+        f().map_err(Into::into)
+    }
+    // Like `send`, but only records the send event if the clock was sent
+    fn try_send<E, F>(&mut self, send_fn: F) -> Result<(), OrderError>
+    where
+        E: Into<OrderError>,
+        F: FnOnce(Event) -> Result<(), E>,
+    {
+        let e = self
+            .last_event()
+            .unwrap_or(&Event::new(self.pid(), self.n_procs()))
+            .extend();
+        send_fn(e.clone()).map_err(Into::into)?;
+        trace_event("send", self.pid(), &e);
+        self.push_event(e);
+        Ok(())
+    }
+    // Like `recv`, but only records the receive event if a clock was received
+    fn try_recv<E, F>(&mut self, recv_fn: F) -> Result<(), OrderError>
+    where
+        E: Into<OrderError>,
+        F: FnOnce() -> Result<Event, E>,
+    {
+        let e_recv = recv_fn().map_err(Into::into)?;
+        self.recv(|| e_recv);
+        Ok(())
+    }
+    // Sends new clock to process `pid` over the transport
+    fn send_via<T: Transport<Event>>(
+        &mut self,
+        transport: &mut T,
+        pid: usize,
+    ) -> Result<(), OrderError> {
+        self.try_send(|e| transport.send_to(pid, e))
+    }
+    // Sends one new clock to every other process over the transport, i.e. a single send event. The event is recorded
+    // if any process was sent the clock, even if sending to another failed.
+    fn send_all<T: Transport<Event>>(&mut self, transport: &mut T) -> Result<(), OrderError> {
+        let e = self
+            .last_event()
+            .unwrap_or(&Event::new(self.pid(), self.n_procs()))
+            .extend();
+        let (mut sent, mut res) = (false, Ok(()));
+        for pid in (0..self.n_procs()).filter(|&j| j != self.pid()) {
+            match transport.send_to(pid, e.clone()) {
+                Ok(()) => sent = true,
+                Err(err) => res = res.and(Err(err)),
+            }
+        }
+        if sent {
+            trace_event("send_all", self.pid(), &e);
+            self.push_event(e);
+        }
+        res
+    }
+    // Receives clock from any process over the transport, returning the sender's pid
+    fn recv_via<T: Transport<Event>>(&mut self, transport: &mut T) -> Result<usize, OrderError> {
+        let m = transport.recv()?;
+        Ok(self.recv_from(|| m))
+    }
+}
+
+/// Object-safe counterpart of `OrdProcess`, so that processes of different types can be stored as
+/// `Box<dyn DynProcess<C>>` and driven uniformly, e.g. by a simulator.
+///
+/// Implemented for every `OrdProcess`, whose overrides still apply.
+///
+/// # Examples
+/// ```
+/// use rads::order::metrics::Metered;
+/// use rads::order::vector_clock::VectorClock;
+/// use rads::order::{DynProcess, Process};
+///
+/// let mut ps: Vec<Box<dyn DynProcess<VectorClock>>> = vec![
+///     Box::new(Process::new(0, 2)),
+///     Box::new(Metered::new(Process::new(1, 2))),
+/// ];
+/// let e = ps[0].dyn_send();
+/// ps[1].dyn_recv_from(0, e);
+/// assert!(ps[0].events()[0] < ps[1].events()[0]);
+/// ```
+pub trait DynProcess<Event: LogicalClock>: HasEvents<Event> {
+    fn dyn_exec(&mut self, f: &mut dyn FnMut());
+    // Returns the new clock to send to receiving processes in a lossless FIFO channel
+    fn dyn_send(&mut self) -> Event;
+    fn dyn_recv(&mut self, e: Event);
+    fn dyn_recv_from(&mut self, from: usize, e: Event);
+    fn dyn_send_via(
+        &mut self,
+        transport: &mut dyn Transport<Event>,
+        pid: usize,
+    ) -> Result<(), OrderError>;
+    fn dyn_send_all(&mut self, transport: &mut dyn Transport<Event>) -> Result<(), OrderError>;
+    fn dyn_recv_via(&mut self, transport: &mut dyn Transport<Event>) -> Result<usize, OrderError>;
 }
 
-// Helper function
-fn pairwise_max<'a, I>(a: I, b: I) -> impl Iterator<Item = usize> + 'a
+impl<Event: LogicalClock, P: OrdProcess<Event>> DynProcess<Event> for P {
+    fn dyn_exec(&mut self, f: &mut dyn FnMut()) {
+        self.exec(f)
+    }
+    fn dyn_send(&mut self) -> Event {
+        let mut sent = None;
+        self.send(|e| sent = Some(e));
+        sent.expect("Expect send to pass on the new clock")
+    }
+    fn dyn_recv(&mut self, e: Event) {
+        self.recv(|| e)
+    }
+    fn dyn_recv_from(&mut self, from: usize, e: Event) {
+        self.recv_from(|| (from, e));
+    }
+    fn dyn_send_via(
+        &mut self,
+        mut transport: &mut dyn Transport<Event>,
+        pid: usize,
+    ) -> Result<(), OrderError> {
+        self.send_via(&mut transport, pid)
+    }
+    fn dyn_send_all(&mut self, mut transport: &mut dyn Transport<Event>) -> Result<(), OrderError> {
+        self.send_all(&mut transport)
+    }
+    fn dyn_recv_via(
+        &mut self,
+        mut transport: &mut dyn Transport<Event>,
+    ) -> Result<usize, OrderError> {
+        self.recv_via(&mut transport)
+    }
+}
+
+/// Entry of a clock that counts events, e.g. `usize` or `u32` to save space.
+pub trait Counter: Ord + Copy + core::fmt::Display {
+    const ZERO: Self;
+    fn incr(self) -> Self;
+}
+
+macro_rules! impl_counter {
+    ($($t:ty),*) => {$(
+        impl Counter for $t {
+            const ZERO: Self = 0;
+            fn incr(self) -> Self {
+                self + 1
+            }
+        }
+    )*};
+}
+impl_counter!(u8, u16, u32, u64, u128, usize);
+
+// Helper struct, displays a clock with `LogicalClock::fmt_clock`
+#[cfg(any(feature = "tracing", all(feature = "std", feature = "serde"), test))]
+pub(crate) struct ClockField<'a, C>(pub(crate) &'a C);
+
+#[cfg(any(feature = "tracing", all(feature = "std", feature = "serde"), test))]
+impl<C: LogicalClock> core::fmt::Display for ClockField<'_, C> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        self.0.fmt_clock(f)
+    }
+}
+
+// Helper function, traces an event of process `pid` with its clock, behind the `tracing` feature
+#[cfg_attr(not(feature = "tracing"), allow(unused_variables))]
+pub(crate) fn trace_event<C: LogicalClock>(kind: &'static str, pid: usize, e: &C) {
+    #[cfg(feature = "tracing")]
+    tracing::trace!(target: "rads::order", kind, pid, clock = %ClockField(e));
+}
+
+// Helper function, keeps the tail of the longer sequence, since processes it lacks joined later and were never seen
+fn pairwise_max<'a, T, I>(a: I, b: I) -> impl Iterator<Item = T> + 'a
 where
-    I: Iterator<Item = &'a usize> + 'a,
+    T: Ord + Copy + 'a,
+    I: Iterator<Item = &'a T> + 'a,
 {
-    a.zip(b).map(|(i, j)| *i.max(j))
+    let (mut a, mut b) = (a.fuse(), b.fuse());
+    core::iter::from_fn(move || match (a.next(), b.next()) {
+        (Some(i), Some(j)) => Some(*i.max(j)),
+        (Some(i), None) | (None, Some(i)) => Some(*i),
+        (None, None) => None,
+    })
+}
+
+// Helper function, parses "[1,2,3]"
+fn parse_list<T>(s: &str) -> anyhow::Result<Vec<T>>
+where
+    T: core::str::FromStr,
+    T::Err: core::fmt::Display + core::fmt::Debug + Send + Sync + 'static,
+{
+    let s = s.trim();
+    let inner = s
+        .strip_prefix('[')
+        .and_then(|s| s.strip_suffix(']'))
+        .ok_or_else(|| anyhow::anyhow!("Expect list in [..], got {s:?}"))?;
+    if inner.trim().is_empty() {
+        return Ok(Vec::new());
+    }
+    inner
+        .split(',')
+        .map(|v| v.trim().parse().map_err(anyhow::Error::msg))
+        .collect()
+}
+
+// Helper function, splits "<pid>:<clock>"
+fn split_pid(s: &str) -> anyhow::Result<(usize, &str)> {
+    let (i, clk) = s
+        .split_once(':')
+        .ok_or_else(|| anyhow::anyhow!("Expect <pid>:<clock>, got {s:?}"))?;
+    Ok((i.trim().parse().map_err(anyhow::Error::msg)?, clk))
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use crate::order::lamport_clock::LamportClock;
+    use crate::order::{CausalOrd, HasEvents, LogicalClock, OrdProcess, OrderError, Process};
+    use rand::{Rng, SeedableRng};
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn try_send_recv() {
+        let mut p0 = Process::<LamportClock>::new(0, 2);
+        let mut p1 = Process::<LamportClock>::new(1, 2);
+        let (tx, rx) = std::sync::mpsc::channel();
+        p0.try_send(|e| tx.send(e)).unwrap();
+        p1.try_recv(|| rx.recv()).unwrap();
+        assert_eq!(p1.events().len(), 1);
+
+        // Nothing recorded when the channel hangs up
+        drop(tx);
+        assert!(matches!(
+            p1.try_recv(|| rx.recv()),
+            Err(OrderError::Disconnected)
+        ));
+        assert_eq!(p1.events().len(), 1);
+        drop(rx);
+        let (tx, _) = std::sync::mpsc::channel();
+        assert!(matches!(
+            p0.try_send(|e| tx.send(e)),
+            Err(OrderError::Disconnected)
+        ));
+        assert_eq!(p0.events().len(), 1);
+    }
+
+    #[test]
+    fn observe_defaults_to_merge() {
+        /// Clock of an external crate, written before `observe` existed
+        #[derive(Clone, PartialEq, Debug)]
+        struct Counter(usize);
+        impl LogicalClock for Counter {
+            fn new(_i: usize, _n_procs: usize) -> Self {
+                Self(0)
+            }
+            fn extend(&self) -> Self {
+                Self(self.0 + 1)
+            }
+            fn merge(&self, other: &Self) -> Self {
+                Self(self.0.max(other.0) + 1)
+            }
+        }
+        let c = Counter(2);
+        assert_eq!(Counter(1).observe(&c), Counter(1).merge(&c));
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn send_all() {
+        use crate::order::transport::{MpscTransport, Transport};
+        use crate::order::vector_clock::VectorClock;
+
+        let mut ts = MpscTransport::mesh(3);
+        let mut p0 = Process::<VectorClock>::new(0, 3);
+        p0.send_all(&mut ts[0]).unwrap();
+        assert_eq!(p0.events().len(), 1);
+        assert!(ts[0].try_recv().unwrap().is_none()); // not to self
+        for t in &mut ts[1..] {
+            let mut p = Process::<VectorClock>::new(t.pid(), 3);
+            assert_eq!(p.recv_via(t).unwrap(), 0);
+            assert!(p0.events()[0] < p.events()[0]);
+        }
+
+        // Recorded once any peer received it
+        let mut t0 = ts.remove(0);
+        ts.pop();
+        assert!(matches!(
+            p0.send_all(&mut t0),
+            Err(OrderError::Disconnected)
+        ));
+        assert_eq!(p0.events().len(), 2);
+        ts.clear();
+        assert!(p0.send_all(&mut t0).is_err());
+        assert_eq!(p0.events().len(), 2);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn dyn_process() {
+        use crate::order::metrics::Metered;
+        use crate::order::transport::{MpscTransport, Transport};
+        use crate::order::vector_clock::VectorClock;
+        use crate::order::DynProcess;
+
+        let mut ps: Vec<Box<dyn DynProcess<VectorClock>>> = vec![
+            Box::new(Process::new(0, 3)),
+            Box::new(Metered::new(Process::new(1, 3))),
+            Box::new(Process::new(2, 3)),
+        ];
+        let mut ts: Vec<Box<dyn Transport<VectorClock>>> = MpscTransport::mesh(3)
+            .into_iter()
+            .map(|t| Box::new(t) as Box<dyn Transport<VectorClock>>)
+            .collect();
+        let mut n_calls = 0;
+        ps[0].dyn_exec(&mut || n_calls += 1);
+        ps[0].dyn_send_all(ts[0].as_mut()).unwrap();
+        assert_eq!(ps[1].dyn_recv_via(ts[1].as_mut()).unwrap(), 0);
+        ps[1].dyn_send_via(ts[1].as_mut(), 2).unwrap();
+        assert_eq!(ps[2].dyn_recv_via(ts[2].as_mut()).unwrap(), 0);
+        assert_eq!(ps[2].dyn_recv_via(ts[2].as_mut()).unwrap(), 1);
+        let e = ps[2].dyn_send();
+        ps[0].dyn_recv(e);
+
+        assert_eq!(n_calls, 1);
+        assert_eq!(
+            ps.iter().map(|p| p.events().len()).collect::<Vec<_>>(),
+            [3, 2, 3]
+        );
+        assert!(ps[1].events()[1] < ps[0].events()[2]);
+    }
+
+    #[test]
+    fn fmt_clock() {
+        use crate::order::epoch_clock::EpochClock;
+        use crate::order::ClockField;
+
+        let e = LamportClock::new(1, 2).extend();
+        assert_eq!(ClockField(&e).to_string(), "1:2");
+        let e = EpochClock::<LamportClock>::new(0, 2).next_epoch();
+        assert_eq!(ClockField(&e).to_string(), "epoch 1 0:1");
+        let e = crate::order::vector_clock::VectorClock::new(0, 2);
+        assert_eq!(ClockField(&e).to_string(), "0:[1,0]");
+    }
+
+    #[test]
+    #[cfg(all(feature = "tracing", feature = "std"))]
+    fn traces_events() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+        use tracing::span;
+
+        struct CountEvents(Arc<AtomicUsize>);
+        impl tracing::Subscriber for CountEvents {
+            fn enabled(&self, _: &tracing::Metadata<'_>) -> bool {
+                true
+            }
+            fn new_span(&self, _: &span::Attributes<'_>) -> span::Id {
+                span::Id::from_u64(1)
+            }
+            fn record(&self, _: &span::Id, _: &span::Record<'_>) {}
+            fn record_follows_from(&self, _: &span::Id, _: &span::Id) {}
+            fn event(&self, _: &tracing::Event<'_>) {
+                self.0.fetch_add(1, Ordering::Relaxed);
+            }
+            fn enter(&self, _: &span::Id) {}
+            fn exit(&self, _: &span::Id) {}
+        }
+
+        let n_events = Arc::new(AtomicUsize::new(0));
+        tracing::subscriber::with_default(CountEvents(n_events.clone()), || {
+            let mut p0 = Process::<LamportClock>::new(0, 2);
+            let mut p1 = Process::<LamportClock>::new(1, 2);
+            let mut msg = None;
+            p0.exec(|| {});
+            p0.send(|e| msg = Some(e));
+            p1.recv(|| msg.unwrap());
+        });
+        assert_eq!(n_events.load(Ordering::Relaxed), 3);
+    }
+
+    #[test]
+    fn try_exec() {
+        let mut p = Process::<LamportClock>::new(0, 1);
+        assert_eq!(p.try_exec(|| Ok::<_, OrderError>(1)).unwrap(), 1);
+        let err = p
+            .try_exec(|| Err::<(), _>(anyhow::anyhow!("user error")))
+            .unwrap_err();
+        assert_eq!(err.to_string(), "user error");
+        assert_eq!(p.events().len(), 2);
+    }
+
+    // Events of a random run of exec, send and recv between processes, in a lossless FIFO channel
+    pub(crate) fn random_history<C: LogicalClock>(
+        seed: u64,
+        n_procs: usize,
+        n_events: usize,
+    ) -> Vec<C> {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(seed);
+        let mut last: Vec<_> = (0..n_procs).map(|i| C::new(i, n_procs)).collect();
+        let mut in_flight = vec![std::collections::VecDeque::new(); n_procs];
+        let mut events = last.clone();
+        for _ in 0..n_events {
+            let i = rng.gen_range(0..n_procs);
+            let e = match rng.gen_range(0..3) {
+                0 => last[i].extend(),
+                1 => {
+                    let e = last[i].extend();
+                    in_flight[rng.gen_range(0..n_procs)].push_back(e.clone());
+                    e
+                }
+                _ => match in_flight[i].pop_front() {
+                    Some(e_recv) => last[i].merge(&e_recv),
+                    None => last[i].extend(),
+                },
+            };
+            last[i] = e.clone();
+            events.push(e);
+        }
+        events
+    }
+
+    // Partial order laws, over random histories
+    pub(crate) fn assert_causal_ord_laws<C: LogicalClock + CausalOrd>() {
+        use std::cmp::Ordering::{Equal, Greater, Less};
+        for seed in 0..10 {
+            let events = random_history::<C>(seed, 4, 40);
+            for s in &events {
+                assert_eq!(s.partial_cmp(s), Some(Equal), "reflexivity");
+                for t in &events {
+                    let st = s.partial_cmp(t);
+                    let ts = t.partial_cmp(s);
+                    assert_eq!(st, ts.map(std::cmp::Ordering::reverse), "antisymmetry");
+                    assert_eq!(st.is_none(), ts.is_none(), "concurrency symmetry");
+                    for u in &events {
+                        if s < t && t < u {
+                            assert!(s < u, "transitivity");
+                        }
+                        if s > t && t > u {
+                            assert!(s > u, "transitivity");
+                        }
+                    }
+                }
+            }
+            // Program order
+            assert!(events
+                .iter()
+                .any(|s| events.iter().any(|t| s.partial_cmp(t) == Some(Less))));
+            assert!(events
+                .iter()
+                .any(|s| events.iter().any(|t| s.partial_cmp(t) == Some(Greater))));
+        }
+    }
 }
@@ -0,0 +1,173 @@
+#[cfg(feature = "serde")]
+use crate::order::OrderError;
+use crate::order::{HasEvents, LogicalClock, OrdProcess};
+use alloc::vec::Vec;
+
+/// Process that logs the clock of every event it executes, sends or receives.
+///
+/// # Examples
+/// ```
+/// use rads::order::{HasEvents, OrdProcess, Process};
+/// use rads::order::vector_clock::VectorClock;
+///
+/// let mut p0 = Process::<VectorClock>::new(0, 2);
+/// let mut p1 = Process::<VectorClock>::new(1, 2);
+/// let mut msg = None;
+/// p0.send(|e| msg = Some(e));
+/// p1.recv(|| msg.unwrap());
+/// assert!(p0.events()[0] < p1.events()[0]);
+/// ```
+#[derive(Clone)]
+#[cfg_attr(test, derive(Debug))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Process<C> {
+    i: usize,
+    n_procs: usize,
+    events: Vec<C>,
+    /// Sender of each event, if received with `recv_from`
+    senders: Vec<Option<usize>>,
+}
+
+impl<C: LogicalClock> Process<C> {
+    pub fn new(i: usize, n_procs: usize) -> Self {
+        assert!(
+            i < n_procs,
+            "Expect 0-based index of process {i} < n_procs={n_procs}"
+        );
+        Self {
+            i,
+            n_procs,
+            events: Vec::new(),
+            senders: Vec::new(),
+        }
+    }
+    /// Copy of the event log so far, which outlives the process
+    pub fn snapshot(&self) -> Vec<C> {
+        self.events.clone()
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<C: LogicalClock + serde::Serialize + serde::de::DeserializeOwned> Process<C> {
+    /// Pid, number of processes and event log as bytes, e.g. to checkpoint or move the process
+    pub fn serialize_state(&self) -> Result<Vec<u8>, OrderError> {
+        serde_json::to_vec(self).map_err(|e| OrderError::Other(e.into()))
+    }
+    /// Inverse of `serialize_state`
+    pub fn restore_state(bytes: &[u8]) -> Result<Self, OrderError> {
+        let p: Self = serde_json::from_slice(bytes).map_err(|e| OrderError::Other(e.into()))?;
+        if p.i >= p.n_procs || p.senders.len() != p.events.len() {
+            return Err(OrderError::Other(anyhow::anyhow!(
+                "Invalid state of process {} of {} with {} events",
+                p.i,
+                p.n_procs,
+                p.events.len()
+            )));
+        }
+        Ok(p)
+    }
+}
+
+impl<C: LogicalClock> HasEvents<C> for Process<C> {
+    fn last_event(&self) -> Option<&C> {
+        self.events.last()
+    }
+    fn push_event(&mut self, e: C) {
+        self.events.push(e);
+        self.senders.push(None);
+    }
+    fn push_recv_event(&mut self, e: C, from: usize) {
+        self.events.push(e);
+        self.senders.push(Some(from));
+    }
+    fn pid(&self) -> usize {
+        self.i
+    }
+    fn n_procs(&self) -> usize {
+        self.n_procs
+    }
+    fn events(&self) -> &[C] {
+        self.events.as_slice()
+    }
+    // Known if received with `recv_from`
+    fn sender(&self, idx: usize) -> Option<usize> {
+        self.senders.get(idx).copied().flatten()
+    }
+}
+
+impl<C: LogicalClock> OrdProcess<C> for Process<C> {}
+
+#[cfg(test)]
+mod tests {
+    use crate::order::lamport_clock::LamportClock;
+    use crate::order::matrix_clock::MatrixClock;
+    use crate::order::{HasEvents, OrdProcess, Process};
+
+    #[test]
+    fn snapshot_outlives_process() {
+        let mut p = Process::<LamportClock>::new(0, 2);
+        p.exec(|| {});
+        let snapshot = p.snapshot();
+        p.exec(|| {});
+        assert_eq!(snapshot.len(), 1);
+        assert_eq!(p.events().len(), 2);
+        assert_eq!(snapshot[0], p.events()[0]);
+    }
+
+    #[test]
+    fn any_clock() {
+        let mut p0 = Process::<MatrixClock>::new(0, 2);
+        let mut p1 = Process::<MatrixClock>::new(1, 2);
+        p0.exec(|| {});
+        let mut msg = None;
+        p0.send(|e| msg = Some(e));
+        p1.recv(|| msg.unwrap());
+        assert_eq!((p0.pid(), p0.n_procs()), (0, 2));
+        assert!(p0.events().iter().all(|e| e < &p1.events()[0]));
+    }
+
+    #[test]
+    fn recv_from() {
+        let mut p0 = Process::<LamportClock>::new(0, 3);
+        let mut p2 = Process::<LamportClock>::new(2, 3);
+        let mut msg = None;
+        p0.send(|e| msg = Some(e));
+        p2.recv(|| msg.unwrap());
+        assert_eq!(p2.recv_from(|| (0, msg.unwrap())), 0);
+        p2.exec(|| {});
+        assert_eq!(p2.sender(0), None); // unknown
+        assert_eq!(p2.sender(1), Some(0));
+        assert_eq!(p2.sender(2), None); // local
+        assert_eq!(p2.sender(3), None); // out of bounds
+        assert!(p0.events()[0] < p2.events()[1]);
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn serialize_state() {
+        let mut p0 = Process::<MatrixClock>::new(0, 2);
+        let mut p1 = Process::<MatrixClock>::new(1, 2);
+        let mut msg = None;
+        p0.send(|e| msg = Some(e));
+        p1.exec(|| {});
+        p1.recv_from(|| (0, msg.unwrap()));
+
+        let q1 = Process::<MatrixClock>::restore_state(&p1.serialize_state().unwrap()).unwrap();
+        assert_eq!((q1.pid(), q1.n_procs()), (1, 2));
+        assert!(q1.events() == p1.events());
+        assert_eq!(q1.sender(1), Some(0));
+
+        assert!(Process::<MatrixClock>::restore_state(b"garbage").is_err());
+        let bad = p1.serialize_state().unwrap();
+        let bad = String::from_utf8(bad)
+            .unwrap()
+            .replace("\"n_procs\":2", "\"n_procs\":1");
+        assert!(Process::<MatrixClock>::restore_state(bad.as_bytes()).is_err());
+    }
+
+    #[test]
+    #[should_panic]
+    fn unknown_pid() {
+        Process::<LamportClock>::new(2, 2);
+    }
+}
@@ -0,0 +1,272 @@
+use crate::order::transport::Transport;
+use crate::order::vector_clock::VectorClock;
+use crate::order::{Message, OrderError};
+use alloc::collections::{BTreeMap, VecDeque};
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// Timestamp of a point-to-point message under SES, with the latest known sends to other destinations that the
+/// message depends on.
+#[derive(Clone, PartialEq, Eq)]
+#[cfg_attr(test, derive(Debug))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SesClock {
+    /// Sender's vector clock at the send
+    pub clock: VectorClock,
+    /// Vector clock of the latest send to each destination that happened before this send
+    pub deps: BTreeMap<usize, VectorClock>,
+}
+
+/// Causal unicast (Schiper–Eggli–Sandoz), so that if sending `m` to process `p` happens before sending `n` to `p`,
+/// then `p` delivers `m` before `n`.
+///
+/// Unlike `CausalDelivery`, which broadcasts, each message carries the timestamps of earlier sends to other
+/// destinations instead. A received message is held back until its receiver's clock covers the send to it that the
+/// message depends on.
+///
+/// # Examples
+/// ```
+/// use rads::order::ses::SesDelivery;
+/// use rads::order::transport::MpscTransport;
+///
+/// let mut ps: Vec<_> = MpscTransport::mesh(2).into_iter().map(SesDelivery::new).collect();
+/// ps[0].send_to(1, "hello").unwrap();
+/// let m = ps[1].deliver().unwrap();
+/// assert_eq!((m.from, m.payload), (0, "hello"));
+/// ```
+pub struct SesDelivery<T, M> {
+    transport: T,
+    clock: VectorClock,
+    /// Latest known send to each other destination
+    deps: BTreeMap<usize, VectorClock>,
+    /// Sequence number of the next message to each process
+    next_seq: Vec<u64>,
+    held: Vec<Message<SesClock, M>>,
+    ready: VecDeque<Message<SesClock, M>>,
+}
+
+impl<T, M> SesDelivery<T, M>
+where
+    T: Transport<Message<SesClock, M>>,
+{
+    pub fn new(transport: T) -> Self {
+        let (i, n_procs) = (transport.pid(), transport.n_procs());
+        Self {
+            transport,
+            clock: VectorClock::from_vec(i, vec![0; n_procs]),
+            deps: BTreeMap::new(),
+            next_seq: vec![0; n_procs],
+            held: Vec::new(),
+            ready: VecDeque::new(),
+        }
+    }
+    pub fn pid(&self) -> usize {
+        self.transport.pid()
+    }
+    /// Counts own sends and deliveries, and everything they depend on
+    pub fn clock(&self) -> &VectorClock {
+        &self.clock
+    }
+    /// Number of messages received but held back for missing dependencies
+    pub fn n_held(&self) -> usize {
+        self.held.len()
+    }
+    pub fn into_inner(self) -> T {
+        self.transport
+    }
+    /// Sends to process `pid`, returning the message's clock
+    pub fn send_to(&mut self, pid: usize, payload: M) -> Result<VectorClock, OrderError> {
+        let n_procs = self.next_seq.len();
+        let seq = *self.next_seq.get(pid).ok_or_else(|| {
+            OrderError::Other(anyhow::anyhow!(
+                "Expect 0-based index of process {pid} < n_procs={n_procs}"
+            ))
+        })?;
+        let clock = self.tick();
+        let stamp = SesClock {
+            clock: clock.clone(),
+            deps: self.deps.clone(),
+        };
+        let m = Message::new(self.pid(), seq, stamp, payload);
+        if pid == self.pid() {
+            self.ready.push_back(m);
+        } else {
+            self.transport.send_to(pid, m)?;
+            self.deps.insert(pid, clock.clone());
+        }
+        self.clock = clock.clone();
+        self.next_seq[pid] += 1;
+        Ok(clock)
+    }
+    /// Blocks until a message can be delivered
+    pub fn deliver(&mut self) -> Result<Message<SesClock, M>, OrderError> {
+        loop {
+            if let Some(m) = self.ready.pop_front() {
+                return Ok(m);
+            }
+            let (_, m) = self.transport.recv()?;
+            self.hold(m)?;
+        }
+    }
+    /// Like `deliver`, but returns None instead of blocking
+    pub fn try_deliver(&mut self) -> Result<Option<Message<SesClock, M>>, OrderError> {
+        while self.ready.is_empty() {
+            match self.transport.try_recv()? {
+                Some((_, m)) => self.hold(m)?,
+                None => break,
+            }
+        }
+        Ok(self.ready.pop_front())
+    }
+
+    fn tick(&self) -> VectorClock {
+        let own = self.clock.get(self.pid()).unwrap_or_default();
+        self.clock.advance(own + 1)
+    }
+
+    /// Holds back a received message, then readies every message whose dependencies were delivered
+    fn hold(&mut self, m: Message<SesClock, M>) -> Result<(), OrderError> {
+        let n_procs = self.clock.len();
+        if is_malformed(&m, n_procs) {
+            return Err(OrderError::Other(anyhow::anyhow!(
+                "Expect clocks of {n_procs} processes from a process among them, from process {}",
+                m.from
+            )));
+        }
+        self.held.push(m);
+        while let Some(idx) = self.held.iter().position(|m| self.is_deliverable(m)) {
+            let m = self.held.swap_remove(idx);
+            let i = self.pid();
+            for (&pid, clock) in m.clock.deps.iter().filter(|(&pid, _)| pid != i) {
+                let dep = match self.deps.get(&pid) {
+                    Some(seen) => seen.join(clock),
+                    None => clock.clone(),
+                };
+                self.deps.insert(pid, dep);
+            }
+            self.clock = self.clock.join(&m.clock.clock);
+            self.clock = self.tick();
+            self.ready.push_back(m);
+        }
+        Ok(())
+    }
+
+    /// No earlier send to self that it depends on is pending
+    fn is_deliverable(&self, m: &Message<SesClock, M>) -> bool {
+        match m.clock.deps.get(&self.pid()) {
+            Some(dep) => self.clock.dominates(dep),
+            None => true,
+        }
+    }
+}
+
+// Helper function, checks that all pids and clocks are of `n_procs` processes
+fn is_malformed<M>(m: &Message<SesClock, M>, n_procs: usize) -> bool {
+    m.from >= n_procs
+        || m.clock.clock.len() != n_procs
+        || m.clock
+            .deps
+            .iter()
+            .any(|(&pid, dep)| pid >= n_procs || dep.len() != n_procs)
+}
+
+#[cfg(test)]
+#[cfg(feature = "std")]
+mod tests {
+    use crate::order::ses::{SesClock, SesDelivery};
+    use crate::order::transport::{MpscTransport, Transport};
+    use crate::order::vector_clock::VectorClock;
+    use crate::order::Message;
+
+    #[test]
+    fn holds_back() {
+        let mut ts = MpscTransport::<Message<SesClock, &str>>::mesh(3);
+        let mut p2 = SesDelivery::new(ts.pop().unwrap());
+        let mut p1 = SesDelivery::new(ts.pop().unwrap());
+        let t0 = ts.pop().unwrap();
+
+        // p0 sends m1 to p2, then m2 to p1, but m1 is slow
+        let (tx, rx) = std::sync::mpsc::channel();
+        let mut p0 = SesDelivery::new(DelayTo2 { t: t0, slow: tx });
+        p0.send_to(2, "m1").unwrap();
+        p0.send_to(1, "m2").unwrap();
+        assert_eq!(p1.deliver().unwrap().payload, "m2");
+        p1.send_to(2, "m3").unwrap();
+
+        assert_eq!(p2.try_deliver().unwrap(), None);
+        assert_eq!(p2.n_held(), 1);
+        let mut t0 = p0.into_inner().t;
+        t0.send_to(2, rx.recv().unwrap()).unwrap();
+        assert_eq!(p2.deliver().unwrap().payload, "m1");
+        assert_eq!(p2.deliver().unwrap().payload, "m3");
+        assert_eq!(p2.n_held(), 0);
+        assert!(p2.clock() == &VectorClock::from_vec(2, vec![2, 2, 2]));
+
+        // Nothing pending to depend on, including sends to self
+        p2.send_to(1, "m4").unwrap();
+        p2.send_to(2, "m5").unwrap();
+        assert_eq!(p1.deliver().unwrap().payload, "m4");
+        assert_eq!(p2.deliver().unwrap().payload, "m5");
+    }
+
+    /// Withholds messages to process 2
+    struct DelayTo2<T, E> {
+        t: T,
+        slow: std::sync::mpsc::Sender<E>,
+    }
+
+    impl<E, T: Transport<E>> Transport<E> for DelayTo2<T, E> {
+        fn pid(&self) -> usize {
+            self.t.pid()
+        }
+        fn n_procs(&self) -> usize {
+            self.t.n_procs()
+        }
+        fn send_to(&mut self, pid: usize, e: E) -> Result<(), crate::order::OrderError> {
+            match pid {
+                2 => Ok(self.slow.send(e)?),
+                _ => self.t.send_to(pid, e),
+            }
+        }
+        fn recv(&mut self) -> Result<(usize, E), crate::order::OrderError> {
+            self.t.recv()
+        }
+        fn try_recv(&mut self) -> Result<Option<(usize, E)>, crate::order::OrderError> {
+            self.t.try_recv()
+        }
+    }
+
+    #[test]
+    fn causal_order() {
+        const N_PROCS: usize = 3;
+        const N_MSGS: usize = 30;
+        let ths: Vec<_> = MpscTransport::mesh(N_PROCS)
+            .into_iter()
+            .map(|t| {
+                std::thread::spawn(move || {
+                    let mut p = SesDelivery::new(t);
+                    let i = p.pid();
+                    let mut log = Vec::new();
+                    for k in 0..N_MSGS {
+                        p.send_to((i + 1 + k % 2) % N_PROCS, k).unwrap();
+                        while let Some(m) = p.try_deliver().unwrap() {
+                            log.push(m.clock.clock);
+                        }
+                    }
+                    while log.len() < N_MSGS {
+                        log.push(p.deliver().unwrap().clock.clock);
+                    }
+                    log
+                })
+            })
+            .collect();
+        for th in ths {
+            let log = th.join().unwrap();
+            for (a, s) in log.iter().enumerate() {
+                for t in &log[a + 1..] {
+                    assert_ne!(t.partial_cmp(s), Some(std::cmp::Ordering::Less));
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,247 @@
+use crate::order::matrix_clock::MatrixClock;
+use crate::order::vector_clock::VectorClock;
+use crate::order::{HasEvents, LogicalClock, OrdProcess, Process};
+use alloc::collections::VecDeque;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::ops::Range;
+use proptest::prelude::*;
+
+/// Step of a causal history.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Op {
+    Exec(usize),
+    Send {
+        from: usize,
+        to: usize,
+    },
+    /// Receives the oldest message in flight from `from` to `to`
+    Recv {
+        from: usize,
+        to: usize,
+    },
+}
+
+/// Valid run of `n_procs` processes over lossless FIFO channels, i.e. every receive has a matching send before it.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct History {
+    pub n_procs: usize,
+    pub ops: Vec<Op>,
+}
+
+impl History {
+    /// Event logs of every process after running the history, with any clock
+    pub fn replay<C: LogicalClock>(&self) -> Vec<Process<C>> {
+        let mut ps: Vec<_> = (0..self.n_procs)
+            .map(|i| Process::<C>::new(i, self.n_procs))
+            .collect();
+        let mut channels = vec![vec![VecDeque::new(); self.n_procs]; self.n_procs];
+        for op in &self.ops {
+            match *op {
+                Op::Exec(i) => ps[i].exec(|| {}),
+                Op::Send { from, to } => ps[from].send(|e| channels[from][to].push_back(e)),
+                Op::Recv { from, to } => {
+                    let e = channels[from][to]
+                        .pop_front()
+                        .expect("Expect a message in flight");
+                    ps[to].recv_from(|| (from, e));
+                }
+            }
+        }
+        ps
+    }
+
+    /// Whether the event `(pid, idx)` at `s` happens before the one at `t`, by program order and sends before their
+    /// receives, independently of any clock
+    pub fn happens_before(&self, s: (usize, usize), t: (usize, usize)) -> bool {
+        let preds = self.predecessors();
+        let mut stack = vec![t];
+        let mut seen = vec![Vec::new(); self.n_procs];
+        for (i, seen) in seen.iter_mut().enumerate() {
+            *seen = vec![false; preds[i].len()];
+        }
+        while let Some((pid, idx)) = stack.pop() {
+            let direct = idx.checked_sub(1).map(|prev| (pid, prev));
+            for pred in direct.into_iter().chain(preds[pid][idx]) {
+                if pred == s {
+                    return true;
+                }
+                if !seen[pred.0][pred.1] {
+                    seen[pred.0][pred.1] = true;
+                    stack.push(pred);
+                }
+            }
+        }
+        false
+    }
+
+    /// Matching send of every event that is a receive
+    fn predecessors(&self) -> Vec<Vec<Option<(usize, usize)>>> {
+        let mut preds = vec![Vec::new(); self.n_procs];
+        let mut channels = vec![vec![VecDeque::new(); self.n_procs]; self.n_procs];
+        for op in &self.ops {
+            match *op {
+                Op::Exec(i) => preds[i].push(None),
+                Op::Send { from, to } => {
+                    channels[from][to].push_back((from, preds[from].len()));
+                    preds[from].push(None);
+                }
+                Op::Recv { from, to } => {
+                    let send = channels[from][to].pop_front();
+                    preds[to].push(send);
+                }
+            }
+        }
+        preds
+    }
+}
+
+/// Valid causal histories of some `n_procs` processes with up to `max_ops` steps.
+///
+/// # Examples
+/// ```
+/// use proptest::prelude::*;
+/// use rads::order::strategy::history;
+/// use rads::order::vector_clock::VectorClock;
+/// use rads::order::HasEvents;
+///
+/// proptest!(|(h in history(1..4, 20))| {
+///     let ps = h.replay::<VectorClock>();
+///     for (i, p) in ps.iter().enumerate() {
+///         for idx in 1..p.events().len() {
+///             prop_assert!(p.events()[idx - 1] < p.events()[idx]);
+///             prop_assert!(h.happens_before((i, idx - 1), (i, idx)));
+///         }
+///     }
+/// });
+/// ```
+pub fn history(n_procs: Range<usize>, max_ops: usize) -> impl Strategy<Value = History> {
+    assert!(n_procs.start > 0, "Expect at least 1 process");
+    n_procs.prop_flat_map(move |n| {
+        // Raw choices, which are then mapped onto ops that are valid given the messages in flight
+        proptest::collection::vec((0..3u8, 0..n, 0..n), 0..=max_ops).prop_map(move |choices| {
+            let mut in_flight: Vec<(usize, usize)> = Vec::new();
+            let ops = choices
+                .into_iter()
+                .map(|(kind, a, b)| match kind {
+                    1 if a != b => {
+                        in_flight.push((a, b));
+                        Op::Send { from: a, to: b }
+                    }
+                    2 if !in_flight.is_empty() => {
+                        // Oldest in flight on some channel, so that channels stay FIFO
+                        let (from, to) = in_flight[a % in_flight.len()];
+                        let idx = in_flight.iter().position(|&c| c == (from, to)).unwrap();
+                        in_flight.remove(idx);
+                        Op::Recv { from, to }
+                    }
+                    _ => Op::Exec(a),
+                })
+                .collect();
+            History { n_procs: n, ops }
+        })
+    })
+}
+
+/// Arbitrary vector clocks of some `n_procs` processes with entries up to `max_entry`, which need not be reachable.
+pub fn vector_clock(n_procs: Range<usize>, max_entry: usize) -> impl Strategy<Value = VectorClock> {
+    assert!(n_procs.start > 0, "Expect at least 1 process");
+    n_procs.prop_flat_map(move |n| {
+        (0..n, proptest::collection::vec(0..=max_entry, n))
+            .prop_map(|(i, clk)| VectorClock::from_vec(i, clk))
+    })
+}
+
+/// Matrix clocks reachable in a causal history, since rows must be consistent with each other.
+pub fn matrix_clock(n_procs: Range<usize>, max_ops: usize) -> impl Strategy<Value = MatrixClock> {
+    history(n_procs, max_ops)
+        .prop_flat_map(|h| {
+            let events: Vec<MatrixClock> = h
+                .replay::<MatrixClock>()
+                .iter()
+                .flat_map(|p| p.events().to_vec())
+                .collect();
+            // Initial clock in case nothing happened
+            let init = MatrixClock::new(0, h.n_procs);
+            (Just(events), Just(init), any::<prop::sample::Index>())
+        })
+        .prop_map(|(events, init, idx)| match events.is_empty() {
+            true => init,
+            false => events[idx.index(events.len())].clone(),
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::order::lamport_clock::LamportClock;
+    use crate::order::matrix_clock::MatrixClock;
+    use crate::order::strategy::{history, matrix_clock, vector_clock, History};
+    use crate::order::vector_clock::VectorClock;
+    use crate::order::{HasEvents, LogicalClock};
+    use proptest::prelude::*;
+
+    /// Every pair of events in the history, by (pid, idx)
+    fn pairs(h: &History, lens: &[usize]) -> Vec<((usize, usize), (usize, usize))> {
+        let events: Vec<_> = (0..h.n_procs)
+            .flat_map(|i| (0..lens[i]).map(move |idx| (i, idx)))
+            .collect();
+        events
+            .iter()
+            .flat_map(|&s| events.iter().map(move |&t| (s, t)))
+            .collect()
+    }
+
+    proptest! {
+        #[test]
+        fn join_is_a_semilattice(
+            (a, b, c) in (1..5usize).prop_flat_map(|n| {
+                let clock = || vector_clock(n..n + 1, 10);
+                (clock(), clock(), clock())
+            })
+        ) {
+            prop_assert!(a.join(&b).iter().eq(b.join(&a).iter()));
+            prop_assert_eq!(a.join(&b).join(&c), a.join(&b.join(&c)));
+            prop_assert_eq!(a.join(&a), a.clone());
+            prop_assert!(a <= a.join(&b) && b <= a.join(&b));
+            prop_assert_eq!(a >= b, a.dominates(&b));
+        }
+
+        #[test]
+        fn vector_clock_iff_happens_before(h in history(1..4, 24)) {
+            let ps = h.replay::<VectorClock>();
+            let lens: Vec<_> = ps.iter().map(|p| p.events().len()).collect();
+            for (s, t) in pairs(&h, &lens) {
+                let (es, et) = (&ps[s.0].events()[s.1], &ps[t.0].events()[t.1]);
+                prop_assert_eq!(es < et, h.happens_before(s, t), "{:?} < {:?}", s, t);
+            }
+        }
+
+        #[test]
+        fn lamport_clock_if_happens_before(h in history(1..4, 24)) {
+            let ps = h.replay::<LamportClock>();
+            let lens: Vec<_> = ps.iter().map(|p| p.events().len()).collect();
+            for (s, t) in pairs(&h, &lens).into_iter().filter(|&(s, t)| h.happens_before(s, t)) {
+                prop_assert!(ps[s.0].events()[s.1] < ps[t.0].events()[t.1]);
+            }
+        }
+
+        #[test]
+        fn observe_is_a_semilattice(h in history(1..4, 24)) {
+            // Clocks of the same process, since observe keeps the observer's pid
+            for p in h.replay::<MatrixClock>() {
+                let es = p.events();
+                for ((a, b), c) in es.iter().zip(es.iter().rev()).zip(es.iter().skip(1)) {
+                    prop_assert!(a.observe(b) == b.observe(a));
+                    prop_assert!(a.observe(b).observe(c) == a.observe(&b.observe(c)));
+                    prop_assert!(a.merge(b) == a.observe(b).extend());
+                }
+            }
+        }
+
+        #[test]
+        fn observe_is_idempotent(a in matrix_clock(1..4, 24)) {
+            prop_assert!(a.observe(&a) == a);
+            prop_assert!(a < a.extend());
+        }
+    }
+}
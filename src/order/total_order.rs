@@ -0,0 +1,240 @@
+use crate::order::lamport_clock::{LamportClock, TotalLamport};
+use crate::order::transport::Transport;
+use crate::order::{LogicalClock, Message, OrderError};
+use alloc::collections::{BTreeMap, BTreeSet, VecDeque};
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// Payload of a message between `TotalOrderDelivery`s.
+#[derive(Clone, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TotalOrderMsg<M> {
+    Data(M),
+    /// Acknowledges the data message of this id
+    Ack(TotalLamport),
+}
+
+/// Total order multicast, so that every process delivers the same messages in the same order, namely by Lamport
+/// timestamp and then pid.
+///
+/// Every process acknowledges every message to all processes. A queued message is delivered once it has the smallest
+/// timestamp and every process acknowledged it, since FIFO channels then rule out any earlier message in flight.
+///
+/// # Examples
+/// ```
+/// use rads::order::total_order::TotalOrderDelivery;
+/// use rads::order::transport::MpscTransport;
+///
+/// let mut ps: Vec<_> = MpscTransport::mesh(2).into_iter().map(TotalOrderDelivery::new).collect();
+/// ps[1].multicast("b").unwrap();
+/// ps[0].multicast("a").unwrap();
+/// assert_eq!(ps[0].try_deliver().unwrap(), None); // until p1 acknowledges
+/// for p in ps.iter_mut().rev() {
+///     assert_eq!(p.deliver().unwrap().payload, "a"); // tie broken by pid
+///     assert_eq!(p.deliver().unwrap().payload, "b");
+/// }
+/// ```
+pub struct TotalOrderDelivery<T, M> {
+    transport: T,
+    clock: LamportClock,
+    /// Sequence number of the next message to each process
+    next_seq: Vec<u64>,
+    queue: BTreeMap<TotalLamport, Message<LamportClock, M>>,
+    /// Processes that acknowledged each message, which may arrive before the message
+    acks: BTreeMap<TotalLamport, BTreeSet<usize>>,
+    ready: VecDeque<Message<LamportClock, M>>,
+}
+
+impl<T, M> TotalOrderDelivery<T, M>
+where
+    T: Transport<Message<LamportClock, TotalOrderMsg<M>>>,
+    M: Clone,
+{
+    pub fn new(transport: T) -> Self {
+        let (i, n_procs) = (transport.pid(), transport.n_procs());
+        Self {
+            transport,
+            clock: LamportClock::new(i, n_procs),
+            next_seq: vec![0; n_procs],
+            queue: BTreeMap::new(),
+            acks: BTreeMap::new(),
+            ready: VecDeque::new(),
+        }
+    }
+    pub fn pid(&self) -> usize {
+        self.transport.pid()
+    }
+    pub fn clock(&self) -> &LamportClock {
+        &self.clock
+    }
+    /// Number of messages received but not yet delivered
+    pub fn n_queued(&self) -> usize {
+        self.queue.len()
+    }
+    pub fn into_inner(self) -> T {
+        self.transport
+    }
+    /// Sends to every process including self, returning the message's id in the total order.
+    ///
+    /// If sending to some process fails, still sends to the others and queues the message at self, so that every
+    /// process that received it agrees on its place, and returns the first error.
+    pub fn multicast(&mut self, payload: M) -> Result<TotalLamport, OrderError> {
+        self.clock = self.clock.extend();
+        let id = self.clock.total();
+        let sent = self.send_all(TotalOrderMsg::Data(payload.clone()));
+        let i = self.pid();
+        let m = Message::new(i, self.next_seq[i], self.clock, payload);
+        self.next_seq[i] += 1;
+        self.enqueue(id, m);
+        self.ready_all();
+        sent.map(|_| id)
+    }
+    /// Blocks until the next message in the total order can be delivered
+    pub fn deliver(&mut self) -> Result<Message<LamportClock, M>, OrderError> {
+        loop {
+            if let Some(m) = self.ready.pop_front() {
+                return Ok(m);
+            }
+            let (from, m) = self.transport.recv()?;
+            self.on_recv(from, m)?;
+        }
+    }
+    /// Like `deliver`, but returns None instead of blocking
+    pub fn try_deliver(&mut self) -> Result<Option<Message<LamportClock, M>>, OrderError> {
+        while self.ready.is_empty() {
+            match self.transport.try_recv()? {
+                Some((from, m)) => self.on_recv(from, m)?,
+                None => break,
+            }
+        }
+        Ok(self.ready.pop_front())
+    }
+
+    /// Sends to every other process, even if sending to some fails, returning the first error
+    fn send_all(&mut self, msg: TotalOrderMsg<M>) -> Result<(), OrderError> {
+        let i = self.pid();
+        (0..self.next_seq.len())
+            .filter(|&j| j != i)
+            .map(|pid| {
+                let m = Message::new(i, self.next_seq[pid], self.clock, msg.clone());
+                self.transport.send_to(pid, m)?;
+                self.next_seq[pid] += 1;
+                Ok(())
+            })
+            .fold(Ok(()), Result::and)
+    }
+
+    /// Queues a message, acknowledged by its sender and self
+    fn enqueue(&mut self, id: TotalLamport, m: Message<LamportClock, M>) {
+        let i = self.pid();
+        let acks = self.acks.entry(id).or_default();
+        acks.insert(m.from);
+        acks.insert(i);
+        self.queue.insert(id, m);
+    }
+
+    fn on_recv(
+        &mut self,
+        from: usize,
+        m: Message<LamportClock, TotalOrderMsg<M>>,
+    ) -> Result<(), OrderError> {
+        let n_procs = self.next_seq.len();
+        if from >= n_procs || m.from != from || m.clock.pid() != from {
+            return Err(OrderError::Other(anyhow::anyhow!(
+                "Expect message of process {} from process {from} < n_procs={n_procs}",
+                m.from
+            )));
+        }
+        self.clock = self.clock.merge(&m.clock);
+        // Readies messages even if acknowledging failed, like `multicast`
+        let acked = match m.payload {
+            TotalOrderMsg::Data(payload) => {
+                let id = m.clock.total();
+                self.enqueue(id, Message::new(from, m.seq, m.clock, payload));
+                self.send_all(TotalOrderMsg::Ack(id))
+            }
+            TotalOrderMsg::Ack(id) => {
+                self.acks.entry(id).or_default().insert(from);
+                Ok(())
+            }
+        };
+        self.ready_all();
+        acked
+    }
+
+    /// Readies queued messages in order, while the first is acknowledged by all
+    fn ready_all(&mut self) {
+        let n_procs = self.next_seq.len();
+        while let Some(entry) = self.queue.first_entry() {
+            let id = *entry.key();
+            if self.acks.get(&id).map_or(0, BTreeSet::len) < n_procs {
+                break;
+            }
+            self.acks.remove(&id);
+            self.ready.push_back(entry.remove());
+        }
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "std")]
+mod tests {
+    use crate::order::total_order::TotalOrderDelivery;
+    use crate::order::transport::MpscTransport;
+
+    #[test]
+    fn same_order_everywhere() {
+        const N_PROCS: usize = 4;
+        const N_MSGS: usize = 15;
+        let barrier = std::sync::Arc::new(std::sync::Barrier::new(N_PROCS));
+        let ths: Vec<_> = MpscTransport::mesh(N_PROCS)
+            .into_iter()
+            .map(|t| {
+                let barrier = barrier.clone();
+                std::thread::spawn(move || {
+                    let mut p = TotalOrderDelivery::new(t);
+                    let mut log = Vec::new();
+                    for k in 0..N_MSGS {
+                        p.multicast((p.pid(), k)).unwrap();
+                        while let Some(m) = p.try_deliver().unwrap() {
+                            log.push((m.clock.total(), m.payload));
+                        }
+                    }
+                    while log.len() < N_PROCS * N_MSGS {
+                        let m = p.deliver().unwrap();
+                        log.push((m.clock.total(), m.payload));
+                    }
+                    assert_eq!(p.n_queued(), 0);
+                    barrier.wait(); // before hanging up
+                    log
+                })
+            })
+            .collect();
+        let logs: Vec<_> = ths.into_iter().map(|th| th.join().unwrap()).collect();
+        for log in &logs {
+            assert_eq!(log, &logs[0]);
+            assert!(log.windows(2).all(|w| w[0].0 < w[1].0));
+            for i in 0..N_PROCS {
+                let from_i: Vec<_> = log.iter().filter(|(_, (j, _))| *j == i).collect();
+                assert!(from_i.iter().enumerate().all(|(k, (_, (_, m)))| *m == k));
+            }
+        }
+    }
+
+    #[test]
+    fn multicast_despite_failed_send() {
+        let mut ts = MpscTransport::mesh(3);
+        drop(ts.pop());
+        let mut p1 = TotalOrderDelivery::new(ts.pop().unwrap());
+        let mut p0 = TotalOrderDelivery::new(ts.pop().unwrap());
+        assert!(p0.multicast("m1").is_err());
+        // Queued at p0 as at p1, which acknowledges it to p0 despite failing to p2
+        assert_eq!(p0.n_queued(), 1);
+        assert!(p1.try_deliver().is_err());
+        assert_eq!(p1.n_queued(), 1);
+        assert_eq!(p0.try_deliver().unwrap(), None);
+        assert!(p0.multicast("m2").is_err());
+        assert!(p1.try_deliver().is_err());
+        assert_eq!((p0.n_queued(), p1.n_queued()), (2, 2));
+    }
+}
@@ -0,0 +1,536 @@
+use crate::order::transport::Transport;
+use crate::order::vector_clock::VectorClock;
+use crate::order::{ClockField, HasEvents, LogicalClock, OrdProcess, OrderError, Process};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::marker::PhantomData;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+/// Kind of a traced event.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TraceKind {
+    Exec,
+    Send,
+    Recv,
+    /// Delivered by a delivery layer, e.g. `CausalDelivery`, some time after it was received
+    Deliver,
+}
+
+/// One line of a trace.
+///
+/// The schema of each line is
+/// ```json
+/// {"pid": 0, "kind": "exec" | "send" | "recv" | "deliver", "peer": 1 | null, "clock": <clock>, "text": "0:[1,0]"}
+/// ```
+/// where `peer` is the receiver of a send or the sender of a receive or delivery, if known, `clock` is the clock as
+/// serialized by serde and `text` is the clock as formatted by `LogicalClock::fmt_clock`. A send without a peer may be
+/// to any or all processes, e.g. one event of `send_all()` for every other process.
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct TraceEvent<E> {
+    pub pid: usize,
+    pub kind: TraceKind,
+    pub peer: Option<usize>,
+    pub clock: E,
+    pub text: String,
+}
+
+impl<E: LogicalClock> TraceEvent<E> {
+    pub fn new(pid: usize, kind: TraceKind, peer: Option<usize>, clock: E) -> Self {
+        let text = ClockField(&clock).to_string();
+        Self {
+            pid,
+            kind,
+            peer,
+            clock,
+            text,
+        }
+    }
+}
+
+/// Appends events of any number of processes to one JSON lines trace, in the order they are recorded.
+///
+/// Clones share the trace, e.g. one per thread. Panics if an event cannot be written by a `Traced` process, like a
+/// `Wal`.
+///
+/// # Examples
+/// ```
+/// use rads::order::trace::{read_trace, TraceKind, TraceRecorder, Traced};
+/// use rads::order::vector_clock::VectorClock;
+/// use rads::order::{OrdProcess, Process};
+///
+/// let path = std::env::temp_dir().join(format!("rads-doc-{}.jsonl", std::process::id()));
+/// let recorder = TraceRecorder::create(&path).unwrap();
+/// let mut p0 = Traced::new(Process::<VectorClock>::new(0, 2), recorder.clone());
+/// let mut p1 = Traced::new(Process::<VectorClock>::new(1, 2), recorder.clone());
+/// let mut msg = None;
+/// p0.send(|e| msg = Some(e));
+/// p1.recv_from(|| (0, msg.unwrap()));
+/// recorder.flush().unwrap();
+///
+/// let trace = read_trace::<VectorClock, _>(&path).unwrap();
+/// assert_eq!(trace[1].kind, TraceKind::Recv);
+/// assert_eq!((trace[1].peer, trace[1].text.as_str()), (Some(0), "1:[2,2]"));
+/// std::fs::remove_file(path).unwrap();
+/// ```
+#[derive(Clone)]
+pub struct TraceRecorder {
+    out: Arc<Mutex<Box<dyn Write + Send>>>,
+}
+
+impl TraceRecorder {
+    pub fn new<W: Write + Send + 'static>(out: W) -> Self {
+        Self {
+            out: Arc::new(Mutex::new(Box::new(out))),
+        }
+    }
+    /// Records to the file at `path`, replacing any file there
+    pub fn create<Q: AsRef<Path>>(path: Q) -> Result<Self, OrderError> {
+        Ok(Self::new(BufWriter::new(File::create(path)?)))
+    }
+    pub fn record<E: Serialize>(&self, event: &TraceEvent<E>) -> Result<(), OrderError> {
+        let line = serde_json::to_vec(event).map_err(|e| OrderError::Other(e.into()))?;
+        // One write per line while locked, so that lines of concurrent processes don't interleave
+        let mut out = self.out.lock().map_err(poisoned)?;
+        out.write_all(&line)?;
+        Ok(out.write_all(b"\n")?)
+    }
+    /// Records a delivery by a delivery layer, which a `Traced` process cannot see
+    pub fn deliver<E>(&self, pid: usize, from: usize, clock: E) -> Result<(), OrderError>
+    where
+        E: LogicalClock + Serialize,
+    {
+        self.record(&TraceEvent::new(pid, TraceKind::Deliver, Some(from), clock))
+    }
+    pub fn flush(&self) -> Result<(), OrderError> {
+        let mut out = self.out.lock().map_err(poisoned)?;
+        Ok(out.flush()?)
+    }
+}
+
+fn poisoned<T>(_: T) -> OrderError {
+    OrderError::Other(anyhow::anyhow!(
+        "Expect no process to panic while recording a trace"
+    ))
+}
+
+/// Reads a trace written by a `TraceRecorder`.
+pub fn read_trace<E: DeserializeOwned, Q: AsRef<Path>>(
+    path: Q,
+) -> Result<Vec<TraceEvent<E>>, OrderError> {
+    std::fs::read_to_string(path)?
+        .lines()
+        .map(|line| serde_json::from_str(line).map_err(|e| OrderError::Other(e.into())))
+        .collect()
+}
+
+/// Formats a trace of vector clocks as a ShiViz log, to parse with ShiViz's default regex
+/// `(?<event>.*)\n(?<host>\S*) (?<clock>{.*})`.
+pub fn to_shiviz(trace: &[TraceEvent<VectorClock>]) -> String {
+    let mut log = String::new();
+    for e in trace {
+        let clock: Vec<_> = e
+            .clock
+            .iter()
+            .enumerate()
+            .filter(|(_, v)| **v > 0)
+            .map(|(j, v)| format!("\"p{j}\":{v}"))
+            .collect();
+        let peer = e.peer.map(|j| format!(" p{j}")).unwrap_or_default();
+        log.push_str(&format!(
+            "{:?}{peer}\np{} {{{}}}\n",
+            e.kind,
+            e.pid,
+            clock.join(",")
+        ));
+    }
+    log
+}
+
+/// Re-executes a trace of `n_procs` fresh processes, checking that every recomputed clock matches the recorded one,
+/// e.g. to catch nondeterminism or a regression of a clock implementation.
+///
+/// Each receive takes the oldest send from its peer that was addressed to it or to all. A receive without a peer takes
+/// that of the first process whose merge yields the recorded clock instead. Deliveries are skipped, since delivery
+/// layers keep clocks of their own.
+///
+/// # Examples
+/// ```
+/// use rads::order::trace::{replay_trace, TraceEvent, TraceKind};
+/// use rads::order::vector_clock::VectorClock;
+/// use rads::order::HasEvents;
+///
+/// let trace = vec![
+///     TraceEvent::new(0, TraceKind::Send, Some(1), VectorClock::from_vec(0, vec![2, 0])),
+///     TraceEvent::new(1, TraceKind::Recv, Some(0), VectorClock::from_vec(1, vec![2, 2])),
+/// ];
+/// let ps = replay_trace(&trace, 2).unwrap();
+/// assert_eq!(ps[1].sender(0), Some(0));
+///
+/// let mut regressed = trace.clone();
+/// regressed[1].clock = VectorClock::from_vec(1, vec![2, 1]);
+/// assert!(replay_trace(&regressed, 2).is_err());
+/// ```
+pub fn replay_trace<E>(
+    trace: &[TraceEvent<E>],
+    n_procs: usize,
+) -> Result<Vec<Process<E>>, OrderError>
+where
+    E: LogicalClock + PartialEq,
+{
+    let mut ps: Vec<_> = (0..n_procs)
+        .map(|i| Process::<E>::new(i, n_procs))
+        .collect();
+    // Sends of each process with their receiver if known, and how many of them each process has looked past
+    let mut sends: Vec<Vec<(Option<usize>, E)>> = vec![Vec::new(); n_procs];
+    let mut cursors = vec![vec![0; n_procs]; n_procs];
+    for (idx, event) in trace.iter().enumerate() {
+        let pid = event.pid;
+        let check_pid = |j: usize| match j < n_procs {
+            true => Ok(j),
+            false => Err(OrderError::Other(anyhow::anyhow!(
+                "Expect 0-based index of process {j} < n_procs={n_procs} at event {idx}"
+            ))),
+        };
+        let p = &mut ps[check_pid(pid)?];
+        match (event.kind, event.peer) {
+            (TraceKind::Exec, _) => p.exec(|| {}),
+            (TraceKind::Send, to) => {
+                let to = to.map(check_pid).transpose()?;
+                p.send(|e| sends[pid].push((to, e)));
+            }
+            (TraceKind::Recv, peer) => {
+                let froms = match peer {
+                    Some(from) => vec![check_pid(from)?],
+                    None => (0..n_procs).filter(|&j| j != pid).collect(),
+                };
+                let last = p
+                    .last_event()
+                    .cloned()
+                    .unwrap_or_else(|| E::new(pid, n_procs));
+                // Without a sender, takes the first process whose next send to this one yields the recorded clock
+                let (from, skipped) = froms
+                    .into_iter()
+                    .find_map(|from| {
+                        let cursor = cursors[from][pid];
+                        let skipped = sends[from][cursor..]
+                            .iter()
+                            .position(|(to, _)| to.is_none_or(|to| to == pid))?;
+                        let e = &sends[from][cursor + skipped].1;
+                        (peer.is_some() || last.merge(e) == event.clock).then_some((from, skipped))
+                    })
+                    .ok_or_else(|| match peer {
+                        Some(from) => OrderError::Other(anyhow::anyhow!(
+                            "Expect a send from process {from} to {pid} before event {idx}"
+                        )),
+                        None => OrderError::Other(anyhow::anyhow!(
+                            "Expect a send to process {pid} that matches receive event {idx}"
+                        )),
+                    })?;
+                let cursor = &mut cursors[from][pid];
+                *cursor += skipped + 1;
+                let e = sends[from][*cursor - 1].1.clone();
+                p.recv_from(|| (from, e));
+            }
+            (TraceKind::Deliver, _) => continue,
+        }
+        let replayed = p.last_event().expect("Expect an event");
+        if replayed != &event.clock {
+            return Err(OrderError::Other(anyhow::anyhow!(
+                "Expect recorded clock {} of event {idx} to match replayed clock {}",
+                event.text,
+                ClockField(replayed)
+            )));
+        }
+    }
+    Ok(ps)
+}
+
+/// Process that records every event it executes, sends or receives to a `TraceRecorder`, while delegating to the inner
+/// process.
+pub struct Traced<P, E> {
+    inner: P,
+    recorder: TraceRecorder,
+    _event: PhantomData<E>,
+}
+
+impl<E: LogicalClock + Serialize, P: OrdProcess<E>> Traced<P, E> {
+    pub fn new(inner: P, recorder: TraceRecorder) -> Self {
+        Self {
+            inner,
+            recorder,
+            _event: PhantomData,
+        }
+    }
+    pub fn inner(&self) -> &P {
+        &self.inner
+    }
+    pub fn into_inner(self) -> P {
+        self.inner
+    }
+    /// Sender of the last event, if the inner process knows it
+    fn last_sender(&self) -> Option<usize> {
+        self.inner.sender(self.inner.events().len().checked_sub(1)?)
+    }
+    fn record_last(&self, kind: TraceKind, peer: Option<usize>) {
+        if let Some(e) = self.inner.last_event() {
+            let event = TraceEvent::new(self.pid(), kind, peer, e.clone());
+            self.recorder
+                .record(&event)
+                .expect("Failed to record trace event");
+        }
+    }
+}
+
+impl<E: LogicalClock + Serialize, P: OrdProcess<E>> HasEvents<E> for Traced<P, E> {
+    fn last_event(&self) -> Option<&E> {
+        self.inner.last_event()
+    }
+    fn push_event(&mut self, e: E) {
+        self.inner.push_event(e)
+    }
+    fn push_recv_event(&mut self, e: E, from: usize) {
+        self.inner.push_recv_event(e, from)
+    }
+    fn pid(&self) -> usize {
+        self.inner.pid()
+    }
+    fn n_procs(&self) -> usize {
+        self.inner.n_procs()
+    }
+    fn events(&self) -> &[E] {
+        self.inner.events()
+    }
+    fn sender(&self, idx: usize) -> Option<usize> {
+        self.inner.sender(idx)
+    }
+}
+
+// Delegates to the inner process, so that its overrides still apply
+impl<E: LogicalClock + Serialize, P: OrdProcess<E>> OrdProcess<E> for Traced<P, E> {
+    fn exec<F: FnOnce()>(&mut self, f: F) {
+        self.inner.exec(f);
+        self.record_last(TraceKind::Exec, None);
+    }
+    fn send<F: FnOnce(E)>(&mut self, send_fn: F) {
+        self.inner.send(send_fn);
+        self.record_last(TraceKind::Send, None);
+    }
+    fn recv<F: FnOnce() -> E>(&mut self, recv_fn: F) {
+        self.inner.recv(recv_fn);
+        self.record_last(TraceKind::Recv, self.last_sender());
+    }
+    fn recv_from<F: FnOnce() -> (usize, E)>(&mut self, recv_fn: F) -> usize {
+        let from = self.inner.recv_from(recv_fn);
+        self.record_last(TraceKind::Recv, Some(from));
+        from
+    }
+    fn try_exec<T, Err, F>(&mut self, f: F) -> Result<T, OrderError>
+    where
+        Err: Into<OrderError>,
+        F: FnOnce() -> Result<T, Err>,
+    {
+        let res = self.inner.try_exec(f);
+        self.record_last(TraceKind::Exec, None); // even if f failed
+        res
+    }
+    fn try_send<Err, F>(&mut self, send_fn: F) -> Result<(), OrderError>
+    where
+        Err: Into<OrderError>,
+        F: FnOnce(E) -> Result<(), Err>,
+    {
+        self.inner.try_send(send_fn)?;
+        self.record_last(TraceKind::Send, None);
+        Ok(())
+    }
+    fn try_recv<Err, F>(&mut self, recv_fn: F) -> Result<(), OrderError>
+    where
+        Err: Into<OrderError>,
+        F: FnOnce() -> Result<E, Err>,
+    {
+        self.inner.try_recv(recv_fn)?;
+        self.record_last(TraceKind::Recv, self.last_sender());
+        Ok(())
+    }
+    fn send_via<T: Transport<E>>(
+        &mut self,
+        transport: &mut T,
+        pid: usize,
+    ) -> Result<(), OrderError> {
+        self.inner.send_via(transport, pid)?;
+        self.record_last(TraceKind::Send, Some(pid));
+        Ok(())
+    }
+    fn recv_via<T: Transport<E>>(&mut self, transport: &mut T) -> Result<usize, OrderError> {
+        let from = self.inner.recv_via(transport)?;
+        self.record_last(TraceKind::Recv, Some(from));
+        Ok(from)
+    }
+    fn send_all<T: Transport<E>>(&mut self, transport: &mut T) -> Result<(), OrderError> {
+        let n_events = self.inner.events().len();
+        let res = self.inner.send_all(transport);
+        if self.inner.events().len() > n_events {
+            self.record_last(TraceKind::Send, None);
+        }
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::order::lamport_clock::LamportClock;
+    use crate::order::matrix_clock::MatrixClock;
+    use crate::order::trace::{
+        replay_trace, to_shiviz, TraceEvent, TraceKind, TraceRecorder, Traced,
+    };
+    use crate::order::transport::MpscTransport;
+    use crate::order::vector_clock::VectorClock;
+    use crate::order::{HasEvents, OrdProcess, OrderError, Process};
+    use std::io::Write;
+    use std::sync::{Arc, Mutex};
+
+    /// In-memory trace
+    #[derive(Clone, Default)]
+    struct Buf(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Buf {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Buf {
+        fn lines(&self) -> Vec<String> {
+            let bytes = self.0.lock().unwrap().clone();
+            String::from_utf8(bytes)
+                .unwrap()
+                .lines()
+                .map(String::from)
+                .collect()
+        }
+    }
+
+    #[test]
+    fn schema() {
+        let buf = Buf::default();
+        let recorder = TraceRecorder::new(buf.clone());
+        let mut ts = MpscTransport::mesh(2);
+        let mut p0 = Traced::new(Process::<LamportClock>::new(0, 2), recorder.clone());
+        let mut p1 = Process::<LamportClock>::new(1, 2);
+        p0.exec(|| {});
+        p0.send_via(&mut ts[0], 1).unwrap();
+        p1.recv_via(&mut ts[1]).unwrap();
+        recorder.deliver(1, 0, *p1.last_event().unwrap()).unwrap();
+        assert_eq!(
+            buf.lines(),
+            vec![
+                r#"{"pid":0,"kind":"exec","peer":null,"clock":{"i":0,"clk":2},"text":"0:2"}"#,
+                r#"{"pid":0,"kind":"send","peer":1,"clock":{"i":0,"clk":3},"text":"0:3"}"#,
+                r#"{"pid":1,"kind":"deliver","peer":0,"clock":{"i":1,"clk":4},"text":"1:4"}"#,
+            ]
+        );
+    }
+
+    #[test]
+    fn shiviz() {
+        let trace = vec![
+            TraceEvent::new(
+                0,
+                TraceKind::Send,
+                Some(1),
+                VectorClock::from_vec(0, vec![1, 0]),
+            ),
+            TraceEvent::new(
+                1,
+                TraceKind::Recv,
+                Some(0),
+                VectorClock::from_vec(1, vec![1, 1]),
+            ),
+        ];
+        assert_eq!(
+            to_shiviz(&trace),
+            "Send p1\np0 {\"p0\":1}\nRecv p0\np1 {\"p0\":1,\"p1\":1}\n"
+        );
+    }
+
+    #[test]
+    fn replays_recorded_run() {
+        let buf = Buf::default();
+        let recorder = TraceRecorder::new(buf.clone());
+        let mut ts = MpscTransport::mesh(3);
+        let mut ps: Vec<_> = (0..3)
+            .map(|i| Traced::new(Process::<MatrixClock>::new(i, 3), recorder.clone()))
+            .collect();
+        ps[0].send_all(&mut ts[0]).unwrap();
+        ps[0].send_via(&mut ts[0], 2).unwrap();
+        ps[2].exec(|| {});
+        ps[2].recv_via(&mut ts[2]).unwrap();
+        ps[1].recv_via(&mut ts[1]).unwrap();
+        ps[1].send_via(&mut ts[1], 2).unwrap();
+        ps[2].recv_via(&mut ts[2]).unwrap();
+        ps[2].recv_via(&mut ts[2]).unwrap();
+        let trace: Vec<TraceEvent<MatrixClock>> = buf
+            .lines()
+            .iter()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(trace.len(), 8);
+
+        let replayed = replay_trace(&trace, 3).unwrap();
+        for (p, q) in ps.iter().zip(&replayed) {
+            assert!(p.events() == q.events());
+        }
+        assert_eq!(replayed[2].sender(3), Some(1));
+
+        // Regressed clock
+        let mut bad = trace.clone();
+        bad[4].clock = bad[3].clock.clone();
+        let err = replay_trace(&bad, 3).unwrap_err().to_string();
+        assert!(err.contains("of event 4"), "{err}");
+        // Receive without a send, e.g. from a nondeterministic run
+        let mut bad = trace.clone();
+        bad.remove(1);
+        assert!(replay_trace(&bad, 3).is_err());
+        bad[0].peer = Some(3);
+        assert!(replay_trace(&bad, 3).is_err());
+    }
+
+    #[test]
+    fn replays_recv_without_sender() {
+        let buf = Buf::default();
+        let recorder = TraceRecorder::new(buf.clone());
+        let mut ps: Vec<_> = (0..3)
+            .map(|i| Traced::new(Process::<VectorClock>::new(i, 3), recorder.clone()))
+            .collect();
+        let (mut to_p2, mut from_p1) = (vec![], None);
+        ps[0].send(|e| to_p2.push(e));
+        ps[1].exec(|| {});
+        ps[1].send(|e| from_p1 = Some(e));
+        ps[2].recv(|| from_p1.take().unwrap());
+        ps[2]
+            .try_recv(|| to_p2.pop().ok_or(OrderError::Disconnected))
+            .unwrap();
+        let trace: Vec<TraceEvent<VectorClock>> = buf
+            .lines()
+            .iter()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!((trace[3].peer, trace[4].peer), (None, None));
+
+        let replayed = replay_trace(&trace, 3).unwrap();
+        assert_eq!(
+            (replayed[2].sender(0), replayed[2].sender(1)),
+            (Some(1), Some(0))
+        );
+        let mut bad = trace.clone();
+        bad[3].clock = VectorClock::from_vec(2, vec![0, 1, 2]);
+        let err = replay_trace(&bad, 3).unwrap_err().to_string();
+        assert!(err.contains("matches receive event 3"), "{err}");
+    }
+}
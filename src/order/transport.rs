@@ -0,0 +1,1039 @@
+use crate::order::{Message, OrderError};
+use alloc::collections::{BTreeMap, VecDeque};
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// Lossless FIFO channels between every pair of processes, as assumed by `OrdProcess::send` and `OrdProcess::recv`.
+pub trait Transport<E> {
+    /// Own pid, to which other processes send
+    fn pid(&self) -> usize;
+    fn n_procs(&self) -> usize;
+    /// Fails if `pid` is out of range or own pid, since a process records its own events without sending them
+    fn send_to(&mut self, pid: usize, e: E) -> Result<(), OrderError>;
+    /// Blocks until any process sends, returning the sender's pid
+    fn recv(&mut self) -> Result<(usize, E), OrderError>;
+    /// Like `recv`, but returns None instead of blocking
+    fn try_recv(&mut self) -> Result<Option<(usize, E)>, OrderError>;
+}
+
+// Forwards to the borrowed transport, e.g. so that `&mut dyn Transport` can be passed where a sized one is expected
+impl<E, T: Transport<E> + ?Sized> Transport<E> for &mut T {
+    fn pid(&self) -> usize {
+        (**self).pid()
+    }
+    fn n_procs(&self) -> usize {
+        (**self).n_procs()
+    }
+    fn send_to(&mut self, pid: usize, e: E) -> Result<(), OrderError> {
+        (**self).send_to(pid, e)
+    }
+    fn recv(&mut self) -> Result<(usize, E), OrderError> {
+        (**self).recv()
+    }
+    fn try_recv(&mut self) -> Result<Option<(usize, E)>, OrderError> {
+        (**self).try_recv()
+    }
+}
+
+/// Transport between threads of the same process.
+///
+/// # Examples
+/// ```
+/// use rads::order::transport::{MpscTransport, Transport};
+/// use rads::order::{OrdProcess, Process};
+/// use rads::order::vector_clock::VectorClock;
+///
+/// let mut ts = MpscTransport::mesh(2);
+/// let mut t1 = ts.pop().unwrap();
+/// let mut t0 = ts.pop().unwrap();
+/// let th = std::thread::spawn(move || {
+///     let mut p = Process::<VectorClock>::new(0, 2);
+///     p.send_via(&mut t0, 1).unwrap();
+/// });
+/// let mut p = Process::<VectorClock>::new(1, 2);
+/// assert_eq!(p.recv_via(&mut t1).unwrap(), 0);
+/// th.join().unwrap();
+/// ```
+#[cfg(feature = "std")]
+pub struct MpscTransport<E> {
+    i: usize,
+    /// Senders to every other process, but none to itself, so that `recv` fails once every other process hung up
+    txs: Vec<Option<std::sync::mpsc::Sender<(usize, E)>>>,
+    rx: std::sync::mpsc::Receiver<(usize, E)>,
+}
+
+#[cfg(feature = "std")]
+impl<E> MpscTransport<E> {
+    /// Connects `n_procs` transports all-to-all, where the i-th belongs to process i
+    pub fn mesh(n_procs: usize) -> Vec<Self> {
+        let (txs, rxs): (Vec<_>, Vec<_>) = (0..n_procs).map(|_| std::sync::mpsc::channel()).unzip();
+        rxs.into_iter()
+            .enumerate()
+            .map(|(i, rx)| Self {
+                i,
+                txs: (txs.iter().enumerate())
+                    .map(|(j, tx)| (j != i).then(|| tx.clone()))
+                    .collect(),
+                rx,
+            })
+            .collect()
+    }
+    /// Like `recv`, but returns None if no process sent within `timeout`
+    pub fn recv_timeout(
+        &mut self,
+        timeout: std::time::Duration,
+    ) -> Result<Option<(usize, E)>, OrderError> {
+        match self.rx.recv_timeout(timeout) {
+            Ok(m) => Ok(Some(m)),
+            Err(std::sync::mpsc::RecvTimeoutError::Timeout) => Ok(None),
+            Err(std::sync::mpsc::RecvTimeoutError::Disconnected) => Err(OrderError::Disconnected),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<E> Transport<E> for MpscTransport<E> {
+    fn pid(&self) -> usize {
+        self.i
+    }
+    fn n_procs(&self) -> usize {
+        self.txs.len()
+    }
+    fn send_to(&mut self, pid: usize, e: E) -> Result<(), OrderError> {
+        let n_procs = self.txs.len();
+        let tx = self.txs.get(pid).ok_or_else(|| {
+            OrderError::Other(anyhow::anyhow!(
+                "Expect 0-based index of process {pid} < n_procs={n_procs}"
+            ))
+        })?;
+        let tx = tx.as_ref().ok_or_else(|| {
+            OrderError::Other(anyhow::anyhow!(
+                "Expect process {pid} to send to other processes, not itself"
+            ))
+        })?;
+        Ok(tx.send((self.i, e))?)
+    }
+    fn recv(&mut self) -> Result<(usize, E), OrderError> {
+        Ok(self.rx.recv()?)
+    }
+    fn try_recv(&mut self) -> Result<Option<(usize, E)>, OrderError> {
+        match self.rx.try_recv() {
+            Ok(m) => Ok(Some(m)),
+            Err(std::sync::mpsc::TryRecvError::Empty) => Ok(None),
+            Err(std::sync::mpsc::TryRecvError::Disconnected) => Err(OrderError::Disconnected),
+        }
+    }
+}
+
+/// FIFO channels on top of a transport that may reorder or duplicate messages, by numbering the messages to each
+/// process and holding back those that overtook earlier ones.
+///
+/// # Examples
+/// ```
+/// use rads::order::transport::{FifoChannel, MpscTransport, Transport};
+///
+/// let mut ts: Vec<_> = MpscTransport::mesh(2).into_iter().map(FifoChannel::new).collect();
+/// ts[0].send_to(1, "hello").unwrap();
+/// assert_eq!(ts[1].recv().unwrap(), (0, "hello"));
+/// ```
+pub struct FifoChannel<T, E> {
+    inner: T,
+    /// Sequence number of the next message to each process
+    next_seq: Vec<u64>,
+    /// Sequence number of the next message to deliver from each process
+    expected: Vec<u64>,
+    pending: Vec<BTreeMap<u64, E>>,
+    ready: VecDeque<(usize, E)>,
+}
+
+impl<T: Transport<Message<(), E>>, E> FifoChannel<T, E> {
+    pub fn new(inner: T) -> Self {
+        let n_procs = inner.n_procs();
+        Self {
+            inner,
+            next_seq: vec![0; n_procs],
+            expected: vec![0; n_procs],
+            pending: (0..n_procs).map(|_| BTreeMap::new()).collect(),
+            ready: VecDeque::new(),
+        }
+    }
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+    /// Number of messages received but held back for earlier ones
+    pub fn n_held(&self) -> usize {
+        self.pending.iter().map(BTreeMap::len).sum()
+    }
+
+    /// Holds back a received message, then readies every message of its sender that is next in line
+    fn hold(&mut self, from: usize, m: Message<(), E>) -> Result<(), OrderError> {
+        let n_procs = self.expected.len();
+        if from >= n_procs || m.from != from {
+            return Err(OrderError::Other(anyhow::anyhow!(
+                "Expect message of process {} from process {from} < n_procs={n_procs}",
+                m.from
+            )));
+        }
+        if m.seq >= self.expected[from] {
+            self.pending[from].insert(m.seq, m.payload);
+        } // Else a duplicate
+        while let Some(e) = self.pending[from].remove(&self.expected[from]) {
+            self.expected[from] += 1;
+            self.ready.push_back((from, e));
+        }
+        Ok(())
+    }
+}
+
+impl<T: Transport<Message<(), E>>, E> Transport<E> for FifoChannel<T, E> {
+    fn pid(&self) -> usize {
+        self.inner.pid()
+    }
+    fn n_procs(&self) -> usize {
+        self.inner.n_procs()
+    }
+    fn send_to(&mut self, pid: usize, e: E) -> Result<(), OrderError> {
+        let n_procs = self.next_seq.len();
+        let seq = self.next_seq.get_mut(pid).ok_or_else(|| {
+            OrderError::Other(anyhow::anyhow!(
+                "Expect 0-based index of process {pid} < n_procs={n_procs}"
+            ))
+        })?;
+        self.inner
+            .send_to(pid, Message::new(self.inner.pid(), *seq, (), e))?;
+        *seq += 1;
+        Ok(())
+    }
+    fn recv(&mut self) -> Result<(usize, E), OrderError> {
+        loop {
+            if let Some(m) = self.ready.pop_front() {
+                return Ok(m);
+            }
+            let (from, m) = self.inner.recv()?;
+            self.hold(from, m)?;
+        }
+    }
+    fn try_recv(&mut self) -> Result<Option<(usize, E)>, OrderError> {
+        while self.ready.is_empty() {
+            match self.inner.try_recv()? {
+                Some((from, m)) => self.hold(from, m)?,
+                None => break,
+            }
+        }
+        Ok(self.ready.pop_front())
+    }
+}
+
+/// Transport across machines, framing serialized messages over one TCP stream per pair of processes, which keeps each
+/// channel FIFO.
+///
+/// Process i listens on `addrs[i]`, connects to every process before it and accepts every process after it.
+///
+/// # Examples
+/// ```
+/// use rads::order::transport::{TcpTransport, Transport};
+/// use rads::order::{OrdProcess, Process};
+/// use rads::order::vector_clock::VectorClock;
+/// use std::net::TcpListener;
+///
+/// let ls = [TcpListener::bind("127.0.0.1:0").unwrap(), TcpListener::bind("127.0.0.1:0").unwrap()];
+/// let addrs: Vec<_> = ls.iter().map(|l| l.local_addr().unwrap()).collect();
+/// let [l0, l1] = ls;
+/// let a = addrs.clone();
+/// let th = std::thread::spawn(move || {
+///     let mut t0 = TcpTransport::with_listener(0, l0, &a).unwrap();
+///     let mut p = Process::<VectorClock>::new(0, 2);
+///     p.send_via(&mut t0, 1).unwrap();
+/// });
+/// let mut t1 = TcpTransport::with_listener(1, l1, &addrs).unwrap();
+/// let mut p = Process::<VectorClock>::new(1, 2);
+/// assert_eq!(p.recv_via(&mut t1).unwrap(), 0);
+/// th.join().unwrap();
+/// ```
+#[cfg(feature = "net")]
+pub struct TcpTransport<E> {
+    i: usize,
+    /// Write half to each process, except self
+    streams: Vec<Option<std::net::TcpStream>>,
+    rx: std::sync::mpsc::Receiver<Result<(usize, E), OrderError>>,
+}
+
+#[cfg(feature = "net")]
+impl<E> TcpTransport<E>
+where
+    E: serde::Serialize + serde::de::DeserializeOwned + Send + 'static,
+{
+    /// How long to keep connecting to processes that have yet to listen
+    const CONNECT_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+
+    /// Listens on `addrs[i]`, then connects to all other processes
+    pub fn bind(i: usize, addrs: &[std::net::SocketAddr]) -> Result<Self, OrderError> {
+        let n_procs = addrs.len();
+        let addr = addrs.get(i).ok_or_else(|| {
+            OrderError::Other(anyhow::anyhow!(
+                "Expect 0-based index of process {i} < n_procs={n_procs}"
+            ))
+        })?;
+        Self::with_listener(i, std::net::TcpListener::bind(addr)?, addrs)
+    }
+
+    /// Like `bind`, but with a listener bound beforehand, e.g. to port 0
+    pub fn with_listener(
+        i: usize,
+        listener: std::net::TcpListener,
+        addrs: &[std::net::SocketAddr],
+    ) -> Result<Self, OrderError> {
+        let n_procs = addrs.len();
+        if i >= n_procs {
+            return Err(OrderError::Other(anyhow::anyhow!(
+                "Expect 0-based index of process {i} < n_procs={n_procs}"
+            )));
+        }
+        let mut streams: Vec<Option<std::net::TcpStream>> = (0..n_procs).map(|_| None).collect();
+        for (j, addr) in addrs.iter().enumerate().take(i) {
+            let mut stream = Self::connect(addr)?;
+            std::io::Write::write_all(&mut stream, &(i as u64).to_be_bytes())?;
+            streams[j] = Some(stream);
+        }
+        for _ in i + 1..n_procs {
+            let (mut stream, _) = listener.accept()?;
+            let mut pid = [0; 8];
+            std::io::Read::read_exact(&mut stream, &mut pid)?;
+            let j = u64::from_be_bytes(pid) as usize;
+            match streams.get_mut(j) {
+                Some(s @ None) if j > i => *s = Some(stream),
+                _ => {
+                    return Err(OrderError::Other(anyhow::anyhow!(
+                        "Unexpected handshake from process {j}"
+                    )))
+                }
+            }
+        }
+
+        let (tx, rx) = std::sync::mpsc::channel();
+        for (j, stream) in streams.iter().enumerate() {
+            let Some(stream) = stream else { continue };
+            stream.set_nodelay(true)?;
+            let mut reader = std::io::BufReader::new(stream.try_clone()?);
+            let tx = tx.clone();
+            std::thread::spawn(move || {
+                // Stops on EOF, or once the transport is dropped
+                while let Some(m) = read_frame(&mut reader).transpose() {
+                    let m = m.and_then(|buf| {
+                        serde_json::from_slice(&buf).map_err(|e| OrderError::Other(e.into()))
+                    });
+                    let stop = m.is_err();
+                    if tx.send(m.map(|e| (j, e))).is_err() || stop {
+                        break;
+                    }
+                }
+            });
+        }
+        Ok(Self { i, streams, rx })
+    }
+
+    fn connect(addr: &std::net::SocketAddr) -> Result<std::net::TcpStream, OrderError> {
+        let start = std::time::Instant::now();
+        loop {
+            match std::net::TcpStream::connect(addr) {
+                Ok(stream) => return Ok(stream),
+                Err(e)
+                    if e.kind() == std::io::ErrorKind::ConnectionRefused
+                        && start.elapsed() < Self::CONNECT_TIMEOUT =>
+                {
+                    std::thread::sleep(std::time::Duration::from_millis(10))
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+}
+
+/// Longest frame to read, so that a corrupt or malicious length can't make the reader allocate up to 4 GiB
+#[cfg(feature = "net")]
+const MAX_FRAME: usize = 16 << 20;
+
+/// Reads a frame of a big-endian u32 length, then as many bytes, or None on EOF before the frame
+#[cfg(feature = "net")]
+fn read_frame(r: &mut impl std::io::Read) -> Result<Option<Vec<u8>>, OrderError> {
+    let mut len = [0; 4];
+    match r.read_exact(&mut len) {
+        Ok(()) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e.into()),
+    }
+    let len = u32::from_be_bytes(len) as usize;
+    if len > MAX_FRAME {
+        return Err(OrderError::Other(anyhow::anyhow!(
+            "Expect frame of {len} bytes <= {MAX_FRAME}"
+        )));
+    }
+    let mut buf = vec![0; len];
+    r.read_exact(&mut buf)?;
+    Ok(Some(buf))
+}
+
+#[cfg(feature = "net")]
+impl<E> Transport<E> for TcpTransport<E>
+where
+    E: serde::Serialize + serde::de::DeserializeOwned + Send + 'static,
+{
+    fn pid(&self) -> usize {
+        self.i
+    }
+    fn n_procs(&self) -> usize {
+        self.streams.len()
+    }
+    fn send_to(&mut self, pid: usize, e: E) -> Result<(), OrderError> {
+        let n_procs = self.streams.len();
+        if pid == self.i {
+            return Err(OrderError::Other(anyhow::anyhow!(
+                "Expect process {pid} to send to other processes, not itself"
+            )));
+        }
+        let stream = self
+            .streams
+            .get_mut(pid)
+            .and_then(Option::as_mut)
+            .ok_or_else(|| {
+                OrderError::Other(anyhow::anyhow!(
+                    "Expect 0-based index of process {pid} < n_procs={n_procs}"
+                ))
+            })?;
+        let buf = serde_json::to_vec(&e).map_err(|e| OrderError::Other(e.into()))?;
+        if buf.len() > MAX_FRAME {
+            return Err(OrderError::Other(anyhow::anyhow!(
+                "Message of {} bytes is longer than frames of {MAX_FRAME}",
+                buf.len()
+            )));
+        }
+        let len = buf.len() as u32;
+        let mut frame = Vec::with_capacity(4 + buf.len());
+        frame.extend_from_slice(&len.to_be_bytes());
+        frame.extend_from_slice(&buf);
+        match std::io::Write::write_all(stream, &frame) {
+            Err(e)
+                if matches!(
+                    e.kind(),
+                    std::io::ErrorKind::BrokenPipe | std::io::ErrorKind::ConnectionReset
+                ) =>
+            {
+                Err(OrderError::Disconnected)
+            }
+            r => Ok(r?),
+        }
+    }
+    fn recv(&mut self) -> Result<(usize, E), OrderError> {
+        self.rx.recv()?
+    }
+    fn try_recv(&mut self) -> Result<Option<(usize, E)>, OrderError> {
+        match self.rx.try_recv() {
+            Ok(m) => m.map(Some),
+            Err(std::sync::mpsc::TryRecvError::Empty) => Ok(None),
+            Err(std::sync::mpsc::TryRecvError::Disconnected) => Err(OrderError::Disconnected),
+        }
+    }
+}
+
+#[cfg(feature = "net")]
+impl<E> Drop for TcpTransport<E> {
+    fn drop(&mut self) {
+        // Unblocks reader threads, and tells peers
+        for s in self.streams.iter().flatten() {
+            let _ = s.shutdown(std::net::Shutdown::Both);
+        }
+    }
+}
+
+/// Transport across machines over UDP, which numbers, acknowledges and retransmits datagrams, then reorders them per
+/// sender, so that channels are lossless and FIFO after all.
+///
+/// Unacknowledged messages are lost once the transport is dropped, so `flush` before dropping. Since UDP has no
+/// connections, `recv` waits for peers indefinitely.
+///
+/// # Examples
+/// ```
+/// use rads::order::transport::{Transport, UdpTransport};
+/// use std::net::UdpSocket;
+/// use std::time::Duration;
+///
+/// let ss = [UdpSocket::bind("127.0.0.1:0").unwrap(), UdpSocket::bind("127.0.0.1:0").unwrap()];
+/// let addrs: Vec<_> = ss.iter().map(|s| s.local_addr().unwrap()).collect();
+/// let [s0, s1] = ss;
+/// let mut t0 = UdpTransport::with_socket(0, s0, &addrs).unwrap();
+/// let mut t1 = UdpTransport::with_socket(1, s1, &addrs).unwrap();
+/// t0.send_to(1, "hello".to_string()).unwrap();
+/// t0.flush(Duration::from_secs(1)).unwrap();
+/// assert_eq!(t1.recv().unwrap(), (0, "hello".to_string()));
+/// ```
+#[cfg(feature = "net")]
+pub struct UdpTransport<E> {
+    i: usize,
+    link: std::sync::Arc<UdpLink>,
+    /// Sequence number of the next message to each process
+    next_seq: Vec<u64>,
+    rx: std::sync::mpsc::Receiver<Result<(usize, E), OrderError>>,
+}
+
+/// Datagrams sent but not acknowledged, by receiver and sequence number, with when they were last sent
+#[cfg(feature = "net")]
+type Unacked = std::collections::BTreeMap<(usize, u64), (Vec<u8>, std::time::Instant)>;
+
+/// Socket shared between the transport, which sends, and its thread, which receives and retransmits
+#[cfg(feature = "net")]
+struct UdpLink {
+    socket: std::net::UdpSocket,
+    addrs: Vec<std::net::SocketAddr>,
+    unacked: std::sync::Mutex<Unacked>,
+    stop: std::sync::atomic::AtomicBool,
+    /// Drops every n-th datagram, to test retransmission
+    #[cfg(test)]
+    drop_every: std::sync::atomic::AtomicUsize,
+    #[cfg(test)]
+    n_sent: std::sync::atomic::AtomicUsize,
+}
+
+#[cfg(feature = "net")]
+impl UdpLink {
+    const DATA: u8 = 0;
+    const ACK: u8 = 1;
+    /// Kind, sender pid and sequence number
+    const HEADER: usize = 17;
+
+    fn datagram(kind: u8, pid: usize, seq: u64, payload: &[u8]) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(Self::HEADER + payload.len());
+        buf.push(kind);
+        buf.extend_from_slice(&(pid as u64).to_be_bytes());
+        buf.extend_from_slice(&seq.to_be_bytes());
+        buf.extend_from_slice(payload);
+        buf
+    }
+
+    fn send(&self, pid: usize, buf: &[u8]) -> std::io::Result<()> {
+        #[cfg(test)]
+        {
+            use std::sync::atomic::Ordering;
+            let n = self.n_sent.fetch_add(1, Ordering::Relaxed) + 1;
+            let every = self.drop_every.load(Ordering::Relaxed);
+            if every > 0 && n.is_multiple_of(every) {
+                return Ok(());
+            }
+        }
+        self.socket.send_to(buf, self.addrs[pid]).map(|_| ())
+    }
+}
+
+#[cfg(feature = "net")]
+impl<E> UdpTransport<E>
+where
+    E: serde::Serialize + serde::de::DeserializeOwned + Send + 'static,
+{
+    /// How long to wait for an acknowledgement before retransmitting
+    const RETRANSMIT_AFTER: std::time::Duration = std::time::Duration::from_millis(20);
+    /// Largest payload that fits in a UDP datagram
+    const MAX_PAYLOAD: usize = 65507 - UdpLink::HEADER;
+
+    /// Binds to `addrs[i]`, from which it sends to and receives from all other processes
+    pub fn bind(i: usize, addrs: &[std::net::SocketAddr]) -> Result<Self, OrderError> {
+        let n_procs = addrs.len();
+        let addr = addrs.get(i).ok_or_else(|| {
+            OrderError::Other(anyhow::anyhow!(
+                "Expect 0-based index of process {i} < n_procs={n_procs}"
+            ))
+        })?;
+        Self::with_socket(i, std::net::UdpSocket::bind(addr)?, addrs)
+    }
+
+    /// Like `bind`, but with a socket bound beforehand, e.g. to port 0
+    pub fn with_socket(
+        i: usize,
+        socket: std::net::UdpSocket,
+        addrs: &[std::net::SocketAddr],
+    ) -> Result<Self, OrderError> {
+        let n_procs = addrs.len();
+        if i >= n_procs {
+            return Err(OrderError::Other(anyhow::anyhow!(
+                "Expect 0-based index of process {i} < n_procs={n_procs}"
+            )));
+        }
+        socket.set_read_timeout(Some(Self::RETRANSMIT_AFTER / 4))?;
+        let link = std::sync::Arc::new(UdpLink {
+            socket,
+            addrs: addrs.to_vec(),
+            unacked: Default::default(),
+            stop: Default::default(),
+            #[cfg(test)]
+            drop_every: Default::default(),
+            #[cfg(test)]
+            n_sent: Default::default(),
+        });
+        let (tx, rx) = std::sync::mpsc::channel();
+        let l = link.clone();
+        std::thread::spawn(move || Self::run(i, &l, &tx));
+        Ok(Self {
+            i,
+            link,
+            next_seq: vec![0; n_procs],
+            rx,
+        })
+    }
+
+    /// Blocks until every peer acknowledged every message sent so far, or the timeout passes
+    pub fn flush(&self, timeout: std::time::Duration) -> Result<(), OrderError> {
+        let start = std::time::Instant::now();
+        while !self.link.unacked.lock().unwrap().is_empty() {
+            if start.elapsed() > timeout {
+                return Err(OrderError::Disconnected);
+            }
+            std::thread::sleep(Self::RETRANSMIT_AFTER / 4);
+        }
+        Ok(())
+    }
+
+    /// Acknowledges and reorders received datagrams, and retransmits unacknowledged ones, until the transport drops
+    fn run(i: usize, link: &UdpLink, tx: &std::sync::mpsc::Sender<Result<(usize, E), OrderError>>) {
+        let n_procs = link.addrs.len();
+        let mut expected = vec![0; n_procs];
+        let mut pending = vec![std::collections::BTreeMap::new(); n_procs];
+        let mut buf = vec![0; 65536];
+        while !link.stop.load(std::sync::atomic::Ordering::Relaxed) {
+            match link.socket.recv_from(&mut buf) {
+                Ok((len, _)) if len >= UdpLink::HEADER => {
+                    let pid = u64::from_be_bytes(buf[1..9].try_into().unwrap()) as usize;
+                    let seq = u64::from_be_bytes(buf[9..17].try_into().unwrap());
+                    match buf[0] {
+                        _ if pid >= n_procs => {} // Stray datagram
+                        UdpLink::DATA => {
+                            let _ = link.send(pid, &UdpLink::datagram(UdpLink::ACK, i, seq, &[]));
+                            if seq >= expected[pid] {
+                                pending[pid].insert(seq, buf[UdpLink::HEADER..len].to_vec());
+                            }
+                            while let Some(payload) = pending[pid].remove(&expected[pid]) {
+                                expected[pid] += 1;
+                                let m = serde_json::from_slice(&payload)
+                                    .map(|e| (pid, e))
+                                    .map_err(|e| OrderError::Other(e.into()));
+                                if tx.send(m).is_err() {
+                                    return;
+                                }
+                            }
+                        }
+                        UdpLink::ACK => {
+                            link.unacked.lock().unwrap().remove(&(pid, seq));
+                        }
+                        _ => {}
+                    }
+                }
+                Ok(_) => {}
+                Err(e)
+                    if matches!(
+                        e.kind(),
+                        std::io::ErrorKind::WouldBlock
+                            | std::io::ErrorKind::TimedOut
+                            | std::io::ErrorKind::ConnectionRefused
+                    ) => {}
+                Err(e) => {
+                    let _ = tx.send(Err(e.into()));
+                    return;
+                }
+            }
+            let now = std::time::Instant::now();
+            for ((pid, _), (datagram, sent)) in link.unacked.lock().unwrap().iter_mut() {
+                if now.duration_since(*sent) > Self::RETRANSMIT_AFTER {
+                    let _ = link.send(*pid, datagram);
+                    *sent = now;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(feature = "net")]
+impl<E> Transport<E> for UdpTransport<E>
+where
+    E: serde::Serialize + serde::de::DeserializeOwned + Send + 'static,
+{
+    fn pid(&self) -> usize {
+        self.i
+    }
+    fn n_procs(&self) -> usize {
+        self.next_seq.len()
+    }
+    fn send_to(&mut self, pid: usize, e: E) -> Result<(), OrderError> {
+        let n_procs = self.next_seq.len();
+        if pid == self.i {
+            return Err(OrderError::Other(anyhow::anyhow!(
+                "Expect process {pid} to send to other processes, not itself"
+            )));
+        }
+        let seq = self.next_seq.get_mut(pid).ok_or_else(|| {
+            OrderError::Other(anyhow::anyhow!(
+                "Expect 0-based index of process {pid} < n_procs={n_procs}"
+            ))
+        })?;
+        let payload = serde_json::to_vec(&e).map_err(|e| OrderError::Other(e.into()))?;
+        if payload.len() > Self::MAX_PAYLOAD {
+            return Err(OrderError::Other(anyhow::anyhow!(
+                "Message of {} bytes is too long",
+                payload.len()
+            )));
+        }
+        let datagram = UdpLink::datagram(UdpLink::DATA, self.i, *seq, &payload);
+        // Only retransmitted once sent, since a failed send leaves nothing to acknowledge
+        self.link.send(pid, &datagram)?;
+        self.link
+            .unacked
+            .lock()
+            .unwrap()
+            .insert((pid, *seq), (datagram, std::time::Instant::now()));
+        *seq += 1;
+        Ok(())
+    }
+    fn recv(&mut self) -> Result<(usize, E), OrderError> {
+        self.rx.recv()?
+    }
+    fn try_recv(&mut self) -> Result<Option<(usize, E)>, OrderError> {
+        match self.rx.try_recv() {
+            Ok(m) => m.map(Some),
+            Err(std::sync::mpsc::TryRecvError::Empty) => Ok(None),
+            Err(std::sync::mpsc::TryRecvError::Disconnected) => Err(OrderError::Disconnected),
+        }
+    }
+}
+
+#[cfg(feature = "net")]
+impl<E> Drop for UdpTransport<E> {
+    fn drop(&mut self) {
+        self.link
+            .stop
+            .store(true, std::sync::atomic::Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "std")]
+mod tests {
+    use crate::order::transport::{FifoChannel, MpscTransport, Transport};
+    #[cfg(feature = "net")]
+    use crate::order::transport::{TcpTransport, UdpTransport};
+    use crate::order::vector_clock::VectorClock;
+    use crate::order::{HasEvents, Message, OrdProcess, OrderError, Process};
+    use rand::{Rng, SeedableRng};
+
+    #[test]
+    fn mock_scheduler() {
+        let ths: Vec<_> = MpscTransport::mesh(3)
+            .into_iter()
+            .map(|mut t| {
+                std::thread::spawn(move || {
+                    let mut p = Process::<VectorClock>::new(t.pid(), t.n_procs());
+                    match p.pid() {
+                        0 => {
+                            p.exec(|| {});
+                            p.send_via(&mut t, 1).unwrap();
+                        }
+                        1 => {
+                            assert_eq!(p.recv_via(&mut t).unwrap(), 0);
+                            p.send_via(&mut t, 2).unwrap();
+                        }
+                        _ => assert_eq!(p.recv_via(&mut t).unwrap(), 1),
+                    }
+                    p
+                })
+            })
+            .collect();
+        let ps: Vec<_> = ths.into_iter().map(|th| th.join().unwrap()).collect();
+        assert!(ps[0].events().iter().all(|s| s < &ps[2].events()[0]));
+        assert!(ps[1].events().iter().all(|s| s < &ps[2].events()[0]));
+    }
+
+    #[test]
+    fn try_recv() {
+        let mut ts = MpscTransport::<usize>::mesh(2);
+        assert!(ts[0].try_recv().unwrap().is_none());
+        ts[1].send_to(0, 42).unwrap();
+        ts[1].send_to(0, 7).unwrap();
+        assert_eq!(ts[0].try_recv().unwrap(), Some((1, 42)));
+        assert_eq!(ts[0].recv().unwrap(), (1, 7));
+        assert!(matches!(ts[0].send_to(0, 0), Err(OrderError::Other(_)))); // to self
+        assert!(matches!(ts[0].send_to(2, 0), Err(OrderError::Other(_))));
+    }
+
+    #[test]
+    fn disconnected() {
+        let mut ts = MpscTransport::<usize>::mesh(2);
+        let mut t0 = ts.remove(0);
+        drop(ts);
+        assert!(matches!(t0.send_to(1, 0), Err(OrderError::Disconnected)));
+        // Holds no sender to itself that would keep it waiting
+        assert!(matches!(t0.recv(), Err(OrderError::Disconnected)));
+        assert!(matches!(t0.try_recv(), Err(OrderError::Disconnected)));
+    }
+
+    /// Delivers whatever was sent in random order, with duplicates
+    struct Shuffled<E> {
+        t: MpscTransport<E>,
+        buf: Vec<(usize, E)>,
+        rng: rand::rngs::StdRng,
+    }
+
+    impl<E: Clone> Transport<E> for Shuffled<E> {
+        fn pid(&self) -> usize {
+            self.t.pid()
+        }
+        fn n_procs(&self) -> usize {
+            self.t.n_procs()
+        }
+        fn send_to(&mut self, pid: usize, e: E) -> Result<(), OrderError> {
+            if self.rng.gen_bool(0.2) {
+                self.t.send_to(pid, e.clone())?;
+            }
+            self.t.send_to(pid, e)
+        }
+        fn recv(&mut self) -> Result<(usize, E), OrderError> {
+            while let Some(m) = self.t.try_recv()? {
+                self.buf.push(m);
+            }
+            if self.buf.is_empty() {
+                return self.t.recv();
+            }
+            let idx = self.rng.gen_range(0..self.buf.len());
+            Ok(self.buf.swap_remove(idx))
+        }
+        fn try_recv(&mut self) -> Result<Option<(usize, E)>, OrderError> {
+            while let Some(m) = self.t.try_recv()? {
+                self.buf.push(m);
+            }
+            if self.buf.is_empty() {
+                return Ok(None);
+            }
+            let idx = self.rng.gen_range(0..self.buf.len());
+            Ok(Some(self.buf.swap_remove(idx)))
+        }
+    }
+
+    #[test]
+    fn fifo_channel() {
+        let mut ts: Vec<_> = MpscTransport::<Message<(), usize>>::mesh(3)
+            .into_iter()
+            .enumerate()
+            .map(|(i, t)| {
+                FifoChannel::new(Shuffled {
+                    t,
+                    buf: Vec::new(),
+                    rng: rand::rngs::StdRng::seed_from_u64(i as u64),
+                })
+            })
+            .collect();
+        for m in 0..100 {
+            ts[1].send_to(0, m).unwrap();
+            ts[2].send_to(0, m).unwrap();
+        }
+        let (mut from1, mut from2) = (0, 0);
+        while let Some((from, m)) = ts[0].try_recv().unwrap() {
+            let next = if from == 1 { &mut from1 } else { &mut from2 };
+            assert_eq!(m, *next);
+            *next += 1;
+        }
+        assert_eq!((from1, from2), (100, 100));
+        assert_eq!(ts[0].n_held(), 0);
+        assert!(ts[0].try_recv().unwrap().is_none()); // dropped duplicates
+    }
+
+    #[test]
+    fn fifo_channel_holds_back() {
+        let mut ts = MpscTransport::<Message<(), &str>>::mesh(2);
+        let mut t1 = FifoChannel::new(ts.pop().unwrap());
+        ts[0].send_to(1, Message::new(0, 1, (), "second")).unwrap();
+        assert!(t1.try_recv().unwrap().is_none());
+        assert_eq!(t1.n_held(), 1);
+        ts[0].send_to(1, Message::new(0, 0, (), "first")).unwrap();
+        assert_eq!(t1.recv().unwrap(), (0, "first"));
+        assert_eq!(t1.recv().unwrap(), (0, "second"));
+        ts[0].send_to(1, Message::new(1, 2, (), "spoofed")).unwrap();
+        assert!(matches!(t1.recv(), Err(OrderError::Other(_))));
+    }
+
+    #[cfg(feature = "net")]
+    fn tcp_mesh<E>(n_procs: usize) -> Vec<TcpTransport<E>>
+    where
+        E: serde::Serialize + serde::de::DeserializeOwned + Send + 'static,
+    {
+        let ls: Vec<_> = (0..n_procs)
+            .map(|_| std::net::TcpListener::bind("127.0.0.1:0").unwrap())
+            .collect();
+        let addrs: Vec<_> = ls.iter().map(|l| l.local_addr().unwrap()).collect();
+        let ths: Vec<_> = ls
+            .into_iter()
+            .enumerate()
+            .map(|(i, l)| {
+                let addrs = addrs.clone();
+                std::thread::spawn(move || TcpTransport::with_listener(i, l, &addrs).unwrap())
+            })
+            .collect();
+        ths.into_iter().map(|th| th.join().unwrap()).collect()
+    }
+
+    #[test]
+    #[cfg(feature = "net")]
+    fn tcp_mock_scheduler() {
+        let ths: Vec<_> = tcp_mesh(3)
+            .into_iter()
+            .map(|mut t| {
+                std::thread::spawn(move || {
+                    let mut p = Process::<VectorClock>::new(t.pid(), t.n_procs());
+                    match p.pid() {
+                        0 => {
+                            p.exec(|| {});
+                            p.send_via(&mut t, 1).unwrap();
+                            p.send_via(&mut t, 2).unwrap();
+                        }
+                        1 => {
+                            assert_eq!(p.recv_via(&mut t).unwrap(), 0);
+                            p.send_via(&mut t, 2).unwrap();
+                        }
+                        _ => {
+                            p.recv_via(&mut t).unwrap();
+                            p.recv_via(&mut t).unwrap();
+                        }
+                    }
+                    p
+                })
+            })
+            .collect();
+        let ps: Vec<_> = ths.into_iter().map(|th| th.join().unwrap()).collect();
+        assert!(ps[0].events().iter().all(|s| s < &ps[2].events()[1]));
+        assert!(ps[1].events().iter().all(|s| s < &ps[2].events()[1]));
+    }
+
+    #[test]
+    #[cfg(feature = "net")]
+    fn tcp_fifo() {
+        let mut ts = tcp_mesh::<usize>(2);
+        for m in 0..100 {
+            ts[1].send_to(0, m).unwrap();
+        }
+        assert!(matches!(ts[0].send_to(0, 7), Err(OrderError::Other(_)))); // to self
+        for m in 0..100 {
+            assert_eq!(ts[0].recv().unwrap(), (1, m));
+        }
+        assert!(ts[0].try_recv().unwrap().is_none());
+        assert!(matches!(ts[0].send_to(2, 0), Err(OrderError::Other(_))));
+    }
+
+    #[test]
+    #[cfg(feature = "net")]
+    fn tcp_disconnected() {
+        let mut ts = tcp_mesh::<usize>(2);
+        let mut t0 = ts.remove(0);
+        drop(ts);
+        assert!(matches!(t0.recv(), Err(OrderError::Disconnected)));
+    }
+
+    #[test]
+    #[cfg(feature = "net")]
+    fn tcp_frame_too_long() {
+        use crate::order::transport::{read_frame, MAX_FRAME};
+        let len = (MAX_FRAME as u32 + 1).to_be_bytes();
+        assert!(matches!(
+            read_frame(&mut len.as_slice()),
+            Err(OrderError::Other(_))
+        ));
+        let mut frame = 3u32.to_be_bytes().to_vec();
+        frame.extend_from_slice(b"abc");
+        assert_eq!(read_frame(&mut frame.as_slice()).unwrap().unwrap(), b"abc");
+    }
+
+    #[test]
+    #[cfg(feature = "net")]
+    fn udp_failed_send() {
+        let socket = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        let addrs = [socket.local_addr().unwrap(), "127.0.0.1:0".parse().unwrap()];
+        let mut t = UdpTransport::<usize>::with_socket(0, socket, &addrs).unwrap();
+        assert!(t.send_to(1, 42).is_err());
+        // Nothing left to retransmit
+        assert!(t.link.unacked.lock().unwrap().is_empty());
+        assert_eq!(t.next_seq[1], 0);
+    }
+
+    #[cfg(feature = "net")]
+    fn udp_mesh<E>(n_procs: usize, drop_every: usize) -> Vec<UdpTransport<E>>
+    where
+        E: serde::Serialize + serde::de::DeserializeOwned + Send + 'static,
+    {
+        let ss: Vec<_> = (0..n_procs)
+            .map(|_| std::net::UdpSocket::bind("127.0.0.1:0").unwrap())
+            .collect();
+        let addrs: Vec<_> = ss.iter().map(|s| s.local_addr().unwrap()).collect();
+        ss.into_iter()
+            .enumerate()
+            .map(|(i, s)| {
+                let t = UdpTransport::with_socket(i, s, &addrs).unwrap();
+                t.link
+                    .drop_every
+                    .store(drop_every, std::sync::atomic::Ordering::Relaxed);
+                t
+            })
+            .collect()
+    }
+
+    #[test]
+    #[cfg(feature = "net")]
+    fn udp_fifo_despite_loss() {
+        let mut ts = udp_mesh::<usize>(2, 3);
+        for m in 0..100 {
+            ts[1].send_to(0, m).unwrap();
+            ts[0].send_to(1, m).unwrap();
+        }
+        assert!(matches!(ts[0].send_to(0, 7), Err(OrderError::Other(_)))); // to self
+        for m in 0..100 {
+            assert_eq!(ts[0].recv().unwrap(), (1, m));
+            assert_eq!(ts[1].recv().unwrap(), (0, m));
+        }
+        for t in &ts {
+            t.flush(std::time::Duration::from_secs(5)).unwrap();
+        }
+        assert!(ts[0].try_recv().unwrap().is_none());
+        assert!(matches!(ts[0].send_to(2, 0), Err(OrderError::Other(_))));
+    }
+
+    #[test]
+    #[cfg(feature = "net")]
+    fn udp_mock_scheduler() {
+        let ths: Vec<_> = udp_mesh(3, 2)
+            .into_iter()
+            .map(|mut t| {
+                std::thread::spawn(move || {
+                    let mut p = Process::<VectorClock>::new(t.pid(), t.n_procs());
+                    match p.pid() {
+                        0 => {
+                            p.exec(|| {});
+                            p.send_via(&mut t, 1).unwrap();
+                        }
+                        1 => {
+                            assert_eq!(p.recv_via(&mut t).unwrap(), 0);
+                            p.send_via(&mut t, 2).unwrap();
+                        }
+                        _ => assert_eq!(p.recv_via(&mut t).unwrap(), 1),
+                    }
+                    t.flush(std::time::Duration::from_secs(5)).unwrap();
+                    p
+                })
+            })
+            .collect();
+        let ps: Vec<_> = ths.into_iter().map(|th| th.join().unwrap()).collect();
+        assert!(ps[0].events().iter().all(|s| s < &ps[2].events()[0]));
+    }
+
+    #[test]
+    #[cfg(feature = "net")]
+    fn udp_flush_timeout() {
+        let mut ts = udp_mesh::<usize>(2, 0);
+        ts.pop();
+        ts[0].send_to(1, 0).unwrap();
+        assert!(matches!(
+            ts[0].flush(std::time::Duration::from_millis(100)),
+            Err(OrderError::Disconnected)
+        ));
+    }
+}
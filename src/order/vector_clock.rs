@@ -1,5 +1,8 @@
 use super::LogicalClock;
-use crate::order::{pairwise_max, CausalOrd, HasEvents, OrdProcess};
+use crate::order::{
+    pairwise_max, parse_list, split_pid, CausalOrd, Counter, Process, Tombstone, TotalOrder,
+};
+use alloc::vec::Vec;
 
 /// Vector Clock is used to compare if one event happens before (<) / after (>) another or if they are concurrent (None).
 ///
@@ -22,13 +25,29 @@ use crate::order::{pairwise_max, CausalOrd, HasEvents, OrdProcess};
 /// assert!(e2.partial_cmp(&f2) == None);
 /// assert!(f1 < f2);
 /// ```
+///
+/// [`VectorClock`] counts events, but [`VectorClockOf`] entries can be any ordered value, e.g. physical timestamps in
+/// a version vector.
+/// ```
+/// use rads::order::vector_clock::VectorClockOf;
+///
+/// let s = VectorClockOf::from_vec(0, vec![1_700_000_000_u64, 0]);
+/// let t = VectorClockOf::from_vec(1, vec![0, 1_700_000_050_u64]);
+/// assert!(s.partial_cmp(&t) == None);
+/// assert!(s < s.join(&t));
+/// ```
 #[derive(Clone)]
-pub struct VectorClock {
+#[cfg_attr(any(test, feature = "proptest"), derive(Debug))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct VectorClockOf<T> {
     i: usize,
-    clk: Vec<usize>,
+    clk: Vec<T>,
 }
 
-impl LogicalClock for VectorClock {
+/// Vector clock counting events, see [`VectorClockOf`]
+pub type VectorClock = VectorClockOf<usize>;
+
+impl<T: Counter> LogicalClock for VectorClockOf<T> {
     fn new(i: usize, n_procs: usize) -> Self {
         assert!(
             i < n_procs,
@@ -36,44 +55,120 @@ impl LogicalClock for VectorClock {
         );
         Self {
             i,
-            clk: (0..n_procs).map(|j| usize::from(i == j)).collect(),
+            clk: (0..n_procs)
+                .map(|j| if i == j { T::ZERO.incr() } else { T::ZERO })
+                .collect(),
         }
     }
     fn extend(&self) -> Self {
         let mut e = self.clone();
-        e.clk[e.i] += 1;
+        e.clk[e.i] = e.clk[e.i].incr();
         e
     }
     fn merge(&self, other: &Self) -> Self {
-        debug_assert_eq!(
-            self.clk.len(),
-            other.clk.len(),
-            "Cannot merge with process that is aware of differing processes"
-        );
         debug_assert!(
-            self.clk[self.i] >= other.clk[self.i],
+            other.clk.get(self.i).is_none_or(|t| &self.clk[self.i] >= t),
             "Process from different scheduler detected. Process' own clock's invariant broken."
         );
+        self.join(other).extend()
+    }
+    fn observe(&self, other: &Self) -> Self {
+        self.join(other)
+    }
+    fn fmt_clock(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        core::fmt::Display::fmt(self, f)
+    }
+    fn byte_size(&self) -> usize {
+        core::mem::size_of::<Self>() + self.clk.len() * core::mem::size_of::<T>()
+    }
+}
+
+impl<T: Ord + Copy> VectorClockOf<T> {
+    /// Clock of process `i` with the given entries, e.g. to track physical timestamps instead of event counts
+    pub fn from_vec(i: usize, clk: Vec<T>) -> Self {
+        assert!(
+            i < clk.len(),
+            "Expect 0-based index of process {i} < n_procs={}",
+            clk.len()
+        );
+        Self { i, clk }
+    }
+    /// Pairwise max of both clocks, keeping this clock's process.
+    ///
+    /// If `other` knows of more processes, e.g. ones that joined since, the clock grows to know of them too.
+    pub fn join(&self, other: &Self) -> Self {
         Self {
             i: self.i,
-            clk: pairwise_max(self.clk.iter(), other.clk.iter())
-                .enumerate()
-                .map(|(i, v)| v + usize::from(i == self.i))
-                .collect(),
+            clk: pairwise_max(self.clk.iter(), other.clk.iter()).collect(),
         }
     }
-}
-
-impl PartialOrd for VectorClock {
-    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        if self.clk.len() != other.clk.len() {
+    /// Sets this process' own entry, which must not go backwards
+    pub fn advance(&self, v: T) -> Self {
+        assert!(v >= self.clk[self.i], "Process' own entry cannot go back");
+        let mut e = self.clone();
+        e.clk[e.i] = v;
+        e
+    }
+    /// Process of this clock
+    pub fn pid(&self) -> usize {
+        self.i
+    }
+    /// Number of processes this clock knows of
+    pub fn len(&self) -> usize {
+        self.clk.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.clk.is_empty()
+    }
+    /// Number of events of process `pid` that happened before this event
+    pub fn get(&self, pid: usize) -> Option<T> {
+        self.clk.get(pid).copied()
+    }
+    pub fn iter(&self) -> core::slice::Iter<'_, T> {
+        self.clk.iter()
+    }
+    /// Marks this process as retired, to be compacted out of clocks after its final event
+    pub fn retire(&self) -> Tombstone<T> {
+        Tombstone {
+            pid: self.i,
+            last: self.clk[self.i],
+        }
+    }
+    /// Removes entries of retired processes, renumbering the rest (see [`Tombstone::renumber`]).
+    ///
+    /// None if this process retired or has not seen the final event of every retired process.
+    pub fn compact(&self, retired: &[Tombstone<T>]) -> Option<Self> {
+        let i = Tombstone::renumber(self.i, retired)?;
+        if !retired.iter().all(|t| self.clk.get(t.pid) == Some(&t.last)) {
             return None;
         }
-        use std::cmp::Ordering::{Equal, Greater, Less};
-        self.clk
-            .iter()
-            .zip(&other.clk)
-            .try_fold(Equal, |acc, (s, t)| match (acc, s.cmp(t)) {
+        Some(Self {
+            i,
+            clk: (0..self.clk.len())
+                .filter(|j| retired.iter().all(|t| t.pid != *j))
+                .map(|j| self.clk[j])
+                .collect(),
+        })
+    }
+}
+
+impl<T: Counter + core::ops::Sub<Output = T>> VectorClockOf<T> {
+    /// Number of events of each process that this clock has seen but `other` has not, skipping processes without any
+    pub fn diff(&self, other: &Self) -> Vec<(usize, T)> {
+        self.entries(other)
+            .enumerate()
+            .filter(|(_, (s, t))| s > t)
+            .map(|(pid, (s, t))| (pid, s - t))
+            .collect()
+    }
+}
+
+// Missing entries are of processes that joined later, which the clock has seen no events of
+impl<T: Counter> PartialOrd for VectorClockOf<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<core::cmp::Ordering> {
+        use core::cmp::Ordering::{Equal, Greater, Less};
+        self.entries(other)
+            .try_fold(Equal, |acc, (s, t)| match (acc, s.cmp(&t)) {
                 (Less, Greater) | (Greater, Less) => None,
                 (_, Less) | (Less, _) => Some(Less),
                 (_, Greater) | (Greater, _) => Some(Greater),
@@ -82,54 +177,107 @@ impl PartialOrd for VectorClock {
     }
 }
 
-impl CausalOrd for VectorClock {}
+impl<T: Counter> CausalOrd for VectorClockOf<T> {}
 
-impl PartialEq for VectorClock {
+// Missing entries are zero, like `partial_cmp`
+impl<T: Counter> PartialEq for VectorClockOf<T> {
     fn eq(&self, other: &Self) -> bool {
-        self.i == other.i && self.clk == other.clk
+        self.i == other.i && self.trimmed() == other.trimmed()
     }
 }
 
-pub struct VecProcess {
-    i: usize,
-    n_procs: usize,
-    events: Vec<VectorClock>,
+impl<T: Counter> Eq for VectorClockOf<T> {}
+
+impl<T: Counter + core::hash::Hash> core::hash::Hash for VectorClockOf<T> {
+    fn hash<H: core::hash::Hasher>(&self, state: &mut H) {
+        self.i.hash(state);
+        self.trimmed().hash(state);
+    }
 }
 
-impl VecProcess {
-    pub fn new(i: usize, n_procs: usize) -> Self {
-        Self {
-            i,
-            n_procs,
-            events: Vec::new(),
-        }
+impl<T: Counter> VectorClockOf<T> {
+    /// Whether this clock has seen at least everything `other` has seen, i.e. `self >= other`
+    pub fn dominates(&self, other: &Self) -> bool {
+        self >= other
+    }
+    /// Entries of both clocks, where missing ones are zero
+    fn entries<'a>(&'a self, other: &'a Self) -> impl Iterator<Item = (T, T)> + 'a {
+        let entry = |c: &Self, j: usize| c.clk.get(j).copied().unwrap_or(T::ZERO);
+        (0..self.clk.len().max(other.clk.len())).map(move |j| (entry(self, j), entry(other, j)))
+    }
+    /// Entries without trailing zeros, which are equal for clocks that differ only in how many processes they know of
+    fn trimmed(&self) -> &[T] {
+        let n = self.clk.iter().rposition(|v| *v != T::ZERO);
+        &self.clk[..n.map_or(0, |j| j + 1)]
     }
 }
 
-impl HasEvents<VectorClock> for VecProcess {
-    fn last_event(&self) -> Option<&VectorClock> {
-        self.events.last()
+/// Formats as `<pid>:[<clock>,..]`, e.g. `0:[1,4,2]`
+impl<T: core::fmt::Display> core::fmt::Display for VectorClockOf<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}:[", self.i)?;
+        for (j, v) in self.clk.iter().enumerate() {
+            if j > 0 {
+                write!(f, ",")?;
+            }
+            write!(f, "{v}")?;
+        }
+        write!(f, "]")
     }
-    fn push_event(&mut self, e: VectorClock) {
-        self.events.push(e)
+}
+
+impl<T> core::str::FromStr for VectorClockOf<T>
+where
+    T: core::str::FromStr,
+    T::Err: core::fmt::Display + core::fmt::Debug + Send + Sync + 'static,
+{
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (i, clk) = split_pid(s)?;
+        let clk = parse_list(clk)?;
+        anyhow::ensure!(
+            i < clk.len(),
+            "Expect 0-based index of process {i} < n_procs={}",
+            clk.len()
+        );
+        Ok(Self { i, clk })
     }
-    fn pid(&self) -> usize {
-        self.i
+}
+
+impl Ord for TotalOrder<VectorClock> {
+    fn cmp(&self, other: &Self) -> core::cmp::Ordering {
+        // s < t implies sum(s) < sum(t), so sums extend happens before. Ties break by pid, then lexicographically to
+        // stay consistent with Eq.
+        let sum = |c: &VectorClock| c.clk.iter().sum::<usize>();
+        sum(&self.0)
+            .cmp(&sum(&other.0))
+            .then(self.0.i.cmp(&other.0.i))
+            .then_with(|| self.0.trimmed().cmp(other.0.trimmed()))
     }
-    fn n_procs(&self) -> usize {
-        self.n_procs
+}
+
+impl PartialOrd for TotalOrder<VectorClock> {
+    fn partial_cmp(&self, other: &Self) -> Option<core::cmp::Ordering> {
+        Some(self.cmp(other))
     }
-    fn events(&self) -> &[VectorClock] {
-        self.events.as_slice()
+}
+
+impl PartialEq for TotalOrder<VectorClock> {
+    fn eq(&self, other: &Self) -> bool {
+        self.0 == other.0
     }
 }
 
-impl OrdProcess<VectorClock> for VecProcess {}
+impl Eq for TotalOrder<VectorClock> {}
+
+pub type VecProcess = Process<VectorClock>;
 
 #[cfg(test)]
 mod tests {
-    use crate::order::vector_clock::VecProcess;
-    use crate::order::{vector_clock::VectorClock, HasEvents, LogicalClock, OrdProcess};
+    use crate::order::vector_clock::{VecProcess, VectorClockOf};
+    use crate::order::{
+        vector_clock::VectorClock, HasEvents, LogicalClock, OrdProcess, TotalOrder,
+    };
     use rand::Rng;
 
     #[test]
@@ -157,6 +305,166 @@ mod tests {
         assert_eq!(f2.partial_cmp(&f2), Some(std::cmp::Ordering::Equal));
     }
 
+    #[test]
+    fn grows_on_merge() {
+        // p2 joined after p0 and p1 started
+        let e2 = VectorClock::new(0, 2).extend();
+        let g1 = VectorClock::new(2, 3);
+        assert_eq!(e2.merge(&g1).iter().copied().collect::<Vec<_>>(), [3, 0, 1]);
+        let g2 = g1.merge(&e2);
+        assert_eq!(g2.iter().copied().collect::<Vec<_>>(), [2, 0, 2]);
+        assert!(e2 < g2 && e2.partial_cmp(&g1).is_none());
+        assert_eq!(e2.join(&g1).len(), 3);
+    }
+
+    #[test]
+    fn accessors() {
+        let e1 = VectorClock::new(0, 3);
+        let e2 = e1.extend();
+        let f1 = VectorClock::new(2, 3).merge(&e2);
+        assert_eq!(f1.len(), 3);
+        assert_eq!(f1.get(0), Some(2));
+        assert_eq!(f1.get(1), Some(0));
+        assert_eq!(f1.get(2), Some(2));
+        assert_eq!(f1.get(3), None);
+        assert_eq!(f1.iter().copied().collect::<Vec<_>>(), vec![2, 0, 2]);
+
+        assert!(e2.dominates(&e1));
+        assert!(e2.dominates(&e2));
+        assert!(!e1.dominates(&e2));
+        assert!(f1.dominates(&e2));
+        assert!(!e2.dominates(&f1));
+        // Missing entries of processes that joined later are zero
+        assert!(VectorClock::new(0, 2).dominates(&e1) && e1.dominates(&VectorClock::new(0, 2)));
+        assert!(!VectorClock::new(0, 2).dominates(&e2));
+    }
+
+    #[test]
+    fn observe_does_not_extend() {
+        let e2 = VectorClock::new(0, 3).extend();
+        let f1 = VectorClock::new(1, 3);
+        let monitor = VectorClock::new(2, 3);
+        let m = monitor.observe(&e2).observe(&f1);
+        assert_eq!(m.iter().copied().collect::<Vec<_>>(), vec![2, 1, 1]);
+        assert!(e2 < m);
+        assert!(f1 < m);
+        assert_eq!(m.observe(&e2), m); // idempotent
+        assert!(m < monitor.merge(&e2).merge(&f1));
+    }
+
+    #[test]
+    fn diff() {
+        let e2 = VectorClock::new(0, 3).extend();
+        let f1 = VectorClock::new(1, 3);
+        let g2 = VectorClock::new(2, 3).merge(&e2);
+        assert_eq!(g2.diff(&f1), vec![(0, 2), (2, 2)]);
+        assert_eq!(f1.diff(&g2), vec![(1, 1)]);
+        assert_eq!(g2.diff(&e2), vec![(2, 2)]);
+        assert_eq!(e2.diff(&g2), vec![]); // g2 has seen all of e2
+        assert_eq!(g2.diff(&g2), vec![]);
+        // p2 joined after e2
+        let e3 = VectorClock::new(0, 2).extend().extend();
+        assert_eq!(e3.diff(&g2), vec![(0, 1)]);
+        assert_eq!(g2.diff(&e3), vec![(2, 2)]);
+    }
+
+    #[test]
+    fn display_from_str() {
+        let e = VectorClock::new(2, 3).merge(&VectorClock::new(0, 3).extend());
+        assert_eq!(e.to_string(), "2:[2,0,2]");
+        assert_eq!("2:[2,0,2]".parse::<VectorClock>().unwrap(), e);
+        assert_eq!(" 2: [2, 0, 2] ".parse::<VectorClock>().unwrap(), e);
+
+        assert!("[2,0,2]".parse::<VectorClock>().is_err()); // no pid
+        assert!("3:[2,0,2]".parse::<VectorClock>().is_err()); // unknown pid
+        assert!("0:[2,-1]".parse::<VectorClock>().is_err());
+        assert!("0:2,0".parse::<VectorClock>().is_err());
+    }
+
+    #[test]
+    fn generic_entries() {
+        // Smaller counters
+        let e1 = VectorClockOf::<u8>::new(0, 2);
+        let f2 = VectorClockOf::<u8>::new(1, 2).merge(&e1.extend());
+        assert!(e1 < f2);
+        assert_eq!(f2.to_string(), "1:[2,2]");
+
+        // Physical timestamps, where merging keeps the latest write of each replica
+        let s = VectorClockOf::from_vec(0, vec![100_u64, 0]);
+        let t = VectorClockOf::from_vec(1, vec![90_u64, 105]);
+        assert_eq!(s.partial_cmp(&t), None);
+        let u = t.join(&s).advance(120);
+        assert!(s < u && t < u);
+        assert_eq!(u.iter().copied().collect::<Vec<_>>(), vec![100, 120]);
+        assert_eq!(u.diff(&t), vec![(0, 10), (1, 15)]);
+        assert_eq!("1:[100,120]".parse::<VectorClockOf<u64>>().unwrap(), u);
+    }
+
+    #[test]
+    #[should_panic]
+    fn advance_backwards() {
+        VectorClockOf::from_vec(0, vec![100_u64, 0]).advance(99);
+    }
+
+    #[test]
+    fn retire_and_compact() {
+        let e1 = VectorClock::new(0, 3);
+        let f2 = VectorClock::new(1, 3).merge(&e1);
+        let g1 = VectorClock::new(2, 3);
+        let retired = [f2.retire()];
+        assert_eq!(retired[0].last, 2);
+
+        // Has not seen f2 yet
+        assert_eq!(g1.compact(&retired), None);
+        let g2 = g1.merge(&f2);
+        let e2 = e1.merge(&f2);
+        assert_eq!(f2.compact(&retired), None); // retired itself
+
+        let e3 = e2.compact(&retired).unwrap();
+        let g3 = g2.compact(&retired).unwrap();
+        assert_eq!(e3.to_string(), "0:[2,0]");
+        assert_eq!(g3.to_string(), "1:[1,2]"); // renumbered
+        assert_eq!(e3.partial_cmp(&g3), None);
+        assert!(e3 < g3.merge(&e3));
+    }
+
+    #[test]
+    fn hash_eq() {
+        let e1 = VectorClock::new(0, 2);
+        let f1 = VectorClock::new(1, 2);
+        let seen = std::collections::HashMap::from([(e1.clone(), 'e'), (f1.clone(), 'f')]);
+        assert_eq!(seen.get(&e1), Some(&'e'));
+        assert_eq!(seen.get(&f1), Some(&'f'));
+        assert_eq!(seen.get(&e1.extend()), None);
+
+        // Clocks of different lengths, e.g. before and after p2 joined
+        let e3 = VectorClock::from_vec(0, vec![1, 0, 0]);
+        assert_eq!(e3.partial_cmp(&e1), Some(std::cmp::Ordering::Equal));
+        assert!(e3 == e1 && e3 != VectorClock::from_vec(1, vec![1, 0, 0]));
+        assert_eq!(seen.get(&e3), Some(&'e'));
+        assert_eq!(
+            TotalOrder(e3).cmp(&TotalOrder(e1)),
+            std::cmp::Ordering::Equal
+        );
+    }
+
+    #[test]
+    fn total_order() {
+        let e1 = VectorClock::new(0, 2);
+        let e2 = e1.extend();
+        let f1 = VectorClock::new(1, 2);
+        let f2 = f1.merge(&e2);
+
+        // Concurrent events break ties with pid
+        assert_eq!(e1.partial_cmp(&f1), None);
+        assert!(TotalOrder(e1.clone()) < TotalOrder(f1.clone()));
+
+        // Happens before is preserved
+        let mut sorted = [&f2, &e2, &f1, &e1].map(|c| TotalOrder(c.clone()));
+        sorted.sort();
+        assert_eq!(sorted.map(|c| c.0), [e1, f1, e2, f2]);
+    }
+
     #[test]
     fn mock_scheduler() {
         let (tx3_2, rx3_2) = std::sync::mpsc::channel::<VectorClock>();
@@ -217,4 +525,9 @@ mod tests {
         let t = rng.gen_range(0..=200);
         std::thread::sleep(std::time::Duration::from_millis(t));
     }
+
+    #[test]
+    fn causal_ord_laws() {
+        crate::order::tests::assert_causal_ord_laws::<VectorClock>();
+    }
 }
@@ -0,0 +1,174 @@
+use crate::order::vector_clock::VectorClock;
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+use core::cmp::Ordering;
+
+/// Version Vector counts the updates of each replica sparsely, so that entries can be pruned to keep long-lived
+/// replica metadata bounded.
+///
+/// Replicas without an entry have made no updates (that were not pruned). Each entry also remembers the (caller
+/// provided) timestamp of its latest update, for age-based pruning.
+///
+/// # Examples
+/// ```
+/// use rads::order::version_vector::{Prune, VersionVector};
+///
+/// let mut s = VersionVector::default();
+/// s.increment(0, 100);
+/// let mut t = s.clone();
+/// t.increment(1, 200);
+/// assert!(s < t);
+/// assert_eq!(t.prune(&Prune::OlderThan(150)), vec![0]);
+/// assert_eq!(t.get(0), 0);
+/// assert_eq!(t.get(1), 1);
+/// ```
+#[derive(Clone, Default, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct VersionVector {
+    // pid -> (counter, stamp of latest update)
+    entries: BTreeMap<usize, (usize, u64)>,
+}
+
+/// Which entries to discard from a [`VersionVector`].
+pub enum Prune<'a> {
+    /// Entries last updated before the timestamp. Pruning too early may cause false conflicts, never lost updates.
+    OlderThan(u64),
+    /// Entries already seen by every replica, e.g. the minimum of a matrix clock. Only vectors pruned by the same
+    /// frontier remain comparable.
+    DominatedBy(&'a VectorClock),
+}
+
+impl VersionVector {
+    /// Number of replicas with an entry
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+    /// Number of updates of replica `pid`
+    pub fn get(&self, pid: usize) -> usize {
+        self.entries.get(&pid).map_or(0, |(c, _)| *c)
+    }
+    /// Records an update by replica `pid` at time `stamp`
+    pub fn increment(&mut self, pid: usize, stamp: u64) {
+        let (c, s) = self.entries.entry(pid).or_insert((0, stamp));
+        *c += 1;
+        *s = stamp.max(*s);
+    }
+    pub fn merge(&self, other: &Self) -> Self {
+        let mut v = self.clone();
+        for (pid, (c, s)) in &other.entries {
+            let (vc, vs) = v.entries.entry(*pid).or_insert((0, *s));
+            *vc = (*c).max(*vc);
+            *vs = (*s).max(*vs);
+        }
+        v
+    }
+    /// Discards entries by the policy, returning the pruned pids
+    pub fn prune(&mut self, policy: &Prune) -> Vec<usize> {
+        let pruned: Vec<_> = self
+            .entries
+            .iter()
+            .filter(|(pid, (c, s))| match policy {
+                Prune::OlderThan(t) => s < t,
+                Prune::DominatedBy(frontier) => frontier.get(**pid).is_some_and(|f| *c <= f),
+            })
+            .map(|(pid, _)| *pid)
+            .collect();
+        pruned.iter().for_each(|pid| {
+            self.entries.remove(pid);
+        });
+        #[cfg(feature = "tracing")]
+        tracing::debug!(target: "rads::order", reclaimed = pruned.len(), "prune");
+        pruned
+    }
+}
+
+// Compares counters only, like `partial_cmp`, since stamps are just hints for pruning
+impl PartialEq for VersionVector {
+    fn eq(&self, other: &Self) -> bool {
+        (self.entries.keys())
+            .chain(other.entries.keys())
+            .all(|pid| self.get(*pid) == other.get(*pid))
+    }
+}
+
+impl PartialOrd for VersionVector {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        use core::cmp::Ordering::{Equal, Greater, Less};
+        self.entries
+            .keys()
+            .chain(other.entries.keys())
+            .try_fold(Equal, |acc, pid| {
+                match (acc, self.get(*pid).cmp(&other.get(*pid))) {
+                    (Less, Greater) | (Greater, Less) => None,
+                    (_, Less) | (Less, _) => Some(Less),
+                    (_, Greater) | (Greater, _) => Some(Greater),
+                    (Equal, Equal) => Some(Equal),
+                }
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::order::vector_clock::VectorClock;
+    use crate::order::version_vector::{Prune, VersionVector};
+    use crate::order::LogicalClock;
+
+    #[test]
+    fn partial_ord() {
+        let mut s = VersionVector::default();
+        assert_eq!(s.partial_cmp(&s), Some(std::cmp::Ordering::Equal));
+        let mut t = s.clone();
+        s.increment(0, 0);
+        t.increment(1, 0);
+        assert_eq!(s.partial_cmp(&t), None); // siblings
+        let u = s.merge(&t);
+        assert_eq!(s.partial_cmp(&u), Some(std::cmp::Ordering::Less));
+        assert_eq!(u.partial_cmp(&t), Some(std::cmp::Ordering::Greater));
+        assert_eq!(u.get(0), 1);
+        assert_eq!(u.get(1), 1);
+        assert_eq!(u.get(2), 0);
+    }
+
+    #[test]
+    fn eq_ignores_stamps() {
+        let (mut s, mut t) = (VersionVector::default(), VersionVector::default());
+        s.increment(0, 10);
+        t.increment(0, 20);
+        assert_eq!(s.partial_cmp(&t), Some(std::cmp::Ordering::Equal));
+        assert_eq!(s, t);
+        t.increment(1, 20);
+        assert_ne!(s, t);
+    }
+
+    #[test]
+    fn prune_older_than() {
+        let mut v = VersionVector::default();
+        v.increment(0, 10);
+        v.increment(1, 20);
+        v.increment(0, 30);
+        v.increment(2, 15);
+        assert_eq!(v.prune(&Prune::OlderThan(20)), vec![2]);
+        assert_eq!(v.len(), 2);
+        assert_eq!(v.prune(&Prune::OlderThan(31)), vec![0, 1]);
+        assert!(v.is_empty());
+    }
+
+    #[test]
+    fn prune_dominated_by_frontier() {
+        // Everyone has seen 2 events of p0 and 2 events of p1
+        let frontier = VectorClock::new(1, 3).merge(&VectorClock::new(0, 3).extend());
+        assert_eq!(frontier.iter().copied().collect::<Vec<_>>(), vec![2, 2, 0]);
+
+        let mut v = VersionVector::default();
+        (0..2).for_each(|_| v.increment(0, 0));
+        (0..3).for_each(|_| v.increment(1, 0));
+        v.increment(2, 0);
+        assert_eq!(v.prune(&Prune::DominatedBy(&frontier)), vec![0]);
+        assert_eq!(v.get(1), 3);
+        assert_eq!(v.get(2), 1);
+    }
+}
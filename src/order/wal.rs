@@ -0,0 +1,240 @@
+use crate::order::{HasEvents, LogicalClock, OrdProcess, OrderError, Process};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+
+/// Write-ahead log of a process, which appends every event to a file before recording it, so that a crashed process
+/// can `Process::recover` its clock and event history on restart.
+///
+/// The log is one JSON object per line, starting with the pid and number of processes, then every event with its
+/// sender if known. Overrides of `OrdProcess` methods of the inner process are bypassed.
+///
+/// Panics if an event cannot be written, since recording it anyway would lose it on recovery.
+///
+/// # Examples
+/// ```
+/// use rads::order::wal::Persist;
+/// use rads::order::{HasEvents, OrdProcess, Process};
+/// use rads::order::vector_clock::VectorClock;
+///
+/// let path = std::env::temp_dir().join(format!("rads-doc-{}.wal", std::process::id()));
+/// let mut p = Process::<VectorClock>::new(0, 2).persist_to(&path).unwrap();
+/// p.exec(|| {});
+/// p.recv_from(|| (1, VectorClock::from_vec(1, vec![0, 1])));
+/// drop(p); // crash
+///
+/// let p = Process::<VectorClock>::recover(&path).unwrap();
+/// assert_eq!(p.events().len(), 2);
+/// assert_eq!(p.sender(1), Some(1));
+/// std::fs::remove_file(path).unwrap();
+/// ```
+pub struct Wal<P, E> {
+    inner: P,
+    log: BufWriter<File>,
+    _event: PhantomData<E>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Header {
+    pid: usize,
+    n_procs: usize,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Entry<E> {
+    event: E,
+    from: Option<usize>,
+}
+
+/// Starts a write-ahead log of any process.
+pub trait Persist<E: LogicalClock + Serialize>: HasEvents<E> + Sized {
+    /// Logs the events so far to `path`, replacing any log there, then logs every later event
+    fn persist_to<Q: AsRef<Path>>(self, path: Q) -> Result<Wal<Self, E>, OrderError> {
+        // Replace the log at once, so that a crash leaves either the old or the new log
+        let path = path.as_ref();
+        let mut tmp = PathBuf::from(path);
+        tmp.as_mut_os_string().push(".tmp");
+        let mut log = BufWriter::new(File::create(&tmp)?);
+        write_line(
+            &mut log,
+            &Header {
+                pid: self.pid(),
+                n_procs: self.n_procs(),
+            },
+        )?;
+        for (idx, event) in self.events().iter().enumerate() {
+            let from = self.sender(idx);
+            write_line(&mut log, &Entry { event, from })?;
+        }
+        log.flush()?;
+        log.get_ref().sync_all()?;
+        std::fs::rename(&tmp, path)?;
+
+        let log = BufWriter::new(File::options().append(true).open(path)?);
+        Ok(Wal {
+            inner: self,
+            log,
+            _event: PhantomData,
+        })
+    }
+}
+
+impl<E: LogicalClock + Serialize, P: HasEvents<E>> Persist<E> for P {}
+
+fn write_line<T: Serialize>(log: &mut BufWriter<File>, t: &T) -> Result<(), OrderError> {
+    serde_json::to_writer(&mut *log, t).map_err(|e| OrderError::Other(e.into()))?;
+    Ok(log.write_all(b"\n")?)
+}
+
+impl<E: LogicalClock + Serialize, P: HasEvents<E>> Wal<P, E> {
+    pub fn inner(&self) -> &P {
+        &self.inner
+    }
+    /// Stops logging
+    pub fn into_inner(self) -> P {
+        self.inner
+    }
+    fn append(&mut self, event: &E, from: Option<usize>) {
+        write_line(&mut self.log, &Entry { event, from })
+            .and_then(|()| Ok(self.log.flush()?))
+            .expect("Failed to write ahead event");
+    }
+}
+
+impl<E: LogicalClock + Serialize, P: HasEvents<E>> HasEvents<E> for Wal<P, E> {
+    fn last_event(&self) -> Option<&E> {
+        self.inner.last_event()
+    }
+    fn push_event(&mut self, e: E) {
+        self.append(&e, None);
+        self.inner.push_event(e)
+    }
+    fn push_recv_event(&mut self, e: E, from: usize) {
+        self.append(&e, Some(from));
+        self.inner.push_recv_event(e, from)
+    }
+    fn pid(&self) -> usize {
+        self.inner.pid()
+    }
+    fn n_procs(&self) -> usize {
+        self.inner.n_procs()
+    }
+    fn events(&self) -> &[E] {
+        self.inner.events()
+    }
+    fn sender(&self, idx: usize) -> Option<usize> {
+        self.inner.sender(idx)
+    }
+}
+
+impl<E: LogicalClock + Serialize, P: HasEvents<E>> OrdProcess<E> for Wal<P, E> {}
+
+impl<C: LogicalClock + DeserializeOwned> Process<C> {
+    /// Restores a process from its write-ahead log, ignoring an event torn by a crash mid-write
+    pub fn recover<Q: AsRef<Path>>(path: Q) -> Result<Self, OrderError> {
+        let log = std::fs::read_to_string(path)?;
+        let mut lines: Vec<_> = log.split('\n').collect();
+        lines.pop(); // After the last newline, i.e. empty or torn
+        let mut lines = lines.into_iter();
+        let header: Header = parse_line(lines.next().unwrap_or_default())?;
+        if header.pid >= header.n_procs {
+            return Err(OrderError::Other(anyhow::anyhow!(
+                "Expect 0-based index of process {} < n_procs={}",
+                header.pid,
+                header.n_procs
+            )));
+        }
+        let mut p = Self::new(header.pid, header.n_procs);
+        for line in lines {
+            let entry: Entry<C> = parse_line(line)?;
+            match entry.from {
+                Some(from) => p.push_recv_event(entry.event, from),
+                None => p.push_event(entry.event),
+            }
+        }
+        Ok(p)
+    }
+}
+
+fn parse_line<T: DeserializeOwned>(line: &str) -> Result<T, OrderError> {
+    serde_json::from_str(line).map_err(|e| OrderError::Other(e.into()))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::order::lamport_clock::LamportClock;
+    use crate::order::matrix_clock::MatrixClock;
+    use crate::order::wal::Persist;
+    use crate::order::{HasEvents, OrdProcess, Process};
+    use std::io::Write;
+
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("rads-{name}-{}.wal", std::process::id()))
+    }
+
+    #[test]
+    fn recover() {
+        let path = temp_path("recover");
+        let mut p0 = Process::<MatrixClock>::new(0, 2);
+        let mut p1 = Process::<MatrixClock>::new(1, 2);
+        p0.exec(|| {}); // before logging
+        let mut p0 = p0.persist_to(&path).unwrap();
+        let mut msg = None;
+        p1.send(|e| msg = Some(e));
+        p0.recv_from(|| (1, msg.unwrap()));
+        p0.send(|_| {});
+        let before = p0.into_inner();
+
+        let after = Process::<MatrixClock>::recover(&path).unwrap();
+        assert_eq!((after.pid(), after.n_procs()), (0, 2));
+        assert!(before.events() == after.events());
+        assert_eq!(after.sender(1), Some(1));
+        assert_eq!(after.sender(2), None);
+
+        // Keeps logging after recovery
+        let mut p0 = after.persist_to(&path).unwrap();
+        p0.exec(|| {});
+        drop(p0);
+        assert_eq!(
+            Process::<MatrixClock>::recover(&path)
+                .unwrap()
+                .events()
+                .len(),
+            4
+        );
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn torn_write() {
+        let path = temp_path("torn");
+        let mut p = Process::<LamportClock>::new(1, 2)
+            .persist_to(&path)
+            .unwrap();
+        p.exec(|| {});
+        p.exec(|| {});
+        drop(p);
+        let mut f = std::fs::File::options().append(true).open(&path).unwrap();
+        f.write_all(b"{\"event\":{\"i\":1,").unwrap();
+        drop(f);
+
+        let p = Process::<LamportClock>::recover(&path).unwrap();
+        assert_eq!(p.events().len(), 2);
+        assert_eq!(p.last_event().unwrap().get(), 3);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn corrupt() {
+        let path = temp_path("corrupt");
+        std::fs::write(&path, "{\"pid\":2,\"n_procs\":2}\n").unwrap();
+        assert!(Process::<LamportClock>::recover(&path).is_err());
+        std::fs::write(&path, "{\"pid\":0,\"n_procs\":2}\nnot json\n").unwrap();
+        assert!(Process::<LamportClock>::recover(&path).is_err());
+        std::fs::remove_file(&path).unwrap();
+        assert!(Process::<LamportClock>::recover(&path).is_err());
+    }
+}
@@ -0,0 +1,249 @@
+use crate::sim::{Network, Node, Sim};
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+use core::ops::Range;
+
+/// Seed of an interleaving that violated an invariant, to replay it with `Explorer::run`.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Counterexample {
+    pub seed: u64,
+    pub reason: String,
+}
+
+impl fmt::Display for Counterexample {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Invariant violated under seed {}: {}",
+            self.seed, self.reason
+        )
+    }
+}
+
+/// Re-runs a scenario under many seeded schedules, checking invariants on the final nodes of each run.
+///
+/// # Examples
+/// ```
+/// use rads::order::vector_clock::VectorClock;
+/// use rads::order::{HasEvents, OrdProcess, Process};
+/// use rads::sim::{Context, Explorer, Node};
+///
+/// struct Gossip(Process<VectorClock>);
+/// impl Node<VectorClock> for Gossip {
+///     fn on_start(&mut self, ctx: &mut Context<'_, VectorClock>) {
+///         let to = (ctx.pid() + 1) % ctx.n_procs();
+///         self.0.send(|e| ctx.send(to, e));
+///     }
+///     fn on_message(&mut self, _: &mut Context<'_, VectorClock>, from: usize, e: VectorClock) {
+///         self.0.recv_from(|| (from, e));
+///     }
+/// }
+///
+/// Explorer::new(0..100).assert(
+///     || (0..3).map(|i| Gossip(Process::new(i, 3))).collect(),
+///     |nodes| match nodes.iter().all(|n| n.0.events().len() == 2) {
+///         true => Ok(()),
+///         false => Err("Expect a send and a recv per process".into()),
+///     },
+/// );
+/// ```
+pub struct Explorer {
+    seeds: Range<u64>,
+    network: Network,
+    max_steps: u64,
+}
+
+impl Explorer {
+    /// Explores one schedule per seed, with the default `Network`
+    pub fn new(seeds: Range<u64>) -> Self {
+        Self {
+            seeds,
+            network: Network::default(),
+            max_steps: 1_000_000,
+        }
+    }
+    pub fn with_network(mut self, network: Network) -> Self {
+        self.network = network;
+        self
+    }
+    /// Runs that don't quiesce within `max_steps` are counterexamples, e.g. for livelocks
+    pub fn with_max_steps(mut self, max_steps: u64) -> Self {
+        self.max_steps = max_steps;
+        self
+    }
+
+    /// Runs the scenario under `seed` until it quiesces or exceeds the max steps, e.g. to debug a counterexample
+    pub fn run<N, M, S>(&self, seed: u64, scenario: S) -> Sim<N, M>
+    where
+        N: Node<M>,
+        M: Clone,
+        S: FnOnce() -> Vec<N>,
+    {
+        let mut sim = Sim::new(seed, scenario()).with_network(self.network.clone());
+        while sim.n_steps() < self.max_steps && sim.step() {}
+        sim
+    }
+
+    /// Returns the first seed whose run violates the invariant, panics or doesn't quiesce
+    pub fn check<N, M, S, I>(&self, scenario: S, invariant: I) -> Result<(), Counterexample>
+    where
+        N: Node<M>,
+        M: Clone,
+        S: Fn() -> Vec<N>,
+        I: Fn(&[N]) -> Result<(), String>,
+    {
+        for seed in self.seeds.clone() {
+            self.check_seed(seed, &scenario, &invariant)
+                .map_err(|reason| Counterexample { seed, reason })?;
+        }
+        Ok(())
+    }
+
+    /// Like `check`, but panics with the counterexample, e.g. in tests
+    pub fn assert<N, M, S, I>(&self, scenario: S, invariant: I)
+    where
+        N: Node<M>,
+        M: Clone,
+        S: Fn() -> Vec<N>,
+        I: Fn(&[N]) -> Result<(), String>,
+    {
+        if let Err(e) = self.check(scenario, invariant) {
+            panic!("{e}");
+        }
+    }
+
+    #[cfg(feature = "std")]
+    fn check_seed<N, M, S, I>(&self, seed: u64, scenario: &S, invariant: &I) -> Result<(), String>
+    where
+        N: Node<M>,
+        M: Clone,
+        S: Fn() -> Vec<N>,
+        I: Fn(&[N]) -> Result<(), String>,
+    {
+        let run =
+            std::panic::AssertUnwindSafe(|| self.check_run(self.run(seed, scenario), invariant));
+        std::panic::catch_unwind(run).unwrap_or_else(|e| {
+            let msg = match (e.downcast_ref::<&str>(), e.downcast_ref::<String>()) {
+                (Some(s), _) => s,
+                (_, Some(s)) => s.as_str(),
+                _ => "?",
+            };
+            Err(format!("Panicked: {msg}"))
+        })
+    }
+
+    #[cfg(not(feature = "std"))]
+    fn check_seed<N, M, S, I>(&self, seed: u64, scenario: &S, invariant: &I) -> Result<(), String>
+    where
+        N: Node<M>,
+        M: Clone,
+        S: Fn() -> Vec<N>,
+        I: Fn(&[N]) -> Result<(), String>,
+    {
+        self.check_run(self.run(seed, scenario), invariant)
+    }
+
+    fn check_run<N, M, I>(&self, sim: Sim<N, M>, invariant: &I) -> Result<(), String>
+    where
+        N: Node<M>,
+        M: Clone,
+        I: Fn(&[N]) -> Result<(), String>,
+    {
+        if sim.n_pending() > 0 {
+            return Err(format!("Did not quiesce within {} steps", self.max_steps));
+        }
+        invariant(sim.nodes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::sim::{Context, Counterexample, Explorer, Network, Node};
+
+    /// Sends its pid to the next process, then relays what it receives, so that the last delivery depends on the
+    /// schedule
+    struct Relay {
+        last: Option<usize>,
+        n_hops: usize,
+    }
+
+    impl Node<(usize, usize)> for Relay {
+        fn on_start(&mut self, ctx: &mut Context<'_, (usize, usize)>) {
+            let to = (ctx.pid() + 1) % ctx.n_procs();
+            ctx.send(to, (ctx.pid(), 0));
+        }
+        fn on_message(
+            &mut self,
+            ctx: &mut Context<'_, (usize, usize)>,
+            _: usize,
+            (v, hops): (usize, usize),
+        ) {
+            self.last = Some(v);
+            if hops < self.n_hops {
+                let to = (ctx.pid() + 1) % ctx.n_procs();
+                ctx.send(to, (v, hops + 1));
+            }
+        }
+    }
+
+    fn relays(n_hops: usize) -> Vec<Relay> {
+        (0..3).map(|_| Relay { last: None, n_hops }).collect()
+    }
+
+    #[test]
+    fn reports_failing_seed() {
+        let explorer = Explorer::new(0..100).with_network(Network {
+            fifo: false,
+            ..Default::default()
+        });
+        // Each process delivers from its predecessor, but which value arrives last depends on the schedule
+        let res = explorer.check(
+            || relays(2),
+            |nodes| match nodes[0].last {
+                Some(2) => Ok(()),
+                last => Err(format!("Expect 2, but got {last:?}")),
+            },
+        );
+        let Err(Counterexample { seed, .. }) = res else {
+            panic!("Expect a counterexample");
+        };
+        let sim = explorer.run(seed, || relays(2));
+        assert_ne!(sim.nodes()[0].last, Some(2));
+        assert!(explorer
+            .check(
+                || relays(2),
+                |nodes| match nodes.iter().all(|n| n.last.is_some()) {
+                    true => Ok(()),
+                    false => Err("Expect every process to deliver".into()),
+                }
+            )
+            .is_ok());
+    }
+
+    #[test]
+    fn reports_livelocks() {
+        let explorer = Explorer::new(5..10).with_max_steps(10);
+        let e = explorer
+            .check(|| relays(usize::MAX), |_| Ok(()))
+            .unwrap_err();
+        assert_eq!(
+            (e.seed, e.reason.as_str()),
+            (5, "Did not quiesce within 10 steps")
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn reports_panics() {
+        let explorer = Explorer::new(5..10).with_max_steps(10);
+        let e = explorer
+            .check(|| relays(0), |_| -> Result<(), String> { panic!("oops") })
+            .unwrap_err();
+        assert_eq!(
+            e.to_string(),
+            "Invariant violated under seed 5: Panicked: oops"
+        );
+    }
+}
@@ -0,0 +1,552 @@
+mod explore;
+mod model;
+mod network;
+mod recovery;
+mod rng;
+mod supervisor;
+
+pub use explore::{Counterexample, Explorer};
+pub use model::{Choice, Coverage, ModelChecker, Violation};
+pub use network::{Delay, Network, Partition};
+pub use recovery::{Envelope, LocalCheckpoint, Recoverable, CHECKPOINT_TIMER};
+pub use rng::SimRng;
+pub use supervisor::{Recover, Supervised};
+
+use alloc::collections::BinaryHeap;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::cmp::{Ordering, Reverse};
+
+/// Logical process driven by a `Sim`, which reacts to messages and timers.
+pub trait Node<M> {
+    /// Runs once at virtual time 0, before any message
+    fn on_start(&mut self, _ctx: &mut Context<'_, M>) {}
+    fn on_message(&mut self, ctx: &mut Context<'_, M>, from: usize, msg: M);
+    /// Runs when a timer set with `Context::set_timer` fires
+    fn on_timer(&mut self, _ctx: &mut Context<'_, M>, _timer: u64) {}
+    /// Runs when the process crashes, which keeps all state unless overridden, e.g. by `Supervised`
+    fn on_crash(&mut self) {}
+    /// Runs when the process restarts after a crash, which starts it again unless overridden
+    fn on_restart(&mut self, ctx: &mut Context<'_, M>) {
+        self.on_start(ctx)
+    }
+}
+
+/// What a node can do while handling an event.
+pub struct Context<'a, M> {
+    pid: usize,
+    n_procs: usize,
+    now: u64,
+    rng: &'a mut SimRng,
+    outbox: &'a mut Vec<Action<M>>,
+    persist: bool,
+}
+
+enum Action<M> {
+    Send { to: usize, msg: M },
+    Timer { after: u64, timer: u64 },
+}
+
+impl<M> Context<'_, M> {
+    pub fn pid(&self) -> usize {
+        self.pid
+    }
+    pub fn n_procs(&self) -> usize {
+        self.n_procs
+    }
+    /// Virtual time
+    pub fn now(&self) -> u64 {
+        self.now
+    }
+    /// Seeded randomness, e.g. for timeouts
+    pub fn rng(&mut self) -> &mut SimRng {
+        self.rng
+    }
+    /// Sends over the simulated `Network`, by default a lossless FIFO channel with a random latency
+    pub fn send(&mut self, to: usize, msg: M) {
+        assert!(
+            to < self.n_procs,
+            "Expect 0-based index of process {to} < n_procs={}",
+            self.n_procs
+        );
+        self.outbox.push(Action::Send { to, msg });
+    }
+    /// Fires `on_timer(timer)` after `after` units of virtual time, unless the process crashes first
+    pub fn set_timer(&mut self, after: u64, timer: u64) {
+        self.outbox.push(Action::Timer { after, timer });
+    }
+    /// Persists a checkpoint once the handler returns, e.g. before replying, if the node is `Supervised`
+    pub fn persist(&mut self) {
+        self.persist = true;
+    }
+}
+
+#[derive(Clone)]
+enum Event<M> {
+    Message {
+        from: usize,
+        to: usize,
+        msg: M,
+    },
+    /// Timer of the given incarnation of process `pid`, which is dropped if it crashed since
+    Timer {
+        pid: usize,
+        timer: u64,
+        incarnation: u64,
+    },
+    Crash {
+        pid: usize,
+    },
+    Restart {
+        pid: usize,
+    },
+}
+
+/// Event due at `at`, where `seq` breaks ties in order of scheduling, so that runs are deterministic
+struct Scheduled<M> {
+    at: u64,
+    seq: u64,
+    event: Event<M>,
+}
+
+impl<M> PartialEq for Scheduled<M> {
+    fn eq(&self, other: &Self) -> bool {
+        (self.at, self.seq) == (other.at, other.seq)
+    }
+}
+
+impl<M> Eq for Scheduled<M> {}
+
+impl<M> PartialOrd for Scheduled<M> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<M> Ord for Scheduled<M> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.at, self.seq).cmp(&(other.at, other.seq))
+    }
+}
+
+/// Single-threaded deterministic simulator, which runs logical processes in virtual time, so that a run is
+/// reproducible from its seed alone.
+///
+/// Each message is delivered after a random latency, but by default never before an earlier message on the same
+/// channel, since the crate's protocols assume FIFO channels. `with_network` injects faults instead.
+///
+/// # Examples
+/// ```
+/// use rads::order::vector_clock::VectorClock;
+/// use rads::order::{HasEvents, OrdProcess, Process};
+/// use rads::sim::{Context, Node, Sim};
+///
+/// struct Ping(Process<VectorClock>);
+/// impl Node<VectorClock> for Ping {
+///     fn on_start(&mut self, ctx: &mut Context<'_, VectorClock>) {
+///         if ctx.pid() == 0 {
+///             self.0.send(|e| ctx.send(1, e));
+///         }
+///     }
+///     fn on_message(&mut self, ctx: &mut Context<'_, VectorClock>, from: usize, e: VectorClock) {
+///         self.0.recv_from(|| (from, e));
+///         if self.0.events().len() < 4 {
+///             self.0.send(|e| ctx.send(from, e));
+///         }
+///     }
+/// }
+///
+/// let mut sim = Sim::new(42, (0..2).map(|i| Ping(Process::new(i, 2))).collect());
+/// sim.run();
+/// let [p0, p1] = sim.nodes() else { unreachable!() };
+/// assert!(p0.0.events()[0] < p1.0.events()[0]);
+/// ```
+pub struct Sim<N, M> {
+    nodes: Vec<N>,
+    rng: SimRng,
+    now: u64,
+    seq: u64,
+    started: bool,
+    queue: BinaryHeap<Reverse<Scheduled<M>>>,
+    /// Latest delivery time of each channel, by sender then receiver
+    last_delivery: Vec<Vec<u64>>,
+    network: Network,
+    n_steps: u64,
+    n_dropped: u64,
+    n_duplicated: u64,
+    /// Whether each process is up, and how many times it crashed
+    up: Vec<bool>,
+    incarnations: Vec<u64>,
+}
+
+impl<N: Node<M>, M: Clone> Sim<N, M> {
+    /// Simulates `nodes`, where the i-th node is process i, with latencies of 1 to 10 units of virtual time
+    pub fn new(seed: u64, nodes: Vec<N>) -> Self {
+        let n_procs = nodes.len();
+        Self {
+            nodes,
+            rng: SimRng::new(seed),
+            now: 0,
+            seq: 0,
+            started: false,
+            queue: BinaryHeap::new(),
+            last_delivery: vec![vec![0; n_procs]; n_procs],
+            network: Network::default(),
+            n_steps: 0,
+            n_dropped: 0,
+            n_duplicated: 0,
+            up: vec![true; n_procs],
+            incarnations: vec![0; n_procs],
+        }
+    }
+    /// Delivers messages after `min` to `max` units of virtual time
+    pub fn with_latency(mut self, min: u64, max: u64) -> Self {
+        assert!(min <= max, "Expect min={min} <= max={max} latency");
+        self.network.delay = Delay::Uniform { min, max };
+        self
+    }
+    /// Drops, duplicates, delays and partitions messages as configured
+    pub fn with_network(mut self, network: Network) -> Self {
+        self.network = network;
+        self
+    }
+    /// To change faults mid-run, e.g. to heal the network
+    pub fn network_mut(&mut self) -> &mut Network {
+        &mut self.network
+    }
+    pub fn nodes(&self) -> &[N] {
+        &self.nodes
+    }
+    pub fn nodes_mut(&mut self) -> &mut [N] {
+        &mut self.nodes
+    }
+    pub fn into_nodes(self) -> Vec<N> {
+        self.nodes
+    }
+    /// Virtual time of the latest event
+    pub fn now(&self) -> u64 {
+        self.now
+    }
+    /// Number of messages and timers handled so far
+    pub fn n_steps(&self) -> u64 {
+        self.n_steps
+    }
+    /// Number of messages lost, whether dropped or partitioned
+    pub fn n_dropped(&self) -> u64 {
+        self.n_dropped
+    }
+    pub fn n_duplicated(&self) -> u64 {
+        self.n_duplicated
+    }
+    /// Number of messages, timers, crashes and restarts yet to be handled
+    pub fn n_pending(&self) -> usize {
+        self.queue.len()
+    }
+    pub fn is_up(&self, pid: usize) -> bool {
+        self.up[pid]
+    }
+    /// Crashes process `pid` at virtual time `at`, after which messages to it are lost and its timers are cancelled
+    pub fn crash_at(&mut self, at: u64, pid: usize) {
+        self.check_pid(pid);
+        self.schedule(at, Event::Crash { pid });
+    }
+    /// Restarts process `pid` at virtual time `at`, if it crashed by then
+    pub fn restart_at(&mut self, at: u64, pid: usize) {
+        self.check_pid(pid);
+        self.schedule(at, Event::Restart { pid });
+    }
+
+    /// Handles the next message or timer, returning false once there are none
+    pub fn step(&mut self) -> bool {
+        self.start();
+        let Some(Reverse(Scheduled { at, event, .. })) = self.queue.pop() else {
+            return false;
+        };
+        self.now = at;
+        match event {
+            Event::Message { from, to, .. } if self.network.is_partitioned(at, from, to) => {
+                self.n_dropped += 1;
+            }
+            Event::Message { to, .. } if !self.up[to] => self.n_dropped += 1,
+            Event::Message { from, to, msg } => {
+                self.n_steps += 1;
+                self.handle(to, |node, ctx| node.on_message(ctx, from, msg));
+            }
+            Event::Timer {
+                pid, incarnation, ..
+            } if incarnation != self.incarnations[pid] => {}
+            Event::Timer { pid, timer, .. } => {
+                self.n_steps += 1;
+                self.handle(pid, |node, ctx| node.on_timer(ctx, timer));
+            }
+            Event::Crash { pid } if self.up[pid] => {
+                self.up[pid] = false;
+                self.incarnations[pid] += 1;
+                self.nodes[pid].on_crash();
+            }
+            Event::Restart { pid } if !self.up[pid] => {
+                self.up[pid] = true;
+                self.handle(pid, |node, ctx| node.on_restart(ctx));
+            }
+            Event::Crash { .. } | Event::Restart { .. } => {}
+        }
+        true
+    }
+    /// Runs until there are no more messages or timers, returning the number of steps
+    pub fn run(&mut self) -> u64 {
+        let start = self.n_steps;
+        while self.step() {}
+        self.n_steps - start
+    }
+    /// Runs every event due up to virtual time `t`
+    pub fn run_until(&mut self, t: u64) {
+        self.start();
+        while self.queue.peek().is_some_and(|Reverse(s)| s.at <= t) {
+            self.step();
+        }
+        self.now = self.now.max(t);
+    }
+
+    fn start(&mut self) {
+        if self.started {
+            return;
+        }
+        self.started = true;
+        for pid in 0..self.nodes.len() {
+            self.handle(pid, |node, ctx| node.on_start(ctx));
+        }
+    }
+
+    fn handle<F: FnOnce(&mut N, &mut Context<'_, M>)>(&mut self, pid: usize, f: F) {
+        let mut outbox = Vec::new();
+        let mut ctx = Context {
+            pid,
+            n_procs: self.nodes.len(),
+            now: self.now,
+            rng: &mut self.rng,
+            outbox: &mut outbox,
+            persist: false,
+        };
+        f(&mut self.nodes[pid], &mut ctx);
+        for action in outbox {
+            match action {
+                Action::Send { to, msg } => self.transmit(pid, to, msg),
+                Action::Timer { after, timer } => {
+                    let incarnation = self.incarnations[pid];
+                    let event = Event::Timer {
+                        pid,
+                        timer,
+                        incarnation,
+                    };
+                    self.schedule(self.now + after, event)
+                }
+            }
+        }
+    }
+
+    fn check_pid(&self, pid: usize) {
+        assert!(
+            pid < self.nodes.len(),
+            "Expect 0-based index of process {pid} < n_procs={}",
+            self.nodes.len()
+        );
+    }
+
+    fn transmit(&mut self, from: usize, to: usize, msg: M) {
+        if self.rng.gen_bool(self.network.drop) {
+            self.n_dropped += 1;
+            return;
+        }
+        let copy = match self.rng.gen_bool(self.network.duplicate) {
+            true => {
+                self.n_duplicated += 1;
+                Some(msg.clone())
+            }
+            false => None,
+        };
+        for msg in core::iter::once(msg).chain(copy) {
+            let mut at = self.now + self.network.delay.sample(&mut self.rng);
+            if self.network.fifo {
+                let last = &mut self.last_delivery[from][to];
+                *last = (*last).max(at);
+                at = *last;
+            }
+            self.schedule(at, Event::Message { from, to, msg });
+        }
+    }
+
+    fn schedule(&mut self, at: u64, event: Event<M>) {
+        self.seq += 1;
+        self.queue.push(Reverse(Scheduled {
+            at,
+            seq: self.seq,
+            event,
+        }));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::order::vector_clock::VectorClock;
+    use crate::order::{HasEvents, OrdProcess, Process};
+    use crate::sim::{Context, Delay, Network, Node, Partition, Sim};
+
+    /// Every process sends `n` numbered messages to every other process, logging deliveries
+    struct Chatty {
+        p: Process<VectorClock>,
+        n: usize,
+        log: Vec<(u64, usize, usize)>,
+    }
+
+    impl Node<(usize, VectorClock)> for Chatty {
+        fn on_start(&mut self, ctx: &mut Context<'_, (usize, VectorClock)>) {
+            let (i, n_procs) = (ctx.pid(), ctx.n_procs());
+            for k in 0..self.n {
+                for to in (0..n_procs).filter(|&j| j != i) {
+                    self.p.send(|e| ctx.send(to, (k, e)));
+                }
+            }
+            ctx.set_timer(5, 0);
+        }
+        fn on_message(
+            &mut self,
+            ctx: &mut Context<'_, (usize, VectorClock)>,
+            from: usize,
+            (k, e): (usize, VectorClock),
+        ) {
+            self.p.recv_from(|| (from, e));
+            self.log.push((ctx.now(), from, k));
+        }
+        fn on_timer(&mut self, ctx: &mut Context<'_, (usize, VectorClock)>, timer: u64) {
+            assert_eq!(ctx.now(), 5);
+            self.log.push((ctx.now(), usize::MAX, timer as usize));
+        }
+    }
+
+    fn chatty_sim(seed: u64, n: usize) -> Sim<Chatty, (usize, VectorClock)> {
+        let nodes = (0..3)
+            .map(|i| Chatty {
+                p: Process::new(i, 3),
+                n,
+                log: Vec::new(),
+            })
+            .collect();
+        Sim::new(seed, nodes)
+    }
+
+    fn chatty(seed: u64) -> Vec<Vec<(u64, usize, usize)>> {
+        let mut sim = chatty_sim(seed, 5);
+        assert_eq!(sim.run(), 3 * 2 * 5 + 3);
+        assert_eq!(sim.n_pending(), 0);
+        sim.into_nodes().into_iter().map(|n| n.log).collect()
+    }
+
+    #[test]
+    fn deterministic() {
+        assert_eq!(chatty(1), chatty(1));
+        assert_ne!(chatty(1), chatty(2));
+    }
+
+    #[test]
+    fn fifo_channels() {
+        for seed in 0..20 {
+            for log in chatty(seed) {
+                assert!(log.windows(2).all(|w| w[0].0 <= w[1].0)); // virtual time moves forward
+                for from in 0..3 {
+                    let ks: Vec<_> = log
+                        .iter()
+                        .filter(|(_, f, _)| *f == from)
+                        .map(|(_, _, k)| *k)
+                        .collect();
+                    assert!(ks.windows(2).all(|w| w[0] < w[1]));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn run_until() {
+        let nodes = (0..2)
+            .map(|i| Chatty {
+                p: Process::new(i, 2),
+                n: 1,
+                log: Vec::new(),
+            })
+            .collect();
+        let mut sim = Sim::new(0, nodes).with_latency(10, 10);
+        sim.run_until(9);
+        assert_eq!(sim.now(), 9);
+        assert!(sim.nodes().iter().all(|n| n.log.len() == 1)); // only timers
+        sim.run_until(10);
+        assert!(sim.nodes().iter().all(|n| n.log.len() == 2));
+        assert!(sim.nodes()[0].p.events()[0] < sim.nodes()[1].p.events()[1]);
+        assert!(!sim.step());
+    }
+
+    #[test]
+    fn drops_and_duplicates() {
+        let network = Network {
+            drop: 0.2,
+            duplicate: 0.2,
+            ..Default::default()
+        };
+        let mut sim = chatty_sim(3, 50).with_network(network);
+        let n_steps = sim.run();
+        let (n_dropped, n_duplicated) = (sim.n_dropped(), sim.n_duplicated());
+        assert!(n_dropped > 0 && n_duplicated > 0);
+        assert_eq!(n_steps + n_dropped, 3 * 2 * 50 + 3 + n_duplicated);
+    }
+
+    #[test]
+    fn reorders_unless_fifo() {
+        let network = Network {
+            delay: Delay::Bimodal {
+                fast: (1, 1),
+                slow: (20, 30),
+                p_slow: 0.3,
+            },
+            fifo: false,
+            ..Default::default()
+        };
+        let mut sim = chatty_sim(0, 20).with_network(network);
+        sim.run();
+        let reordered = sim.nodes().iter().any(|n| {
+            let ks: Vec<_> = n
+                .log
+                .iter()
+                .filter(|(_, f, _)| *f < 3)
+                .map(|(_, _, k)| *k)
+                .collect();
+            ks.windows(2).any(|w| w[0] > w[1])
+        });
+        assert!(reordered);
+    }
+
+    #[test]
+    fn partitions_then_heals() {
+        let network = Network {
+            delay: Delay::Fixed(10),
+            partitions: vec![Partition {
+                start: 0,
+                end: 15,
+                side: vec![0],
+            }],
+            ..Default::default()
+        };
+        let mut sim = chatty_sim(0, 1).with_network(network);
+        sim.run();
+        assert_eq!(sim.n_dropped(), 4); // to and from p0
+        assert_eq!(sim.nodes()[0].log.len(), 1); // only its timer
+        assert_eq!(sim.nodes()[1].log.len(), 2);
+
+        // Healed by the time of delivery
+        let mut sim = chatty_sim(0, 1).with_latency(20, 20);
+        sim.network_mut().partitions = vec![Partition {
+            start: 0,
+            end: 15,
+            side: vec![0],
+        }];
+        sim.run();
+        assert_eq!(sim.n_dropped(), 0);
+    }
+}
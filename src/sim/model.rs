@@ -0,0 +1,310 @@
+use crate::sim::{Action, Context, Event, Node, SimRng};
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+
+/// Event that the `ModelChecker` chose to handle next.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Choice {
+    Deliver { from: usize, to: usize },
+    Timer { pid: usize, timer: u64 },
+}
+
+/// Interleaving that violated a safety predicate, from the start of the run.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Violation {
+    pub trace: Vec<Choice>,
+    pub reason: String,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Safety violated after {:?}: {}", self.trace, self.reason)
+    }
+}
+
+/// Size of an exhaustive search.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub struct Coverage {
+    /// States reached, counting a state once per path to it
+    pub n_states: usize,
+    /// Interleavings run until nothing was left to handle
+    pub n_complete: usize,
+    /// Interleavings cut short by the max depth
+    pub n_truncated: usize,
+}
+
+/// Bounded model checker, which enumerates every order of handling messages and timers, checking a safety predicate
+/// in every reachable state.
+///
+/// The number of interleavings grows factorially, so keep to small configurations, e.g. 3 processes and 6 messages.
+/// Timers may fire at any point, since virtual time is not modelled. Every path starts from the same seed of
+/// `Context::rng`.
+///
+/// # Examples
+/// ```
+/// use rads::sim::{Context, ModelChecker, Node};
+///
+/// #[derive(Clone)]
+/// struct Log(Vec<usize>);
+/// impl Node<usize> for Log {
+///     fn on_start(&mut self, ctx: &mut Context<'_, usize>) {
+///         if ctx.pid() > 0 {
+///             ctx.send(0, ctx.pid());
+///         }
+///     }
+///     fn on_message(&mut self, _: &mut Context<'_, usize>, _: usize, msg: usize) {
+///         self.0.push(msg);
+///     }
+/// }
+///
+/// let res = ModelChecker::new().check(
+///     || vec![Log(Vec::new()); 3],
+///     |nodes| match nodes[0].0[..] {
+///         [2, 1] => Err("p1's message arrived last".into()),
+///         _ => Ok(()),
+///     },
+/// );
+/// assert_eq!(res.unwrap_err().trace.len(), 2);
+/// ```
+pub struct ModelChecker {
+    fifo: bool,
+    max_depth: usize,
+}
+
+impl Default for ModelChecker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Clone)]
+struct State<N, M> {
+    nodes: Vec<N>,
+    /// Messages and timers not yet handled, in order of sending
+    pending: Vec<Event<M>>,
+    rng: SimRng,
+}
+
+impl ModelChecker {
+    /// Checks FIFO channels up to 32 events deep
+    pub fn new() -> Self {
+        Self {
+            fifo: true,
+            max_depth: 32,
+        }
+    }
+    /// Also delivers messages out of order on each channel
+    pub fn with_reordering(mut self) -> Self {
+        self.fifo = false;
+        self
+    }
+    /// Stops exploring a path after `max_depth` events, in case the protocol never quiesces
+    pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// Returns the first interleaving to reach a state that violates the predicate
+    pub fn check<N, M, S, P>(&self, scenario: S, predicate: P) -> Result<Coverage, Violation>
+    where
+        N: Node<M> + Clone,
+        M: Clone,
+        S: FnOnce() -> Vec<N>,
+        P: Fn(&[N]) -> Result<(), String>,
+    {
+        let mut state = State {
+            nodes: scenario(),
+            pending: Vec::new(),
+            rng: SimRng::new(0),
+        };
+        for pid in 0..state.nodes.len() {
+            state.handle(pid, 0, |node, ctx| node.on_start(ctx));
+        }
+        let mut coverage = Coverage {
+            n_states: 1,
+            ..Default::default()
+        };
+        self.dfs(state, &mut Vec::new(), &predicate, &mut coverage)?;
+        Ok(coverage)
+    }
+
+    fn dfs<N, M, P>(
+        &self,
+        state: State<N, M>,
+        trace: &mut Vec<Choice>,
+        predicate: &P,
+        coverage: &mut Coverage,
+    ) -> Result<(), Violation>
+    where
+        N: Node<M> + Clone,
+        M: Clone,
+        P: Fn(&[N]) -> Result<(), String>,
+    {
+        predicate(&state.nodes).map_err(|reason| Violation {
+            trace: trace.clone(),
+            reason,
+        })?;
+        let enabled: Vec<_> = (0..state.pending.len())
+            .filter(|&idx| self.is_enabled(&state.pending, idx))
+            .collect();
+        if enabled.is_empty() {
+            coverage.n_complete += 1;
+            return Ok(());
+        }
+        if trace.len() >= self.max_depth {
+            coverage.n_truncated += 1;
+            return Ok(());
+        }
+        for idx in enabled {
+            let mut next = state.clone();
+            let depth = trace.len() as u64 + 1;
+            match next.pending.remove(idx) {
+                Event::Message { from, to, msg } => {
+                    trace.push(Choice::Deliver { from, to });
+                    next.handle(to, depth, |node, ctx| node.on_message(ctx, from, msg));
+                }
+                Event::Timer { pid, timer, .. } => {
+                    trace.push(Choice::Timer { pid, timer });
+                    next.handle(pid, depth, |node, ctx| node.on_timer(ctx, timer));
+                }
+                Event::Crash { .. } | Event::Restart { .. } => unreachable!("Expect no crashes"),
+            }
+            coverage.n_states += 1;
+            self.dfs(next, trace, predicate, coverage)?;
+            trace.pop();
+        }
+        Ok(())
+    }
+
+    /// Timers are always enabled, and so are messages unless an earlier one on the same FIFO channel is pending
+    fn is_enabled<M>(&self, pending: &[Event<M>], idx: usize) -> bool {
+        match pending[idx] {
+            Event::Message { from, to, .. } if self.fifo => !pending[..idx].iter().any(
+                |e| matches!(e, Event::Message { from: f, to: t, .. } if (*f, *t) == (from, to)),
+            ),
+            _ => true,
+        }
+    }
+}
+
+impl<N: Node<M>, M> State<N, M> {
+    fn handle<F: FnOnce(&mut N, &mut Context<'_, M>)>(&mut self, pid: usize, now: u64, f: F) {
+        let mut outbox = Vec::new();
+        let mut ctx = Context {
+            pid,
+            n_procs: self.nodes.len(),
+            now,
+            rng: &mut self.rng,
+            outbox: &mut outbox,
+            persist: false,
+        };
+        f(&mut self.nodes[pid], &mut ctx);
+        self.pending
+            .extend(outbox.into_iter().map(|action| match action {
+                Action::Send { to, msg } => Event::Message { from: pid, to, msg },
+                Action::Timer { timer, .. } => Event::Timer {
+                    pid,
+                    timer,
+                    incarnation: 0,
+                },
+            }));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::sim::{Choice, Context, Coverage, ModelChecker, Node};
+
+    /// p0 sends "a" to p2, then "x" to p1, which forwards "b" to p2 on receipt
+    #[derive(Clone, Default)]
+    struct Forward(Vec<&'static str>);
+
+    impl Node<&'static str> for Forward {
+        fn on_start(&mut self, ctx: &mut Context<'_, &'static str>) {
+            if ctx.pid() == 0 {
+                ctx.send(2, "a");
+                ctx.send(1, "x");
+            }
+        }
+        fn on_message(&mut self, ctx: &mut Context<'_, &'static str>, _: usize, msg: &'static str) {
+            self.0.push(msg);
+            if msg == "x" {
+                ctx.send(2, "b");
+            }
+        }
+    }
+
+    fn forwards() -> Vec<Forward> {
+        vec![Forward::default(); 3]
+    }
+
+    #[test]
+    fn finds_causal_violation() {
+        let v = ModelChecker::new()
+            .check(forwards, |nodes| match nodes[2].0[..] {
+                ["b", ..] => Err("Expect a before b".into()),
+                _ => Ok(()),
+            })
+            .unwrap_err();
+        assert_eq!(
+            v.trace,
+            vec![
+                Choice::Deliver { from: 0, to: 1 },
+                Choice::Deliver { from: 1, to: 2 }
+            ]
+        );
+    }
+
+    #[test]
+    fn covers_every_interleaving() {
+        // a, x, b: a anywhere before, between or after x then b
+        let coverage = ModelChecker::new().check(forwards, |_| Ok(())).unwrap();
+        assert_eq!(
+            coverage,
+            Coverage {
+                n_states: 1 + 2 + 3 + 3,
+                n_complete: 3,
+                n_truncated: 0,
+            }
+        );
+    }
+
+    #[test]
+    fn reorders_and_truncates() {
+        #[derive(Clone)]
+        struct Pair(Vec<u8>);
+        impl Node<u8> for Pair {
+            fn on_start(&mut self, ctx: &mut Context<'_, u8>) {
+                if ctx.pid() == 0 {
+                    ctx.send(1, 1);
+                    ctx.send(1, 2);
+                }
+            }
+            fn on_message(&mut self, ctx: &mut Context<'_, u8>, _: usize, msg: u8) {
+                self.0.push(msg);
+                ctx.set_timer(1, msg.into());
+            }
+            fn on_timer(&mut self, ctx: &mut Context<'_, u8>, _: u64) {
+                ctx.set_timer(1, 0); // forever
+            }
+        }
+        let in_order = |nodes: &[Pair]| match nodes[1].0[..] {
+            [2, ..] => Err("Reordered".into()),
+            _ => Ok(()),
+        };
+        let scenario = || vec![Pair(Vec::new()), Pair(Vec::new())];
+        let coverage = ModelChecker::new()
+            .with_max_depth(4)
+            .check(scenario, in_order)
+            .unwrap();
+        assert_eq!((coverage.n_complete, coverage.n_truncated > 0), (0, true));
+        let v = ModelChecker::new()
+            .with_reordering()
+            .with_max_depth(4)
+            .check(scenario, in_order)
+            .unwrap_err();
+        assert_eq!(v.trace, vec![Choice::Deliver { from: 0, to: 1 }]);
+        assert_eq!(v.reason, "Reordered");
+    }
+}
@@ -0,0 +1,123 @@
+use crate::sim::SimRng;
+use alloc::vec::Vec;
+
+/// Distribution of message latencies, in units of virtual time.
+#[derive(Clone, PartialEq, Debug)]
+pub enum Delay {
+    Fixed(u64),
+    /// Uniform in `[min, max]`
+    Uniform {
+        min: u64,
+        max: u64,
+    },
+    /// Usually `fast`, but `slow` with probability `p_slow`, e.g. for congestion or GC pauses
+    Bimodal {
+        fast: (u64, u64),
+        slow: (u64, u64),
+        p_slow: f64,
+    },
+}
+
+impl Delay {
+    pub fn sample(&self, rng: &mut SimRng) -> u64 {
+        match *self {
+            Delay::Fixed(d) => d,
+            Delay::Uniform { min, max } => rng.gen_range(min, max),
+            Delay::Bimodal { fast, slow, p_slow } => {
+                let (min, max) = if rng.gen_bool(p_slow) { slow } else { fast };
+                rng.gen_range(min, max)
+            }
+        }
+    }
+}
+
+/// Isolates the processes in `side` from the rest during virtual time `[start, end)`.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Partition {
+    pub start: u64,
+    pub end: u64,
+    pub side: Vec<usize>,
+}
+
+impl Partition {
+    /// Whether a message from `from` to `to` is lost at virtual time `t`
+    pub fn cuts(&self, t: u64, from: usize, to: usize) -> bool {
+        (self.start..self.end).contains(&t) && self.side.contains(&from) != self.side.contains(&to)
+    }
+}
+
+/// Faults of a simulated network. The default is lossless FIFO channels with latencies of 1 to 10.
+///
+/// # Examples
+/// ```
+/// use rads::sim::{Delay, Network, Partition};
+///
+/// let network = Network {
+///     drop: 0.1,
+///     delay: Delay::Bimodal { fast: (1, 5), slow: (50, 100), p_slow: 0.01 },
+///     partitions: vec![Partition { start: 100, end: 200, side: vec![0] }],
+///     ..Default::default()
+/// };
+/// assert!(network.partitions[0].cuts(150, 1, 0));
+/// ```
+#[derive(Clone, PartialEq, Debug)]
+pub struct Network {
+    /// Probability of losing each message
+    pub drop: f64,
+    /// Probability of delivering each message twice
+    pub duplicate: f64,
+    pub delay: Delay,
+    /// Delivers in order of sending on each channel, whatever the delays
+    pub fifo: bool,
+    /// Messages delivered while partitioned are lost
+    pub partitions: Vec<Partition>,
+}
+
+impl Default for Network {
+    fn default() -> Self {
+        Self {
+            drop: 0.0,
+            duplicate: 0.0,
+            delay: Delay::Uniform { min: 1, max: 10 },
+            fifo: true,
+            partitions: Vec::new(),
+        }
+    }
+}
+
+impl Network {
+    pub fn is_partitioned(&self, t: u64, from: usize, to: usize) -> bool {
+        self.partitions.iter().any(|p| p.cuts(t, from, to))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::sim::{Delay, Partition, SimRng};
+
+    #[test]
+    fn delays() {
+        let mut rng = SimRng::new(0);
+        assert_eq!(Delay::Fixed(3).sample(&mut rng), 3);
+        let bimodal = Delay::Bimodal {
+            fast: (1, 2),
+            slow: (100, 100),
+            p_slow: 0.5,
+        };
+        let ds: Vec<_> = (0..100).map(|_| bimodal.sample(&mut rng)).collect();
+        assert!(ds.iter().all(|d| [1, 2, 100].contains(d)));
+        assert!(ds.contains(&100) && ds.contains(&1));
+    }
+
+    #[test]
+    fn partition_cuts() {
+        let p = Partition {
+            start: 10,
+            end: 20,
+            side: vec![0, 1],
+        };
+        assert!(p.cuts(10, 0, 2) && p.cuts(19, 2, 1));
+        assert!(!p.cuts(15, 0, 1) && !p.cuts(15, 2, 2));
+        assert!(!p.cuts(9, 0, 2) && !p.cuts(20, 0, 2));
+    }
+}
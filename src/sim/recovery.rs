@@ -0,0 +1,478 @@
+use crate::sim::{Action, Context, Node, Recover};
+use alloc::collections::BTreeMap;
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// Timer of the coordinator's periodic checkpoints, which a `Recoverable` node must not use itself
+pub const CHECKPOINT_TIMER: u64 = u64::MAX;
+
+/// Messages between `Recoverable` nodes, tagged with the epoch, i.e. the number of rollbacks, they were sent in.
+#[derive(Clone, Debug)]
+pub enum Envelope<M> {
+    App {
+        epoch: u64,
+        msg: M,
+    },
+    /// Chandy-Lamport marker of checkpoint `seq`
+    Marker {
+        epoch: u64,
+        seq: u64,
+    },
+    /// Tells the coordinator that the sender recorded checkpoint `seq`
+    Done {
+        epoch: u64,
+        seq: u64,
+    },
+    /// Tells every process that all recorded checkpoint `seq`, so that it is consistent
+    Commit {
+        epoch: u64,
+        seq: u64,
+    },
+    /// Rolls every process back to checkpoint `to`, or to the start if None
+    Rollback {
+        epoch: u64,
+        to: Option<u64>,
+    },
+}
+
+impl<M> Envelope<M> {
+    pub fn epoch(&self) -> u64 {
+        match self {
+            Self::App { epoch, .. }
+            | Self::Marker { epoch, .. }
+            | Self::Done { epoch, .. }
+            | Self::Commit { epoch, .. }
+            | Self::Rollback { epoch, .. } => *epoch,
+        }
+    }
+}
+
+/// Checkpoint of a process's state, along with the messages in flight to it.
+#[derive(Clone, Debug)]
+pub struct LocalCheckpoint<C, M> {
+    pub seq: u64,
+    pub state: C,
+    /// Messages sent before the sender's checkpoint but received after this one, by sender, which are replayed on
+    /// rollback
+    pub channels: Vec<Vec<M>>,
+}
+
+/// Checkpoint being recorded, along with whether the marker arrived on each incoming channel
+struct Recording<C, M> {
+    checkpoint: LocalCheckpoint<C, M>,
+    recorded: Vec<bool>,
+}
+
+/// Wraps a node with coordinated checkpoints and rollback-recovery, so that a crash in a `Sim` rolls every process
+/// back to the latest consistent checkpoint.
+///
+/// Every `interval` units of virtual time, process 0 takes a Chandy-Lamport snapshot of the nodes' `Recover` state,
+/// logging the messages in flight. Once every process recorded it, process 0 commits it. Checkpoints survive crashes,
+/// and a restarted process rolls every process back to the latest checkpoint it knows was committed, replaying the
+/// logged messages. Messages of earlier epochs are dropped, since the rollback undid them.
+///
+/// Processes crash one at a time, i.e. a crashed process restarts before another crashes, so that none misses a
+/// rollback. Channels must be lossless and FIFO while processes are up.
+///
+/// # Examples
+/// ```
+/// use rads::sim::{Context, Node, Recover, Recoverable, Sim};
+///
+/// /// Passes a token back and forth, counting how many times it held it
+/// struct Token(u64);
+/// impl Node<()> for Token {
+///     fn on_start(&mut self, ctx: &mut Context<'_, ()>) {
+///         if ctx.pid() == 0 {
+///             ctx.send(1, ());
+///         }
+///     }
+///     fn on_message(&mut self, ctx: &mut Context<'_, ()>, from: usize, _: ()) {
+///         self.0 += 1;
+///         if ctx.now() < 100 {
+///             ctx.send(from, ());
+///         }
+///     }
+/// }
+/// impl Recover for Token {
+///     type Checkpoint = u64;
+///     fn checkpoint(&self) -> u64 {
+///         self.0
+///     }
+///     fn recover(_: usize, _: usize, checkpoint: Option<u64>) -> Self {
+///         Token(checkpoint.unwrap_or_default())
+///     }
+/// }
+///
+/// let nodes = (0..2).map(|_| Recoverable::new(Token(0), 10)).collect();
+/// let mut sim = Sim::new(0, nodes).with_latency(1, 1);
+/// sim.crash_at(50, 1);
+/// sim.restart_at(60, 1);
+/// sim.run_until(200);
+/// let [p0, p1] = sim.nodes() else { unreachable!() };
+/// assert_eq!((p0.epoch(), p1.epoch()), (1, 1));
+/// // The token was lost while p1 was down, but the rollback restored it, along with the counts of both
+/// assert!(p0.node().0.abs_diff(p1.node().0) <= 1);
+/// assert!(p0.node().0 + p1.node().0 > 60);
+/// ```
+pub struct Recoverable<N: Recover, M> {
+    node: N,
+    interval: u64,
+    // Durable
+    checkpoints: BTreeMap<u64, LocalCheckpoint<N::Checkpoint, M>>,
+    committed: Option<u64>,
+    epoch: u64,
+    next_seq: u64,
+    n_rollbacks: usize,
+    // Volatile
+    recording: Option<Recording<N::Checkpoint, M>>,
+    /// Checkpoint that the coordinator awaits, and how many processes recorded it
+    awaiting: Option<(u64, usize)>,
+    /// Messages of a later epoch, which wait for its rollback
+    early: Vec<(usize, Envelope<M>)>,
+}
+
+impl<N: Recover, M: Clone> Recoverable<N, M> {
+    /// Checkpoints every `interval` units of virtual time, or never if 0
+    pub fn new(node: N, interval: u64) -> Self {
+        Self {
+            node,
+            interval,
+            checkpoints: BTreeMap::new(),
+            committed: None,
+            epoch: 0,
+            next_seq: 0,
+            n_rollbacks: 0,
+            recording: None,
+            awaiting: None,
+            early: Vec::new(),
+        }
+    }
+    pub fn node(&self) -> &N {
+        &self.node
+    }
+    pub fn into_inner(self) -> N {
+        self.node
+    }
+    /// Latest checkpoint that every process recorded
+    pub fn committed(&self) -> Option<u64> {
+        self.committed
+    }
+    /// Checkpoint `seq` of this process, if it recorded it and has yet to discard it
+    pub fn checkpoint(&self, seq: u64) -> Option<&LocalCheckpoint<N::Checkpoint, M>> {
+        self.checkpoints.get(&seq)
+    }
+    /// Number of rollbacks so far, which tags every message
+    pub fn epoch(&self) -> u64 {
+        self.epoch
+    }
+    pub fn n_rollbacks(&self) -> usize {
+        self.n_rollbacks
+    }
+}
+
+impl<N: Node<M> + Recover, M: Clone> Recoverable<N, M> {
+    /// Runs a handler of the node, tagging the messages it sends with the epoch
+    fn app<F: FnOnce(&mut N, &mut Context<'_, M>)>(
+        &mut self,
+        ctx: &mut Context<'_, Envelope<M>>,
+        f: F,
+    ) {
+        let mut outbox = Vec::new();
+        let mut app_ctx = Context {
+            pid: ctx.pid,
+            n_procs: ctx.n_procs,
+            now: ctx.now,
+            rng: &mut *ctx.rng,
+            outbox: &mut outbox,
+            persist: false,
+        };
+        f(&mut self.node, &mut app_ctx);
+        for action in outbox {
+            match action {
+                Action::Send { to, msg } => ctx.send(
+                    to,
+                    Envelope::App {
+                        epoch: self.epoch,
+                        msg,
+                    },
+                ),
+                Action::Timer { after, timer } => ctx.set_timer(after, timer),
+            }
+        }
+    }
+
+    /// Starts the next checkpoint, unless the previous one is yet to commit
+    fn initiate(&mut self, ctx: &mut Context<'_, Envelope<M>>) {
+        if self.awaiting.is_some() || self.recording.is_some() {
+            return;
+        }
+        let seq = self.next_seq;
+        self.next_seq += 1;
+        self.awaiting = Some((seq, 0));
+        self.record(ctx, seq, None);
+    }
+
+    /// Records the node's state, which stops recording the channel from `from` if its marker arrived, then sends the
+    /// marker to all other processes
+    fn record(&mut self, ctx: &mut Context<'_, Envelope<M>>, seq: u64, from: Option<usize>) {
+        let (i, n) = (ctx.pid(), ctx.n_procs());
+        self.recording = Some(Recording {
+            checkpoint: LocalCheckpoint {
+                seq,
+                state: self.node.checkpoint(),
+                channels: vec![Vec::new(); n],
+            },
+            recorded: (0..n).map(|j| j == i || Some(j) == from).collect(),
+        });
+        let epoch = self.epoch;
+        broadcast(ctx, Envelope::Marker { epoch, seq });
+        self.complete_if_recorded(ctx);
+    }
+
+    fn on_marker(&mut self, ctx: &mut Context<'_, Envelope<M>>, from: usize, seq: u64) {
+        match self.recording.as_mut() {
+            Some(r) if r.checkpoint.seq == seq => r.recorded[from] = true,
+            Some(_) => {}
+            None if self.checkpoints.contains_key(&seq) => {}
+            None => return self.record(ctx, seq, Some(from)),
+        }
+        self.complete_if_recorded(ctx);
+    }
+
+    /// Persists the checkpoint once the marker arrived on every channel, and tells the coordinator
+    fn complete_if_recorded(&mut self, ctx: &mut Context<'_, Envelope<M>>) {
+        if !self
+            .recording
+            .as_ref()
+            .is_some_and(|r| r.recorded.iter().all(|&r| r))
+        {
+            return;
+        }
+        let Some(Recording { checkpoint, .. }) = self.recording.take() else {
+            return;
+        };
+        let seq = checkpoint.seq;
+        self.checkpoints.insert(seq, checkpoint);
+        match ctx.pid() {
+            0 => self.on_done(ctx, seq),
+            _ => ctx.send(
+                0,
+                Envelope::Done {
+                    epoch: self.epoch,
+                    seq,
+                },
+            ),
+        }
+    }
+
+    fn on_done(&mut self, ctx: &mut Context<'_, Envelope<M>>, seq: u64) {
+        let Some((awaited, n_done)) = self.awaiting.as_mut() else {
+            return;
+        };
+        if *awaited != seq {
+            return;
+        }
+        *n_done += 1;
+        if *n_done == ctx.n_procs() {
+            self.awaiting = None;
+            let epoch = self.epoch;
+            broadcast(ctx, Envelope::Commit { epoch, seq });
+            self.commit(seq);
+        }
+    }
+
+    /// Discards checkpoints before the previous commit, which a restarted process may still roll back to
+    fn commit(&mut self, seq: u64) {
+        self.committed = self.committed.max(Some(seq));
+        self.checkpoints.retain(|&k, _| k + 1 >= seq);
+    }
+
+    /// Restores checkpoint `to` and replays the messages it logged, then handles messages that waited for `epoch`
+    fn rollback(&mut self, ctx: &mut Context<'_, Envelope<M>>, epoch: u64, to: Option<u64>) {
+        (self.epoch, self.committed) = (epoch, to);
+        self.n_rollbacks += 1;
+        (self.recording, self.awaiting) = (None, None);
+        self.checkpoints
+            .retain(|&k, _| to.is_some_and(|to| k <= to));
+        let checkpoint = to.map(|k| {
+            self.checkpoints.get(&k).cloned().unwrap_or_else(|| {
+                panic!(
+                    "Expect process {} to keep committed checkpoint {k}",
+                    ctx.pid()
+                )
+            })
+        });
+        let state = checkpoint.as_ref().map(|c| c.state.clone());
+        self.node = N::recover(ctx.pid(), ctx.n_procs(), state);
+        for (from, msgs) in checkpoint.into_iter().flat_map(|c| c.channels).enumerate() {
+            for msg in msgs {
+                self.app(ctx, |node, ctx| node.on_message(ctx, from, msg));
+            }
+        }
+        for (from, env) in core::mem::take(&mut self.early) {
+            self.on_message(ctx, from, env);
+        }
+    }
+}
+
+fn broadcast<M: Clone>(ctx: &mut Context<'_, Envelope<M>>, env: Envelope<M>) {
+    let (i, n) = (ctx.pid(), ctx.n_procs());
+    for to in (0..n).filter(|&j| j != i) {
+        ctx.send(to, env.clone());
+    }
+}
+
+impl<N: Node<M> + Recover, M: Clone> Node<Envelope<M>> for Recoverable<N, M> {
+    fn on_start(&mut self, ctx: &mut Context<'_, Envelope<M>>) {
+        if ctx.pid() == 0 && self.interval > 0 {
+            ctx.set_timer(self.interval, CHECKPOINT_TIMER);
+        }
+        self.app(ctx, |node, ctx| node.on_start(ctx));
+    }
+    fn on_message(&mut self, ctx: &mut Context<'_, Envelope<M>>, from: usize, env: Envelope<M>) {
+        match env.epoch().cmp(&self.epoch) {
+            core::cmp::Ordering::Less => return,
+            core::cmp::Ordering::Greater if !matches!(env, Envelope::Rollback { .. }) => {
+                return self.early.push((from, env));
+            }
+            _ => {}
+        }
+        match env {
+            Envelope::App { msg, .. } => {
+                if let Some(r) = self.recording.as_mut().filter(|r| !r.recorded[from]) {
+                    r.checkpoint.channels[from].push(msg.clone());
+                }
+                self.app(ctx, |node, ctx| node.on_message(ctx, from, msg));
+            }
+            Envelope::Marker { seq, .. } => self.on_marker(ctx, from, seq),
+            Envelope::Done { seq, .. } => self.on_done(ctx, seq),
+            Envelope::Commit { seq, .. } => self.commit(seq),
+            Envelope::Rollback { epoch, to } if epoch > self.epoch => self.rollback(ctx, epoch, to),
+            Envelope::Rollback { .. } => {}
+        }
+    }
+    fn on_timer(&mut self, ctx: &mut Context<'_, Envelope<M>>, timer: u64) {
+        if timer != CHECKPOINT_TIMER {
+            return self.app(ctx, |node, ctx| node.on_timer(ctx, timer));
+        }
+        self.initiate(ctx);
+        ctx.set_timer(self.interval, CHECKPOINT_TIMER);
+    }
+    /// Loses the volatile state, keeping checkpoints
+    fn on_crash(&mut self) {
+        self.node.on_crash();
+        (self.recording, self.awaiting) = (None, None);
+        self.early.clear();
+    }
+    /// Rolls every process back to the latest committed checkpoint
+    fn on_restart(&mut self, ctx: &mut Context<'_, Envelope<M>>) {
+        let (epoch, to) = (self.epoch + 1, self.committed);
+        broadcast(ctx, Envelope::Rollback { epoch, to });
+        self.rollback(ctx, epoch, to);
+        if ctx.pid() == 0 && self.interval > 0 {
+            ctx.set_timer(self.interval, CHECKPOINT_TIMER);
+        }
+        self.app(ctx, |node, ctx| node.on_restart(ctx));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::sim::{Context, Node, Recover, Recoverable, Sim};
+
+    const N_PROCS: usize = 3;
+    const BALANCE: u64 = 100;
+
+    /// Transfers money to random processes until time 300, which must neither be created nor destroyed
+    struct Bank(u64);
+
+    impl Node<u64> for Bank {
+        fn on_start(&mut self, ctx: &mut Context<'_, u64>) {
+            let after = ctx.rng().gen_range(1, 5);
+            ctx.set_timer(after, 0);
+        }
+        fn on_message(&mut self, _: &mut Context<'_, u64>, _: usize, amount: u64) {
+            self.0 += amount;
+        }
+        fn on_timer(&mut self, ctx: &mut Context<'_, u64>, _: u64) {
+            let to = (ctx.pid() + ctx.rng().gen_range(1, N_PROCS as u64 - 1) as usize) % N_PROCS;
+            let amount = ctx.rng().gen_range(0, self.0 / 2);
+            self.0 -= amount;
+            ctx.send(to, amount);
+            if ctx.now() < 300 {
+                let after = ctx.rng().gen_range(1, 5);
+                ctx.set_timer(after, 0);
+            }
+        }
+    }
+
+    impl Recover for Bank {
+        type Checkpoint = u64;
+        fn checkpoint(&self) -> u64 {
+            self.0
+        }
+        fn recover(_: usize, _: usize, checkpoint: Option<u64>) -> Self {
+            Bank(checkpoint.unwrap_or(BALANCE))
+        }
+    }
+
+    fn banks(seed: u64) -> Sim<Recoverable<Bank, u64>, crate::sim::Envelope<u64>> {
+        let nodes = (0..N_PROCS)
+            .map(|_| Recoverable::new(Bank(BALANCE), 20))
+            .collect();
+        Sim::new(seed, nodes).with_latency(1, 10)
+    }
+
+    fn total(sim: &Sim<Recoverable<Bank, u64>, crate::sim::Envelope<u64>>) -> u64 {
+        sim.nodes().iter().map(|p| p.node().0).sum()
+    }
+
+    #[test]
+    fn checkpoints_are_consistent() {
+        for seed in 0..5 {
+            let mut sim = banks(seed);
+            sim.run_until(400);
+            assert_eq!(total(&sim), N_PROCS as u64 * BALANCE);
+            let committed = sim.nodes()[0].committed().unwrap();
+            assert!(committed >= 10);
+            // Money in flight across the cut is logged, so every committed checkpoint has it all
+            for seq in committed.saturating_sub(1)..=committed {
+                let cps: Vec<_> = sim
+                    .nodes()
+                    .iter()
+                    .map(|p| p.checkpoint(seq).unwrap())
+                    .collect();
+                let in_flight: u64 = cps.iter().flat_map(|c| &c.channels).flatten().sum();
+                let states: u64 = cps.iter().map(|c| c.state).sum();
+                assert_eq!(states + in_flight, N_PROCS as u64 * BALANCE);
+            }
+            assert!(sim.nodes().iter().all(|p| p.epoch() == 0));
+        }
+    }
+
+    #[test]
+    fn rolls_back_after_crash() {
+        for (seed, pid) in (0..6).zip((0..N_PROCS).cycle()) {
+            let mut sim = banks(seed);
+            sim.crash_at(105, pid);
+            sim.restart_at(130, pid);
+            sim.run_until(400);
+            // Transfers to the crashed process would be lost, unless rolled back
+            assert_eq!(total(&sim), N_PROCS as u64 * BALANCE, "seed {seed}");
+            assert!(sim.nodes().iter().all(|p| p.epoch() == 1));
+            assert!(sim.nodes().iter().all(|p| p.n_rollbacks() == 1));
+            // Checkpoints resume after the rollback
+            assert!(sim.nodes()[0].committed() > Some(6));
+        }
+    }
+
+    #[test]
+    fn rolls_back_to_start() {
+        let mut sim = banks(0);
+        sim.crash_at(10, 2);
+        sim.restart_at(15, 2);
+        sim.run_until(400);
+        assert_eq!(total(&sim), N_PROCS as u64 * BALANCE);
+        assert!(sim.nodes().iter().all(|p| p.epoch() == 1));
+    }
+}
@@ -0,0 +1,60 @@
+/// Small seeded pseudo random number generator (SplitMix64), so that simulations are reproducible from their seed
+/// alone.
+#[derive(Clone, Debug)]
+pub struct SimRng {
+    state: u64,
+}
+
+impl SimRng {
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+    /// Uniform in `[lo, hi]`
+    pub fn gen_range(&mut self, lo: u64, hi: u64) -> u64 {
+        assert!(lo <= hi, "Expect lo={lo} <= hi={hi}");
+        match (hi - lo).checked_add(1) {
+            Some(span) => lo + self.next_u64() % span,
+            None => self.next_u64(),
+        }
+    }
+    /// True with probability `p`
+    pub fn gen_bool(&mut self, p: f64) -> bool {
+        // 53 bits of precision, like f64's mantissa
+        ((self.next_u64() >> 11) as f64) < p * (1u64 << 53) as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::sim::SimRng;
+
+    #[test]
+    fn reproducible() {
+        let xs: Vec<_> = (0..5).map(|_| SimRng::new(7).next_u64()).collect();
+        assert!(xs.iter().all(|x| *x == xs[0]));
+        let mut a = SimRng::new(7);
+        let mut b = SimRng::new(8);
+        assert_ne!(a.next_u64(), b.next_u64());
+    }
+
+    #[test]
+    fn ranges() {
+        let mut rng = SimRng::new(0);
+        for _ in 0..1000 {
+            assert!((3..=5).contains(&rng.gen_range(3, 5)));
+        }
+        assert_eq!(rng.gen_range(4, 4), 4);
+        rng.gen_range(0, u64::MAX);
+        assert!(!rng.gen_bool(0.0));
+        assert!(rng.gen_bool(1.0));
+        let n = (0..10_000).filter(|_| rng.gen_bool(0.3)).count();
+        assert!((2500..3500).contains(&n));
+    }
+}
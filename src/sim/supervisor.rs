@@ -0,0 +1,204 @@
+use crate::sim::{Context, Node};
+
+/// Node whose durable state can be checkpointed, so that it can recover from a crash.
+pub trait Recover {
+    type Checkpoint: Clone;
+    /// Durable part of the state, e.g. a Raft node's term, vote and log
+    fn checkpoint(&self) -> Self::Checkpoint;
+    /// Rebuilds process `pid` from its last checkpoint, or from scratch if it never persisted one
+    fn recover(pid: usize, n_procs: usize, checkpoint: Option<Self::Checkpoint>) -> Self;
+}
+
+/// Supervises a node, so that a crash in a `Sim` loses its volatile state, and a restart recovers it from the last
+/// checkpoint persisted by `Context::persist`.
+///
+/// # Examples
+/// ```
+/// use rads::sim::{Context, Node, Recover, Sim, Supervised};
+///
+/// /// Counts messages, but only persists even counts
+/// struct Counter(u64);
+/// impl Node<()> for Counter {
+///     fn on_start(&mut self, ctx: &mut Context<'_, ()>) {
+///         if ctx.pid() == 0 {
+///             (0..3).for_each(|_| ctx.send(1, ()));
+///         }
+///     }
+///     fn on_message(&mut self, ctx: &mut Context<'_, ()>, _: usize, _: ()) {
+///         self.0 += 1;
+///         if self.0.is_multiple_of(2) {
+///             ctx.persist();
+///         }
+///     }
+/// }
+/// impl Recover for Counter {
+///     type Checkpoint = u64;
+///     fn checkpoint(&self) -> u64 {
+///         self.0
+///     }
+///     fn recover(_: usize, _: usize, checkpoint: Option<u64>) -> Self {
+///         Counter(checkpoint.unwrap_or_default())
+///     }
+/// }
+///
+/// let nodes = (0..2).map(|_| Supervised::new(Counter(0))).collect();
+/// let mut sim = Sim::new(0, nodes).with_latency(1, 1);
+/// sim.crash_at(2, 1);
+/// sim.run();
+/// assert_eq!(sim.nodes()[1].node().0, 2);
+/// ```
+pub struct Supervised<N: Recover> {
+    node: N,
+    disk: Option<N::Checkpoint>,
+    pid: usize,
+    n_procs: usize,
+    n_crashes: usize,
+}
+
+impl<N: Recover> Supervised<N> {
+    pub fn new(node: N) -> Self {
+        Self {
+            node,
+            disk: None,
+            pid: 0,
+            n_procs: 0,
+            n_crashes: 0,
+        }
+    }
+    pub fn node(&self) -> &N {
+        &self.node
+    }
+    pub fn into_inner(self) -> N {
+        self.node
+    }
+    /// Last persisted checkpoint
+    pub fn disk(&self) -> Option<&N::Checkpoint> {
+        self.disk.as_ref()
+    }
+    pub fn n_crashes(&self) -> usize {
+        self.n_crashes
+    }
+
+    /// Runs a handler of the node, then persists if it asked to
+    fn supervise<M, F: FnOnce(&mut N, &mut Context<'_, M>)>(
+        &mut self,
+        ctx: &mut Context<'_, M>,
+        f: F,
+    ) {
+        (self.pid, self.n_procs) = (ctx.pid(), ctx.n_procs());
+        f(&mut self.node, ctx);
+        if core::mem::take(&mut ctx.persist) {
+            self.disk = Some(self.node.checkpoint());
+        }
+    }
+}
+
+impl<M, N: Node<M> + Recover> Node<M> for Supervised<N> {
+    fn on_start(&mut self, ctx: &mut Context<'_, M>) {
+        self.supervise(ctx, |node, ctx| node.on_start(ctx));
+    }
+    fn on_message(&mut self, ctx: &mut Context<'_, M>, from: usize, msg: M) {
+        self.supervise(ctx, |node, ctx| node.on_message(ctx, from, msg));
+    }
+    fn on_timer(&mut self, ctx: &mut Context<'_, M>, timer: u64) {
+        self.supervise(ctx, |node, ctx| node.on_timer(ctx, timer));
+    }
+    fn on_crash(&mut self) {
+        self.node.on_crash();
+        self.node = N::recover(self.pid, self.n_procs, self.disk.clone());
+        self.n_crashes += 1;
+    }
+    fn on_restart(&mut self, ctx: &mut Context<'_, M>) {
+        self.supervise(ctx, |node, ctx| node.on_restart(ctx));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::sim::{Context, Node, Recover, Sim, Supervised};
+
+    /// p0 sends a numbered message every 10 units of time, which p1 logs, persisting after every other message
+    struct Ticker {
+        log: Vec<u64>,
+        n_starts: usize,
+    }
+
+    impl Node<u64> for Ticker {
+        fn on_start(&mut self, ctx: &mut Context<'_, u64>) {
+            self.n_starts += 1;
+            if ctx.pid() == 0 {
+                ctx.set_timer(10, 0);
+            }
+        }
+        fn on_message(&mut self, ctx: &mut Context<'_, u64>, _: usize, k: u64) {
+            self.log.push(k);
+            if self.log.len().is_multiple_of(2) {
+                ctx.persist();
+            }
+        }
+        fn on_timer(&mut self, ctx: &mut Context<'_, u64>, k: u64) {
+            self.log.push(k);
+            ctx.send(1, k);
+            if k < 9 {
+                ctx.set_timer(10, k + 1);
+            }
+        }
+    }
+
+    impl Recover for Ticker {
+        type Checkpoint = Vec<u64>;
+        fn checkpoint(&self) -> Vec<u64> {
+            self.log.clone()
+        }
+        fn recover(_: usize, _: usize, checkpoint: Option<Vec<u64>>) -> Self {
+            Ticker {
+                log: checkpoint.unwrap_or_default(),
+                n_starts: 0,
+            }
+        }
+    }
+
+    fn tickers() -> Sim<Supervised<Ticker>, u64> {
+        let nodes = (0..2)
+            .map(|_| {
+                Supervised::new(Ticker {
+                    log: Vec::new(),
+                    n_starts: 0,
+                })
+            })
+            .collect();
+        Sim::new(0, nodes).with_latency(1, 1)
+    }
+
+    #[test]
+    fn loses_volatile_state() {
+        let mut sim = tickers();
+        sim.crash_at(32, 1); // after 0, 1, 2 arrived at 11, 21, 31
+        sim.restart_at(55, 1);
+        sim.run();
+        let p1 = &sim.nodes()[1];
+        // 2 was never persisted, and 3, 4 were lost while down
+        assert_eq!(p1.node().log, vec![0, 1, 5, 6, 7, 8, 9]);
+        assert_eq!((p1.n_crashes(), p1.node().n_starts), (1, 1));
+        assert_eq!(sim.n_dropped(), 2);
+        assert!(sim.is_up(1));
+    }
+
+    #[test]
+    fn cancels_timers() {
+        let mut sim = tickers();
+        sim.crash_at(25, 0);
+        sim.run_until(100);
+        assert!(!sim.is_up(0));
+        assert_eq!(sim.nodes()[0].node().log, Vec::<u64>::new()); // never persisted
+        assert_eq!(sim.nodes()[1].node().log, vec![0, 1]);
+
+        // Restarts from scratch, re-arming its timer
+        sim.restart_at(100, 0);
+        sim.run();
+        assert_eq!(
+            sim.nodes()[1].node().log,
+            vec![0, 1, 0, 1, 2, 3, 4, 5, 6, 7, 8, 9]
+        );
+    }
+}
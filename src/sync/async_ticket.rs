@@ -0,0 +1,291 @@
+use super::stats::{LockStats, Recorder};
+use super::AsyncNoStarveMutex;
+use std::collections::{BTreeMap, BTreeSet};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll, Waker};
+
+/// First-come first-served mutex for async tasks, which wakes the next in line instead of spinning (ticket lock).
+///
+/// Each task takes the next ticket when it wants the lock, and its future resolves once the ticket is served. A future
+/// dropped before it resolves gives up its ticket, which is then skipped.
+///
+/// # Examples
+/// ```
+/// use rads::sync::async_ticket::{AsyncTicket, AsyncTicketN};
+/// use rads::sync::AsyncNoStarveMutex;
+/// use std::future::Future;
+/// use std::task::{Context, Waker};
+///
+/// let mu = std::sync::Arc::new(AsyncTicket::default());
+/// let (mut a, mut b) = (AsyncTicketN::new(&mu), AsyncTicketN::new(&mu));
+/// let mut cx = Context::from_waker(Waker::noop());
+/// let mut want_a = std::pin::pin!(a.want_lock());
+/// let mut want_b = std::pin::pin!(b.want_lock());
+/// // b waits its turn, without blocking the thread
+/// let guard_a = want_a.as_mut().poll(&mut cx);
+/// assert!(guard_a.is_ready());
+/// assert!(want_b.as_mut().poll(&mut cx).is_pending());
+/// drop(guard_a);
+/// assert!(want_b.as_mut().poll(&mut cx).is_ready());
+/// ```
+#[derive(Default)]
+pub struct AsyncTicket {
+    next: AtomicUsize,
+    serving: AtomicUsize,
+    /// Only changes with `serving`, so that no task misses its turn
+    waiting: std::sync::Mutex<Waiting>,
+    /// Number of handles constructed, each of which is a holder in the stats
+    handles: AtomicUsize,
+    /// Recorded only if constructed `with_stats`
+    stats: Option<Recorder>,
+}
+#[derive(Default)]
+struct Waiting {
+    /// Waker of each pending ticket's task, by ticket
+    wakers: BTreeMap<usize, Waker>,
+    /// Tickets whose futures dropped before their turn
+    abandoned: BTreeSet<usize>,
+}
+impl AsyncTicket {
+    /// Records how long each handle waits for the lock, retrievable with `stats()`, at the cost of serializing the
+    /// bookkeeping
+    pub fn with_stats(self) -> Self {
+        Self {
+            stats: Some(Recorder::default()),
+            ..self
+        }
+    }
+    /// Stats recorded so far, by the order the handles were constructed in, if constructed `with_stats`
+    pub fn stats(&self) -> Option<LockStats> {
+        self.stats.as_ref().map(Recorder::stats)
+    }
+    fn record(&self, f: impl FnOnce(&Recorder)) {
+        if let Some(stats) = &self.stats {
+            f(stats);
+        }
+    }
+    /// Serves the next ticket not abandoned, and wakes its task
+    fn release(&self) {
+        let mut waiting = self.waiting.lock().unwrap();
+        let mut serving = self.serving.load(Ordering::SeqCst) + 1;
+        while waiting.abandoned.remove(&serving) {
+            serving += 1;
+        }
+        self.serving.store(serving, Ordering::SeqCst);
+        if let Some(waker) = waiting.wakers.remove(&serving) {
+            waker.wake();
+        }
+    }
+}
+
+pub struct AsyncTicketN {
+    mu: Arc<AsyncTicket>,
+    /// Index of the handle in the stats
+    id: usize,
+}
+pub struct AsyncTicketWant<'a> {
+    mu: &'a AsyncTicketN,
+    /// None once resolved to the guard
+    ticket: Option<usize>,
+}
+pub struct AsyncTicketGuard<'a>(&'a AsyncTicketN);
+impl AsyncTicketN {
+    pub fn new(mu: &Arc<AsyncTicket>) -> Self {
+        Self {
+            mu: mu.clone(),
+            id: mu.handles.fetch_add(1, Ordering::SeqCst),
+        }
+    }
+}
+impl<'a> AsyncNoStarveMutex<'a, AsyncTicketGuard<'a>, AsyncTicketWant<'a>> for AsyncTicketN {
+    fn want_lock(&'a mut self) -> AsyncTicketWant<'a> {
+        self.mu.record(|stats| stats.want(self.id));
+        let ticket = self.mu.next.fetch_add(1, Ordering::SeqCst);
+        AsyncTicketWant {
+            mu: self,
+            ticket: Some(ticket),
+        }
+    }
+}
+
+impl<'a> Future for AsyncTicketWant<'a> {
+    type Output = AsyncTicketGuard<'a>;
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<AsyncTicketGuard<'a>> {
+        let (mu, ticket) = (self.mu, self.ticket.expect("Expect to poll until ready"));
+        if mu.mu.serving.load(Ordering::SeqCst) != ticket {
+            let mut waiting = mu.mu.waiting.lock().unwrap();
+            // Checks again, since it may have been served before the waker was in place
+            if mu.mu.serving.load(Ordering::SeqCst) != ticket {
+                waiting.wakers.insert(ticket, cx.waker().clone());
+                return Poll::Pending;
+            }
+        }
+        self.ticket = None;
+        mu.mu.record(|stats| stats.acquire(mu.id));
+        Poll::Ready(AsyncTicketGuard(mu))
+    }
+}
+impl Drop for AsyncTicketWant<'_> {
+    fn drop(&mut self) {
+        let Some(ticket) = self.ticket else {
+            return;
+        };
+        let mut waiting = self.mu.mu.waiting.lock().unwrap();
+        waiting.wakers.remove(&ticket);
+        if self.mu.mu.serving.load(Ordering::SeqCst) == ticket {
+            // Its turn came, so pass it on as if it locked
+            drop(waiting);
+            self.mu.mu.release();
+        } else {
+            waiting.abandoned.insert(ticket);
+        }
+    }
+}
+
+impl Drop for AsyncTicketGuard<'_> {
+    fn drop(&mut self) {
+        self.0.mu.record(Recorder::release);
+        self.0.mu.release();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::sync::async_ticket::{AsyncTicket, AsyncTicketN};
+    use crate::sync::AsyncNoStarveMutex;
+    use std::future::Future;
+    use std::sync::atomic::Ordering;
+    use std::sync::Arc;
+    use std::task::{Context, Poll, Wake, Waker};
+    const N_THREADS: i32 = 4;
+    const WORK: i32 = 10_000 / N_THREADS;
+
+    /// Wakes by unparking the thread that blocks on the future
+    struct Unpark(std::thread::Thread);
+    impl Wake for Unpark {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+    /// Minimal executor, which parks the thread until the future is woken
+    fn block_on<F: Future>(fut: F) -> F::Output {
+        let mut fut = std::pin::pin!(fut);
+        let waker = Waker::from(Arc::new(Unpark(std::thread::current())));
+        let mut cx = Context::from_waker(&waker);
+        loop {
+            match fut.as_mut().poll(&mut cx) {
+                Poll::Ready(out) => return out,
+                Poll::Pending => std::thread::park(),
+            }
+        }
+    }
+
+    #[test]
+    fn mutual_exclusion() {
+        let data = Arc::new(std::sync::atomic::AtomicI32::new(0));
+        let mu = Arc::new(AsyncTicket::default());
+        let ths = (0..N_THREADS)
+            .map(|_| {
+                let data = data.clone();
+                let mut mu = AsyncTicketN::new(&mu);
+                std::thread::spawn(move || {
+                    for _ in 0..WORK {
+                        let _guard = block_on(mu.lock());
+                        let i = data.load(Ordering::Relaxed);
+                        std::thread::yield_now();
+                        data.store(i + 1, Ordering::Relaxed);
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        ths.into_iter().for_each(|th| th.join().unwrap());
+        assert_eq!(data.load(Ordering::Relaxed), WORK * N_THREADS);
+    }
+
+    #[test]
+    fn first_come_first_served() {
+        let mu = Arc::new(AsyncTicket::default());
+        let mut hs: Vec<_> = (0..3).map(|_| AsyncTicketN::new(&mu)).collect();
+        let mut cx = Context::from_waker(Waker::noop());
+        let mut wants: Vec<_> = hs.iter_mut().map(|h| Box::pin(h.want_lock())).collect();
+        let guard = wants[0].as_mut().poll(&mut cx);
+        assert!(wants[2].as_mut().poll(&mut cx).is_pending());
+        assert!(wants[1].as_mut().poll(&mut cx).is_pending());
+        drop(guard);
+        // 1 wanted it before 2
+        assert!(wants[2].as_mut().poll(&mut cx).is_pending());
+        let guard = wants[1].as_mut().poll(&mut cx);
+        assert!(guard.is_ready());
+        drop(guard);
+        assert!(wants[2].as_mut().poll(&mut cx).is_ready());
+    }
+
+    #[test]
+    fn abandons() {
+        let mu = Arc::new(AsyncTicket::default());
+        let (mut a, mut b, mut c) = (
+            AsyncTicketN::new(&mu),
+            AsyncTicketN::new(&mu),
+            AsyncTicketN::new(&mu),
+        );
+        let mut cx = Context::from_waker(Waker::noop());
+        let guard = block_on(a.lock());
+        let mut want_b = Box::pin(b.want_lock());
+        let mut want_c = Box::pin(c.want_lock());
+        assert!(want_b.as_mut().poll(&mut cx).is_pending());
+        // Gives up before its turn, so c is next
+        drop(want_b);
+        drop(guard);
+        let guard = want_c.as_mut().poll(&mut cx);
+        assert!(guard.is_ready());
+        assert_eq!(mu.serving.load(Ordering::SeqCst), 2);
+        drop(guard);
+        drop(want_c);
+
+        // Gives up once its turn came, passing it on
+        let want_a = a.want_lock();
+        let mut want_b = Box::pin(b.want_lock());
+        assert!(want_b.as_mut().poll(&mut cx).is_pending());
+        drop(want_a);
+        assert!(want_b.as_mut().poll(&mut cx).is_ready());
+    }
+
+    #[test]
+    fn wakes_next() {
+        let mu = Arc::new(AsyncTicket::default());
+        let mut a = AsyncTicketN::new(&mu);
+        let guard = block_on(a.lock());
+        let th = std::thread::spawn({
+            let mut b = AsyncTicketN::new(&mu);
+            move || drop(block_on(b.lock()))
+        });
+        while mu.waiting.lock().unwrap().wakers.is_empty() {
+            std::thread::yield_now();
+        }
+        drop(guard);
+        th.join().unwrap();
+    }
+
+    #[test]
+    fn stats() {
+        let mu = Arc::new(AsyncTicket::default().with_stats());
+        let (mut a, mut b) = (AsyncTicketN::new(&mu), AsyncTicketN::new(&mu));
+        let mut cx = Context::from_waker(Waker::noop());
+        let guard = block_on(a.lock());
+        let mut want_b = Box::pin(b.want_lock());
+        assert!(want_b.as_mut().poll(&mut cx).is_pending());
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        drop(guard);
+        drop(want_b.as_mut().poll(&mut cx));
+        drop(want_b);
+        let stats = mu.stats().unwrap();
+        assert_eq!(stats.holders.len(), 2);
+        assert!(stats.holders.iter().all(|h| h.acquisitions == 1));
+        assert!(stats.holders[1].max_wait >= std::time::Duration::from_millis(10));
+        assert_eq!(stats.handoffs, 1);
+        assert_eq!(AsyncTicket::default().stats(), None);
+    }
+}
@@ -0,0 +1,148 @@
+use super::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// N-ary mutex with a single shared bit per thread, the least space any mutex for `n` threads needs (Burns-Lynch).
+///
+/// A thread raises its bit only while no lower-indexed thread has raised its own, starting over if one did, then
+/// waits for every higher-indexed thread to lower its bit. Hence
+/// 1. Mutual Exclusion - of any 2 threads with raised bits, the higher one backs off or waits for the lower one.
+/// 2. Deadlock Freedom - the lowest-indexed thread that wants the lock never backs off, so some thread enters.
+/// 3. No Starvation is NOT guaranteed - lower-indexed threads take priority, so a higher one starves if lower ones
+///    keep reacquiring the lock.
+///
+/// # Examples
+/// ```
+/// use crate::rads::sync::burns_lynch::{BurnsLynch, BurnsLynchN};
+/// use std::sync::atomic::Ordering;
+/// use rads::sync::Mutex;
+///
+/// let data = std::sync::Arc::new(std::sync::atomic::AtomicI32::new(0));
+/// let mu = std::sync::Arc::new(BurnsLynch::new(4));
+/// let ths = (0..4)
+///     .map(|n| {
+///         let data = data.clone();
+///         let mut mu = BurnsLynchN::new(n, &mu);
+///         let n = n as i32 + 1;
+///         std::thread::spawn(move || {
+///             for _ in 0..1_000 {
+///                 let _guard = mu.lock();
+///                 let i = data.load(Ordering::Relaxed);
+///                 data.store(i + n, Ordering::Relaxed);
+///             }
+///         })
+///     })
+///     .collect::<Vec<_>>();
+/// ths.into_iter().for_each(|th| th.join().unwrap());
+/// assert_eq!(data.load(Ordering::Relaxed), 1_000 * (1 + 2 + 3 + 4));
+/// ```
+pub struct BurnsLynch {
+    flags: Vec<AtomicBool>,
+}
+impl BurnsLynch {
+    pub fn new(size: usize) -> Self {
+        assert!(size > 1, "Do you really need a mutex of size {size}?");
+        Self {
+            flags: (0..size).map(|_| AtomicBool::new(false)).collect(),
+        }
+    }
+}
+
+pub struct BurnsLynchN {
+    n: usize,
+    burns_lynch: std::sync::Arc<BurnsLynch>,
+}
+pub struct BurnsLynchGuard<'a>(&'a BurnsLynchN);
+impl BurnsLynchN {
+    // Does not check if index is taken.
+    pub fn new(n: usize, burns_lynch: &std::sync::Arc<BurnsLynch>) -> Self {
+        let size = burns_lynch.flags.len();
+        assert!(n < size, "0-based user index {n} >= mutex of size={size}");
+        Self {
+            n,
+            burns_lynch: burns_lynch.clone(),
+        }
+    }
+}
+
+impl<'a> Mutex<'a, BurnsLynchGuard<'a>> for BurnsLynchN {
+    fn lock(&'a mut self) -> BurnsLynchGuard<'a> {
+        // Algorithm requires no reordering of variables, hence SeqCst
+        let (flags, n) = (&self.burns_lynch.flags, self.n);
+        // Lower-indexed thread whose bit is raised, if any
+        let lower = || flags[..n].iter().find(|f| f.load(Ordering::SeqCst));
+        loop {
+            flags[n].store(false, Ordering::SeqCst);
+            if let Some(f) = lower() {
+                while f.load(Ordering::SeqCst) {
+                    std::thread::yield_now();
+                }
+                continue;
+            }
+            flags[n].store(true, Ordering::SeqCst);
+            if lower().is_none() {
+                break;
+            }
+        }
+        for f in &flags[n + 1..] {
+            while f.load(Ordering::SeqCst) {
+                std::thread::yield_now();
+            }
+        }
+        BurnsLynchGuard(self)
+    }
+}
+
+impl Drop for BurnsLynchGuard<'_> {
+    fn drop(&mut self) {
+        self.0.burns_lynch.flags[self.0.n].store(false, Ordering::SeqCst);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::sync::{
+        burns_lynch::{BurnsLynch, BurnsLynchN},
+        Mutex,
+    };
+    use std::sync::atomic::Ordering;
+    use std::sync::Arc;
+
+    #[test]
+    fn mutual_exclusion() {
+        let mu = Arc::new(BurnsLynch::new(4));
+        crate::sync::tests::mutual_exclusion(|n| {
+            let mut mu = BurnsLynchN::new(n, &mu);
+            move |f| {
+                let _guard = mu.lock();
+                f()
+            }
+        });
+    }
+
+    #[test]
+    fn lower_index_takes_priority() {
+        let mu = Arc::new(BurnsLynch::new(3));
+        let log = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let mut p1 = BurnsLynchN::new(1, &mu);
+        let guard = p1.lock();
+        let ths: Vec<_> = [2, 0]
+            .into_iter()
+            .map(|n| {
+                // p2 waits first, but p0 overtakes it once p1 releases
+                std::thread::sleep(std::time::Duration::from_millis(10));
+                let (mut p, log) = (BurnsLynchN::new(n, &mu), log.clone());
+                std::thread::spawn(move || {
+                    let _guard = p.lock();
+                    log.lock().unwrap().push(n);
+                })
+            })
+            .collect();
+        while !mu.flags[0].load(Ordering::SeqCst) {
+            std::thread::yield_now();
+        }
+        drop(guard);
+        ths.into_iter().for_each(|th| th.join().unwrap());
+        assert_eq!(*log.lock().unwrap(), [0, 2]);
+        assert!(mu.flags.iter().all(|f| !f.load(Ordering::SeqCst)));
+    }
+}
@@ -0,0 +1,361 @@
+use super::{NoStarveMutex, Reacquire, WantGuard};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+/// N-ary mutex for threads grouped by cluster, e.g. NUMA node or core cluster, that hands the lock to waiters on the
+/// same cluster, so that the data it protects stays in that cluster's caches (Dice-Marathe-Shavit cohort lock).
+///
+/// Each cluster has a local ticket lock, and clusters take turns by a global ticket lock. The first thread of a cluster
+/// to hold its local lock also takes the global one, which then passes from thread to thread within the cluster, up to
+/// `bound` times in a row while others of the cluster wait, before it goes to the next cluster. Both are first-come
+/// first-served, so every thread enters eventually.
+///
+/// # Examples
+/// ```
+/// use crate::rads::sync::cohort::{CohortLock, CohortN};
+/// use std::sync::atomic::Ordering;
+/// use rads::sync::NoStarveMutex;
+///
+/// let data = std::sync::Arc::new(std::sync::atomic::AtomicI32::new(0));
+/// // 2 clusters, handing off within one up to 4 times in a row
+/// let mu = std::sync::Arc::new(CohortLock::new(2, 4));
+/// let ths = (0..4)
+///     .map(|n| {
+///         let data = data.clone();
+///         let mut mu = CohortN::new(n % 2, &mu);
+///         std::thread::spawn(move || {
+///             for _ in 0..1_000 {
+///                 let _guard = mu.lock();
+///                 let i = data.load(Ordering::Relaxed);
+///                 data.store(i + 1, Ordering::Relaxed);
+///             }
+///         })
+///     })
+///     .collect::<Vec<_>>();
+/// ths.into_iter().for_each(|th| th.join().unwrap());
+/// assert_eq!(data.load(Ordering::Relaxed), 4 * 1_000);
+/// ```
+pub struct CohortLock {
+    global: Ticket,
+    clusters: Vec<Cluster>,
+    /// Maximum number of times in a row the lock passes within a cluster while others wait
+    bound: usize,
+}
+impl CohortLock {
+    pub fn new(n_clusters: usize, bound: usize) -> Self {
+        assert!(n_clusters > 0, "Expect at least 1 cluster");
+        Self {
+            global: Ticket::new(),
+            clusters: (0..n_clusters).map(|_| Cluster::default()).collect(),
+            bound,
+        }
+    }
+}
+
+/// First-come first-served lock, which any thread may release on behalf of the one that locked it
+#[derive(Default)]
+pub(super) struct Ticket {
+    next: AtomicUsize,
+    serving: AtomicUsize,
+}
+impl Ticket {
+    pub(super) const fn new() -> Self {
+        Self {
+            next: AtomicUsize::new(0),
+            serving: AtomicUsize::new(0),
+        }
+    }
+    pub(super) fn take(&self) -> usize {
+        self.next.fetch_add(1, Ordering::SeqCst)
+    }
+    pub(super) fn wait(&self, ticket: usize) {
+        while self.serving.load(Ordering::SeqCst) != ticket {
+            std::thread::yield_now();
+        }
+    }
+    /// Takes the ticket being served, if no other thread holds or waits for the lock
+    pub(super) fn try_take(&self) -> Option<usize> {
+        let serving = self.serving.load(Ordering::SeqCst);
+        self.next
+            .compare_exchange(serving, serving + 1, Ordering::SeqCst, Ordering::SeqCst)
+            .ok()
+    }
+    pub(super) fn release(&self) {
+        self.serving.fetch_add(1, Ordering::SeqCst);
+    }
+    /// Whether any thread took a ticket after the one being served
+    fn has_waiters(&self) -> bool {
+        self.next.load(Ordering::SeqCst) > self.serving.load(Ordering::SeqCst) + 1
+    }
+}
+
+#[derive(Default)]
+struct Cluster {
+    local: Ticket,
+    /// Whether the global lock passes to whoever holds the local lock
+    owns_global: AtomicBool,
+    /// Number of times in a row the global lock passed within the cluster
+    n_handoffs: AtomicUsize,
+}
+impl Cluster {
+    /// Starts passing the global lock within the cluster, once a thread of it took the global lock
+    fn own_global(&self) {
+        self.owns_global.store(true, Ordering::SeqCst);
+        self.n_handoffs.store(0, Ordering::SeqCst);
+    }
+}
+
+pub struct CohortN {
+    cluster: usize,
+    cohort: std::sync::Arc<CohortLock>,
+}
+pub struct CohortWant<'a>(Option<(&'a CohortN, usize)>);
+pub struct CohortGuard<'a>(&'a CohortN);
+impl CohortN {
+    /// Thread of `cluster`, of which any number may share the lock
+    pub fn new(cluster: usize, cohort: &std::sync::Arc<CohortLock>) -> Self {
+        let n_clusters = cohort.clusters.len();
+        assert!(
+            cluster < n_clusters,
+            "0-based cluster index {cluster} >= n_clusters={n_clusters}"
+        );
+        Self {
+            cluster,
+            cohort: cohort.clone(),
+        }
+    }
+    fn cluster(&self) -> &Cluster {
+        &self.cohort.clusters[self.cluster]
+    }
+    /// Takes the local and global locks if both are free
+    fn try_take(&self) -> bool {
+        let cluster = self.cluster();
+        if cluster.local.try_take().is_none() {
+            return false;
+        }
+        if !cluster.owns_global.load(Ordering::SeqCst) {
+            if self.cohort.global.try_take().is_none() {
+                cluster.local.release();
+                return false;
+            }
+            cluster.own_global();
+        }
+        true
+    }
+}
+impl<'a> NoStarveMutex<'a, CohortGuard<'a>, CohortWant<'a>> for CohortN {
+    fn want_lock(&'a mut self) -> CohortWant<'a> {
+        let ticket = self.cluster().local.take();
+        CohortWant(Some((self, ticket)))
+    }
+    // Takes a ticket only if it would be served right away, since a ticket cannot be given back
+    fn try_lock(&'a mut self) -> Option<CohortGuard<'a>> {
+        self.try_take().then(|| CohortGuard(self))
+    }
+    // Retries `try_lock()` instead of waiting on a ticket, since a dropped `CohortWant` still waits for its turn
+    fn lock_timeout(&'a mut self, dur: std::time::Duration) -> Option<CohortGuard<'a>> {
+        let deadline = std::time::Instant::now() + dur;
+        while !self.try_take() {
+            if std::time::Instant::now() >= deadline {
+                return None;
+            }
+            std::thread::yield_now();
+        }
+        Some(CohortGuard(self))
+    }
+}
+
+impl Reacquire for CohortGuard<'_> {
+    fn reacquire(self, f: impl FnOnce()) -> Self {
+        let c = self.0;
+        drop(self);
+        f();
+        CohortWant(Some((c, c.cluster().local.take()))).wait()
+    }
+}
+
+impl<'a> WantGuard<'a, CohortGuard<'a>> for CohortWant<'a> {
+    fn wait(mut self) -> CohortGuard<'a> {
+        let (c, ticket) = self.0.take().unwrap();
+        let cluster = c.cluster();
+        cluster.local.wait(ticket);
+        if !cluster.owns_global.load(Ordering::SeqCst) {
+            c.cohort.global.wait(c.cohort.global.take());
+            cluster.own_global();
+        }
+        CohortGuard(c)
+    }
+    // Keeps the turn of the local lock if the global lock is taken, so that waiting later resumes from there
+    fn try_wait(mut self) -> Result<CohortGuard<'a>, Self> {
+        let (c, ticket) = self.0.unwrap();
+        let cluster = c.cluster();
+        if cluster.local.serving.load(Ordering::SeqCst) != ticket {
+            return Err(self);
+        }
+        if !cluster.owns_global.load(Ordering::SeqCst) {
+            if c.cohort.global.try_take().is_none() {
+                return Err(self);
+            }
+            cluster.own_global();
+        }
+        self.0 = None;
+        Ok(CohortGuard(c))
+    }
+    fn is_ready(&self) -> bool {
+        let (c, ticket) = self.0.unwrap();
+        let cluster = c.cluster();
+        let global = &c.cohort.global;
+        cluster.local.serving.load(Ordering::SeqCst) == ticket
+            && (cluster.owns_global.load(Ordering::SeqCst)
+                || global.next.load(Ordering::SeqCst) == global.serving.load(Ordering::SeqCst))
+    }
+}
+impl Drop for CohortWant<'_> {
+    fn drop(&mut self) {
+        // Unlike flags, a ticket cannot be given back, so it must be served to pass the lock on
+        if let Some((c, ticket)) = self.0.take() {
+            super::abandon();
+            drop(CohortWant(Some((c, ticket))).wait());
+        }
+    }
+}
+
+impl Drop for CohortGuard<'_> {
+    fn drop(&mut self) {
+        let (cluster, bound) = (self.0.cluster(), self.0.cohort.bound);
+        if cluster.local.has_waiters() && cluster.n_handoffs.load(Ordering::SeqCst) < bound {
+            cluster.n_handoffs.fetch_add(1, Ordering::SeqCst);
+        } else {
+            cluster.owns_global.store(false, Ordering::SeqCst);
+            self.0.cohort.global.release();
+        }
+        cluster.local.release();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::sync::{
+        cohort::{CohortLock, CohortN},
+        NoStarveMutex, WantGuard,
+    };
+    use std::sync::atomic::Ordering;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn mutual_exclusion() {
+        let mu = Arc::new(CohortLock::new(2, 3));
+        crate::sync::tests::mutual_exclusion(|n| {
+            let mut mu = CohortN::new(n % 2, &mu);
+            move |f| {
+                let _guard = mu.lock();
+                f()
+            }
+        });
+    }
+
+    /// Order in which threads a and b of cluster 0, and c of cluster 1, enter after a, which re-locks once b and c
+    /// wait
+    fn handoffs(bound: usize) -> Vec<&'static str> {
+        let mu = Arc::new(CohortLock::new(2, bound));
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let wait_until = |f: &dyn Fn() -> bool| {
+            while !f() {
+                std::thread::yield_now();
+            }
+        };
+        let mut a = CohortN::new(0, &mu);
+        let guard = a.lock();
+        let th_c = std::thread::spawn({
+            let (mut c, log) = (CohortN::new(1, &mu), log.clone());
+            move || {
+                let _guard = c.lock();
+                log.lock().unwrap().push("c");
+            }
+        });
+        wait_until(&|| mu.global.next.load(Ordering::SeqCst) == 2);
+        let th_b = std::thread::spawn({
+            let (mut b, log, mu) = (CohortN::new(0, &mu), log.clone(), mu.clone());
+            move || {
+                let _guard = b.lock();
+                log.lock().unwrap().push("b");
+                // Until a wants the lock again
+                while mu.clusters[0].local.next.load(Ordering::SeqCst) < 3 {
+                    std::thread::yield_now();
+                }
+            }
+        });
+        wait_until(&|| mu.clusters[0].local.next.load(Ordering::SeqCst) == 2);
+        drop(guard);
+        drop(a.lock());
+        log.lock().unwrap().push("a");
+        th_b.join().unwrap();
+        th_c.join().unwrap();
+        let log = log.lock().unwrap().clone();
+        log
+    }
+
+    #[test]
+    fn prefers_same_cluster() {
+        // b overtakes c, which waited first, and so does a unless that exceeds the bound
+        assert_eq!(handoffs(2), ["b", "a", "c"]);
+        assert_eq!(handoffs(1), ["b", "c", "a"]);
+        // Clusters take turns
+        assert_eq!(handoffs(0), ["c", "b", "a"]);
+    }
+
+    #[test]
+    fn try_lock() {
+        let mu = Arc::new(CohortLock::new(2, 1));
+        let (mut a, mut b, mut c) = (
+            CohortN::new(0, &mu),
+            CohortN::new(0, &mu),
+            CohortN::new(1, &mu),
+        );
+        let guard = a.try_lock().unwrap();
+        assert!(b.try_lock().is_none());
+        assert!(c.try_lock().is_none());
+        drop(guard);
+        // Neither took a ticket it cannot give back
+        assert_eq!(mu.global.next.load(Ordering::SeqCst), 1);
+
+        // c holds its local lock, but waits for the global one
+        let guard = a.lock();
+        let want = c.want_lock();
+        let want = want.try_wait().err().unwrap();
+        drop(guard);
+        let guard = want.try_wait().ok().unwrap();
+        assert!(b.try_lock().is_none());
+        drop(guard);
+        assert!(b.try_lock().is_some());
+    }
+
+    #[test]
+    fn lock_timeout() {
+        let mu = Arc::new(CohortLock::new(2, 1));
+        let (mut a, mut b) = (CohortN::new(0, &mu), CohortN::new(1, &mu));
+        let guard = a.lock();
+        // Returns without waiting for a ticket to be served
+        assert!(b
+            .lock_timeout(std::time::Duration::from_millis(10))
+            .is_none());
+        drop(guard);
+        assert!(b.lock_timeout(std::time::Duration::ZERO).is_some());
+        assert!(a.try_lock().is_some());
+    }
+
+    #[test]
+    fn failed_try_lock_keeps_lock() {
+        let mu = Arc::new(CohortLock::new(2, 1));
+        let (mut a, mut b, mut c) = (
+            CohortN::new(0, &mu),
+            CohortN::new(0, &mu),
+            CohortN::new(1, &mu),
+        );
+        let _guard = a.try_lock().unwrap();
+        // Failing releases nothing of a's, so trying again fails as well
+        for _ in 0..2 {
+            assert!(b.try_lock().is_none());
+            assert!(c.try_lock().is_none());
+        }
+    }
+}
@@ -0,0 +1,176 @@
+use super::Barrier;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+/// Barrier for `n` threads that arrive at the leaves of a tree, so that at most `radix` threads contend on each counter
+/// (combining tree barrier).
+///
+/// The last thread to arrive at a node goes on to its parent, while the others spin on the node's sense. The last
+/// thread to arrive at the root completes the phase, and on its way back down flips the sense of every node it combined
+/// at, which releases the threads waiting there. Each thread alternates its own sense each phase, so that the barrier
+/// needs no reset.
+///
+/// # Examples
+/// ```
+/// use rads::sync::combining_tree::{CombiningTree, CombiningTreeN};
+/// use rads::sync::Barrier;
+/// use std::sync::atomic::{AtomicUsize, Ordering};
+///
+/// let arrived = std::sync::Arc::new(AtomicUsize::new(0));
+/// let tree = std::sync::Arc::new(CombiningTree::new(4, 2));
+/// let ths = (0..4)
+///     .map(|n| {
+///         let arrived = arrived.clone();
+///         let mut barrier = CombiningTreeN::new(n, &tree);
+///         std::thread::spawn(move || {
+///             arrived.fetch_add(1, Ordering::SeqCst);
+///             barrier.wait();
+///             assert_eq!(arrived.load(Ordering::SeqCst), 4);
+///         })
+///     })
+///     .collect::<Vec<_>>();
+/// ths.into_iter().for_each(|th| th.join().unwrap());
+/// ```
+pub struct CombiningTree {
+    /// Leaves first, each for up to `radix` consecutive threads, then every level up to the root
+    nodes: Vec<Node>,
+    radix: usize,
+    size: usize,
+}
+struct Node {
+    /// Number of threads arrived in the current phase
+    count: AtomicUsize,
+    /// Number of threads that arrive each phase, i.e. of threads at a leaf, else of children
+    width: usize,
+    parent: Option<usize>,
+    /// Flipped once every thread arrived at the root
+    sense: AtomicBool,
+}
+impl Node {
+    fn new(width: usize) -> Self {
+        Self {
+            count: AtomicUsize::new(0),
+            width,
+            parent: None,
+            sense: AtomicBool::new(false),
+        }
+    }
+}
+impl CombiningTree {
+    pub fn new(size: usize, radix: usize) -> Self {
+        assert!(size > 1, "Do you really need a barrier of size {size}?");
+        assert!(radix > 1, "Expect a radix of at least 2, got {radix}");
+        let mut nodes: Vec<_> = (0..size.div_ceil(radix))
+            .map(|leaf| Node::new(radix.min(size - leaf * radix)))
+            .collect();
+        let mut level: Vec<_> = (0..nodes.len()).collect();
+        while level.len() > 1 {
+            level = level
+                .chunks(radix)
+                .map(|children| {
+                    let parent = nodes.len();
+                    nodes.push(Node::new(children.len()));
+                    children
+                        .iter()
+                        .for_each(|&c| nodes[c].parent = Some(parent));
+                    parent
+                })
+                .collect();
+        }
+        Self { nodes, radix, size }
+    }
+    /// Arrives at the node, and returns once every thread arrived at the root
+    fn arrive(&self, node: usize, sense: bool) {
+        let Node {
+            count,
+            width,
+            parent,
+            sense: node_sense,
+        } = &self.nodes[node];
+        // Algorithm requires no reordering of variables, hence SeqCst
+        if count.fetch_add(1, Ordering::SeqCst) + 1 == *width {
+            if let Some(parent) = *parent {
+                self.arrive(parent, sense);
+            }
+            // Resets before releasing, since released threads may arrive again right away
+            count.store(0, Ordering::SeqCst);
+            node_sense.store(sense, Ordering::SeqCst);
+        } else {
+            while node_sense.load(Ordering::SeqCst) != sense {
+                std::thread::yield_now();
+            }
+        }
+    }
+}
+
+pub struct CombiningTreeN {
+    n: usize,
+    tree: std::sync::Arc<CombiningTree>,
+    /// Sense that the nodes take once every thread arrived in the current phase
+    sense: bool,
+}
+impl CombiningTreeN {
+    // Does not check if index is taken.
+    pub fn new(n: usize, tree: &std::sync::Arc<CombiningTree>) -> Self {
+        let size = tree.size;
+        assert!(n < size, "0-based user index {n} >= barrier of size={size}");
+        Self {
+            n,
+            tree: tree.clone(),
+            sense: true,
+        }
+    }
+}
+
+impl Barrier for CombiningTreeN {
+    fn wait(&mut self) {
+        self.tree.arrive(self.n / self.tree.radix, self.sense);
+        self.sense = !self.sense;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::sync::combining_tree::{CombiningTree, CombiningTreeN};
+    use crate::sync::Barrier;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    const PHASES: usize = 1_000;
+
+    /// Checks that no thread leaves a phase before every thread arrived in it
+    fn phases(size: usize, radix: usize) {
+        let tree = Arc::new(CombiningTree::new(size, radix));
+        let arrived: Arc<Vec<_>> = Arc::new((0..PHASES).map(|_| AtomicUsize::new(0)).collect());
+        let ths: Vec<_> = (0..size)
+            .map(|n| {
+                let arrived = arrived.clone();
+                let mut barrier = CombiningTreeN::new(n, &tree);
+                std::thread::spawn(move || {
+                    for phase in 0..PHASES {
+                        arrived[phase].fetch_add(1, Ordering::SeqCst);
+                        barrier.wait();
+                        assert_eq!(arrived[phase].load(Ordering::SeqCst), size);
+                    }
+                })
+            })
+            .collect();
+        ths.into_iter().for_each(|th| th.join().unwrap());
+    }
+
+    #[test]
+    fn shape() {
+        // 3 leaves, under 2 nodes, under the root
+        let tree = CombiningTree::new(5, 2);
+        let widths: Vec<_> = tree.nodes.iter().map(|node| node.width).collect();
+        assert_eq!(widths, [2, 2, 1, 2, 1, 2]);
+        let parents: Vec<_> = tree.nodes.iter().map(|node| node.parent).collect();
+        assert_eq!(parents, [Some(3), Some(3), Some(4), Some(5), Some(5), None]);
+    }
+
+    #[test]
+    fn waits_for_every_thread() {
+        phases(4, 2);
+        phases(5, 2);
+        phases(4, 4);
+        phases(3, 8);
+    }
+}
@@ -0,0 +1,218 @@
+use super::Reacquire;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// Condition variable for the crate's guards, which `std::sync::Condvar` cannot release and reacquire.
+///
+/// A waiting thread queues up before it releases the lock, so it misses no notification sent once it released. Like
+/// std's, a thread may wake up although the condition it waits for does not hold yet, so check it in a loop.
+///
+/// # Examples
+/// ```
+/// use rads::sync::{Condvar, Protected};
+///
+/// let (mut consumer, mut producer) = Protected::binary(Vec::new());
+/// let cv = std::sync::Arc::new(Condvar::default());
+/// let th = std::thread::spawn({
+///     let cv = cv.clone();
+///     move || {
+///         let mut guard = consumer.lock();
+///         while guard.is_empty() {
+///             guard = cv.wait(guard);
+///         }
+///         guard.pop().unwrap()
+///     }
+/// });
+/// producer.lock().push("ready");
+/// cv.notify_all();
+/// assert_eq!(th.join().unwrap(), "ready");
+/// ```
+#[derive(Default)]
+pub struct Condvar {
+    /// Whether each waiting thread was notified, in the order they waited
+    waiting: Mutex<VecDeque<(std::thread::Thread, Arc<AtomicBool>)>>,
+}
+impl Condvar {
+    /// Releases the lock until notified, then waits for the lock again
+    pub fn wait<G: Reacquire>(&self, guard: G) -> G {
+        let notified = Arc::new(AtomicBool::new(false));
+        let me = (std::thread::current(), notified.clone());
+        self.waiting.lock().unwrap().push_back(me);
+        guard.reacquire(|| {
+            while !notified.load(Ordering::SeqCst) {
+                std::thread::park();
+            }
+        })
+    }
+    /// Wakes up the thread that waited longest, if any
+    pub fn notify_one(&self) {
+        if let Some(waiter) = self.waiting.lock().unwrap().pop_front() {
+            Self::notify(waiter);
+        }
+    }
+    pub fn notify_all(&self) {
+        let waiting = std::mem::take(&mut *self.waiting.lock().unwrap());
+        waiting.into_iter().for_each(Self::notify);
+    }
+    fn notify((th, notified): (std::thread::Thread, Arc<AtomicBool>)) {
+        notified.store(true, Ordering::SeqCst);
+        th.unpark();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::sync::cohort::{CohortLock, CohortN};
+    use crate::sync::filter::{FilterLock, FilterLockN};
+    use crate::sync::lamports_bakery::{Bakery, BakeryN};
+    use crate::sync::peterson::Peterson;
+    use crate::sync::{Condvar, NoStarveMutex, Protected};
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+    use std::sync::Arc;
+    const N_THREADS: usize = 4;
+
+    #[test]
+    fn wait_releases_lock() {
+        let cv = Arc::new(Condvar::default());
+        let ready = Arc::new(AtomicBool::new(false));
+        let (mut mu_a, mut mu_b) = Peterson::binary_mutex();
+        let th = std::thread::spawn({
+            let (cv, ready) = (cv.clone(), ready.clone());
+            move || {
+                let mut guard = mu_a.lock();
+                while !ready.load(Ordering::SeqCst) {
+                    guard = cv.wait(guard);
+                }
+            }
+        });
+        // Locks while a waits
+        while cv.waiting.lock().unwrap().is_empty() {
+            std::thread::yield_now();
+        }
+        let guard = mu_b.lock();
+        ready.store(true, Ordering::SeqCst);
+        cv.notify_one();
+        drop(guard);
+        th.join().unwrap();
+    }
+
+    /// Waits on the condvar under the lock until the closure returns true
+    type Lock = Box<dyn FnMut(&Condvar, &mut dyn FnMut() -> bool) + Send>;
+
+    /// Each thread waits for its turn in a ring, so that every lock must hand over while the others wait
+    fn take_turns(locks: Vec<Lock>) {
+        const ROUNDS: usize = 100;
+        let n_threads = locks.len();
+        let (cv, turn) = (Arc::new(Condvar::default()), Arc::new(AtomicUsize::new(0)));
+        let ths: Vec<_> = (locks.into_iter().enumerate())
+            .map(|(n, mut lock)| {
+                let (cv, turn) = (cv.clone(), turn.clone());
+                std::thread::spawn(move || {
+                    for _ in 0..ROUNDS {
+                        lock(&cv, &mut || {
+                            let mine = turn.load(Ordering::SeqCst) % n_threads == n;
+                            if mine {
+                                turn.fetch_add(1, Ordering::SeqCst);
+                            }
+                            mine
+                        });
+                        cv.notify_all();
+                    }
+                })
+            })
+            .collect();
+        ths.into_iter().for_each(|th| th.join().unwrap());
+        assert_eq!(turn.load(Ordering::SeqCst), ROUNDS * n_threads);
+    }
+
+    #[test]
+    fn take_turns_with_every_lock() {
+        let (mut a, mut b) = Peterson::binary_mutex();
+        take_turns(vec![
+            Box::new(move |cv, f| {
+                let mut guard = a.lock();
+                while !f() {
+                    guard = cv.wait(guard);
+                }
+            }),
+            Box::new(move |cv, f| {
+                let mut guard = b.lock();
+                while !f() {
+                    guard = cv.wait(guard);
+                }
+            }),
+        ]);
+        let bakery = Arc::new(Bakery::new(N_THREADS));
+        take_turns(
+            (0..N_THREADS)
+                .map(|n| -> Lock {
+                    let mut mu = BakeryN::new(n, &bakery);
+                    Box::new(move |cv, f| {
+                        let mut guard = mu.lock();
+                        while !f() {
+                            guard = cv.wait(guard);
+                        }
+                    })
+                })
+                .collect(),
+        );
+        let filter = Arc::new(FilterLock::new(N_THREADS));
+        take_turns(
+            (0..N_THREADS)
+                .map(|n| -> Lock {
+                    let mut mu = FilterLockN::new(n, &filter);
+                    Box::new(move |cv, f| {
+                        let mut guard = mu.lock();
+                        while !f() {
+                            guard = cv.wait(guard);
+                        }
+                    })
+                })
+                .collect(),
+        );
+        let cohort = Arc::new(CohortLock::new(2, 1));
+        take_turns(
+            (0..N_THREADS)
+                .map(|n| -> Lock {
+                    let mut mu = CohortN::new(n % 2, &cohort);
+                    Box::new(move |cv, f| {
+                        let mut guard = mu.lock();
+                        while !f() {
+                            guard = cv.wait(guard);
+                        }
+                    })
+                })
+                .collect(),
+        );
+    }
+
+    #[test]
+    fn protected() {
+        let cv = Arc::new(Condvar::default());
+        let mut data = Protected::n_ary(Vec::new(), N_THREADS);
+        let mut consumer = data.pop().unwrap();
+        let ths: Vec<_> = data
+            .into_iter()
+            .enumerate()
+            .map(|(n, mut producer)| {
+                let cv = cv.clone();
+                std::thread::spawn(move || {
+                    producer.lock().push(n);
+                    cv.notify_one();
+                })
+            })
+            .collect();
+        let mut got = Vec::new();
+        while got.len() < N_THREADS - 1 {
+            let mut guard = consumer.lock();
+            while guard.is_empty() {
+                guard = cv.wait(guard);
+            }
+            got.append(&mut guard);
+        }
+        ths.into_iter().for_each(|th| th.join().unwrap());
+        got.sort();
+        assert_eq!(got, (0..N_THREADS - 1).collect::<Vec<_>>());
+    }
+}
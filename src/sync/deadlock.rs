@@ -0,0 +1,353 @@
+use super::{NoStarveMutex, Reacquire, WantGuard};
+use std::cell::RefCell;
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+/// Identity of a lock shared by all its handles, optionally at a level of a declared lock hierarchy.
+///
+/// Locks with levels must be acquired in increasing order of level.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct LockId {
+    id: usize,
+    level: Option<u32>,
+}
+impl Default for LockId {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl LockId {
+    pub fn new() -> Self {
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+        Self {
+            id: NEXT.fetch_add(1, Ordering::SeqCst),
+            level: None,
+        }
+    }
+    pub fn with_level(level: u32) -> Self {
+        Self {
+            level: Some(level),
+            ..Self::new()
+        }
+    }
+}
+
+/// Locks that some thread wanted while holding each lock, across all threads, by id
+static ORDER: Mutex<BTreeMap<usize, BTreeSet<usize>>> = Mutex::new(BTreeMap::new());
+thread_local! {
+    /// Locks the current thread holds, in the order it acquired them
+    static HELD: RefCell<Vec<LockId>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Records that the current thread wants the lock while holding the others, or panics if that may deadlock
+fn want(wanted: LockId) {
+    let held = HELD.with(|held| held.borrow().clone());
+    if held.contains(&wanted) {
+        panic!("Deadlock: {wanted:?} wanted again by the thread holding it");
+    }
+    if let Some(&higher) = (held.iter())
+        .filter(|h| matches!((h.level, wanted.level), (Some(l), Some(w)) if l >= w))
+        .max_by_key(|h| h.level)
+    {
+        panic!("Lock hierarchy violated: {wanted:?} wanted while holding {higher:?}");
+    }
+    let mut order = ORDER.lock().unwrap_or_else(|e| e.into_inner());
+    // Some thread held `wanted` while wanting one of the held locks, so both threads may wait for each other
+    if let Some(cycle) = held.iter().find_map(|h| path(&order, wanted.id, h.id)) {
+        drop(order);
+        panic!("Potential deadlock: lock order cycle {cycle:?} closed by wanting {wanted:?} while holding {held:?}");
+    }
+    for h in held {
+        order.entry(h.id).or_default().insert(wanted.id);
+    }
+}
+
+/// Lock ids from `from` to `to` in the lock order, if any path leads there
+fn path(order: &BTreeMap<usize, BTreeSet<usize>>, from: usize, to: usize) -> Option<Vec<usize>> {
+    let (mut visited, mut stack) = (BTreeSet::new(), vec![vec![from]]);
+    while let Some(path) = stack.pop() {
+        let last = *path.last().unwrap();
+        if last == to {
+            return Some(path);
+        }
+        if !visited.insert(last) {
+            continue;
+        }
+        for &next in order.get(&last).into_iter().flatten() {
+            stack.push([path.as_slice(), &[next]].concat());
+        }
+    }
+    None
+}
+
+fn acquire(id: LockId) {
+    HELD.with(|held| held.borrow_mut().push(id));
+}
+/// Releases the most recent acquisition, since guards may drop in any order
+fn release(id: LockId) {
+    HELD.with(|held| {
+        let mut held = held.borrow_mut();
+        if let Some(i) = held.iter().rposition(|&h| h == id) {
+            held.remove(i);
+        }
+    });
+}
+
+/// Handle of a `NoStarveMutex` that panics instead of wanting a lock in an order that may deadlock with other threads
+/// (lock order checking).
+///
+/// Every thread records which locks it holds while it wants another, across all `Tracked` locks of the process. A
+/// thread that wants lock `a` while holding `b` panics if any thread ever wanted `b` while holding `a`, even if they
+/// did not deadlock this time. It also panics if it wants a lock it holds already, or one at a lower or the same level
+/// of the declared hierarchy as a lock it holds.
+///
+/// # Examples
+/// ```should_panic
+/// use rads::sync::deadlock::{LockId, Tracked};
+/// use rads::sync::NoStarveMutex;
+///
+/// let (a, b) = (LockId::new(), LockId::new());
+/// let (mu_a, _) = rads::sync::peterson::Peterson::binary_mutex();
+/// let (mu_b, _) = rads::sync::peterson::Peterson::binary_mutex();
+/// let (mut mu_a, mut mu_b) = (Tracked::new(mu_a, a), Tracked::new(mu_b, b));
+/// {
+///     let _a = mu_a.lock();
+///     let _b = mu_b.lock();
+/// }
+/// let _b = mu_b.lock();
+/// // Would deadlock with a thread locking a, then b
+/// let _a = mu_a.lock();
+/// ```
+pub struct Tracked<M> {
+    mu: M,
+    id: LockId,
+}
+pub struct TrackedWant<Want> {
+    /// None once it waited
+    want: Option<Want>,
+    id: LockId,
+}
+pub struct TrackedGuard<Guard> {
+    guard: Guard,
+    id: LockId,
+}
+impl<M> Tracked<M> {
+    /// Tracks the handle as the lock `id`, which every handle of the same lock must share
+    pub fn new(mu: M, id: LockId) -> Self {
+        Self { mu, id }
+    }
+    pub fn into_inner(self) -> M {
+        self.mu
+    }
+}
+
+#[allow(drop_bounds)]
+impl<'a, M, Guard, Want> NoStarveMutex<'a, TrackedGuard<Guard>, TrackedWant<Want>> for Tracked<M>
+where
+    M: NoStarveMutex<'a, Guard, Want>,
+    Guard: Drop + 'a,
+    Want: WantGuard<'a, Guard> + 'a,
+{
+    fn want_lock(&'a mut self) -> TrackedWant<Want> {
+        want(self.id);
+        TrackedWant {
+            want: Some(self.mu.want_lock()),
+            id: self.id,
+        }
+    }
+    // Never waits, so cannot deadlock whatever the order
+    fn try_lock(&'a mut self) -> Option<TrackedGuard<Guard>> {
+        let guard = self.mu.try_lock()?;
+        acquire(self.id);
+        Some(TrackedGuard { guard, id: self.id })
+    }
+    // Lets the handle give up however it must, e.g. the cohort lock's
+    fn lock_timeout(&'a mut self, dur: std::time::Duration) -> Option<TrackedGuard<Guard>> {
+        want(self.id);
+        let guard = self.mu.lock_timeout(dur)?;
+        acquire(self.id);
+        Some(TrackedGuard { guard, id: self.id })
+    }
+}
+
+#[allow(drop_bounds)]
+impl<'a, Guard, Want> WantGuard<'a, TrackedGuard<Guard>> for TrackedWant<Want>
+where
+    Guard: Drop + 'a,
+    Want: WantGuard<'a, Guard>,
+{
+    fn wait(mut self) -> TrackedGuard<Guard> {
+        let guard = self.want.take().unwrap().wait();
+        acquire(self.id);
+        TrackedGuard { guard, id: self.id }
+    }
+    fn try_wait(mut self) -> Result<TrackedGuard<Guard>, Self> {
+        match self.want.take().unwrap().try_wait() {
+            Ok(guard) => {
+                acquire(self.id);
+                Ok(TrackedGuard { guard, id: self.id })
+            }
+            Err(want) => {
+                self.want = Some(want);
+                Err(self)
+            }
+        }
+    }
+    fn is_ready(&self) -> bool {
+        self.want.as_ref().unwrap().is_ready()
+    }
+}
+impl<Want> Drop for TrackedWant<Want> {
+    fn drop(&mut self) {
+        // The inner want gives up its place by itself, and was never held
+    }
+}
+
+impl<Guard> Drop for TrackedGuard<Guard> {
+    fn drop(&mut self) {
+        release(self.id);
+    }
+}
+impl<Guard: Reacquire> Reacquire for TrackedGuard<Guard> {
+    fn reacquire(self, f: impl FnOnce()) -> Self {
+        let id = self.id;
+        release(id);
+        let this = std::mem::ManuallyDrop::new(self);
+        // SAFETY: moved out of the guard that is never dropped, which would release again
+        let guard = unsafe { std::ptr::read(&this.guard) };
+        let guard = guard.reacquire(|| {
+            f();
+            want(id);
+        });
+        acquire(id);
+        Self { guard, id }
+    }
+}
+
+impl<Guard> std::ops::Deref for TrackedGuard<Guard> {
+    type Target = Guard;
+    fn deref(&self) -> &Guard {
+        &self.guard
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::sync::deadlock::{LockId, Tracked};
+    use crate::sync::lamports_bakery::{Bakery, BakeryN};
+    use crate::sync::peterson::Peterson;
+    use crate::sync::{Condvar, NoStarveMutex};
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+    use std::sync::Arc;
+
+    fn bakery(id: LockId) -> Vec<Tracked<BakeryN>> {
+        let bakery = Arc::new(Bakery::new(2));
+        (0..2)
+            .map(|n| Tracked::new(BakeryN::new(n, &bakery), id))
+            .collect()
+    }
+
+    #[test]
+    fn same_order() {
+        let (mut a, mut b) = (bakery(LockId::new()), bakery(LockId::new()));
+        let (mut a1, mut b1) = (a.pop().unwrap(), b.pop().unwrap());
+        let th = std::thread::spawn(move || {
+            for _ in 0..100 {
+                let _a = a1.lock();
+                let _b = b1.lock();
+            }
+        });
+        for _ in 0..100 {
+            let _a = a[0].lock();
+            let _b = b[0].lock();
+        }
+        th.join().unwrap();
+    }
+
+    #[test]
+    fn cycle_across_threads() {
+        let (mut a, mut b) = (bakery(LockId::new()), bakery(LockId::new()));
+        let (mut a1, mut b1) = (a.pop().unwrap(), b.pop().unwrap());
+        std::thread::spawn(move || {
+            let _a = a1.lock();
+            let _b = b1.lock();
+        })
+        .join()
+        .unwrap();
+        let _b = b[0].lock();
+        let locked = catch_unwind(AssertUnwindSafe(|| drop(a[0].lock())));
+        assert!(locked.is_err(), "Expect b then a to close the cycle");
+        // Gave up before it wanted a, so a stays free
+        assert!(a[0].try_lock().is_some());
+    }
+
+    #[test]
+    fn longer_cycle() {
+        let ids = [LockId::new(), LockId::new(), LockId::new()];
+        let mut locks: Vec<_> = ids.iter().map(|&id| bakery(id).pop().unwrap()).collect();
+        let [a, b, c] = &mut locks[..] else {
+            unreachable!()
+        };
+        drop((a.lock(), b.lock()));
+        drop((b.lock(), c.lock()));
+        let _c = c.lock();
+        assert!(catch_unwind(AssertUnwindSafe(|| drop(a.lock()))).is_err());
+    }
+
+    #[test]
+    fn hierarchy() {
+        let (mut low, mut high) = (
+            bakery(LockId::with_level(1)).pop().unwrap(),
+            bakery(LockId::with_level(2)).pop().unwrap(),
+        );
+        drop((low.lock(), high.lock()));
+        let _high = high.lock();
+        let locked = catch_unwind(AssertUnwindSafe(|| drop(low.lock())));
+        assert!(
+            locked.is_err(),
+            "Expect level 1 after level 2 to violate the hierarchy"
+        );
+    }
+
+    #[test]
+    fn relocking() {
+        let (a, b) = Peterson::binary_mutex();
+        let id = LockId::new();
+        let (mut a, mut b) = (Tracked::new(a, id), Tracked::new(b, id));
+        let _a = a.lock();
+        // Both handles of the same lock, which would wait forever
+        assert!(catch_unwind(AssertUnwindSafe(|| drop(b.lock()))).is_err());
+    }
+
+    #[test]
+    fn releases_out_of_order() {
+        let (mut a, mut b) = (bakery(LockId::new()), bakery(LockId::new()));
+        let guard_a = a[0].lock();
+        let guard_b = b[0].lock();
+        drop(guard_a);
+        drop(guard_b);
+        // Neither is held anymore
+        drop((a[1].lock(), b[1].lock()));
+    }
+
+    #[test]
+    fn condvar_releases_while_waiting() {
+        let (a, b) = Peterson::binary_mutex();
+        let id = LockId::new();
+        let (mut a, mut b) = (Tracked::new(a, id), Tracked::new(b, id));
+        let cv = Arc::new(Condvar::default());
+        let guard = a.lock();
+        let th = std::thread::spawn({
+            let cv = cv.clone();
+            move || {
+                drop(b.lock());
+                cv.notify_all();
+            }
+        });
+        // Would panic if it still counted as held when locking again
+        let guard = cv.wait(guard);
+        drop(guard);
+        th.join().unwrap();
+    }
+}
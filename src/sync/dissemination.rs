@@ -0,0 +1,155 @@
+use super::Barrier;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Barrier for `n` threads that signal each other in `ceil(log2(n))` rounds, without any thread or counter that all
+/// of them contend on (Hensgen-Finkel-Manber dissemination barrier).
+///
+/// In round `r`, thread `i` signals thread `i + 2^r` and waits for the signal of thread `i - 2^r`, modulo `n`. After
+/// the last round, every thread has heard, directly or not, from every other thread, so all of them arrived. Each
+/// thread alternates between 2 sets of flags, and flips its sense every other phase, so that the barrier needs no
+/// reset (Mellor-Crummey-Scott).
+///
+/// The rounds only exchange point-to-point signals, so [`schedule`] also serves message-passing barriers.
+///
+/// # Examples
+/// ```
+/// use rads::sync::dissemination::{Dissemination, DisseminationN};
+/// use rads::sync::Barrier;
+/// use std::sync::atomic::{AtomicUsize, Ordering};
+///
+/// let arrived = std::sync::Arc::new(AtomicUsize::new(0));
+/// let barrier = std::sync::Arc::new(Dissemination::new(4));
+/// let ths = (0..4)
+///     .map(|n| {
+///         let arrived = arrived.clone();
+///         let mut barrier = DisseminationN::new(n, &barrier);
+///         std::thread::spawn(move || {
+///             arrived.fetch_add(1, Ordering::SeqCst);
+///             barrier.wait();
+///             assert_eq!(arrived.load(Ordering::SeqCst), 4);
+///         })
+///     })
+///     .collect::<Vec<_>>();
+/// ths.into_iter().for_each(|th| th.join().unwrap());
+/// ```
+pub struct Dissemination {
+    /// Flags that each thread's partners raise, by parity then round
+    flags: Vec<[Vec<AtomicBool>; 2]>,
+}
+impl Dissemination {
+    pub fn new(size: usize) -> Self {
+        assert!(size > 1, "Do you really need a barrier of size {size}?");
+        let rounds = || (0..rounds(size)).map(|_| AtomicBool::new(false)).collect();
+        Self {
+            flags: (0..size).map(|_| [rounds(), rounds()]).collect(),
+        }
+    }
+}
+
+/// Number of rounds for `size` threads, i.e. `ceil(log2(size))`
+pub fn rounds(size: usize) -> usize {
+    size.next_power_of_two().trailing_zeros() as usize
+}
+/// Thread that thread `n` signals, and thread whose signal it waits for, in each round among `size` threads
+pub fn schedule(n: usize, size: usize) -> impl Iterator<Item = (usize, usize)> {
+    (0..rounds(size)).map(move |round| {
+        let distance = 1 << round;
+        ((n + distance) % size, (n + size - distance) % size)
+    })
+}
+
+pub struct DisseminationN {
+    n: usize,
+    barrier: std::sync::Arc<Dissemination>,
+    /// Set of flags for the current phase
+    parity: usize,
+    /// Value the flags take in the current phase
+    sense: bool,
+}
+impl DisseminationN {
+    // Does not check if index is taken.
+    pub fn new(n: usize, barrier: &std::sync::Arc<Dissemination>) -> Self {
+        let size = barrier.flags.len();
+        assert!(n < size, "0-based user index {n} >= barrier of size={size}");
+        Self {
+            n,
+            barrier: barrier.clone(),
+            parity: 0,
+            sense: true,
+        }
+    }
+}
+
+impl Barrier for DisseminationN {
+    fn wait(&mut self) {
+        let (flags, parity, sense) = (&self.barrier.flags, self.parity, self.sense);
+        // Algorithm requires no reordering of variables, hence SeqCst
+        for (round, (to, _)) in schedule(self.n, flags.len()).enumerate() {
+            flags[to][parity][round].store(sense, Ordering::SeqCst);
+            while flags[self.n][parity][round].load(Ordering::SeqCst) != sense {
+                std::thread::yield_now();
+            }
+        }
+        // The other set of flags still holds the previous sense, until it is next used
+        if parity == 1 {
+            self.sense = !sense;
+        }
+        self.parity = 1 - parity;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::sync::dissemination::{rounds, schedule, Dissemination, DisseminationN};
+    use crate::sync::Barrier;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    const PHASES: usize = 1_000;
+
+    /// Checks that no thread leaves a phase before every thread arrived in it
+    fn phases(size: usize) {
+        let barrier = Arc::new(Dissemination::new(size));
+        let arrived: Arc<Vec<_>> = Arc::new((0..PHASES).map(|_| AtomicUsize::new(0)).collect());
+        let ths: Vec<_> = (0..size)
+            .map(|n| {
+                let arrived = arrived.clone();
+                let mut barrier = DisseminationN::new(n, &barrier);
+                std::thread::spawn(move || {
+                    for phase in 0..PHASES {
+                        arrived[phase].fetch_add(1, Ordering::SeqCst);
+                        barrier.wait();
+                        assert_eq!(arrived[phase].load(Ordering::SeqCst), size);
+                    }
+                })
+            })
+            .collect();
+        ths.into_iter().for_each(|th| th.join().unwrap());
+    }
+
+    #[test]
+    fn schedule_reaches_everyone() {
+        assert_eq!((rounds(2), rounds(4), rounds(5)), (1, 2, 3));
+        assert_eq!(schedule(4, 5).collect::<Vec<_>>(), [(0, 3), (1, 2), (3, 0)]);
+        for size in 2..=9 {
+            // Threads each thread heard from, directly or not, after each round
+            let mut heard: Vec<Vec<bool>> = (0..size)
+                .map(|n| (0..size).map(|m| m == n).collect())
+                .collect();
+            for round in 0..rounds(size) {
+                let before = heard.clone();
+                for (n, heard) in heard.iter_mut().enumerate() {
+                    let (_, from) = schedule(n, size).nth(round).unwrap();
+                    (0..size).for_each(|m| heard[m] |= before[from][m]);
+                }
+            }
+            assert!(heard.iter().flatten().all(|&h| h), "size={size}");
+        }
+    }
+
+    #[test]
+    fn waits_for_every_thread() {
+        phases(2);
+        phases(4);
+        phases(5);
+    }
+}
@@ -0,0 +1,296 @@
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+/// Scheme by which lock-free structures free nodes that other threads may still read after unlinking them, e.g.
+/// epoch-based reclamation, so that each structure can take whichever scheme suits its readers.
+pub trait Reclaim {
+    type Guard<'a>: Retire
+    where
+        Self: 'a;
+    // Protects whatever shared nodes the thread loads while the guard lives from being freed
+    fn pin(&mut self) -> Self::Guard<'_>;
+}
+
+/// Guard of a `Reclaim` scheme, through which the thread frees the nodes it unlinked once no other thread reads them.
+pub trait Retire {
+    /// Frees `ptr`, which no thread can load anymore since it was unlinked, once no thread that may have loaded it is
+    /// pinned anymore.
+    ///
+    /// # Safety
+    /// `ptr` must come from `Box::into_raw`, and be retired only once.
+    unsafe fn retire<T: Send + 'static>(&mut self, ptr: *mut T);
+    // Runs `f` once no thread that was pinned when it was deferred is pinned anymore
+    fn defer(&mut self, f: impl FnOnce() + Send + 'static);
+}
+
+/// Deferred destruction once every pinned thread moved on to a later epoch (epoch-based reclamation, Fraser).
+///
+/// A thread pins the current epoch before loading shared nodes, and unpins after. It defers freeing a node it unlinked
+/// to a bag of the current epoch. The epoch advances once every pinned thread saw it, so that once it advanced
+/// twice since a bag's epoch, no pinned thread can still hold a node of that bag, which then frees. Unlike hazard
+/// pointers, readers only publish their epoch instead of every node they load, but a thread that stays pinned holds back
+/// every thread's garbage.
+///
+/// # Examples
+/// ```
+/// use rads::sync::epoch::{Epoch, EpochN, Reclaim, Retire};
+/// use std::sync::atomic::{AtomicPtr, Ordering};
+///
+/// let config = std::sync::Arc::new(AtomicPtr::new(Box::into_raw(Box::new(1))));
+/// let epoch = std::sync::Arc::new(Epoch::new(2));
+/// let (mut reader, mut writer) = (EpochN::new(0, &epoch), EpochN::new(1, &epoch));
+///
+/// let pinned = reader.pin();
+/// // Safe to read, since the writer defers freeing it until the reader unpins
+/// let read = unsafe { &*config.load(Ordering::SeqCst) };
+/// let old = config.swap(Box::into_raw(Box::new(2)), Ordering::SeqCst);
+/// unsafe { writer.pin().retire(old) };
+/// assert_eq!(*read, 1);
+/// drop(pinned);
+/// # drop(writer);
+/// # drop(reader);
+/// # drop(unsafe { Box::from_raw(config.load(Ordering::SeqCst)) });
+/// ```
+pub struct Epoch {
+    epoch: AtomicUsize,
+    locals: Vec<Local>,
+    /// Garbage of handles that dropped before it could be freed, freed once the collector drops
+    orphans: std::sync::Mutex<Vec<Deferred>>,
+}
+type Deferred = Box<dyn FnOnce() + Send>;
+
+#[derive(Default)]
+struct Local {
+    pinned: AtomicBool,
+    /// Epoch the thread saw when it last pinned
+    epoch: AtomicUsize,
+}
+
+impl Epoch {
+    pub fn new(size: usize) -> Self {
+        assert!(size > 0, "Expect at least 1 thread");
+        Self {
+            epoch: AtomicUsize::new(0),
+            locals: (0..size).map(|_| Local::default()).collect(),
+            orphans: std::sync::Mutex::new(Vec::new()),
+        }
+    }
+    /// Number of times the epoch advanced
+    pub fn epoch(&self) -> usize {
+        self.epoch.load(Ordering::SeqCst)
+    }
+    /// Advances the epoch if every pinned thread saw it, and returns the current one
+    fn try_advance(&self) -> usize {
+        let epoch = self.epoch.load(Ordering::SeqCst);
+        let caught_up = (self.locals.iter()).all(|local| {
+            !local.pinned.load(Ordering::SeqCst) || local.epoch.load(Ordering::SeqCst) == epoch
+        });
+        if caught_up {
+            // Fails only if another thread advanced it in the meantime
+            let _ =
+                self.epoch
+                    .compare_exchange(epoch, epoch + 1, Ordering::SeqCst, Ordering::SeqCst);
+        }
+        self.epoch.load(Ordering::SeqCst)
+    }
+}
+impl Drop for Epoch {
+    fn drop(&mut self) {
+        self.orphans.get_mut().unwrap().drain(..).for_each(|f| f());
+    }
+}
+
+pub struct EpochN {
+    n: usize,
+    epoch: std::sync::Arc<Epoch>,
+    /// Garbage deferred at each epoch, oldest first
+    bags: std::collections::VecDeque<(usize, Vec<Deferred>)>,
+}
+pub struct EpochGuard<'a>(&'a mut EpochN);
+impl EpochN {
+    // Does not check if index is taken.
+    pub fn new(n: usize, epoch: &std::sync::Arc<Epoch>) -> Self {
+        let size = epoch.locals.len();
+        assert!(n < size, "0-based user index {n} >= epoch of size={size}");
+        Self {
+            n,
+            epoch: epoch.clone(),
+            bags: std::collections::VecDeque::new(),
+        }
+    }
+    /// Number of deferred functions not run yet
+    pub fn deferred(&self) -> usize {
+        self.bags.iter().map(|(_, bag)| bag.len()).sum()
+    }
+    fn local(&self) -> &Local {
+        &self.epoch.locals[self.n]
+    }
+    /// Runs what was deferred at least 2 epochs ago
+    fn collect(&mut self, epoch: usize) {
+        while (self.bags.front()).is_some_and(|&(deferred_at, _)| deferred_at + 2 <= epoch) {
+            let (_, bag) = self.bags.pop_front().unwrap();
+            bag.into_iter().for_each(|f| f());
+        }
+    }
+}
+impl Reclaim for EpochN {
+    type Guard<'a> = EpochGuard<'a>;
+    fn pin(&mut self) -> EpochGuard<'_> {
+        let local = self.local();
+        // Pinned before publishing the epoch it saw, so that the epoch can't advance twice past it in between
+        local.pinned.store(true, Ordering::SeqCst);
+        local.epoch.store(self.epoch.epoch(), Ordering::SeqCst);
+        let epoch = self.epoch.try_advance();
+        self.collect(epoch);
+        EpochGuard(self)
+    }
+}
+impl Drop for EpochN {
+    fn drop(&mut self) {
+        let bags = self.bags.drain(..).flat_map(|(_, bag)| bag);
+        self.epoch.orphans.lock().unwrap().extend(bags);
+    }
+}
+
+impl Retire for EpochGuard<'_> {
+    unsafe fn retire<T: Send + 'static>(&mut self, ptr: *mut T) {
+        // SAFETY: the caller owns `ptr`, which only runs once no thread can read it
+        let ptr = unsafe { Box::from_raw(ptr) };
+        self.defer(move || drop(ptr));
+    }
+    fn defer(&mut self, f: impl FnOnce() + Send + 'static) {
+        // The current epoch rather than the one this thread pinned at, which may be older, since threads pinned at
+        // either may still read what it unlinked
+        let epoch = self.0.epoch.epoch();
+        match self.0.bags.back_mut() {
+            Some((deferred_at, bag)) if *deferred_at == epoch => bag.push(Box::new(f)),
+            _ => self.0.bags.push_back((epoch, vec![Box::new(f)])),
+        }
+    }
+}
+impl Drop for EpochGuard<'_> {
+    fn drop(&mut self) {
+        self.0.local().pinned.store(false, Ordering::SeqCst);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::sync::epoch::{Epoch, EpochN, Reclaim, Retire};
+    use std::sync::atomic::{AtomicPtr, AtomicUsize, Ordering};
+    use std::sync::Arc;
+    const N_THREADS: usize = 4;
+    const WORK: usize = 1_000;
+
+    /// Lock-free stack (Treiber) that frees popped nodes by any `Reclaim` scheme
+    struct Stack<T> {
+        head: AtomicPtr<Node<T>>,
+    }
+    struct Node<T> {
+        value: T,
+        next: *mut Node<T>,
+    }
+    unsafe impl<T: Send> Send for Node<T> {}
+    unsafe impl<T: Send> Sync for Stack<T> {}
+    impl<T: Copy + Send + 'static> Stack<T> {
+        fn push(&self, value: T) {
+            let node = Box::into_raw(Box::new(Node {
+                value,
+                next: self.head.load(Ordering::SeqCst),
+            }));
+            // SAFETY: not shared until pushed
+            while let Err(head) = (self.head).compare_exchange(
+                unsafe { (*node).next },
+                node,
+                Ordering::SeqCst,
+                Ordering::SeqCst,
+            ) {
+                unsafe { (*node).next = head };
+            }
+        }
+        fn pop(&self, reclaim: &mut impl Reclaim) -> Option<T> {
+            let mut guard = reclaim.pin();
+            loop {
+                let head = self.head.load(Ordering::SeqCst);
+                // SAFETY: pinned, so no other thread frees it even if it pops it first
+                let node = unsafe { head.as_ref() }?;
+                if (self.head)
+                    .compare_exchange(head, node.next, Ordering::SeqCst, Ordering::SeqCst)
+                    .is_ok()
+                {
+                    let value = node.value;
+                    // SAFETY: unlinked, and only the thread that unlinked it retires it
+                    unsafe { guard.retire(head) };
+                    return Some(value);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn defers_while_pinned() {
+        let epoch = Arc::new(Epoch::new(2));
+        let (mut a, mut b) = (EpochN::new(0, &epoch), EpochN::new(1, &epoch));
+        let freed = Arc::new(AtomicUsize::new(0));
+        let pinned = a.pin();
+        b.pin().defer({
+            let freed = freed.clone();
+            move || {
+                freed.fetch_add(1, Ordering::SeqCst);
+            }
+        });
+        for _ in 0..10 {
+            drop(b.pin());
+        }
+        // Holds back the epoch
+        assert_eq!(freed.load(Ordering::SeqCst), 0);
+        assert_eq!(b.deferred(), 1);
+        drop(pinned);
+        for _ in 0..3 {
+            drop(b.pin());
+        }
+        assert_eq!(freed.load(Ordering::SeqCst), 1);
+        assert_eq!(b.deferred(), 0);
+    }
+
+    #[test]
+    fn frees_orphans() {
+        let epoch = Arc::new(Epoch::new(2));
+        let freed = Arc::new(AtomicUsize::new(0));
+        let mut a = EpochN::new(0, &epoch);
+        a.pin().defer({
+            let freed = freed.clone();
+            move || {
+                freed.fetch_add(1, Ordering::SeqCst);
+            }
+        });
+        drop(a);
+        assert_eq!(freed.load(Ordering::SeqCst), 0);
+        drop(epoch);
+        assert_eq!(freed.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn lock_free_stack() {
+        let stack = Arc::new(Stack {
+            head: AtomicPtr::new(std::ptr::null_mut()),
+        });
+        let epoch = Arc::new(Epoch::new(N_THREADS));
+        let ths: Vec<_> = (0..N_THREADS)
+            .map(|n| {
+                let (stack, mut reclaim) = (stack.clone(), EpochN::new(n, &epoch));
+                std::thread::spawn(move || {
+                    (0..WORK)
+                        .map(|i| {
+                            stack.push(n * WORK + i);
+                            stack.pop(&mut reclaim).unwrap()
+                        })
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+        let mut popped: Vec<_> = ths.into_iter().flat_map(|th| th.join().unwrap()).collect();
+        popped.sort_unstable();
+        assert_eq!(popped, (0..WORK * N_THREADS).collect::<Vec<_>>());
+        assert!(stack.head.load(Ordering::SeqCst).is_null());
+    }
+}
@@ -0,0 +1,192 @@
+use super::Mutex;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+/// N-ary mutex that takes `O(1)` steps when uncontended, and `O(n)` otherwise (Lamport's fast mutual exclusion).
+///
+/// A thread announces itself in `x`, then claims `y` if free, and enters right away if nobody overwrote `x` in between.
+/// Otherwise, it waits for every thread that wants the lock to finish its attempt, and enters only if it still holds
+/// `y`, starting over if not. No thread blocks the others forever, but one may keep losing the race, so it is not
+/// starvation-free.
+///
+/// # Examples
+/// ```
+/// use crate::rads::sync::fast_lock::{FastLock, FastLockN};
+/// use std::sync::atomic::Ordering;
+/// use rads::sync::Mutex;
+///
+/// let data = std::sync::Arc::new(std::sync::atomic::AtomicI32::new(0));
+/// let mu = std::sync::Arc::new(FastLock::new(4));
+/// let ths = (0..4)
+///     .map(|n| {
+///         let data = data.clone();
+///         let mut mu = FastLockN::new(n, &mu);
+///         let n = n as i32 + 1;
+///         std::thread::spawn(move || {
+///             for _ in 0..1_000 {
+///                 let _guard = mu.lock();
+///                 let i = data.load(Ordering::Relaxed);
+///                 data.store(i + n, Ordering::Relaxed);
+///             }
+///         })
+///     })
+///     .collect::<Vec<_>>();
+/// ths.into_iter().for_each(|th| th.join().unwrap());
+/// assert_eq!(data.load(Ordering::Relaxed), 1_000 * (1 + 2 + 3 + 4));
+/// ```
+pub struct FastLock {
+    /// Last thread to start an attempt, by 1-based index
+    x: AtomicUsize,
+    /// Thread that claimed the lock, by 1-based index, or `FREE`
+    y: AtomicUsize,
+    /// Whether each thread is attempting to lock
+    b: Vec<AtomicBool>,
+}
+impl FastLock {
+    const FREE: usize = 0;
+    pub fn new(size: usize) -> Self {
+        assert!(size > 1, "Do you really need a mutex of size {size}?");
+        Self {
+            x: AtomicUsize::new(FastLock::FREE),
+            y: AtomicUsize::new(FastLock::FREE),
+            b: (0..size).map(|_| AtomicBool::new(false)).collect(),
+        }
+    }
+}
+
+pub struct FastLockN {
+    n: usize,
+    fast: std::sync::Arc<FastLock>,
+}
+pub struct FastGuard<'a> {
+    mu: &'a FastLockN,
+    fast_path: bool,
+}
+impl FastLockN {
+    // Does not check if index is taken.
+    pub fn new(n: usize, fast: &std::sync::Arc<FastLock>) -> Self {
+        let size = fast.b.len();
+        assert!(
+            n < size,
+            "0-based user index {n} >= fast lock of size={size}"
+        );
+        Self {
+            n,
+            fast: fast.clone(),
+        }
+    }
+}
+
+impl<'a> Mutex<'a, FastGuard<'a>> for FastLockN {
+    fn lock(&'a mut self) -> FastGuard<'a> {
+        // Algorithm requires no reordering of variables, hence SeqCst
+        let (f, me) = (&*self.fast, self.n + 1);
+        let mut fast_path = true;
+        let await_free = |y: &AtomicUsize| {
+            while y.load(Ordering::SeqCst) != FastLock::FREE {
+                std::thread::yield_now();
+            }
+        };
+        loop {
+            f.b[self.n].store(true, Ordering::SeqCst);
+            f.x.store(me, Ordering::SeqCst);
+            if f.y.load(Ordering::SeqCst) != FastLock::FREE {
+                // Held, or claimed by a contender
+                fast_path = false;
+                f.b[self.n].store(false, Ordering::SeqCst);
+                await_free(&f.y);
+                continue;
+            }
+            f.y.store(me, Ordering::SeqCst);
+            if f.x.load(Ordering::SeqCst) == me {
+                break;
+            }
+            // Another thread started an attempt in between, so wait for every attempt to settle who claimed y last
+            fast_path = false;
+            f.b[self.n].store(false, Ordering::SeqCst);
+            for b in &f.b {
+                while b.load(Ordering::SeqCst) {
+                    std::thread::yield_now();
+                }
+            }
+            if f.y.load(Ordering::SeqCst) == me {
+                break;
+            }
+            await_free(&f.y);
+        }
+        FastGuard {
+            mu: self,
+            fast_path,
+        }
+    }
+}
+
+impl FastGuard<'_> {
+    /// Whether the lock was taken without waiting for any other thread, in `O(1)` steps
+    pub fn took_fast_path(&self) -> bool {
+        self.fast_path
+    }
+}
+
+impl Drop for FastGuard<'_> {
+    fn drop(&mut self) {
+        let f = &self.mu.fast;
+        f.y.store(FastLock::FREE, Ordering::SeqCst);
+        f.b[self.mu.n].store(false, Ordering::SeqCst);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::sync::{
+        fast_lock::{FastLock, FastLockN},
+        Mutex,
+    };
+    use std::sync::atomic::Ordering;
+    use std::sync::Arc;
+
+    #[test]
+    fn mutual_exclusion() {
+        let mu = Arc::new(FastLock::new(4));
+        crate::sync::tests::mutual_exclusion(|n| {
+            let mut mu = FastLockN::new(n, &mu);
+            move |f| {
+                let _guard = mu.lock();
+                f()
+            }
+        });
+    }
+
+    #[test]
+    fn fast_path_when_uncontended() {
+        let mu = Arc::new(FastLock::new(3));
+        let mut ps: Vec<_> = (0..3).map(|n| FastLockN::new(n, &mu)).collect();
+        for _ in 0..10 {
+            for p in &mut ps {
+                assert!(p.lock().took_fast_path());
+            }
+        }
+        assert_eq!(mu.y.load(Ordering::SeqCst), FastLock::FREE);
+    }
+
+    #[test]
+    fn slow_path_when_contended() {
+        let mu = Arc::new(FastLock::new(2));
+        let mut p0 = FastLockN::new(0, &mu);
+        let guard = p0.lock();
+        assert!(guard.took_fast_path());
+        let th = std::thread::spawn({
+            let mut p1 = FastLockN::new(1, &mu);
+            move || p1.lock().took_fast_path()
+        });
+        // Until p1 started its attempt
+        while mu.x.load(Ordering::SeqCst) != 2 {
+            std::thread::yield_now();
+        }
+        std::thread::yield_now();
+        assert!(!th.is_finished());
+        drop(guard);
+        assert!(!th.join().unwrap());
+        // Uncontended again
+        assert!(p0.lock().took_fast_path());
+    }
+}
@@ -0,0 +1,265 @@
+use super::{NoStarveMutex, Reacquire, SpinStrategy, Waiters, WantGuard, Yield};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// N-ary mutex to protect critical section fairly, by generalizing Peterson's to `n - 1` levels.
+///
+/// Each thread climbs through the levels, each of which lets at most all but one waiting thread through: the last to
+/// arrive is the victim, and waits there while any other thread is at the same level or above. Unlike the bakery, it
+/// is not first-come-first-served: it is starvation-free, so every waiting thread eventually enters, but not r-bounded
+/// for any r, since a waiting thread may be overtaken arbitrarily many times before it does.
+///
+/// # Examples
+/// ```
+/// use crate::rads::sync::filter::{FilterLock, FilterLockN};
+/// use std::sync::atomic::Ordering;
+/// use rads::sync::NoStarveMutex;
+///
+/// let data = std::sync::Arc::new(std::sync::atomic::AtomicI32::new(0));
+/// let mu = std::sync::Arc::new(FilterLock::new(4));
+/// let ths = (0..4)
+///     .map(|n| {
+///         let data = data.clone();
+///         let mut mu = FilterLockN::new(n, &mu);
+///         let n = n as i32 + 1;
+///         std::thread::spawn(move || {
+///             for _ in 0..1_000 {
+///                 let _guard = mu.lock();
+///                 let i = data.load(Ordering::Relaxed);
+///                 data.store(i + n, Ordering::Relaxed);
+///             }
+///         })
+///     })
+///     .collect::<Vec<_>>();
+/// ths.into_iter().for_each(|th| th.join().unwrap());
+/// assert_eq!(data.load(Ordering::Relaxed), 1_000 * (1 + 2 + 3 + 4));
+/// ```
+pub struct FilterLock {
+    /// Highest level each thread reached, where `FREE` is outside and `n - 1` is the critical section
+    levels: Vec<AtomicUsize>,
+    /// Last thread to enter each level
+    victims: Vec<AtomicUsize>,
+    waiters: Waiters,
+}
+impl FilterLock {
+    const FREE: usize = 0;
+    pub fn new(size: usize) -> Self {
+        assert!(size > 1, "Do you really need a mutex of size {size}?");
+        Self {
+            levels: (0..size)
+                .map(|_| AtomicUsize::new(FilterLock::FREE))
+                .collect(),
+            victims: (0..size).map(|_| AtomicUsize::new(0)).collect(),
+            waiters: Waiters::new(Yield, size),
+        }
+    }
+    /// Waits by `strategy`, e.g. parking instead of spinning during long critical sections
+    pub fn with_strategy(self, strategy: impl SpinStrategy + 'static) -> Self {
+        Self {
+            waiters: Waiters::new(strategy, self.levels.len()),
+            ..self
+        }
+    }
+
+    /// Enters `level` as its victim
+    fn enter(&self, n: usize, level: usize) {
+        // Algorithm requires no reordering of variables, hence SeqCst
+        self.levels[n].store(level, Ordering::SeqCst);
+        self.victims[level].store(n, Ordering::SeqCst);
+        // Frees the previous victim of `level`
+        self.waiters.unpark_all();
+    }
+    /// Leaves for `level`, below the current one, which may free those waiting at higher levels
+    fn leave(&self, n: usize, level: usize) {
+        self.levels[n].store(level, Ordering::SeqCst);
+        self.waiters.unpark_all();
+    }
+    /// Waits until another thread became the victim of `level`, or none is at `level` or above
+    fn wait(&self, n: usize, level: usize) {
+        self.waiters.wait_while(n, || self.must_wait(n, level));
+    }
+    fn must_wait(&self, n: usize, level: usize) -> bool {
+        self.victims[level].load(Ordering::SeqCst) == n && self.others_at(n, level)
+    }
+    /// Whether any thread other than `n` is at `level` or above
+    fn others_at(&self, n: usize, level: usize) -> bool {
+        (self.levels.iter().enumerate()).any(|(k, l)| k != n && l.load(Ordering::SeqCst) >= level)
+    }
+}
+
+pub struct FilterLockN {
+    n: usize,
+    filter: std::sync::Arc<FilterLock>,
+}
+pub struct FilterWant<'a>(Option<&'a FilterLockN>);
+pub struct FilterGuard<'a>(pub(super) &'a FilterLockN);
+impl FilterLockN {
+    // Does not check if index is taken.
+    pub fn new(n: usize, filter: &std::sync::Arc<FilterLock>) -> Self {
+        let size = filter.levels.len();
+        assert!(n < size, "0-based user index {n} >= filter of size={size}");
+        Self {
+            n,
+            filter: filter.clone(),
+        }
+    }
+}
+impl<'a> NoStarveMutex<'a, FilterGuard<'a>, FilterWant<'a>> for FilterLockN {
+    fn want_lock(&'a mut self) -> FilterWant<'a> {
+        // Entering the first level is the doorway, after which later threads are its victims
+        self.filter.enter(self.n, 1);
+        FilterWant(Some(self))
+    }
+}
+impl Reacquire for FilterGuard<'_> {
+    fn reacquire(self, f: impl FnOnce()) -> Self {
+        let mu = self.0;
+        drop(self);
+        f();
+        mu.filter.enter(mu.n, 1);
+        FilterWant(Some(mu)).wait()
+    }
+}
+
+impl<'a> WantGuard<'a, FilterGuard<'a>> for FilterWant<'a> {
+    fn wait(mut self) -> FilterGuard<'a> {
+        let f = self.0.take().unwrap();
+        f.filter.wait(f.n, 1);
+        for level in 2..f.filter.levels.len() {
+            f.filter.enter(f.n, level);
+            f.filter.wait(f.n, level);
+        }
+        FilterGuard(f)
+    }
+    // Climbs back down to the first level if it must wait at any level
+    fn try_wait(mut self) -> Result<FilterGuard<'a>, Self> {
+        let f = self.0.unwrap();
+        for level in 1..f.filter.levels.len() {
+            if level > 1 {
+                f.filter.enter(f.n, level);
+            }
+            if f.filter.must_wait(f.n, level) {
+                f.filter.leave(f.n, 1);
+                return Err(self);
+            }
+        }
+        self.0 = None;
+        Ok(FilterGuard(f))
+    }
+    // Would climb every level without waiting, since it would be the victim of each level above the first
+    fn is_ready(&self) -> bool {
+        let f = self.0.unwrap();
+        !f.filter.must_wait(f.n, 1) && !f.filter.others_at(f.n, 2)
+    }
+}
+impl Drop for FilterWant<'_> {
+    fn drop(&mut self) {
+        if let Some(f) = self.0 {
+            super::abandon();
+            FilterGuard(f); // reuse drop logic
+        }
+    }
+}
+
+impl Drop for FilterGuard<'_> {
+    fn drop(&mut self) {
+        self.0.filter.leave(self.0.n, FilterLock::FREE);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::sync::{
+        filter::{FilterLock, FilterLockN},
+        NoStarveMutex, WantGuard,
+    };
+    use std::sync::atomic::Ordering;
+
+    #[test]
+    fn mutual_exclusion() {
+        let mu = std::sync::Arc::new(FilterLock::new(4));
+        crate::sync::tests::mutual_exclusion(|n| {
+            let mut mu = FilterLockN::new(n, &mu);
+            move |f| {
+                let _guard = mu.lock();
+                f()
+            }
+        });
+    }
+
+    #[test]
+    fn no_starvation() {
+        let mu = std::sync::Arc::new(FilterLock::new(2));
+        // 1. Let p0 locks first, then p1 wants and blocks
+        let p0_first = std::sync::Arc::new(std::sync::Barrier::new(2));
+        let p1_wants = std::sync::Arc::new(std::sync::Barrier::new(2));
+        // 2. p0 release and immediately locks but blocks to p1 who's waiting
+        let p0_release = std::sync::Arc::new(std::sync::Barrier::new(2));
+        // 3. p1 wakes up and locks the mutex, ie no starvation
+        let p1_acquire = std::sync::Arc::new(std::sync::Barrier::new(2));
+        // 4. p1 releases, allowing p0 to lock
+        let p1_release = std::sync::Arc::new(std::sync::Barrier::new(2));
+        // 5. p0 wakes up and locks the mutex
+        let p0_reacquire = std::sync::Arc::new(std::sync::Barrier::new(2));
+
+        let th0 = {
+            let mut mu_a = FilterLockN::new(0, &mu);
+            let p0_first = p0_first.clone();
+            let p0_release = p0_release.clone();
+            let p0_reacquire = p0_reacquire.clone();
+            std::thread::spawn(move || {
+                let _guard = mu_a.lock();
+                p0_first.wait(); // (1)
+                p0_release.wait(); // (2)
+                drop(_guard);
+                let _guard = mu_a.lock(); // (4)
+                p0_reacquire.wait(); // (5)
+            })
+        };
+        let th1 = std::thread::spawn({
+            let mut mu_b = FilterLockN::new(1, &mu);
+            let p1_wants = p1_wants.clone();
+            let p1_release = p1_release.clone();
+            let p1_acquire = p1_acquire.clone();
+            move || {
+                p0_first.wait(); // (1)
+                let mut _want = mu_b.want_lock();
+                p1_wants.wait();
+                let _guard = _want.wait(); // (3)
+                p1_acquire.wait();
+                p1_release.wait();
+            }
+        });
+        p1_wants.wait(); // (1)
+        p0_release.wait(); // (2)
+        p1_acquire.wait(); // (3)
+        std::thread::yield_now(); // (4)
+        assert!(!th0.is_finished());
+        assert!(!th1.is_finished());
+        p1_release.wait();
+        th1.join().unwrap(); // (5)
+        std::thread::yield_now();
+        assert!(!th0.is_finished());
+        p0_reacquire.wait();
+    }
+
+    #[test]
+    fn try_lock() {
+        let mu = std::sync::Arc::new(FilterLock::new(3));
+        let (mut a, mut b, mut c) = (
+            FilterLockN::new(0, &mu),
+            FilterLockN::new(1, &mu),
+            FilterLockN::new(2, &mu),
+        );
+        let guard = a.try_lock().unwrap();
+        assert!(b.try_lock().is_none());
+        assert!(c.try_lock().is_none());
+        drop(guard);
+        assert!(mu.levels.iter().all(|l| l.load(Ordering::SeqCst) == 0));
+
+        let want = b.want_lock();
+        let guard = want.try_wait().ok().unwrap();
+        assert!(c.try_lock().is_none());
+        drop(guard);
+        assert!(c.try_lock().is_some());
+    }
+}
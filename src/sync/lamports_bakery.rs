@@ -1,6 +1,9 @@
-use super::NoStarveMutex;
+use super::shim::atomic::{AtomicBool, AtomicI64, Ordering};
+use super::shim::{store_load_fence, thread};
+use super::stats::{LockStats, Recorder};
+use super::{NoStarveMutex, Reacquire, Slots, SpinStrategy, Waiters, Yield};
 use crate::sync::WantGuard;
-use std::sync::atomic::Ordering;
+use std::sync::LockResult;
 
 /// N-ary mutex to protect critical section fairly.
 ///
@@ -30,19 +33,101 @@ use std::sync::atomic::Ordering;
 /// assert_eq!(data.load(Ordering::Relaxed), 10_000 * (1 + 2 + 3 + 4));
 /// ```
 pub struct Bakery {
-    q_nos: Vec<std::sync::atomic::AtomicI32>,
+    q_nos: Vec<AtomicI64>,
+    /// Largest queue number to hand out, beyond which newcomers wait for the queue to drain
+    q_no_limit: i64,
+    /// Whether each slot is claimed by a `BakeryHandle`
+    taken: Slots,
+    /// Whether a thread panicked while holding the lock
+    poisoned: AtomicBool,
+    waiters: Waiters,
+    /// Recorded only if constructed `with_stats`
+    stats: Option<Recorder>,
 }
 impl Bakery {
-    const ENTER: i32 = -1;
-    const FREE: i32 = 0;
+    const ENTER: i64 = -1;
+    const FREE: i64 = 0;
     pub fn new(size: usize) -> Self {
         assert!(size > 1, "Do you really need a mutex of size {size}?");
         Self {
-            q_nos: (0..size)
-                .map(|_| std::sync::atomic::AtomicI32::new(Bakery::FREE))
-                .collect(),
+            q_nos: (0..size).map(|_| AtomicI64::new(Bakery::FREE)).collect(),
+            q_no_limit: i64::MAX,
+            taken: Slots::new(size),
+            poisoned: AtomicBool::new(false),
+            waiters: Waiters::new(Yield, size),
+            stats: None,
         }
     }
+    /// Sized for as many threads as can run in parallel on this host, to `register` when the thread count isn't known
+    /// statically
+    pub fn for_current_host() -> Self {
+        Self::new(super::host_parallelism())
+    }
+    /// Waits by `strategy`, e.g. parking instead of spinning during long critical sections
+    pub fn with_strategy(self, strategy: impl SpinStrategy + 'static) -> Self {
+        Self {
+            waiters: Waiters::new(strategy, self.q_nos.len()),
+            ..self
+        }
+    }
+    /// Records how long each thread waits for the lock, retrievable with `stats()`, at the cost of serializing the
+    /// bookkeeping
+    pub fn with_stats(self) -> Self {
+        Self {
+            stats: Some(Recorder::new(self.q_nos.len())),
+            ..self
+        }
+    }
+    /// Stats recorded so far, if constructed `with_stats`
+    pub fn stats(&self) -> Option<LockStats> {
+        self.stats.as_ref().map(Recorder::stats)
+    }
+    /// Claims a free slot, which is released once the handle drops, or None if every slot is taken.
+    ///
+    /// Slots of `BakeryN::new` aren't claimed, so use either one or the other for the same bakery.
+    ///
+    /// # Examples
+    /// ```
+    /// use crate::rads::sync::lamports_bakery::Bakery;
+    /// use rads::sync::NoStarveMutex;
+    ///
+    /// let mu = std::sync::Arc::new(Bakery::new(2));
+    /// let mut a = mu.register().unwrap();
+    /// let b = mu.register().unwrap();
+    /// assert!(mu.register().is_none());
+    /// drop(b);
+    /// let c = std::thread::spawn({
+    ///     let mut c = mu.register().unwrap();
+    ///     move || drop(c.lock())
+    /// });
+    /// drop(a.lock());
+    /// c.join().unwrap();
+    /// ```
+    pub fn register(self: &std::sync::Arc<Self>) -> Option<BakeryHandle> {
+        let n = self.taken.claim()?;
+        Some(BakeryHandle(BakeryN::new(n, self)))
+    }
+
+    /// Unparks whoever is first in the queue after `n` left it, the only one who can enter next
+    fn unpark_next(&self, n: usize) {
+        let next = (self.q_nos.iter().enumerate())
+            .map(|(i, q_no)| (q_no.load(Ordering::SeqCst), i))
+            .filter(|&(q_no, i)| q_no > Bakery::FREE && i != n)
+            .min();
+        if let Some((_, i)) = next {
+            self.waiters.unpark(i);
+        }
+    }
+    fn record(&self, f: impl FnOnce(&Recorder)) {
+        if let Some(stats) = &self.stats {
+            f(stats);
+        }
+    }
+    fn max_q_no(&self) -> i64 {
+        self.q_nos
+            .iter()
+            .fold(0, |acc, i| i.load(Ordering::SeqCst).max(acc))
+    }
 }
 
 pub struct BakeryN {
@@ -50,8 +135,29 @@ pub struct BakeryN {
     bakery: std::sync::Arc<Bakery>,
 }
 pub struct BakeryWant<'a>(Option<&'a BakeryN>);
-pub struct BakeryGuard<'a>(&'a BakeryN);
+pub struct BakeryGuard<'a>(pub(super) &'a BakeryN);
 impl BakeryN {
+    /// Takes a queue number
+    fn doorway(&self) {
+        let b = &self.bakery;
+        b.record(|stats| stats.want(self.n));
+        loop {
+            // Optimization: entering the queue is -1, which is fine since -1 < all +ve queue numbers
+            b.q_nos[self.n].store(Bakery::ENTER, Ordering::SeqCst);
+            store_load_fence();
+            if let Some(q_no) = b.max_q_no().checked_add(1).filter(|&q| q <= b.q_no_limit) {
+                b.q_nos[self.n].store(q_no, Ordering::SeqCst);
+                store_load_fence();
+                break;
+            }
+            // Queue numbers only grow while someone is queued, so wait until those queued drain, rather than wrap
+            // around into numbers smaller than theirs, which breaks mutual exclusion
+            b.q_nos[self.n].store(Bakery::FREE, Ordering::SeqCst);
+            while b.max_q_no() >= b.q_no_limit {
+                thread::yield_now();
+            }
+        }
+    }
     // Does not check if index is taken.
     pub fn new(n: usize, bakery: &std::sync::Arc<Bakery>) -> Self {
         let size = bakery.q_nos.len();
@@ -61,46 +167,114 @@ impl BakeryN {
             bakery: bakery.clone(),
         }
     }
+    /// Like `lock()`, but fails if a thread panicked while holding the lock, like `std::sync::Mutex::lock`
+    pub fn lock_checked(&mut self) -> LockResult<BakeryGuard<'_>> {
+        let guard = self.lock();
+        let poisoned = guard.0.bakery.poisoned.load(Ordering::SeqCst);
+        super::lock_result(guard, poisoned)
+    }
+    pub fn is_poisoned(&self) -> bool {
+        self.bakery.poisoned.load(Ordering::SeqCst)
+    }
+    pub fn clear_poison(&self) {
+        self.bakery.poisoned.store(false, Ordering::SeqCst);
+    }
+}
+/// Slot claimed by `Bakery::register`, which no other handle shares.
+pub struct BakeryHandle(BakeryN);
+impl BakeryHandle {
+    /// 0-based index of the claimed slot
+    pub fn index(&self) -> usize {
+        self.0.n
+    }
+    pub fn lock_checked(&mut self) -> LockResult<BakeryGuard<'_>> {
+        self.0.lock_checked()
+    }
+    pub fn is_poisoned(&self) -> bool {
+        self.0.is_poisoned()
+    }
+    pub fn clear_poison(&self) {
+        self.0.clear_poison()
+    }
 }
+impl<'a> NoStarveMutex<'a, BakeryGuard<'a>, BakeryWant<'a>> for BakeryHandle {
+    fn want_lock(&'a mut self) -> BakeryWant<'a> {
+        self.0.want_lock()
+    }
+}
+impl Drop for BakeryHandle {
+    fn drop(&mut self) {
+        self.0.bakery.taken.release(self.0.n);
+    }
+}
+
 impl<'a> NoStarveMutex<'a, BakeryGuard<'a>, BakeryWant<'a>> for BakeryN {
     fn want_lock(&'a mut self) -> BakeryWant<'a> {
-        // Optimization: entering the queue is -1, which is fine since -1 < all +ve queue numbers
-        self.bakery.q_nos[self.n].store(Bakery::ENTER, Ordering::SeqCst);
-        let q_no = 1 + self
-            .bakery
-            .q_nos
-            .iter()
-            .fold(0, |acc, i| i.load(Ordering::SeqCst).max(acc));
-        self.bakery.q_nos[self.n].store(q_no, Ordering::SeqCst);
+        self.doorway();
         BakeryWant(Some(self))
     }
 }
+impl Reacquire for BakeryGuard<'_> {
+    fn reacquire(self, f: impl FnOnce()) -> Self {
+        let b = self.0;
+        drop(self);
+        f();
+        b.doorway();
+        BakeryWant(Some(b)).wait()
+    }
+}
 
 impl<'a> WantGuard<'a, BakeryGuard<'a>> for BakeryWant<'a> {
     fn wait(mut self) -> BakeryGuard<'a> {
         let b = self.0.take().unwrap();
         let q_no = b.bakery.q_nos[b.n].load(Ordering::SeqCst);
         for (i, other_q_no) in b.bakery.q_nos.iter().enumerate() {
-            while other_q_no.load(Ordering::SeqCst) != Bakery::FREE
-                && (other_q_no.load(Ordering::SeqCst), i) < (q_no, b.n)
-            {
-                std::thread::yield_now();
-            }
+            b.bakery.waiters.wait_while(b.n, || loop {
+                let other_q_no = other_q_no.load(Ordering::SeqCst);
+                // Only releasing unparks, so spin past entering the queue, which is brief anyway
+                if other_q_no != Bakery::ENTER {
+                    return other_q_no != Bakery::FREE && (other_q_no, i) < (q_no, b.n);
+                }
+                thread::yield_now();
+            });
         }
+        b.bakery.record(|stats| stats.acquire(b.n));
         BakeryGuard(b)
     }
+    fn try_wait(mut self) -> Result<BakeryGuard<'a>, Self> {
+        if !self.is_ready() {
+            return Err(self);
+        }
+        let b = self.0.take().unwrap();
+        b.bakery.record(|stats| stats.acquire(b.n));
+        Ok(BakeryGuard(b))
+    }
+    fn is_ready(&self) -> bool {
+        let b = self.0.unwrap();
+        let q_no = b.bakery.q_nos[b.n].load(Ordering::SeqCst);
+        !b.bakery.q_nos.iter().enumerate().any(|(i, other_q_no)| {
+            let other_q_no = other_q_no.load(Ordering::SeqCst);
+            other_q_no != Bakery::FREE && (other_q_no, i) < (q_no, b.n)
+        })
+    }
 }
 impl<'a> Drop for BakeryWant<'a> {
     fn drop(&mut self) {
         if let Some(b) = self.0 {
-            BakeryGuard(b); // reuse drop logic
+            super::abandon();
+            // Never entered the critical section, so cannot poison it
+            b.bakery.q_nos[b.n].store(Bakery::FREE, Ordering::SeqCst);
+            b.bakery.unpark_next(b.n);
         }
     }
 }
 
 impl Drop for BakeryGuard<'_> {
     fn drop(&mut self) {
+        super::poison_if_panicking(&self.0.bakery.poisoned);
+        self.0.bakery.record(Recorder::release);
         self.0.bakery.q_nos[self.0.n].store(Bakery::FREE, Ordering::SeqCst);
+        self.0.bakery.unpark_next(self.0.n);
     }
 }
 
@@ -108,9 +282,11 @@ impl Drop for BakeryGuard<'_> {
 mod tests {
     use crate::sync::{
         lamports_bakery::{Bakery, BakeryN},
-        NoStarveMutex, WantGuard,
+        tests::TestData,
+        NoStarveMutex, Park, WantGuard,
     };
     use std::sync::atomic::Ordering;
+    use std::time::Duration;
     const N_THREADS: i32 = 4;
     const WORK: i32 = 1_000_000 / N_THREADS;
 
@@ -179,6 +355,36 @@ mod tests {
         assert_eq!(data.1.load(Ordering::Relaxed), -WORK * N_THREADS);
     }
 
+    #[test]
+    fn parks() {
+        let data = std::sync::Arc::new(TestData::default());
+        let mu = Bakery::new(N_THREADS as usize).with_strategy(Park);
+        let mu = std::sync::Arc::new(mu);
+        let mut mu_0 = BakeryN::new(0, &mu);
+        let guard = mu_0.lock();
+        let ths = (1..N_THREADS as usize)
+            .map(|n| {
+                let data = data.clone();
+                let mut mu = BakeryN::new(n, &mu);
+                std::thread::spawn(move || {
+                    for _ in 0..WORK / 100 {
+                        let _guard = mu.lock();
+                        data.add_then_sub();
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        // Every other thread parks behind p0 until it releases
+        let parked = |n: usize| mu.waiters.parked[n].lock().unwrap().is_some();
+        while !(1..N_THREADS as usize).all(parked) {
+            std::thread::yield_now();
+        }
+        drop(guard);
+        ths.into_iter().for_each(|th| th.join().unwrap());
+        assert_eq!(data.0.load(Ordering::Relaxed), WORK / 100 * (N_THREADS - 1));
+        assert!((0..N_THREADS as usize).all(|n| !parked(n)));
+    }
+
     #[test]
     fn no_starvation() {
         let mu = std::sync::Arc::new(Bakery::new(2));
@@ -240,29 +446,308 @@ mod tests {
         p0_reacquire.wait();
     }
 
-    #[derive(Default)]
-    struct TestData(std::sync::atomic::AtomicI32, std::sync::atomic::AtomicI32);
-    impl TestData {
-        // Relaxed since tests only require order within the same variable
-        //
-        // Memory Ordering Rules:
-        // 1. Thread executes in program order
-        // 2. All threads agree on each var's modification order (generated code)
-        // 3. Different variables can be modified independently, unless SeqCst
-        // 4. Threads may observe modification of different variables in different order
-        fn add_then_sub(&self) {
-            let i = self.0.load(Ordering::Relaxed);
-            let d = self.1.load(Ordering::Relaxed);
-            std::thread::yield_now();
-            self.0.store(i + 1, Ordering::Relaxed);
-            self.1.store(d - 1, Ordering::Relaxed);
-        }
-        fn sub_then_add(&self) {
-            let d = self.1.load(Ordering::Relaxed);
-            let i = self.0.load(Ordering::Relaxed);
-            std::thread::yield_now();
-            self.1.store(d - 1, Ordering::Relaxed);
-            self.0.store(i + 1, Ordering::Relaxed);
+    #[test]
+    fn try_lock() {
+        let mu = std::sync::Arc::new(Bakery::new(3));
+        let (mut a, mut b, mut c) = (
+            BakeryN::new(0, &mu),
+            BakeryN::new(1, &mu),
+            BakeryN::new(2, &mu),
+        );
+        let guard = b.try_lock().unwrap();
+        assert!(a.try_lock().is_none());
+        assert_eq!(mu.q_nos[0].load(Ordering::SeqCst), Bakery::FREE);
+
+        // Queued behind b, so a cannot take it either once b releases
+        let want = c.want_lock();
+        drop(guard);
+        assert!(a.try_lock().is_none());
+        let want = want.try_wait().ok().unwrap();
+        drop(want);
+        assert!(a.try_lock().is_some());
+    }
+
+    #[test]
+    fn poll_wait() {
+        let mu = std::sync::Arc::new(Bakery::new(3));
+        let (mut a, mut b, mut c) = (
+            BakeryN::new(0, &mu),
+            BakeryN::new(1, &mu),
+            BakeryN::new(2, &mu),
+        );
+        let guard = b.lock();
+        let want_c = c.want_lock();
+        let want_a = a.want_lock();
+        assert!(!want_c.is_ready() && !want_a.is_ready());
+        drop(guard);
+        // a stays behind c
+        assert!(want_c.is_ready() && !want_a.is_ready());
+        std::thread::scope(|s| {
+            let th = s.spawn(move || {
+                let mut polls = 0;
+                drop(want_a.poll_wait(|| polls += 1));
+                polls
+            });
+            std::thread::sleep(Duration::from_millis(10));
+            drop(want_c.wait());
+            assert!(th.join().unwrap() > 0);
+        });
+    }
+
+    #[test]
+    fn cancel() {
+        let mu = std::sync::Arc::new(Bakery::new(3));
+        let (mut a, mut b, mut c) = (
+            BakeryN::new(0, &mu),
+            BakeryN::new(1, &mu),
+            BakeryN::new(2, &mu),
+        );
+        let guard = b.lock();
+        let want_c = c.want_lock();
+        let want_a = a.want_lock();
+        drop(guard);
+        // a moves up to the front
+        assert!(!want_a.is_ready());
+        want_c.cancel();
+        assert!(want_a.is_ready());
+        // c queues behind a when it wants the lock again
+        let want_c = c.want_lock();
+        assert!(!want_c.is_ready());
+        drop(want_a.wait());
+        drop(want_c.wait());
+    }
+
+    #[test]
+    #[cfg(all(feature = "strict_want", debug_assertions))]
+    #[should_panic(expected = "without wait() or cancel()")]
+    fn strict_want() {
+        let mu = std::sync::Arc::new(Bakery::new(2));
+        let (mut a, mut b) = (BakeryN::new(0, &mu), BakeryN::new(1, &mu));
+        let _guard = a.lock();
+        drop(b.want_lock());
+    }
+
+    #[test]
+    fn lock_timeout() {
+        let mu = std::sync::Arc::new(Bakery::new(3));
+        let (mut a, mut b, mut c) = (
+            BakeryN::new(0, &mu),
+            BakeryN::new(1, &mu),
+            BakeryN::new(2, &mu),
+        );
+        let guard = b.lock();
+        assert!(a.lock_timeout(Duration::from_millis(10)).is_none());
+        assert_eq!(mu.q_nos[0].load(Ordering::SeqCst), Bakery::FREE);
+
+        // Keeps its place in the queue to wait again
+        let want = c.want_lock().wait_timeout(Duration::from_millis(10));
+        let want = want.err().unwrap();
+        drop(guard);
+        assert!(a.try_lock().is_none());
+        drop(want.wait_timeout(Duration::ZERO).ok().unwrap());
+        assert!(a.lock_timeout(Duration::ZERO).is_some());
+    }
+
+    #[test]
+    fn poison() {
+        let mu = std::sync::Arc::new(Bakery::new(2));
+        let th = std::thread::spawn({
+            let mut mu = mu.register().unwrap();
+            move || {
+                let _guard = mu.lock();
+                panic!("in critical section");
+            }
+        });
+        assert!(th.join().is_err());
+        let mut mu = mu.register().unwrap();
+        assert!(mu.is_poisoned());
+        let guard = mu.lock_checked().err().unwrap().into_inner();
+        drop(guard);
+        mu.clear_poison();
+        assert!(mu.lock_checked().is_ok());
+    }
+
+    #[test]
+    fn for_current_host() {
+        let mu = std::sync::Arc::new(Bakery::for_current_host());
+        let size = std::thread::available_parallelism().map_or(2, |n| n.get().max(2));
+        let handles: Vec<_> = std::iter::from_fn(|| mu.register()).collect();
+        assert_eq!(handles.len(), size);
+    }
+
+    #[test]
+    fn register() {
+        let data = std::sync::Arc::new(TestData::default());
+        let mu = std::sync::Arc::new(Bakery::new(N_THREADS as usize));
+        let ths = (0..N_THREADS * 2)
+            .map(|i| {
+                let (data, mu) = (data.clone(), mu.clone());
+                std::thread::spawn(move || {
+                    // Half the threads wait for a slot that another releases
+                    let mut mu = loop {
+                        match mu.register() {
+                            Some(handle) => break handle,
+                            None => std::thread::yield_now(),
+                        }
+                    };
+                    for _ in 0..WORK / 100 {
+                        let _guard = mu.lock();
+                        if i % 2 == 0 {
+                            data.add_then_sub();
+                        } else {
+                            data.sub_then_add();
+                        }
+                    }
+                    mu.index()
+                })
+            })
+            .collect::<Vec<_>>();
+        for th in ths {
+            assert!(th.join().unwrap() < N_THREADS as usize);
         }
+        assert_eq!(data.0.load(Ordering::Relaxed), WORK / 100 * N_THREADS * 2);
+        assert_eq!(data.1.load(Ordering::Relaxed), -WORK / 100 * N_THREADS * 2);
+        assert!(mu.taken.all_free());
+    }
+
+    #[test]
+    fn wont_overflow() {
+        let mu = std::sync::Arc::new(Bakery {
+            q_no_limit: 3,
+            ..Bakery::new(2)
+        });
+        let mu_a = BakeryN::new(0, &mu);
+        let mu_b = BakeryN::new(1, &mu);
+        // Each takes a larger queue number than the other's, until it would exceed the largest
+        let th_a = std::thread::spawn(move || {
+            let mut mu_a = mu_a;
+            for _ in 0..100 {
+                let _guard = mu_a.lock();
+            }
+        });
+        let th_b = std::thread::spawn(move || {
+            let mut mu_b = mu_b;
+            for _ in 0..100 {
+                let _guard = mu_b.lock();
+            }
+        });
+        th_a.join().unwrap();
+        th_b.join().unwrap();
+        assert_eq!(mu.max_q_no(), Bakery::FREE);
+
+        // Waits for the queued to drain, rather than take a number past the largest
+        let mut mu_a = BakeryN::new(0, &mu);
+        mu.q_nos[1].store(3, Ordering::SeqCst);
+        let th = std::thread::spawn(move || drop(mu_a.lock()));
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        assert!(!th.is_finished());
+        assert_eq!(mu.q_nos[0].load(Ordering::SeqCst), Bakery::FREE);
+        mu.q_nos[1].store(Bakery::FREE, Ordering::SeqCst);
+        th.join().unwrap();
+    }
+
+    #[test]
+    fn stats() {
+        assert_eq!(Bakery::new(2).stats(), None);
+        let mu = std::sync::Arc::new(Bakery::new(N_THREADS as usize).with_stats());
+        let ths = (0..N_THREADS as usize)
+            .map(|n| {
+                let mut mu = BakeryN::new(n, &mu);
+                std::thread::spawn(move || {
+                    for _ in 0..100 {
+                        let _guard = mu.lock();
+                        std::thread::yield_now();
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        ths.into_iter().for_each(|th| th.join().unwrap());
+        let stats = mu.stats().unwrap();
+        assert!(stats.holders.iter().all(|h| h.acquisitions == 100));
+        assert!(stats.holders.iter().all(|h| h.average_wait() <= h.max_wait));
+
+        // b waits for a, so a hands off to b
+        let mu = std::sync::Arc::new(Bakery::new(2).with_stats());
+        let mut a = BakeryN::new(0, &mu);
+        let guard = a.lock();
+        let th = std::thread::spawn({
+            let mut b = BakeryN::new(1, &mu);
+            move || drop(b.lock())
+        });
+        std::thread::sleep(Duration::from_millis(10));
+        drop(guard);
+        th.join().unwrap();
+        let stats = mu.stats().unwrap();
+        assert_eq!(stats.handoffs, 1);
+        assert!(stats.holders[1].max_wait >= Duration::from_millis(10));
+    }
+}
+
+#[cfg(all(test, loom))]
+mod loom_tests {
+    use crate::sync::lamports_bakery::{Bakery, BakeryN};
+    use crate::sync::{NoStarveMutex, WantGuard};
+    use loom::cell::UnsafeCell;
+    use loom::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+    use loom::sync::Arc;
+
+    /// Loom fails on any access to it that the lock does not order before or after the others
+    struct Shared(UnsafeCell<u32>);
+    unsafe impl Sync for Shared {}
+
+    #[test]
+    fn mutual_exclusion() {
+        loom::model(|| {
+            let data = Arc::new(Shared(UnsafeCell::new(0)));
+            let mu = std::sync::Arc::new(Bakery::new(2));
+            let mut p0 = BakeryN::new(0, &mu);
+            let th = loom::thread::spawn({
+                let (data, mut mu) = (data.clone(), BakeryN::new(1, &mu));
+                move || {
+                    let _guard = mu.lock();
+                    data.0.with_mut(|d| unsafe { *d += 1 });
+                }
+            });
+            {
+                let _guard = p0.lock();
+                data.0.with_mut(|d| unsafe { *d += 2 });
+            }
+            th.join().unwrap();
+            assert_eq!(data.0.with(|d| unsafe { *d }), 3);
+        });
+    }
+
+    #[test]
+    fn first_come_first_served() {
+        loom::model(|| {
+            let (p1_wants, entered) = (
+                Arc::new(AtomicBool::new(false)),
+                Arc::new(AtomicUsize::new(0)),
+            );
+            let mu = std::sync::Arc::new(Bakery::new(2));
+            let mut p0 = BakeryN::new(0, &mu);
+            let th = loom::thread::spawn({
+                let (p1_wants, entered) = (p1_wants.clone(), entered.clone());
+                let mut mu = BakeryN::new(1, &mu);
+                move || {
+                    let want = mu.want_lock();
+                    p1_wants.store(true, Ordering::SeqCst);
+                    let _guard = want.wait();
+                    entered.fetch_add(1, Ordering::SeqCst)
+                }
+            });
+            let p1_was_waiting = p1_wants.load(Ordering::SeqCst);
+            let p0_entered = {
+                let _guard = p0.lock();
+                entered.fetch_add(1, Ordering::SeqCst)
+            };
+            th.join().unwrap();
+            if p1_was_waiting {
+                assert_eq!(
+                    p0_entered, 1,
+                    "Expect p1 to enter first, since it took a number before p0"
+                );
+            }
+        });
     }
 }
@@ -1,12 +1,52 @@
+#[cfg(feature = "async")]
+pub mod async_ticket;
+pub mod burns_lynch;
+pub mod cohort;
+pub mod combining_tree;
+pub mod condvar;
+#[cfg(feature = "deadlock_detection")]
+pub mod deadlock;
+pub mod dissemination;
+pub mod epoch;
+pub mod fast_lock;
+pub mod filter;
 pub mod lamports_bakery;
+pub mod once;
 pub mod peterson;
+pub mod priority;
+pub mod protected;
+#[cfg(feature = "lock_api")]
+pub mod raw;
+pub mod rcu;
+pub mod rw_fair;
+pub mod semaphore;
+pub mod seqlock;
+mod shim;
+pub mod spin;
+pub mod stats;
+pub mod tas;
+pub mod tournament;
+pub mod universal;
+
+pub use condvar::Condvar;
+pub use once::{Once, OnceCell};
+pub use protected::Protected;
+pub use rw_fair::{RwFair, RwPolicy};
+pub use seqlock::SeqLock;
+use shim::atomic::{AtomicBool, Ordering};
+pub use spin::{Backoff, Park, SpinLoop, SpinStrategy, Yield};
+pub use stats::{HolderStats, LockStats};
+use std::cell::Cell;
+use std::sync::{LockResult, PoisonError};
+use std::time::{Duration, Instant};
 
 /// Starvation Free Mutex allows for realtime / bounded wait for a critical section.
 ///
 /// The requirements for that are
 /// 1. Mutual Exclusion - spinlocks on shared variables in the mutex to guarantee only one enters the critical section.
 /// 2. No Starvation - assuming OS threads eventually runs, mutexN can never cause mutexM (N!=M) to fail to `wait()`
-/// after `want_lock()`.
+///    after `want_lock()`.
+#[allow(drop_bounds)]
 pub trait NoStarveMutex<'a, Guard: 'a, Want: 'a>
 where
     // Only allow releasing after acquiring guard
@@ -22,12 +62,541 @@ where
     // Indicates you want to lock. You can only be sure you have the lock after `wait()`ing on the `WantGuard`.
     // Holding the `WantGuard` blocks other threads from reacquiring the lock so that you do not starve.
     fn want_lock(&'a mut self) -> Want;
+
+    // Locks only if no other thread holds the lock or is queued before you, instead of waiting, e.g. to back off.
+    // Abandons the attempt otherwise, so that it never blocks others.
+    fn try_lock(&'a mut self) -> Option<Guard> {
+        self.want_lock().try_wait().map_err(WantGuard::cancel).ok()
+    }
+
+    // Waits for the lock up to `dur`, then abandons the attempt like `try_lock()` so that it never blocks others.
+    fn lock_timeout(&'a mut self, dur: Duration) -> Option<Guard> {
+        self.want_lock()
+            .wait_timeout(dur)
+            .map_err(WantGuard::cancel)
+            .ok()
+    }
 }
 
+#[allow(drop_bounds)]
 pub trait WantGuard<'a, Guard: 'a>: Drop
 where
     Guard: Drop,
 {
     // WARNING: because Want drop()s after moving into Guard, implementation must book-keep to tell drop() not to release again
     fn wait(self) -> Guard;
+
+    // Like `wait()`, but gives back the `WantGuard` instead of waiting if it is not your turn yet
+    // Defaults to `wait()` once `is_ready()`, so locks that enter in several steps, e.g. by levels, must override it
+    fn try_wait(self) -> Result<Guard, Self>
+    where
+        Self: Sized,
+    {
+        if self.is_ready() {
+            Ok(self.wait())
+        } else {
+            Err(self)
+        }
+    }
+
+    // Whether `wait()` would return without waiting right now, e.g. to do other work meanwhile, keeping your place
+    fn is_ready(&self) -> bool;
+
+    // Like `wait()`, but calls `pending()` instead of spinning until it is your turn, keeping your place in the queue
+    fn poll_wait(self, mut pending: impl FnMut()) -> Guard
+    where
+        Self: Sized,
+    {
+        while !self.is_ready() {
+            pending();
+        }
+        self.wait()
+    }
+
+    // Like `wait()`, but gives back the `WantGuard` if it is still not your turn after `dur`, to wait again or `cancel()`
+    fn wait_timeout(self, dur: Duration) -> Result<Guard, Self>
+    where
+        Self: Sized,
+    {
+        let deadline = Instant::now() + dur;
+        let mut want = self;
+        loop {
+            want = match want.try_wait() {
+                Ok(guard) => return Ok(guard),
+                Err(want) if Instant::now() >= deadline => return Err(want),
+                Err(want) => want,
+            };
+            std::thread::yield_now();
+        }
+    }
+
+    // Gives up your place in the queue without ever holding the lock, which the threads queued behind you then move up
+    // in. Wanting the lock again queues you last, as if you never wanted it before. Only the cohort lock, whose tickets
+    // cannot be given back, still waits for its turn to pass it on.
+    // Dropping the `WantGuard` cancels too, though with the `strict_want` feature, debug builds panic unless it
+    // `cancel()`s explicitly, since that is easy to do by accident.
+    fn cancel(self)
+    where
+        Self: Sized,
+    {
+        CANCELLING.with(|c| c.set(c.get() + 1));
+        // Resets even if dropping panics
+        struct Cancelled;
+        impl Drop for Cancelled {
+            fn drop(&mut self) {
+                CANCELLING.with(|c| c.set(c.get() - 1));
+            }
+        }
+        let _cancelled = Cancelled;
+        drop(self);
+    }
+}
+
+thread_local! {
+    /// Number of `WantGuard`s the current thread is cancelling, nested if one wraps another
+    static CANCELLING: Cell<usize> = const { Cell::new(0) };
+}
+/// Checks that a `WantGuard` dropped without waiting was cancelled, with the `strict_want` feature
+fn abandon() {
+    #[cfg(feature = "strict_want")]
+    debug_assert!(
+        std::thread::panicking() || CANCELLING.with(Cell::get) > 0,
+        "WantGuard dropped without wait() or cancel(), which gives up its place in the queue"
+    );
+}
+
+/// Mutex for exactly 2 threads, one on each side, that `NAry` composes into a lock for any number of threads.
+///
+/// Unlike `NoStarveMutex`, it locks by side index instead of through a handle and guards, so that each node of a
+/// tournament can share it. Each side may only want it again after releasing it.
+pub trait BinaryMutex: Send + Sync {
+    fn new() -> Self;
+    // Takes the place of `side` (0 or 1) in the queue, like `want_lock()`
+    fn want(&self, side: usize);
+    // Whether `wait()` would return without waiting right now, after wanting
+    fn is_ready(&self, side: usize) -> bool;
+    // Waits until `side` holds the lock, after wanting
+    fn wait(&self, side: usize);
+    // Releases the lock, or gives up the place in the queue, of `side`
+    fn release(&self, side: usize);
+    // Whether the other side wants or holds the lock, so that `side` would have to wait after wanting it
+    fn is_contended(&self, side: usize) -> bool;
+}
+
+/// Guard that can give up its lock for a while and wait for it again, e.g. to wait on a `Condvar`.
+pub trait Reacquire: Sized {
+    // Releases the lock, runs `f` without it, then waits for the lock again like `lock()`
+    fn reacquire(self, f: impl FnOnce()) -> Self;
+}
+
+/// Marks the lock as poisoned if its guard drops while the thread unwinds from a panic in the critical section
+fn poison_if_panicking(poisoned: &AtomicBool) {
+    if std::thread::panicking() {
+        poisoned.store(true, Ordering::SeqCst);
+    }
+}
+fn lock_result<Guard>(guard: Guard, poisoned: bool) -> LockResult<Guard> {
+    match poisoned {
+        true => Err(PoisonError::new(guard)),
+        false => Ok(guard),
+    }
+}
+
+/// Starvation Free Mutex for async tasks, whose `want_lock()` resolves to the guard once it is the task's turn, without
+/// blocking the executor's thread meanwhile.
+///
+/// Dropping the future before it resolves gives up the task's place, so that it never blocks others.
+#[cfg(feature = "async")]
+#[allow(drop_bounds)]
+pub trait AsyncNoStarveMutex<'a, Guard: 'a, Want: 'a>
+where
+    Guard: Drop,
+    Want: std::future::Future<Output = Guard>,
+{
+    // Queues up for the lock right away, so that others that want it later wait for this task, even before it polls
+    fn want_lock(&'a mut self) -> Want;
+
+    // Convenience function that reads better with `.await`
+    fn lock(&'a mut self) -> Want {
+        self.want_lock()
+    }
+}
+
+/// Threads waiting on a lock, by their 0-based index
+struct Waiters {
+    strategy: Box<dyn SpinStrategy>,
+    parked: Vec<shim::Mutex<Option<shim::thread::Thread>>>,
+}
+impl Waiters {
+    fn new(strategy: impl SpinStrategy + 'static, size: usize) -> Self {
+        Self {
+            strategy: Box::new(strategy),
+            parked: (0..size).map(|_| shim::Mutex::new(None)).collect(),
+        }
+    }
+    /// Waits while `blocked()`, which must only turn false once another thread unparks `n`
+    fn wait_while(&self, n: usize, mut blocked: impl FnMut() -> bool) {
+        let parks = self.strategy.parks();
+        if parks {
+            // Registers before checking, so that a release in between still unparks, and park returns at once
+            *self.parked[n].lock().unwrap() = Some(shim::thread::current());
+        }
+        let mut n_failed = 0;
+        while blocked() {
+            self.strategy.pause(n_failed);
+            n_failed = n_failed.saturating_add(1);
+        }
+        if parks {
+            *self.parked[n].lock().unwrap() = None;
+        }
+    }
+    fn unpark(&self, n: usize) {
+        if self.strategy.parks() {
+            if let Some(th) = self.parked[n].lock().unwrap().as_ref() {
+                th.unpark();
+            }
+        }
+    }
+    fn unpark_all(&self) {
+        (0..self.parked.len()).for_each(|n| self.unpark(n));
+    }
+}
+
+/// Threads that can run in parallel on this host, yet at least 2, to size locks whose thread count isn't known statically
+fn host_parallelism() -> usize {
+    std::thread::available_parallelism().map_or(2, |n| n.get().max(2))
+}
+
+/// Registry of a lock's 0-based indices, claimed by handles that drop the claim with them
+struct Slots(Vec<AtomicBool>);
+impl Slots {
+    fn new(size: usize) -> Self {
+        Self((0..size).map(|_| AtomicBool::new(false)).collect())
+    }
+    /// Claims the lowest free index, or None if every index is taken
+    fn claim(&self) -> Option<usize> {
+        self.0.iter().position(|t| {
+            t.compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst)
+                .is_ok()
+        })
+    }
+    fn release(&self, n: usize) {
+        self.0[n].store(false, Ordering::SeqCst);
+    }
+    #[cfg(test)]
+    fn all_free(&self) -> bool {
+        self.0.iter().all(|t| !t.load(Ordering::SeqCst))
+    }
+}
+
+/// Phase synchronization, where every thread waits through its own handle until all threads of the barrier arrived.
+pub trait Barrier {
+    /// Waits until every thread arrived in the current phase, then starts the next one
+    fn wait(&mut self);
+}
+
+/// Mutex that only guarantees Mutual Exclusion, so a thread may wait forever while others keep reacquiring the lock.
+///
+/// Use a `NoStarveMutex` instead if every thread must enter the critical section eventually.
+#[allow(drop_bounds)]
+pub trait Mutex<'a, Guard: 'a>
+where
+    Guard: Drop,
+{
+    // Spins until the lock is free, then takes it
+    fn lock(&'a mut self) -> Guard;
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::sync::burns_lynch::{BurnsLynch, BurnsLynchN};
+    use crate::sync::cohort::{CohortLock, CohortN};
+    use crate::sync::filter::{FilterLock, FilterLockN};
+    use crate::sync::lamports_bakery::{Bakery, BakeryN};
+    use crate::sync::peterson::Peterson;
+    use crate::sync::semaphore::{StrongSemaphore, WeakSemaphore};
+    use crate::sync::tas::TasLock;
+    use crate::sync::{Mutex, NoStarveMutex};
+    use crate::sync::{RwFair, RwPolicy};
+    use std::sync::atomic::{AtomicBool, AtomicI32, Ordering};
+    use std::sync::Arc;
+    const ROUNDS: usize = 1_000;
+
+    /// Number of times thread a reacquires the lock while thread b waits for it, up to `ROUNDS`, where each runs its
+    /// callback in the critical section of its own handle to the same lock
+    fn overtakes<A, B>(mut a: A, mut b: B) -> usize
+    where
+        A: FnMut(&mut dyn FnMut()) + Send + 'static,
+        B: FnMut(&mut dyn FnMut()) + Send + 'static,
+    {
+        let (a_holds, b_acquired) = (
+            Arc::new(AtomicBool::new(false)),
+            Arc::new(AtomicBool::new(false)),
+        );
+        let th_a = std::thread::spawn({
+            let (a_holds, b_acquired) = (a_holds.clone(), b_acquired.clone());
+            move || {
+                // Holds the lock long enough for b to start waiting
+                a(&mut || {
+                    a_holds.store(true, Ordering::SeqCst);
+                    std::thread::sleep(std::time::Duration::from_millis(50));
+                });
+                let mut n = 0;
+                while n < ROUNDS {
+                    let mut overtaken = false;
+                    a(&mut || overtaken = !b_acquired.load(Ordering::SeqCst));
+                    if !overtaken {
+                        break;
+                    }
+                    n += 1;
+                }
+                n
+            }
+        });
+        while !a_holds.load(Ordering::SeqCst) {
+            std::thread::yield_now();
+        }
+        b(&mut || b_acquired.store(true, Ordering::SeqCst));
+        th_a.join().unwrap()
+    }
+
+    /// Counters whose racy read-modify-write loses updates unless a lock serialises the callers
+    #[derive(Default)]
+    pub(super) struct TestData(pub(super) AtomicI32, pub(super) AtomicI32);
+    impl TestData {
+        // Relaxed since tests only require order within the same variable
+        //
+        // Memory Ordering Rules:
+        // 1. Thread executes in program order
+        // 2. All threads agree on each var's modification order (generated code)
+        // 3. Different variables can be modified independently, unless SeqCst
+        // 4. Threads may observe modification of different variables in different order
+        pub(super) fn add_then_sub(&self) {
+            let i = self.0.load(Ordering::Relaxed);
+            let d = self.1.load(Ordering::Relaxed);
+            std::thread::yield_now();
+            self.0.store(i + 1, Ordering::Relaxed);
+            self.1.store(d - 1, Ordering::Relaxed);
+        }
+        pub(super) fn sub_then_add(&self) {
+            let d = self.1.load(Ordering::Relaxed);
+            let i = self.0.load(Ordering::Relaxed);
+            std::thread::yield_now();
+            self.1.store(d - 1, Ordering::Relaxed);
+            self.0.store(i + 1, Ordering::Relaxed);
+        }
+    }
+
+    /// Checks that no update to `TestData` is lost by threads that each run their callback in the critical section of
+    /// their own handle to the same lock, made by `handle(n)` for thread n
+    pub(super) fn mutual_exclusion<H, L>(handle: H)
+    where
+        H: Fn(usize) -> L,
+        L: FnMut(&mut dyn FnMut()) + Send + 'static,
+    {
+        const N_THREADS: usize = 4;
+        const WORK: i32 = 100_000 / N_THREADS as i32;
+        let data = Arc::new(TestData::default());
+        let ths = (0..N_THREADS)
+            .map(|n| {
+                let data = data.clone();
+                let mut lock = handle(n);
+                std::thread::spawn(move || {
+                    for _ in 0..WORK {
+                        lock(&mut || {
+                            if n % 2 == 0 {
+                                data.add_then_sub()
+                            } else {
+                                data.sub_then_add()
+                            }
+                        });
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        for th in ths {
+            th.join().unwrap();
+        }
+        assert_eq!(data.0.load(Ordering::Relaxed), WORK * N_THREADS as i32);
+        assert_eq!(data.1.load(Ordering::Relaxed), -WORK * N_THREADS as i32);
+    }
+
+    #[test]
+    fn fair_locks_never_overtake() {
+        let (mut mu_a, mut mu_b) = Peterson::binary_mutex();
+        let n = overtakes(
+            move |f| {
+                let _guard = mu_a.lock();
+                f()
+            },
+            move |f| {
+                let _guard = mu_b.lock();
+                f()
+            },
+        );
+        assert_eq!(n, 0);
+
+        let bakery = Arc::new(Bakery::new(2));
+        let (mut mu_a, mut mu_b) = (BakeryN::new(0, &bakery), BakeryN::new(1, &bakery));
+        let n = overtakes(
+            move |f| {
+                let _guard = mu_a.lock();
+                f()
+            },
+            move |f| {
+                let _guard = mu_b.lock();
+                f()
+            },
+        );
+        assert_eq!(n, 0);
+
+        let filter = Arc::new(FilterLock::new(2));
+        let (mut mu_a, mut mu_b) = (FilterLockN::new(0, &filter), FilterLockN::new(1, &filter));
+        let n = overtakes(
+            move |f| {
+                let _guard = mu_a.lock();
+                f()
+            },
+            move |f| {
+                let _guard = mu_b.lock();
+                f()
+            },
+        );
+        assert_eq!(n, 0);
+
+        // Across clusters, unlike within the same one
+        let cohort = Arc::new(CohortLock::new(2, 1));
+        let (mut mu_a, mut mu_b) = (CohortN::new(0, &cohort), CohortN::new(1, &cohort));
+        let n = overtakes(
+            move |f| {
+                let _guard = mu_a.lock();
+                f()
+            },
+            move |f| {
+                let _guard = mu_b.lock();
+                f()
+            },
+        );
+        assert_eq!(n, 0);
+    }
+
+    #[test]
+    fn tas_overtakes() {
+        // Negative control, since a releases and reacquires before b notices
+        let mut mu_a = TasLock::new();
+        let mut mu_b = mu_a.clone();
+        let n = overtakes(
+            move |f| {
+                let _guard = mu_a.lock();
+                f()
+            },
+            move |f| {
+                let _guard = mu_b.lock();
+                f()
+            },
+        );
+        assert!(n > 1, "Expect TAS to starve, got {n} overtakes");
+    }
+
+    #[test]
+    fn burns_lynch_overtakes_higher_index() {
+        let burns_lynch = Arc::new(BurnsLynch::new(2));
+        let (mut mu_a, mut mu_b) = (
+            BurnsLynchN::new(0, &burns_lynch),
+            BurnsLynchN::new(1, &burns_lynch),
+        );
+        let n = overtakes(
+            move |f| {
+                let _guard = mu_a.lock();
+                f()
+            },
+            move |f| {
+                let _guard = mu_b.lock();
+                f()
+            },
+        );
+        assert!(n > 1, "Expect Burns-Lynch to starve, got {n} overtakes");
+    }
+
+    #[test]
+    fn strong_semaphore_never_overtakes() {
+        let sem = Arc::new(StrongSemaphore::new(1));
+        let (sem_a, sem_b) = (sem.clone(), sem);
+        let n = overtakes(
+            move |f| {
+                let _permit = sem_a.acquire();
+                f()
+            },
+            move |f| {
+                let _permit = sem_b.acquire();
+                f()
+            },
+        );
+        assert_eq!(n, 0);
+    }
+
+    #[test]
+    fn weak_semaphore_overtakes() {
+        // As TAS, since a takes the permit it just released before b notices
+        let sem = Arc::new(WeakSemaphore::new(1));
+        let (sem_a, sem_b) = (sem.clone(), sem);
+        let n = overtakes(
+            move |f| {
+                let _permit = sem_a.acquire();
+                f()
+            },
+            move |f| {
+                let _permit = sem_b.acquire();
+                f()
+            },
+        );
+        assert!(
+            n > 1,
+            "Expect the weak semaphore to starve, got {n} overtakes"
+        );
+    }
+
+    /// Number of times a reader (or writer if `a_writes`) reacquires the lock while a writer (or reader) waits for it
+    fn rw_overtakes(policy: RwPolicy, a_writes: bool) -> usize {
+        let rw = Arc::new(RwFair::new(()).with_policy(policy));
+        let (rw_a, rw_b) = (rw.clone(), rw);
+        overtakes(
+            move |f| {
+                if a_writes {
+                    let _guard = rw_a.write();
+                    f()
+                } else {
+                    let _guard = rw_a.read();
+                    f()
+                }
+            },
+            move |f| {
+                if a_writes {
+                    let _guard = rw_b.read();
+                    f()
+                } else {
+                    let _guard = rw_b.write();
+                    f()
+                }
+            },
+        )
+    }
+
+    #[test]
+    fn rw_fair_never_overtakes() {
+        // A reader does not read again while a writer waits, nor a writer write again while a reader waits
+        for policy in [RwPolicy::TaskFair, RwPolicy::PhaseFair] {
+            assert_eq!(rw_overtakes(policy, false), 0);
+            assert_eq!(rw_overtakes(policy, true), 0);
+        }
+    }
+
+    #[test]
+    fn rw_preference_overtakes() {
+        let n = rw_overtakes(RwPolicy::ReadPreferring, false);
+        assert!(n > 1, "Expect readers to starve writers, got {n} overtakes");
+        let n = rw_overtakes(RwPolicy::WritePreferring, true);
+        assert!(n > 1, "Expect writers to starve readers, got {n} overtakes");
+        assert_eq!(rw_overtakes(RwPolicy::WritePreferring, false), 0);
+    }
 }
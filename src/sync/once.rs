@@ -0,0 +1,241 @@
+#[cfg(feature = "std")]
+use super::cohort::Ticket;
+use core::cell::UnsafeCell;
+use core::mem::MaybeUninit;
+#[cfg(not(feature = "std"))]
+use core::sync::atomic::AtomicUsize;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+/// Runs an initialization exactly once, however many threads call it, like `std::sync::Once` but on the crate's
+/// first-come first-served ticket lock.
+///
+/// Threads that call it while it runs wait in line, and return once it completed. If the initialization panics, it
+/// does not complete, and the next thread in line runs its own initialization instead, so no thread waits forever.
+///
+/// Unlike the rest of `sync`, it only requires `core`, so it remains without the default `std` feature, where waiting
+/// threads spin instead of yielding.
+///
+/// # Examples
+/// ```
+/// use rads::sync::Once;
+/// use std::sync::atomic::{AtomicUsize, Ordering};
+///
+/// static INIT: Once = Once::new();
+/// static CALLS: AtomicUsize = AtomicUsize::new(0);
+/// let ths = (0..4)
+///     .map(|_| std::thread::spawn(|| INIT.call_once(|| drop(CALLS.fetch_add(1, Ordering::SeqCst)))))
+///     .collect::<Vec<_>>();
+/// ths.into_iter().for_each(|th| th.join().unwrap());
+/// assert!(INIT.is_completed());
+/// assert_eq!(CALLS.load(Ordering::SeqCst), 1);
+/// ```
+#[derive(Default)]
+pub struct Once {
+    ticket: Ticket,
+    done: AtomicBool,
+}
+impl Once {
+    pub const fn new() -> Self {
+        Self {
+            ticket: Ticket::new(),
+            done: AtomicBool::new(false),
+        }
+    }
+    pub fn is_completed(&self) -> bool {
+        self.done.load(Ordering::SeqCst)
+    }
+    /// Runs `f` unless an initialization completed, and returns once one did
+    pub fn call_once(&self, f: impl FnOnce()) {
+        if self.is_completed() {
+            return;
+        }
+        self.ticket.wait(self.ticket.take());
+        // Passes the turn on even if `f` panics
+        let _turn = Turn(&self.ticket);
+        if !self.is_completed() {
+            f();
+            self.done.store(true, Ordering::SeqCst);
+        }
+    }
+}
+
+/// First-come first-served lock that spins, since threads cannot yield without `std`
+#[cfg(not(feature = "std"))]
+#[derive(Default)]
+struct Ticket {
+    next: AtomicUsize,
+    serving: AtomicUsize,
+}
+#[cfg(not(feature = "std"))]
+impl Ticket {
+    const fn new() -> Self {
+        Self {
+            next: AtomicUsize::new(0),
+            serving: AtomicUsize::new(0),
+        }
+    }
+    fn take(&self) -> usize {
+        self.next.fetch_add(1, Ordering::SeqCst)
+    }
+    fn wait(&self, ticket: usize) {
+        while self.serving.load(Ordering::SeqCst) != ticket {
+            core::hint::spin_loop();
+        }
+    }
+    fn release(&self) {
+        self.serving.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+/// Releases the ticket lock when dropped
+struct Turn<'a>(&'a Ticket);
+impl Drop for Turn<'_> {
+    fn drop(&mut self) {
+        self.0.release();
+    }
+}
+
+/// Cell that is written at most once, by whichever thread initializes it first, like `std::sync::OnceLock` but on
+/// [`Once`].
+///
+/// # Examples
+/// ```
+/// use rads::sync::OnceCell;
+///
+/// static CONFIG: OnceCell<String> = OnceCell::new();
+/// assert_eq!(CONFIG.get(), None);
+/// let th = std::thread::spawn(|| CONFIG.get_or_init(|| "from thread".into()).len());
+/// let config = CONFIG.get_or_init(|| "from main".into());
+/// assert_eq!(th.join().unwrap(), config.len());
+/// assert_eq!(CONFIG.set("too late".into()), Err("too late".into()));
+/// ```
+pub struct OnceCell<T> {
+    once: Once,
+    value: UnsafeCell<MaybeUninit<T>>,
+}
+// Only the thread that initializes the value writes it, and every thread may read it afterwards
+unsafe impl<T: Send> Send for OnceCell<T> {}
+unsafe impl<T: Send + Sync> Sync for OnceCell<T> {}
+
+impl<T> Default for OnceCell<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl<T> OnceCell<T> {
+    pub const fn new() -> Self {
+        Self {
+            once: Once::new(),
+            value: UnsafeCell::new(MaybeUninit::uninit()),
+        }
+    }
+    /// Value if initialized, without waiting for a thread initializing it
+    pub fn get(&self) -> Option<&T> {
+        // SAFETY: written before the initialization completed, and never again
+        self.once
+            .is_completed()
+            .then(|| unsafe { (*self.value.get()).assume_init_ref() })
+    }
+    /// Value, initialized with `f` unless another thread initialized it first
+    pub fn get_or_init(&self, f: impl FnOnce() -> T) -> &T {
+        self.once.call_once(|| {
+            // SAFETY: the ticket lock excludes other initializations, and no thread reads it before it completes
+            unsafe { (*self.value.get()).write(f()) };
+        });
+        self.get().expect("Expect the initialization to complete")
+    }
+    /// Initializes with `value`, or gives it back if initialized already
+    pub fn set(&self, value: T) -> Result<(), T> {
+        let mut value = Some(value);
+        self.get_or_init(|| value.take().unwrap());
+        value.map_or(Ok(()), Err)
+    }
+    pub fn into_inner(mut self) -> Option<T> {
+        self.take()
+    }
+    /// Value if initialized, leaving the cell uninitialized
+    pub fn take(&mut self) -> Option<T> {
+        if !self.once.is_completed() {
+            return None;
+        }
+        self.once = Once::new();
+        // SAFETY: initialized, and no longer marked so
+        Some(unsafe { self.value.get_mut().assume_init_read() })
+    }
+}
+impl<T> Drop for OnceCell<T> {
+    fn drop(&mut self) {
+        drop(self.take());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::sync::{Once, OnceCell};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    const N_THREADS: usize = 4;
+
+    #[test]
+    fn call_once() {
+        let (once, calls) = (Arc::new(Once::new()), Arc::new(AtomicUsize::new(0)));
+        let ths: Vec<_> = (0..N_THREADS)
+            .map(|_| {
+                let (once, calls) = (once.clone(), calls.clone());
+                std::thread::spawn(move || {
+                    once.call_once(|| {
+                        // Others wait meanwhile
+                        std::thread::sleep(std::time::Duration::from_millis(10));
+                        calls.fetch_add(1, Ordering::SeqCst);
+                    });
+                    assert_eq!(calls.load(Ordering::SeqCst), 1);
+                })
+            })
+            .collect();
+        ths.into_iter().for_each(|th| th.join().unwrap());
+        assert!(once.is_completed());
+    }
+
+    #[test]
+    fn retries_after_panic() {
+        let once = Once::new();
+        let panicked = std::panic::catch_unwind(|| once.call_once(|| panic!("Expect to retry")));
+        assert!(panicked.is_err());
+        assert!(!once.is_completed());
+        let mut calls = 0;
+        once.call_once(|| calls += 1);
+        once.call_once(|| calls += 1);
+        assert_eq!(calls, 1);
+    }
+
+    #[test]
+    fn once_cell() {
+        let cell = Arc::new(OnceCell::new());
+        assert_eq!(cell.get(), None);
+        let ths: Vec<_> = (0..N_THREADS)
+            .map(|n| {
+                let cell = cell.clone();
+                std::thread::spawn(move || *cell.get_or_init(|| n))
+            })
+            .collect();
+        let got: Vec<_> = ths.into_iter().map(|th| th.join().unwrap()).collect();
+        // Every thread sees the same winner
+        assert!(got.iter().all(|&n| n == got[0]));
+        assert_eq!(cell.set(N_THREADS), Err(N_THREADS));
+        let mut cell = Arc::into_inner(cell).unwrap();
+        assert_eq!(cell.take(), Some(got[0]));
+        assert_eq!(cell.set(N_THREADS), Ok(()));
+        assert_eq!(cell.into_inner(), Some(N_THREADS));
+    }
+
+    #[test]
+    fn drops_value() {
+        let value = Arc::new(());
+        let cell = OnceCell::new();
+        drop(OnceCell::<Arc<()>>::new());
+        cell.set(value.clone()).unwrap();
+        assert_eq!(Arc::strong_count(&value), 2);
+        drop(cell);
+        assert_eq!(Arc::strong_count(&value), 1);
+    }
+}
@@ -1,5 +1,8 @@
-use super::{NoStarveMutex, WantGuard};
-use std::sync::atomic::Ordering;
+use super::shim::atomic::{AtomicBool, Ordering};
+use super::shim::store_load_fence;
+use super::stats::{LockStats, Recorder};
+use super::{BinaryMutex, NoStarveMutex, Reacquire, SpinStrategy, Waiters, WantGuard, Yield};
+use std::sync::LockResult;
 
 /// Binary mutex to protect critical section fairly.
 ///
@@ -31,11 +34,16 @@ use std::sync::atomic::Ordering;
 /// th_b.join().unwrap();
 /// assert_eq!(val.load(Ordering::Relaxed), 3);
 /// ```
-#[derive(Default)]
 pub struct Peterson {
-    a_wants: std::sync::atomic::AtomicBool,
-    b_wants: std::sync::atomic::AtomicBool,
-    a_turn: std::sync::atomic::AtomicBool,
+    a_wants: AtomicBool,
+    b_wants: AtomicBool,
+    a_turn: AtomicBool,
+    /// Whether a thread panicked while holding the lock
+    poisoned: AtomicBool,
+    /// A at 0, B at 1
+    waiters: Waiters,
+    /// Recorded only if constructed `binary_mutex_with_stats`, A at 0, B at 1
+    stats: Option<Recorder>,
 }
 pub struct PetersonA(std::sync::Arc<Peterson>);
 pub struct PetersonB(std::sync::Arc<Peterson>);
@@ -45,75 +53,223 @@ pub struct PetersonAGuard<'a>(&'a PetersonA);
 pub struct PetersonBGuard<'a>(&'a PetersonB);
 
 impl Peterson {
+    const A: usize = 0;
+    const B: usize = 1;
     pub fn binary_mutex() -> (PetersonA, PetersonB) {
-        let p = std::sync::Arc::new(Peterson::default());
+        Self::binary_mutex_with(Yield)
+    }
+    /// Like `binary_mutex()`, but waits by `strategy`, e.g. parking instead of spinning during long critical sections
+    pub fn binary_mutex_with(strategy: impl SpinStrategy + 'static) -> (PetersonA, PetersonB) {
+        Self::binary_mutex_from(strategy, None)
+    }
+    /// Like `binary_mutex_with()`, but records how long each thread waits for the lock, retrievable with `stats()`, at
+    /// the cost of serializing the bookkeeping
+    pub fn binary_mutex_with_stats(
+        strategy: impl SpinStrategy + 'static,
+    ) -> (PetersonA, PetersonB) {
+        Self::binary_mutex_from(strategy, Some(Recorder::new(2)))
+    }
+    fn binary_mutex_from(
+        strategy: impl SpinStrategy + 'static,
+        stats: Option<Recorder>,
+    ) -> (PetersonA, PetersonB) {
+        let p = std::sync::Arc::new(Self::from(strategy, stats));
         (PetersonA(p.clone()), PetersonB(p))
     }
+    fn from(strategy: impl SpinStrategy + 'static, stats: Option<Recorder>) -> Self {
+        Peterson {
+            a_wants: Default::default(),
+            b_wants: Default::default(),
+            a_turn: Default::default(),
+            poisoned: Default::default(),
+            waiters: Waiters::new(strategy, 2),
+            stats,
+        }
+    }
+    fn record(&self, f: impl FnOnce(&Recorder)) {
+        if let Some(stats) = &self.stats {
+            f(stats);
+        }
+    }
+    fn wants(&self, side: usize) -> &AtomicBool {
+        match side {
+            Self::A => &self.a_wants,
+            _ => &self.b_wants,
+        }
+    }
 }
 
+/// Sides are `A` at 0 and `B` at 1, as for the handles, which lock through these
+impl BinaryMutex for Peterson {
+    fn new() -> Self {
+        Self::from(Yield, None)
+    }
+    fn want(&self, side: usize) {
+        self.record(|stats| stats.want(side));
+        // Algorithm requires no reordering of variables, hence SeqCst
+        self.wants(side).store(true, Ordering::SeqCst);
+        // Swaps, so that whoever gives way second also sees the other's flag, even if only the fences were SeqCst
+        self.a_turn.swap(side == Self::B, Ordering::SeqCst);
+        store_load_fence();
+        self.waiters.unpark(1 - side);
+    }
+    fn is_ready(&self, side: usize) -> bool {
+        !self.is_contended(side) || self.a_turn.load(Ordering::SeqCst) == (side == Self::A)
+    }
+    fn wait(&self, side: usize) {
+        self.waiters.wait_while(side, || !self.is_ready(side));
+        self.record(|stats| stats.acquire(side));
+    }
+    fn release(&self, side: usize) {
+        self.wants(side).store(false, Ordering::SeqCst);
+        self.waiters.unpark(1 - side);
+    }
+    fn is_contended(&self, side: usize) -> bool {
+        self.wants(1 - side).load(Ordering::SeqCst)
+    }
+}
+
+impl PetersonA {
+    /// Like `lock()`, but fails if a thread panicked while holding the lock, like `std::sync::Mutex::lock`
+    pub fn lock_checked(&mut self) -> LockResult<PetersonAGuard<'_>> {
+        let guard = self.lock();
+        let poisoned = guard.0 .0.poisoned.load(Ordering::SeqCst);
+        super::lock_result(guard, poisoned)
+    }
+    pub fn is_poisoned(&self) -> bool {
+        self.0.poisoned.load(Ordering::SeqCst)
+    }
+    pub fn clear_poison(&self) {
+        self.0.poisoned.store(false, Ordering::SeqCst);
+    }
+    /// Stats recorded so far, if constructed `binary_mutex_with_stats`
+    pub fn stats(&self) -> Option<LockStats> {
+        self.0.stats.as_ref().map(Recorder::stats)
+    }
+}
 impl<'a> NoStarveMutex<'a, PetersonAGuard<'a>, PetersonAWantGuard<'a>> for PetersonA {
     fn want_lock(&'a mut self) -> PetersonAWantGuard<'a> {
-        // Algorithm requires no reordering of variables, hence SeqCst
-        self.0.a_wants.store(true, Ordering::SeqCst);
-        self.0.a_turn.store(false, Ordering::SeqCst);
+        self.0.want(Peterson::A);
         PetersonAWantGuard(Some(self))
     }
 }
+impl Reacquire for PetersonAGuard<'_> {
+    fn reacquire(self, f: impl FnOnce()) -> Self {
+        let p = self.0;
+        drop(self);
+        f();
+        p.0.want(Peterson::A);
+        PetersonAWantGuard(Some(p)).wait()
+    }
+}
 impl<'a> WantGuard<'a, PetersonAGuard<'a>> for PetersonAWantGuard<'a> {
     fn wait(mut self) -> PetersonAGuard<'a> {
         let p = self.0.take().unwrap();
-        while p.0.b_wants.load(Ordering::SeqCst) && !p.0.a_turn.load(Ordering::SeqCst) {
-            std::thread::yield_now();
-        }
+        p.0.wait(Peterson::A);
         PetersonAGuard(p)
     }
+    fn try_wait(mut self) -> Result<PetersonAGuard<'a>, Self> {
+        if !self.is_ready() {
+            return Err(self);
+        }
+        let p = self.0.take().unwrap();
+        p.0.record(|stats| stats.acquire(Peterson::A));
+        Ok(PetersonAGuard(p))
+    }
+    fn is_ready(&self) -> bool {
+        self.0.unwrap().0.is_ready(Peterson::A)
+    }
 }
 impl Drop for PetersonAWantGuard<'_> {
     fn drop(&mut self) {
         if let Some(p) = self.0 {
-            PetersonAGuard(p); // reuse drop logic
+            super::abandon();
+            // Never entered the critical section, so cannot poison it
+            p.0.release(Peterson::A);
         }
     }
 }
 impl Drop for PetersonAGuard<'_> {
     fn drop(&mut self) {
-        self.0 .0.a_wants.store(false, Ordering::SeqCst)
+        super::poison_if_panicking(&self.0 .0.poisoned);
+        self.0 .0.record(Recorder::release);
+        self.0 .0.release(Peterson::A);
     }
 }
 
+impl PetersonB {
+    /// Like `lock()`, but fails if a thread panicked while holding the lock, like `std::sync::Mutex::lock`
+    pub fn lock_checked(&mut self) -> LockResult<PetersonBGuard<'_>> {
+        let guard = self.lock();
+        let poisoned = guard.0 .0.poisoned.load(Ordering::SeqCst);
+        super::lock_result(guard, poisoned)
+    }
+    pub fn is_poisoned(&self) -> bool {
+        self.0.poisoned.load(Ordering::SeqCst)
+    }
+    pub fn clear_poison(&self) {
+        self.0.poisoned.store(false, Ordering::SeqCst);
+    }
+    /// Stats recorded so far, if constructed `binary_mutex_with_stats`
+    pub fn stats(&self) -> Option<LockStats> {
+        self.0.stats.as_ref().map(Recorder::stats)
+    }
+}
 impl<'a> NoStarveMutex<'a, PetersonBGuard<'a>, PetersonBWantGuard<'a>> for PetersonB {
     fn want_lock(&'a mut self) -> PetersonBWantGuard<'a> {
-        self.0.b_wants.store(true, Ordering::SeqCst);
-        self.0.a_turn.store(true, Ordering::SeqCst);
+        self.0.want(Peterson::B);
         PetersonBWantGuard(Some(self))
     }
 }
+impl Reacquire for PetersonBGuard<'_> {
+    fn reacquire(self, f: impl FnOnce()) -> Self {
+        let p = self.0;
+        drop(self);
+        f();
+        p.0.want(Peterson::B);
+        PetersonBWantGuard(Some(p)).wait()
+    }
+}
 impl<'a> WantGuard<'a, PetersonBGuard<'a>> for PetersonBWantGuard<'a> {
     fn wait(mut self) -> PetersonBGuard<'a> {
         let p = self.0.take().unwrap();
-        while p.0.a_wants.load(Ordering::SeqCst) && p.0.a_turn.load(Ordering::SeqCst) {
-            std::thread::yield_now();
-        }
+        p.0.wait(Peterson::B);
         PetersonBGuard(p)
     }
+    fn try_wait(mut self) -> Result<PetersonBGuard<'a>, Self> {
+        if !self.is_ready() {
+            return Err(self);
+        }
+        let p = self.0.take().unwrap();
+        p.0.record(|stats| stats.acquire(Peterson::B));
+        Ok(PetersonBGuard(p))
+    }
+    fn is_ready(&self) -> bool {
+        self.0.unwrap().0.is_ready(Peterson::B)
+    }
 }
 impl Drop for PetersonBWantGuard<'_> {
     fn drop(&mut self) {
         if let Some(p) = self.0 {
-            PetersonBGuard(p); // reuse drop logic
+            super::abandon();
+            // Never entered the critical section, so cannot poison it
+            p.0.release(Peterson::B);
         }
     }
 }
 impl Drop for PetersonBGuard<'_> {
     fn drop(&mut self) {
-        self.0 .0.b_wants.store(false, Ordering::SeqCst)
+        super::poison_if_panicking(&self.0 .0.poisoned);
+        self.0 .0.record(Recorder::release);
+        self.0 .0.release(Peterson::B);
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::sync::{peterson::Peterson, NoStarveMutex, WantGuard};
+    use crate::sync::{peterson::Peterson, tests::TestData, NoStarveMutex, Park, WantGuard};
     use std::sync::atomic::Ordering;
+    use std::time::Duration;
     const WORK: i32 = 10_000_000 / 2;
 
     #[test]
@@ -243,29 +399,202 @@ mod tests {
         p0_reacquire.wait();
     }
 
-    #[derive(Default)]
-    struct TestData(std::sync::atomic::AtomicI32, std::sync::atomic::AtomicI32);
-    impl TestData {
-        // Relaxed since tests only require order within the same variable
-        //
-        // Memory Ordering Rules:
-        // 1. Thread executes in program order
-        // 2. All threads agree on each var's modification order (generated code)
-        // 3. Different variables can be modified independently, unless SeqCst
-        // 4. Threads may observe modification of different variables in different order
-        fn add_then_sub(&self) {
-            let i = self.0.load(Ordering::Relaxed);
-            let d = self.1.load(Ordering::Relaxed);
+    #[test]
+    fn try_lock() {
+        let (mut mu_a, mut mu_b) = Peterson::binary_mutex();
+        let guard = mu_a.try_lock().unwrap();
+        assert!(mu_b.try_lock().is_none());
+        // b abandoned its attempt, so a can reacquire
+        drop(guard);
+        drop(mu_a.try_lock().unwrap());
+
+        // Queued, though not waiting yet
+        let want = mu_b.want_lock();
+        assert!(mu_a.try_lock().is_none());
+        let guard = want.try_wait().ok().unwrap();
+        drop(guard);
+        assert!(mu_a.try_lock().is_some());
+    }
+
+    #[test]
+    fn poll_wait() {
+        let (mut mu_a, mut mu_b) = Peterson::binary_mutex();
+        let guard = mu_a.lock();
+        let want = mu_b.want_lock();
+        assert!(!want.is_ready());
+        std::thread::scope(|s| {
+            let th = s.spawn(move || {
+                let mut polls = 0;
+                drop(want.poll_wait(|| polls += 1));
+                polls
+            });
+            std::thread::sleep(Duration::from_millis(10));
+            drop(guard);
+            assert!(th.join().unwrap() > 0);
+        });
+        // Ready at once without contention
+        let want = mu_a.want_lock();
+        assert!(want.is_ready());
+        want.cancel();
+    }
+
+    #[test]
+    fn lock_timeout() {
+        let (mut mu_a, mut mu_b) = Peterson::binary_mutex();
+        let guard = mu_a.lock();
+        assert!(mu_b.lock_timeout(Duration::from_millis(10)).is_none());
+        // b gave up, so no longer blocks a
+        drop(guard);
+        drop(mu_a.try_lock().unwrap());
+
+        let th = std::thread::spawn(move || {
+            let _guard = mu_a.lock();
+            std::thread::sleep(Duration::from_millis(10));
+        });
+        assert!(mu_b.lock_timeout(Duration::from_secs(10)).is_some());
+        th.join().unwrap();
+    }
+
+    #[test]
+    fn parks() {
+        let (mut mu_a, mut mu_b) = Peterson::binary_mutex_with(Park);
+        let data = std::sync::Arc::new(TestData::default());
+        let p = mu_a.0.clone();
+        let guard = mu_a.lock();
+        let th = std::thread::spawn({
+            let data = data.clone();
+            move || {
+                for _ in 0..WORK / 1000 {
+                    let _guard = mu_b.lock();
+                    data.sub_then_add();
+                }
+            }
+        });
+        // b parks behind a until it releases
+        let parked = || p.waiters.parked[Peterson::B].lock().unwrap().is_some();
+        while !parked() {
             std::thread::yield_now();
-            self.0.store(i + 1, Ordering::Relaxed);
-            self.1.store(d - 1, Ordering::Relaxed);
         }
-        fn sub_then_add(&self) {
-            let d = self.1.load(Ordering::Relaxed);
-            let i = self.0.load(Ordering::Relaxed);
-            std::thread::yield_now();
-            self.1.store(d - 1, Ordering::Relaxed);
-            self.0.store(i + 1, Ordering::Relaxed);
+        drop(guard);
+        for _ in 0..WORK / 1000 {
+            let _guard = mu_a.lock();
+            data.add_then_sub();
         }
+        th.join().unwrap();
+        assert_eq!(data.0.load(Ordering::Relaxed), 2 * WORK / 1000);
+    }
+
+    #[test]
+    fn stats() {
+        assert_eq!(Peterson::binary_mutex().0.stats(), None);
+        let (mut mu_a, mut mu_b) = Peterson::binary_mutex_with_stats(crate::sync::Yield);
+        let th = std::thread::spawn(move || {
+            for _ in 0..100 {
+                drop(mu_b.lock());
+            }
+        });
+        for _ in 0..100 {
+            drop(mu_a.lock());
+        }
+        th.join().unwrap();
+        let stats = mu_a.stats().unwrap();
+        assert_eq!(stats.holders.len(), 2);
+        assert!(stats.holders.iter().all(|h| h.acquisitions == 100));
+        assert!(stats.max_handoff >= stats.average_handoff());
+    }
+
+    #[test]
+    fn poison() {
+        let (mut mu_a, mut mu_b) = Peterson::binary_mutex();
+        let (mu_a, res) = std::thread::spawn(move || {
+            let res = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                let _guard = mu_a.lock();
+                panic!("in critical section");
+            }));
+            (mu_a, res)
+        })
+        .join()
+        .unwrap();
+        assert!(res.is_err());
+        assert!(mu_a.is_poisoned());
+        // Still locks, yet reports the poison
+        drop(mu_b.lock_checked().err().unwrap().into_inner());
+        drop(mu_b.lock());
+        mu_b.clear_poison();
+        assert!(!mu_a.is_poisoned());
+
+        // Panicking before entering the critical section leaves the lock unpoisoned
+        let res = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            let _want = mu_b.want_lock();
+            panic!("before critical section");
+        }));
+        assert!(res.is_err());
+        assert!(mu_b.lock_checked().is_ok());
+    }
+}
+
+#[cfg(all(test, loom))]
+mod loom_tests {
+    use crate::sync::peterson::Peterson;
+    use crate::sync::{NoStarveMutex, WantGuard};
+    use loom::cell::UnsafeCell;
+    use loom::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+    use loom::sync::Arc;
+
+    /// Loom fails on any access to it that the lock does not order before or after the others
+    struct Shared(UnsafeCell<u32>);
+    unsafe impl Sync for Shared {}
+
+    #[test]
+    fn mutual_exclusion() {
+        loom::model(|| {
+            let data = Arc::new(Shared(UnsafeCell::new(0)));
+            let (mut a, mut b) = Peterson::binary_mutex();
+            let th = loom::thread::spawn({
+                let data = data.clone();
+                move || {
+                    let _guard = a.lock();
+                    data.0.with_mut(|d| unsafe { *d += 1 });
+                }
+            });
+            {
+                let _guard = b.lock();
+                data.0.with_mut(|d| unsafe { *d += 2 });
+            }
+            th.join().unwrap();
+            assert_eq!(data.0.with(|d| unsafe { *d }), 3);
+        });
+    }
+
+    #[test]
+    fn first_come_first_served() {
+        loom::model(|| {
+            let (a_wants, entered) = (
+                Arc::new(AtomicBool::new(false)),
+                Arc::new(AtomicUsize::new(0)),
+            );
+            let (mut a, mut b) = Peterson::binary_mutex();
+            let th = loom::thread::spawn({
+                let (a_wants, entered) = (a_wants.clone(), entered.clone());
+                move || {
+                    let want = a.want_lock();
+                    a_wants.store(true, Ordering::SeqCst);
+                    let _guard = want.wait();
+                    entered.fetch_add(1, Ordering::SeqCst)
+                }
+            });
+            let a_was_waiting = a_wants.load(Ordering::SeqCst);
+            let b_entered = {
+                let _guard = b.lock();
+                entered.fetch_add(1, Ordering::SeqCst)
+            };
+            th.join().unwrap();
+            if a_was_waiting {
+                assert_eq!(
+                    b_entered, 1,
+                    "Expect A to enter first, since it wanted the lock before B"
+                );
+            }
+        });
     }
 }
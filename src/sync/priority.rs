@@ -0,0 +1,338 @@
+use super::{NoStarveMutex, Reacquire, WantGuard};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+/// N-ary mutex that hands the lock to the waiter of highest priority, yet to the longest waiting one once it was
+/// overtaken `bound` times by threads that wanted the lock after it (bounded bypass).
+///
+/// The thread that releases the lock picks who enters next among the waiters, and waiters of equal priority enter
+/// first-come-first-served. A waiter that was bypassed `bound` times goes ahead of any priority, so that every thread
+/// enters after at most `bound + 1` later threads, where the 1 is whoever takes the lock while it is free before the
+/// waiter shows up to the thread releasing it. A `bound` of 0 thus makes it a first-come-first-served lock.
+///
+/// # Examples
+/// ```
+/// use crate::rads::sync::priority::{PriorityLock, PriorityN};
+/// use std::sync::atomic::Ordering;
+/// use rads::sync::NoStarveMutex;
+///
+/// let data = std::sync::Arc::new(std::sync::atomic::AtomicI32::new(0));
+/// // Low priority threads are overtaken at most 4 times in a row
+/// let mu = std::sync::Arc::new(PriorityLock::new(4, 4));
+/// let ths = (0..4)
+///     .map(|n| {
+///         let data = data.clone();
+///         let mut mu = PriorityN::new(n, n % 2, &mu);
+///         std::thread::spawn(move || {
+///             for _ in 0..1_000 {
+///                 let _guard = mu.lock();
+///                 let i = data.load(Ordering::Relaxed);
+///                 data.store(i + 1, Ordering::Relaxed);
+///             }
+///         })
+///     })
+///     .collect::<Vec<_>>();
+/// ths.into_iter().for_each(|th| th.join().unwrap());
+/// assert_eq!(data.load(Ordering::Relaxed), 4 * 1_000);
+/// ```
+pub struct PriorityLock {
+    slots: Vec<Slot>,
+    /// Whether a thread holds the lock, which passes from thread to thread without turning false while any waits
+    held: AtomicBool,
+    /// Order in which threads wanted the lock
+    next_ticket: AtomicUsize,
+    /// Maximum number of later threads to enter before a waiter, besides one that finds the lock free
+    bound: usize,
+}
+impl PriorityLock {
+    pub fn new(size: usize, bound: usize) -> Self {
+        assert!(size > 1, "Do you really need a mutex of size {size}?");
+        Self {
+            slots: (0..size).map(|_| Slot::default()).collect(),
+            held: AtomicBool::new(false),
+            next_ticket: AtomicUsize::new(0),
+            bound,
+        }
+    }
+    /// Hands the lock to the next waiter by priority, or frees it if none waits
+    fn release(&self) {
+        loop {
+            let waiting: Vec<_> = (self.slots.iter().enumerate())
+                .filter(|(_, s)| s.state.load(Ordering::SeqCst) == Slot::WAITING)
+                .map(|(n, s)| (n, s.ticket.load(Ordering::SeqCst), s))
+                .collect();
+            let starved = waiting
+                .iter()
+                .filter(|(_, _, s)| s.bypassed.load(Ordering::SeqCst) >= self.bound)
+                .min_by_key(|&&(_, ticket, _)| ticket);
+            let next = starved.or_else(|| {
+                (waiting.iter()).max_by_key(|&&(_, ticket, s)| {
+                    (s.priority.load(Ordering::SeqCst), std::cmp::Reverse(ticket))
+                })
+            });
+            let Some(&(_, next_ticket, next)) = next else {
+                self.held.store(false, Ordering::SeqCst);
+                return;
+            };
+            // Fails if the waiter gave up in the meantime
+            if (next.state)
+                .compare_exchange(
+                    Slot::WAITING,
+                    Slot::GRANTED,
+                    Ordering::SeqCst,
+                    Ordering::SeqCst,
+                )
+                .is_ok()
+            {
+                for (_, _, s) in waiting
+                    .iter()
+                    .filter(|&&(_, ticket, _)| ticket < next_ticket)
+                {
+                    s.bypassed.fetch_add(1, Ordering::SeqCst);
+                }
+                return;
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+struct Slot {
+    state: AtomicUsize,
+    /// Order in which the thread wanted the lock, among all threads
+    ticket: AtomicUsize,
+    priority: AtomicUsize,
+    /// Number of threads that wanted the lock later yet entered first
+    bypassed: AtomicUsize,
+}
+impl Slot {
+    const IDLE: usize = 0;
+    const WAITING: usize = 1;
+    /// Handed the lock by the thread that released it
+    const GRANTED: usize = 2;
+}
+
+pub struct PriorityN {
+    n: usize,
+    /// Higher goes first
+    priority: usize,
+    lock: std::sync::Arc<PriorityLock>,
+}
+pub struct PriorityWant<'a>(Option<&'a PriorityN>);
+pub struct PriorityGuard<'a>(&'a PriorityN);
+impl PriorityN {
+    // Does not check if index is taken.
+    pub fn new(n: usize, priority: usize, lock: &std::sync::Arc<PriorityLock>) -> Self {
+        let size = lock.slots.len();
+        assert!(
+            n < size,
+            "0-based user index {n} >= priority lock of size={size}"
+        );
+        Self {
+            n,
+            priority,
+            lock: lock.clone(),
+        }
+    }
+    pub fn set_priority(&mut self, priority: usize) {
+        self.priority = priority;
+    }
+    fn slot(&self) -> &Slot {
+        &self.lock.slots[self.n]
+    }
+    fn doorway(&self) {
+        let slot = self.slot();
+        let ticket = self.lock.next_ticket.fetch_add(1, Ordering::SeqCst);
+        slot.ticket.store(ticket, Ordering::SeqCst);
+        slot.priority.store(self.priority, Ordering::SeqCst);
+        slot.bypassed.store(0, Ordering::SeqCst);
+        slot.state.store(Slot::WAITING, Ordering::SeqCst);
+    }
+    /// Enters if handed the lock, or takes it if free, which no releasing thread then hands to anyone
+    fn try_enter(&self) -> bool {
+        let slot = self.slot();
+        let entered = slot.state.load(Ordering::SeqCst) == Slot::GRANTED
+            || (self.lock.held)
+                .compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst)
+                .is_ok();
+        if entered {
+            slot.state.store(Slot::IDLE, Ordering::SeqCst);
+        }
+        entered
+    }
+}
+
+impl<'a> NoStarveMutex<'a, PriorityGuard<'a>, PriorityWant<'a>> for PriorityN {
+    fn want_lock(&'a mut self) -> PriorityWant<'a> {
+        self.doorway();
+        PriorityWant(Some(self))
+    }
+}
+
+impl Reacquire for PriorityGuard<'_> {
+    fn reacquire(self, f: impl FnOnce()) -> Self {
+        let p = self.0;
+        drop(self);
+        f();
+        p.doorway();
+        PriorityWant(Some(p)).wait()
+    }
+}
+
+impl<'a> WantGuard<'a, PriorityGuard<'a>> for PriorityWant<'a> {
+    fn wait(mut self) -> PriorityGuard<'a> {
+        let p = self.0.take().unwrap();
+        while !p.try_enter() {
+            std::thread::yield_now();
+        }
+        PriorityGuard(p)
+    }
+    fn try_wait(mut self) -> Result<PriorityGuard<'a>, Self> {
+        let p = self.0.unwrap();
+        if !p.try_enter() {
+            return Err(self);
+        }
+        self.0 = None;
+        Ok(PriorityGuard(p))
+    }
+    fn is_ready(&self) -> bool {
+        let p = self.0.unwrap();
+        p.slot().state.load(Ordering::SeqCst) == Slot::GRANTED
+            || !p.lock.held.load(Ordering::SeqCst)
+    }
+}
+impl Drop for PriorityWant<'_> {
+    fn drop(&mut self) {
+        if let Some(p) = self.0 {
+            super::abandon();
+            // Fails if handed the lock in the meantime, which then passes on
+            let granted = (p.slot().state)
+                .compare_exchange(
+                    Slot::WAITING,
+                    Slot::IDLE,
+                    Ordering::SeqCst,
+                    Ordering::SeqCst,
+                )
+                .is_err();
+            if granted {
+                p.slot().state.store(Slot::IDLE, Ordering::SeqCst);
+                p.lock.release();
+            }
+        }
+    }
+}
+
+impl Drop for PriorityGuard<'_> {
+    fn drop(&mut self) {
+        self.0.lock.release();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::sync::priority::{PriorityLock, PriorityN};
+    use crate::sync::{NoStarveMutex, WantGuard};
+    use std::sync::atomic::{AtomicI32, Ordering};
+    use std::sync::Arc;
+    const N_THREADS: usize = 4;
+    const WORK: usize = 1_000;
+
+    #[test]
+    fn mutual_exclusion() {
+        let data = Arc::new(AtomicI32::new(0));
+        let mu = Arc::new(PriorityLock::new(N_THREADS, 2));
+        let ths: Vec<_> = (0..N_THREADS)
+            .map(|n| {
+                let data = data.clone();
+                let mut mu = PriorityN::new(n, n, &mu);
+                std::thread::spawn(move || {
+                    for _ in 0..WORK {
+                        // Not atomic, so interleaving would lose increments
+                        let _guard = mu.lock();
+                        let i = data.load(Ordering::Relaxed);
+                        std::thread::yield_now();
+                        data.store(i + 1, Ordering::Relaxed);
+                    }
+                })
+            })
+            .collect();
+        ths.into_iter().for_each(|th| th.join().unwrap());
+        assert_eq!(data.load(Ordering::Relaxed), (WORK * N_THREADS) as i32);
+    }
+
+    #[test]
+    fn prefers_priority() {
+        let mu = Arc::new(PriorityLock::new(3, 1));
+        let (mut a, mut low, mut high) = (
+            PriorityN::new(0, 0, &mu),
+            PriorityN::new(1, 0, &mu),
+            PriorityN::new(2, 1, &mu),
+        );
+        let guard = a.lock();
+        let low = low.want_lock();
+        let high = high.want_lock();
+        drop(guard);
+        assert!(high.is_ready());
+        assert!(!low.is_ready());
+        drop(high.try_wait().ok().unwrap());
+        assert!(low.is_ready());
+        drop(low.wait());
+
+        // First-come-first-served without any bypass
+        let mu = Arc::new(PriorityLock::new(3, 0));
+        let (mut a, mut low, mut high) = (
+            PriorityN::new(0, 0, &mu),
+            PriorityN::new(1, 0, &mu),
+            PriorityN::new(2, 1, &mu),
+        );
+        let guard = a.lock();
+        let low = low.want_lock();
+        let high = high.want_lock();
+        drop(guard);
+        assert!(low.is_ready());
+        assert!(!high.is_ready());
+        drop(low.wait());
+        drop(high.wait());
+    }
+
+    #[test]
+    fn bounded_bypass() {
+        let mu = Arc::new(PriorityLock::new(4, 2));
+        let (mut a, mut low) = (PriorityN::new(0, 0, &mu), PriorityN::new(1, 0, &mu));
+        let (mut h1, mut h2) = (PriorityN::new(2, 1, &mu), PriorityN::new(3, 1, &mu));
+        let guard = a.lock();
+        let low = low.want_lock();
+        let want = h1.want_lock();
+        drop(guard);
+        let guard = want.try_wait().ok().unwrap();
+        let want = h2.want_lock();
+        drop(guard);
+        let guard = want.try_wait().ok().unwrap();
+        // Overtaken twice, so goes ahead of any priority
+        let want = h1.want_lock();
+        drop(guard);
+        assert!(low.is_ready());
+        assert!(!want.is_ready());
+        let guard = low.try_wait().ok().unwrap();
+        drop(guard);
+        drop(want.wait());
+    }
+
+    #[test]
+    fn try_lock() {
+        let mu = Arc::new(PriorityLock::new(3, 1));
+        let (mut a, mut b, mut c) = (
+            PriorityN::new(0, 0, &mu),
+            PriorityN::new(1, 1, &mu),
+            PriorityN::new(2, 0, &mu),
+        );
+        let guard = a.try_lock().unwrap();
+        assert!(b.try_lock().is_none());
+        let want = c.want_lock();
+        drop(guard);
+        // Handed the lock, so passes it on when cancelled
+        assert!(want.is_ready());
+        want.cancel();
+        assert!(b.try_lock().is_some());
+        assert!(!mu.held.load(Ordering::SeqCst));
+    }
+}
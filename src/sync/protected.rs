@@ -0,0 +1,203 @@
+use super::lamports_bakery::{Bakery, BakeryN};
+use super::peterson::{Peterson, PetersonA, PetersonB};
+use super::{NoStarveMutex, Reacquire, WantGuard};
+use std::cell::UnsafeCell;
+use std::ops::{Deref, DerefMut};
+use std::sync::Arc;
+
+/// Data shared by threads that only access it through their own handle `M` of the same mutex, like `std::sync::Mutex`.
+///
+/// Each thread owns a `Protected` with its handle, and all of them share the data, which lives until the last one drops.
+///
+/// # Examples
+/// ```
+/// use rads::sync::Protected;
+///
+/// let ths = Protected::n_ary(0, 4)
+///     .into_iter()
+///     .enumerate()
+///     .map(|(n, mut data)| {
+///         std::thread::spawn(move || {
+///             for _ in 0..1_000 {
+///                 *data.lock() += n + 1;
+///             }
+///             data
+///         })
+///     })
+///     .collect::<Vec<_>>();
+/// let data: Vec<_> = ths.into_iter().map(|th| th.join().unwrap()).collect();
+/// assert_eq!(*data.into_iter().next().unwrap().lock(), 1_000 * (1 + 2 + 3 + 4));
+/// ```
+pub struct Protected<T, M> {
+    data: Arc<UnsafeCell<T>>,
+    mu: M,
+}
+// Only the thread holding the lock accesses the data, as `std::sync::Mutex` does
+unsafe impl<T: Send, M: Send> Send for Protected<T, M> {}
+
+pub struct ProtectedGuard<'a, T, Guard> {
+    data: &'a UnsafeCell<T>,
+    _guard: Guard,
+}
+
+impl<T> Protected<T, PetersonA> {
+    pub fn binary(value: T) -> (Self, Protected<T, PetersonB>) {
+        let (a, b) = Peterson::binary_mutex();
+        // SAFETY: both handles are of the same new mutex
+        unsafe {
+            let a = Protected::new(value, a);
+            let b = a.share(b);
+            (a, b)
+        }
+    }
+}
+
+impl<T> Protected<T, BakeryN> {
+    pub fn n_ary(value: T, size: usize) -> Vec<Self> {
+        let bakery = Arc::new(Bakery::new(size));
+        let data = Arc::new(UnsafeCell::new(value));
+        (0..size)
+            .map(|n| Protected {
+                data: data.clone(),
+                mu: BakeryN::new(n, &bakery),
+            })
+            .collect()
+    }
+}
+
+impl<T, M> Protected<T, M> {
+    /// # Safety
+    /// Every handle sharing the data must be of the same mutex, and none may share its index with another handle.
+    pub unsafe fn new(value: T, mu: M) -> Self {
+        Self {
+            data: Arc::new(UnsafeCell::new(value)),
+            mu,
+        }
+    }
+    /// Shares the data with another handle, e.g. for another thread
+    ///
+    /// # Safety
+    /// As for `new`, `mu` must be of the same mutex as this handle's, and not share its index with any other handle.
+    pub unsafe fn share<N>(&self, mu: N) -> Protected<T, N> {
+        Protected {
+            data: self.data.clone(),
+            mu,
+        }
+    }
+    /// Data if no other handle shares it anymore
+    pub fn into_inner(self) -> Option<T> {
+        Arc::into_inner(self.data).map(UnsafeCell::into_inner)
+    }
+
+    pub fn lock<'a, Guard, Want>(&'a mut self) -> ProtectedGuard<'a, T, Guard>
+    where
+        M: NoStarveMutex<'a, Guard, Want>,
+        Guard: Drop + 'a,
+        Want: WantGuard<'a, Guard> + 'a,
+    {
+        let Self { data, mu } = self;
+        ProtectedGuard {
+            _guard: mu.lock(),
+            data,
+        }
+    }
+    pub fn try_lock<'a, Guard, Want>(&'a mut self) -> Option<ProtectedGuard<'a, T, Guard>>
+    where
+        M: NoStarveMutex<'a, Guard, Want>,
+        Guard: Drop + 'a,
+        Want: WantGuard<'a, Guard> + 'a,
+    {
+        let Self { data, mu } = self;
+        Some(ProtectedGuard {
+            _guard: mu.try_lock()?,
+            data,
+        })
+    }
+    pub fn lock_timeout<'a, Guard, Want>(
+        &'a mut self,
+        dur: std::time::Duration,
+    ) -> Option<ProtectedGuard<'a, T, Guard>>
+    where
+        M: NoStarveMutex<'a, Guard, Want>,
+        Guard: Drop + 'a,
+        Want: WantGuard<'a, Guard> + 'a,
+    {
+        let Self { data, mu } = self;
+        Some(ProtectedGuard {
+            _guard: mu.lock_timeout(dur)?,
+            data,
+        })
+    }
+}
+
+impl<T, Guard: Reacquire> Reacquire for ProtectedGuard<'_, T, Guard> {
+    fn reacquire(self, f: impl FnOnce()) -> Self {
+        Self {
+            _guard: self._guard.reacquire(f),
+            data: self.data,
+        }
+    }
+}
+
+impl<T, Guard> Deref for ProtectedGuard<'_, T, Guard> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        // SAFETY: the guard excludes every other handle of the mutex
+        unsafe { &*self.data.get() }
+    }
+}
+impl<T, Guard> DerefMut for ProtectedGuard<'_, T, Guard> {
+    fn deref_mut(&mut self) -> &mut T {
+        // SAFETY: as for deref, and the guard is borrowed mutably
+        unsafe { &mut *self.data.get() }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::sync::Protected;
+    use std::time::Duration;
+    const N_THREADS: usize = 4;
+    const WORK: usize = 100_000 / N_THREADS;
+
+    #[test]
+    fn mutual_exclusion() {
+        let ths = Protected::n_ary(Vec::new(), N_THREADS)
+            .into_iter()
+            .enumerate()
+            .map(|(n, mut data)| {
+                std::thread::spawn(move || {
+                    for _ in 0..WORK / 100 {
+                        // Not atomic, so interleaving would lose pushes
+                        let mut guard = data.lock();
+                        let len = guard.len();
+                        std::thread::yield_now();
+                        guard.push(n);
+                        assert_eq!(guard.len(), len + 1);
+                    }
+                    data
+                })
+            })
+            .collect::<Vec<_>>();
+        let mut data: Vec<_> = ths.into_iter().map(|th| th.join().unwrap()).collect();
+        assert_eq!(data[0].lock().len(), WORK / 100 * N_THREADS);
+        let last = data.pop().unwrap();
+        assert!(last.into_inner().is_none());
+        data.truncate(1);
+        let v = data.pop().unwrap().into_inner().unwrap();
+        assert!((0..N_THREADS).all(|n| v.iter().filter(|&&m| m == n).count() == WORK / 100));
+    }
+
+    #[test]
+    fn try_lock() {
+        let (mut a, mut b) = Protected::binary(String::new());
+        let mut guard = a.lock();
+        guard.push('a');
+        assert!(b.try_lock().is_none());
+        assert!(b.lock_timeout(Duration::from_millis(10)).is_none());
+        drop(guard);
+        b.try_lock().unwrap().push('b');
+        drop(b);
+        assert_eq!(a.into_inner().unwrap(), "ab");
+    }
+}
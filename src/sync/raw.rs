@@ -0,0 +1,248 @@
+use super::cohort::Ticket;
+use super::filter::{FilterGuard, FilterLock, FilterLockN};
+use super::lamports_bakery::{Bakery, BakeryGuard, BakeryN};
+use super::NoStarveMutex;
+use lock_api::{GuardSend, RawMutex};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, OnceLock};
+
+/// `lock_api::Mutex` over Lamport's Bakery, for up to `N` threads waiting at the same time.
+///
+/// # Examples
+/// ```
+/// use rads::sync::raw::BakeryMutex;
+///
+/// let data = std::sync::Arc::new(BakeryMutex::<_, 4>::new(0));
+/// let ths = (0..8)
+///     .map(|n| {
+///         let data = data.clone();
+///         std::thread::spawn(move || {
+///             for _ in 0..100 {
+///                 *data.lock() += n;
+///             }
+///         })
+///     })
+///     .collect::<Vec<_>>();
+/// ths.into_iter().for_each(|th| th.join().unwrap());
+/// assert_eq!(*data.lock(), 100 * (0..8).sum::<i32>());
+/// ```
+pub type BakeryMutex<T, const N: usize> = lock_api::Mutex<RawBakery<N>, T>;
+/// `lock_api::Mutex` over the filter lock, for up to `N` threads waiting at the same time.
+pub type FilterMutex<T, const N: usize> = lock_api::Mutex<RawFilter<N>, T>;
+/// `lock_api::Mutex` over a ticket lock, for any number of threads.
+pub type TicketMutex<T> = lock_api::Mutex<RawTicket, T>;
+
+/// Slots of an n-ary lock, each of which a thread claims for as long as it waits for or holds the lock.
+struct Slots<const N: usize> {
+    taken: [AtomicBool; N],
+    /// Slot of the thread holding the lock, so that `unlock` needs not be called by the same thread
+    holder: AtomicUsize,
+}
+impl<const N: usize> Slots<N> {
+    const fn new() -> Self {
+        Self {
+            taken: [const { AtomicBool::new(false) }; N],
+            holder: AtomicUsize::new(0),
+        }
+    }
+    /// Waits for a free slot, since there may be more threads than slots
+    fn claim(&self) -> usize {
+        loop {
+            if let Some(n) = self.try_claim() {
+                return n;
+            }
+            std::thread::yield_now();
+        }
+    }
+    fn try_claim(&self) -> Option<usize> {
+        self.taken.iter().position(|t| {
+            t.compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst)
+                .is_ok()
+        })
+    }
+    fn release(&self, n: usize) {
+        self.taken[n].store(false, Ordering::SeqCst);
+    }
+}
+
+/// `lock_api::RawMutex` over a `Bakery` of `N` slots, which threads claim while they wait for or hold the lock.
+pub struct RawBakery<const N: usize> {
+    // Allocated on first use, since `RawMutex::INIT` cannot allocate
+    bakery: OnceLock<Arc<Bakery>>,
+    slots: Slots<N>,
+}
+impl<const N: usize> RawBakery<N> {
+    fn bakery(&self) -> &Arc<Bakery> {
+        self.bakery.get_or_init(|| Arc::new(Bakery::new(N)))
+    }
+}
+unsafe impl<const N: usize> RawMutex for RawBakery<N> {
+    #[allow(clippy::declare_interior_mutable_const)]
+    const INIT: Self = Self {
+        bakery: OnceLock::new(),
+        slots: Slots::new(),
+    };
+    type GuardMarker = GuardSend;
+
+    fn lock(&self) {
+        let n = self.slots.claim();
+        // Released by `unlock`, not the guard
+        std::mem::forget(BakeryN::new(n, self.bakery()).lock());
+        self.slots.holder.store(n, Ordering::SeqCst);
+    }
+    fn try_lock(&self) -> bool {
+        let Some(n) = self.slots.try_claim() else {
+            return false;
+        };
+        match BakeryN::new(n, self.bakery()).try_lock() {
+            Some(guard) => {
+                std::mem::forget(guard);
+                self.slots.holder.store(n, Ordering::SeqCst);
+                true
+            }
+            None => {
+                self.slots.release(n);
+                false
+            }
+        }
+    }
+    unsafe fn unlock(&self) {
+        let n = self.slots.holder.load(Ordering::SeqCst);
+        drop(BakeryGuard(&BakeryN::new(n, self.bakery())));
+        self.slots.release(n);
+    }
+}
+
+/// `lock_api::RawMutex` over a `FilterLock` of `N` levels, which threads claim while they wait for or hold the lock.
+pub struct RawFilter<const N: usize> {
+    // Allocated on first use, since `RawMutex::INIT` cannot allocate
+    filter: OnceLock<Arc<FilterLock>>,
+    slots: Slots<N>,
+}
+impl<const N: usize> RawFilter<N> {
+    fn filter(&self) -> &Arc<FilterLock> {
+        self.filter.get_or_init(|| Arc::new(FilterLock::new(N)))
+    }
+}
+unsafe impl<const N: usize> RawMutex for RawFilter<N> {
+    #[allow(clippy::declare_interior_mutable_const)]
+    const INIT: Self = Self {
+        filter: OnceLock::new(),
+        slots: Slots::new(),
+    };
+    type GuardMarker = GuardSend;
+
+    fn lock(&self) {
+        let n = self.slots.claim();
+        // Released by `unlock`, not the guard
+        std::mem::forget(FilterLockN::new(n, self.filter()).lock());
+        self.slots.holder.store(n, Ordering::SeqCst);
+    }
+    fn try_lock(&self) -> bool {
+        let Some(n) = self.slots.try_claim() else {
+            return false;
+        };
+        match FilterLockN::new(n, self.filter()).try_lock() {
+            Some(guard) => {
+                std::mem::forget(guard);
+                self.slots.holder.store(n, Ordering::SeqCst);
+                true
+            }
+            None => {
+                self.slots.release(n);
+                false
+            }
+        }
+    }
+    unsafe fn unlock(&self) {
+        let n = self.slots.holder.load(Ordering::SeqCst);
+        drop(FilterGuard(&FilterLockN::new(n, self.filter())));
+        self.slots.release(n);
+    }
+}
+
+/// `lock_api::RawMutex` over a first-come first-served ticket lock, which needs no slots.
+pub struct RawTicket(Ticket);
+unsafe impl RawMutex for RawTicket {
+    #[allow(clippy::declare_interior_mutable_const)]
+    const INIT: Self = Self(Ticket::new());
+    type GuardMarker = GuardSend;
+
+    fn lock(&self) {
+        self.0.wait(self.0.take());
+    }
+    fn try_lock(&self) -> bool {
+        self.0.try_take().is_some()
+    }
+    unsafe fn unlock(&self) {
+        self.0.release();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::sync::raw::{
+        BakeryMutex, FilterMutex, RawBakery, RawFilter, RawTicket, TicketMutex,
+    };
+    use lock_api::{Mutex, RawMutex};
+    const N_THREADS: usize = 4;
+    const WORK: usize = 100_000 / N_THREADS;
+
+    /// Runs twice as many threads as `N_THREADS`, pushing to the same `Vec` under the lock
+    fn mutual_exclusion<R: RawMutex + Send + Sync + 'static>() {
+        let data = std::sync::Arc::new(Mutex::<R, _>::new(Vec::new()));
+        let ths = (0..N_THREADS * 2)
+            .map(|n| {
+                let data = data.clone();
+                std::thread::spawn(move || {
+                    for _ in 0..WORK / 100 {
+                        let mut guard = data.lock();
+                        let len = guard.len();
+                        std::thread::yield_now();
+                        guard.push(n);
+                        assert_eq!(guard.len(), len + 1);
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        ths.into_iter().for_each(|th| th.join().unwrap());
+        assert_eq!(data.lock().len(), WORK / 100 * N_THREADS * 2);
+    }
+
+    #[test]
+    fn bakery() {
+        mutual_exclusion::<RawBakery<N_THREADS>>();
+    }
+
+    #[test]
+    fn filter() {
+        mutual_exclusion::<RawFilter<N_THREADS>>();
+    }
+
+    #[test]
+    fn ticket() {
+        mutual_exclusion::<RawTicket>();
+    }
+
+    #[test]
+    fn try_lock() {
+        let bakery = BakeryMutex::<_, 2>::new(0);
+        let guard = bakery.lock();
+        assert!(bakery.try_lock().is_none());
+        drop(guard);
+        *bakery.try_lock().unwrap() += 1;
+        assert!(!bakery.is_locked());
+
+        let filter = FilterMutex::<_, 2>::new(0);
+        let guard = filter.lock();
+        assert!(filter.try_lock().is_none());
+        drop(guard);
+        assert!(filter.try_lock().is_some());
+
+        let ticket = TicketMutex::new(0);
+        let guard = ticket.lock();
+        assert!(ticket.try_lock().is_none());
+        drop(guard);
+        assert!(ticket.try_lock().is_some());
+    }
+}
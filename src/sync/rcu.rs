@@ -0,0 +1,212 @@
+use std::ops::Deref;
+use std::sync::atomic::{AtomicPtr, AtomicUsize, Ordering};
+
+/// Data that readers access without ever waiting, while an updater replaces it with a new version and frees the old
+/// one once no reader can see it anymore (read-copy-update).
+///
+/// Readers count themselves in one of two slots, by the parity of the epoch when they start reading. An update
+/// publishes the new version, then starts the next epoch, so that readers starting from then on see the new version
+/// and count themselves in the other slot. Once the slot of the previous epoch drains, the grace period is over, and no
+/// reader can see the old version anymore.
+///
+/// Updates wait for each other and for readers of the old version, so a thread must not update while it reads.
+///
+/// # Examples
+/// ```
+/// use rads::sync::rcu::Rcu;
+///
+/// let config = std::sync::Arc::new(Rcu::new(vec!["a"]));
+/// let reader = config.read();
+/// let th = std::thread::spawn({
+///     let config = config.clone();
+///     // Waits for the reader of the old version
+///     move || config.update(|v| [v.as_slice(), &["b"]].concat())
+/// });
+/// assert_eq!(*reader, ["a"]);
+/// drop(reader);
+/// th.join().unwrap();
+/// assert_eq!(*config.read(), ["a", "b"]);
+/// ```
+pub struct Rcu<T> {
+    current: AtomicPtr<T>,
+    epoch: AtomicUsize,
+    /// Readers that started in an even or odd epoch, and still read
+    readers: [AtomicUsize; 2],
+    /// Serializes updates, which only readers need not wait for
+    updating: std::sync::Mutex<()>,
+}
+// Readers of every thread share the data, and whichever thread updates last frees it
+unsafe impl<T: Send + Sync> Send for Rcu<T> {}
+unsafe impl<T: Send + Sync> Sync for Rcu<T> {}
+
+pub struct RcuReadGuard<'a, T> {
+    rcu: &'a Rcu<T>,
+    slot: usize,
+    data: &'a T,
+}
+
+impl<T> Rcu<T> {
+    pub fn new(value: T) -> Self {
+        Self {
+            current: AtomicPtr::new(Box::into_raw(Box::new(value))),
+            epoch: AtomicUsize::new(0),
+            readers: [AtomicUsize::new(0), AtomicUsize::new(0)],
+            updating: std::sync::Mutex::new(()),
+        }
+    }
+    /// Number of updates whose grace period started
+    pub fn epoch(&self) -> usize {
+        self.epoch.load(Ordering::SeqCst)
+    }
+    /// Current version, which stays alive until the guard drops, without waiting for updates
+    pub fn read(&self) -> RcuReadGuard<'_, T> {
+        let slot = loop {
+            let epoch = self.epoch.load(Ordering::SeqCst);
+            let slot = epoch % 2;
+            self.readers[slot].fetch_add(1, Ordering::SeqCst);
+            // Counted in the epoch's slot before it ended, so an update waits for this reader
+            if self.epoch.load(Ordering::SeqCst) == epoch {
+                break slot;
+            }
+            self.readers[slot].fetch_sub(1, Ordering::SeqCst);
+        };
+        // SAFETY: the version loaded after counting lives until the slot drains
+        let data = unsafe { &*self.current.load(Ordering::SeqCst) };
+        RcuReadGuard {
+            rcu: self,
+            slot,
+            data,
+        }
+    }
+    /// Publishes the version that `f` makes of the current one, then frees the old version once the grace period of
+    /// its readers is over
+    pub fn update(&self, f: impl FnOnce(&T) -> T) {
+        let _updating = self.updating.lock().unwrap();
+        // SAFETY: only updates free versions, and this one excludes the others
+        let new = Box::new(f(unsafe { &*self.current.load(Ordering::SeqCst) }));
+        let old = self.current.swap(Box::into_raw(new), Ordering::SeqCst);
+        let epoch = self.epoch.fetch_add(1, Ordering::SeqCst);
+        while self.readers[epoch % 2].load(Ordering::SeqCst) > 0 {
+            std::thread::yield_now();
+        }
+        // SAFETY: readers since the new epoch see the new version, and those before it left
+        drop(unsafe { Box::from_raw(old) });
+    }
+}
+
+impl<T> Drop for Rcu<T> {
+    fn drop(&mut self) {
+        // SAFETY: no reader borrows it anymore
+        drop(unsafe { Box::from_raw(*self.current.get_mut()) });
+    }
+}
+
+impl<T> Deref for RcuReadGuard<'_, T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        self.data
+    }
+}
+impl<T> Drop for RcuReadGuard<'_, T> {
+    fn drop(&mut self) {
+        self.rcu.readers[self.slot].fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::sync::rcu::Rcu;
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+    const N_THREADS: usize = 4;
+    const WORK: usize = 1_000;
+
+    /// Counts the versions dropped
+    struct Version(usize, Arc<AtomicUsize>);
+    impl Drop for Version {
+        fn drop(&mut self) {
+            self.1.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn update_waits_for_readers() {
+        let dropped = Arc::new(AtomicUsize::new(0));
+        let rcu = Arc::new(Rcu::new(Version(0, dropped.clone())));
+        let updated = Arc::new(AtomicBool::new(false));
+        let guard = rcu.read();
+        let th = std::thread::spawn({
+            let (rcu, updated) = (rcu.clone(), updated.clone());
+            move || {
+                rcu.update(|v| Version(v.0 + 1, v.1.clone()));
+                updated.store(true, Ordering::SeqCst);
+            }
+        });
+        while rcu.epoch() == 0 {
+            std::thread::yield_now();
+        }
+        // New readers see the new version without waiting, while the old one stays alive
+        assert_eq!(rcu.read().0, 1);
+        std::thread::sleep(Duration::from_millis(50));
+        assert!(!updated.load(Ordering::SeqCst));
+        assert_eq!((guard.0, dropped.load(Ordering::SeqCst)), (0, 0));
+        drop(guard);
+        th.join().unwrap();
+        assert_eq!(dropped.load(Ordering::SeqCst), 1);
+        drop(rcu);
+        assert_eq!(dropped.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn readers_see_whole_versions() {
+        let rcu = Arc::new(Rcu::new((0, vec![0; 16])));
+        let done = Arc::new(AtomicBool::new(false));
+        let readers: Vec<_> = (0..N_THREADS - 1)
+            .map(|_| {
+                let (rcu, done) = (rcu.clone(), done.clone());
+                std::thread::spawn(move || {
+                    let mut last = 0;
+                    while !done.load(Ordering::SeqCst) {
+                        let guard = rcu.read();
+                        let (n, ref v) = *guard;
+                        std::thread::yield_now();
+                        // Freed or torn versions would differ
+                        assert!(v.iter().all(|&m| m == n));
+                        assert!(n >= last, "Expect versions in order");
+                        last = n;
+                    }
+                })
+            })
+            .collect();
+        for n in 1..=WORK {
+            rcu.update(|_| (n, vec![n; 16]));
+        }
+        done.store(true, Ordering::SeqCst);
+        readers.into_iter().for_each(|th| th.join().unwrap());
+        assert_eq!(rcu.read().0, WORK);
+        assert_eq!(rcu.epoch(), WORK);
+    }
+
+    #[test]
+    fn frees_every_version() {
+        let dropped = Arc::new(AtomicUsize::new(0));
+        let rcu = Arc::new(Rcu::new(Version(0, dropped.clone())));
+        let ths: Vec<_> = (0..N_THREADS)
+            .map(|_| {
+                let rcu = rcu.clone();
+                std::thread::spawn(move || {
+                    for _ in 0..WORK / 10 {
+                        rcu.update(|v| Version(v.0 + 1, v.1.clone()));
+                        assert!(rcu.read().0 > 0);
+                    }
+                })
+            })
+            .collect();
+        ths.into_iter().for_each(|th| th.join().unwrap());
+        assert_eq!(rcu.read().0, N_THREADS * WORK / 10);
+        assert_eq!(dropped.load(Ordering::SeqCst), N_THREADS * WORK / 10);
+        drop(rcu);
+        assert_eq!(dropped.load(Ordering::SeqCst), N_THREADS * WORK / 10 + 1);
+    }
+}
@@ -0,0 +1,616 @@
+use super::cohort::Ticket;
+use std::cell::UnsafeCell;
+use std::ops::{Deref, DerefMut};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+/// Which threads a readers-writer lock admits first when both readers and writers want it, chosen at construction.
+#[derive(Clone, Copy, Default, Debug, PartialEq, Eq)]
+pub enum RwPolicy {
+    /// Readers enter whenever no writer holds the lock, which maximizes throughput for read-heavy workloads, though a
+    /// continuous stream of readers starves writers
+    ReadPreferring,
+    /// Readers wait while any writer waits, which keeps the data fresh for write-heavy workloads, though a continuous
+    /// stream of writers starves readers
+    WritePreferring,
+    /// Readers and writers enter first-come first-served by tickets, so that neither starves
+    #[default]
+    TaskFair,
+    /// Readers and writers take turns in phases, so that neither starves, while all readers that waited for a writer
+    /// enter together before the next writer (Brandenburg-Anderson phase-fair ticket lock)
+    PhaseFair,
+}
+
+/// Readers-writer lock that admits readers and writers first-come first-served by default, so that a continuous stream
+/// of readers cannot starve a writer, nor writers a reader.
+///
+/// Like the Bakery, each thread takes a ticket and waits until it is served. A reader lets the next ticket in as soon as
+/// it entered, so that consecutive readers share the lock, while a writer waits for the readers ahead of it to leave and
+/// holds up every ticket after its own. Readers arriving once a writer waits thus queue up behind it. Construct it
+/// `with_policy` to trade this fairness for another [`RwPolicy`].
+///
+/// # Examples
+/// ```
+/// use rads::sync::RwFair;
+///
+/// let data = std::sync::Arc::new(RwFair::new(0));
+/// let ths = (0..4)
+///     .map(|n| {
+///         let data = data.clone();
+///         std::thread::spawn(move || {
+///             for _ in 0..1_000 {
+///                 if n == 0 {
+///                     *data.write() += 1;
+///                 } else {
+///                     assert!(*data.read() <= 1_000);
+///                 }
+///             }
+///         })
+///     })
+///     .collect::<Vec<_>>();
+/// ths.into_iter().for_each(|th| th.join().unwrap());
+/// assert_eq!(*data.read(), 1_000);
+/// ```
+#[derive(Default)]
+pub struct RwFair<T> {
+    policy: RwPolicy,
+    /// Tickets of readers and writers if task-fair, or of writers only if phase-fair
+    ticket: Ticket,
+    /// Number of readers that entered and have not left yet, unless phase-fair
+    readers: AtomicUsize,
+    /// Whether a writer holds or claimed the lock, if read- or write-preferring
+    writer: AtomicBool,
+    /// Number of writers waiting to claim the lock, if write-preferring
+    waiting_writers: AtomicUsize,
+    /// Readers that entered in multiples of `READER`, plus the `WRITER` bits of the writer present, if phase-fair
+    rin: AtomicUsize,
+    /// Readers that left in multiples of `READER`, if phase-fair
+    rout: AtomicUsize,
+    /// Tickets of writers and upgradable readers, if constructed `with_upgrades`
+    upgrades: Option<Ticket>,
+    data: UnsafeCell<T>,
+}
+// Readers share the data between threads, as with `std::sync::RwLock`
+unsafe impl<T: Send> Send for RwFair<T> {}
+unsafe impl<T: Send + Sync> Sync for RwFair<T> {}
+
+/// Increment of `rin` and `rout` per reader, leaving the low bits to the writer
+const READER: usize = 0b100;
+/// Bits of `rin` set while a writer is present, i.e. `PRESENT` and the writer's phase
+const WRITER: usize = 0b11;
+const PRESENT: usize = 0b10;
+const PHASE: usize = 0b1;
+
+pub struct RwFairReadGuard<'a, T>(&'a RwFair<T>);
+pub struct RwFairWriteGuard<'a, T>(&'a RwFair<T>);
+/// Read guard that excludes writers and other upgradable readers, but not readers, until it upgrades to write
+pub struct RwFairUpgradableGuard<'a, T>(&'a RwFair<T>);
+
+impl<T> RwFair<T> {
+    pub fn new(value: T) -> Self {
+        Self {
+            policy: RwPolicy::default(),
+            ticket: Ticket::new(),
+            readers: AtomicUsize::new(0),
+            writer: AtomicBool::new(false),
+            waiting_writers: AtomicUsize::new(0),
+            rin: AtomicUsize::new(0),
+            rout: AtomicUsize::new(0),
+            upgrades: None,
+            data: UnsafeCell::new(value),
+        }
+    }
+    pub fn with_policy(self, policy: RwPolicy) -> Self {
+        Self { policy, ..self }
+    }
+    /// Lets a reader read `upgradable_read`, at the cost of writers waiting in line with upgradable readers first,
+    /// which lets readers that arrived after them enter before them
+    pub fn with_upgrades(self) -> Self {
+        Self {
+            upgrades: Some(Ticket::new()),
+            ..self
+        }
+    }
+    pub fn into_inner(self) -> T {
+        self.data.into_inner()
+    }
+    /// Waits until the policy admits it, then shares the lock with other readers
+    pub fn read(&self) -> RwFairReadGuard<'_, T> {
+        self.enter_read();
+        RwFairReadGuard(self)
+    }
+    /// Waits until no writer or other upgradable reader holds the lock, then shares it with other readers until it
+    /// upgrades, which no writer can enter before.
+    ///
+    /// # Panics
+    /// Unless constructed `with_upgrades`
+    ///
+    /// # Examples
+    /// ```
+    /// use rads::sync::RwFair;
+    ///
+    /// let cache = RwFair::new(None).with_upgrades();
+    /// let guard = cache.upgradable_read();
+    /// // Readers still share the lock meanwhile
+    /// assert_eq!(*cache.read(), None);
+    /// if guard.is_none() {
+    ///     // No writer can fill it in between
+    ///     let mut guard = guard.upgrade();
+    ///     *guard = Some(42);
+    ///     assert_eq!(*guard.downgrade(), Some(42));
+    /// }
+    /// ```
+    pub fn upgradable_read(&self) -> RwFairUpgradableGuard<'_, T> {
+        let upgrades = (self.upgrades.as_ref()).expect("Expect a lock constructed `with_upgrades`");
+        upgrades.wait(upgrades.take());
+        self.enter_read();
+        RwFairUpgradableGuard(self)
+    }
+    /// Waits until the policy admits it and every reader left
+    pub fn write(&self) -> RwFairWriteGuard<'_, T> {
+        if let Some(upgrades) = &self.upgrades {
+            upgrades.wait(upgrades.take());
+        }
+        self.enter_write();
+        RwFairWriteGuard(self)
+    }
+    fn enter_read(&self) {
+        match self.policy {
+            RwPolicy::ReadPreferring => loop {
+                // Counts itself before checking for a writer, which checks for readers after claiming the lock
+                self.readers.fetch_add(1, Ordering::SeqCst);
+                if !self.writer.load(Ordering::SeqCst) {
+                    break;
+                }
+                self.readers.fetch_sub(1, Ordering::SeqCst);
+                spin_while(|| self.writer.load(Ordering::SeqCst));
+            },
+            RwPolicy::WritePreferring => loop {
+                let writing = || {
+                    self.writer.load(Ordering::SeqCst)
+                        || self.waiting_writers.load(Ordering::SeqCst) > 0
+                };
+                spin_while(writing);
+                self.readers.fetch_add(1, Ordering::SeqCst);
+                if !writing() {
+                    break;
+                }
+                self.readers.fetch_sub(1, Ordering::SeqCst);
+            },
+            RwPolicy::TaskFair => {
+                self.ticket.wait(self.ticket.take());
+                // Counts itself before letting the next ticket in, so that a writer behind it waits for it to leave
+                self.readers.fetch_add(1, Ordering::SeqCst);
+                self.ticket.release();
+            }
+            RwPolicy::PhaseFair => {
+                // Waits only for the writer present when it arrived, if any
+                let writer = self.rin.fetch_add(READER, Ordering::SeqCst) & WRITER;
+                if writer != 0 {
+                    spin_while(|| self.rin.load(Ordering::SeqCst) & WRITER == writer);
+                }
+            }
+        }
+    }
+    fn enter_write(&self) {
+        match self.policy {
+            RwPolicy::ReadPreferring => loop {
+                spin_while(|| self.readers.load(Ordering::SeqCst) > 0);
+                if self.claim() {
+                    if self.readers.load(Ordering::SeqCst) == 0 {
+                        break;
+                    }
+                    // Makes way for the reader that arrived meanwhile
+                    self.writer.store(false, Ordering::SeqCst);
+                }
+            },
+            RwPolicy::WritePreferring => {
+                self.waiting_writers.fetch_add(1, Ordering::SeqCst);
+                spin_while(|| !self.claim());
+                self.waiting_writers.fetch_sub(1, Ordering::SeqCst);
+                spin_while(|| self.readers.load(Ordering::SeqCst) > 0);
+            }
+            RwPolicy::TaskFair => {
+                self.ticket.wait(self.ticket.take());
+                spin_while(|| self.readers.load(Ordering::SeqCst) > 0);
+            }
+            RwPolicy::PhaseFair => {
+                let ticket = self.ticket.take();
+                self.ticket.wait(ticket);
+                // Blocks readers arriving from now on, and waits for those that arrived before
+                let entered = self
+                    .rin
+                    .fetch_add(PRESENT | ticket & PHASE, Ordering::SeqCst);
+                spin_while(|| self.rout.load(Ordering::SeqCst) != entered);
+            }
+        }
+    }
+    fn leave_read(&self) {
+        match self.policy {
+            RwPolicy::PhaseFair => self.rout.fetch_add(READER, Ordering::SeqCst),
+            _ => self.readers.fetch_sub(1, Ordering::SeqCst),
+        };
+    }
+    fn leave_write(&self) {
+        match self.policy {
+            RwPolicy::ReadPreferring | RwPolicy::WritePreferring => {
+                self.writer.store(false, Ordering::SeqCst)
+            }
+            RwPolicy::TaskFair => self.ticket.release(),
+            RwPolicy::PhaseFair => {
+                // Lets in the readers that arrived meanwhile, before the next writer
+                self.rin.fetch_and(!WRITER, Ordering::SeqCst);
+                self.ticket.release();
+            }
+        }
+        self.release_upgrades();
+    }
+    /// Lets the next writer or upgradable reader in line wait for the lock, if constructed `with_upgrades`
+    fn release_upgrades(&self) {
+        if let Some(upgrades) = &self.upgrades {
+            upgrades.release();
+        }
+    }
+    /// Claims the lock for a writer, if read- or write-preferring
+    fn claim(&self) -> bool {
+        self.writer
+            .compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst)
+            .is_ok()
+    }
+}
+
+fn spin_while(blocked: impl Fn() -> bool) {
+    while blocked() {
+        std::thread::yield_now();
+    }
+}
+
+impl<'a, T> RwFairWriteGuard<'a, T> {
+    /// Turns into a read guard without letting any writer in between, so that it reads what it wrote
+    pub fn downgrade(self) -> RwFairReadGuard<'a, T> {
+        let lock = self.0;
+        std::mem::forget(self);
+        match lock.policy {
+            RwPolicy::ReadPreferring | RwPolicy::WritePreferring => {
+                lock.readers.fetch_add(1, Ordering::SeqCst);
+                lock.writer.store(false, Ordering::SeqCst);
+            }
+            RwPolicy::TaskFair => {
+                lock.readers.fetch_add(1, Ordering::SeqCst);
+                lock.ticket.release();
+            }
+            RwPolicy::PhaseFair => {
+                // Enters as a reader while still present as the writer, so that the next writer waits for it
+                lock.rin.fetch_add(READER, Ordering::SeqCst);
+                lock.rin.fetch_and(!WRITER, Ordering::SeqCst);
+                lock.ticket.release();
+            }
+        }
+        lock.release_upgrades();
+        RwFairReadGuard(lock)
+    }
+}
+impl<'a, T> RwFairUpgradableGuard<'a, T> {
+    /// Waits until every other reader left, then writes, without letting any writer in between
+    pub fn upgrade(self) -> RwFairWriteGuard<'a, T> {
+        let lock = self.0;
+        std::mem::forget(self);
+        // Writers wait in line behind it, so only readers may enter meanwhile, which the writer waits for
+        lock.leave_read();
+        lock.enter_write();
+        RwFairWriteGuard(lock)
+    }
+    /// Lets the next writer or upgradable reader in line wait for the lock, while still reading
+    pub fn downgrade(self) -> RwFairReadGuard<'a, T> {
+        let lock = self.0;
+        std::mem::forget(self);
+        lock.release_upgrades();
+        RwFairReadGuard(lock)
+    }
+}
+
+impl<T> Drop for RwFairReadGuard<'_, T> {
+    fn drop(&mut self) {
+        self.0.leave_read();
+    }
+}
+impl<T> Drop for RwFairWriteGuard<'_, T> {
+    fn drop(&mut self) {
+        self.0.leave_write();
+    }
+}
+impl<T> Drop for RwFairUpgradableGuard<'_, T> {
+    fn drop(&mut self) {
+        self.0.leave_read();
+        self.0.release_upgrades();
+    }
+}
+
+impl<T> Deref for RwFairReadGuard<'_, T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        // SAFETY: no writer holds the lock while a reader does
+        unsafe { &*self.0.data.get() }
+    }
+}
+impl<T> Deref for RwFairUpgradableGuard<'_, T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        // SAFETY: no writer holds the lock while a reader does
+        unsafe { &*self.0.data.get() }
+    }
+}
+impl<T> Deref for RwFairWriteGuard<'_, T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        // SAFETY: the writer excludes every reader and other writer
+        unsafe { &*self.0.data.get() }
+    }
+}
+impl<T> DerefMut for RwFairWriteGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        // SAFETY: as for deref, and the guard is borrowed mutably
+        unsafe { &mut *self.0.data.get() }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::sync::{RwFair, RwPolicy};
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+    const N_THREADS: usize = 4;
+    const WORK: usize = 10_000 / N_THREADS;
+    const POLICIES: [RwPolicy; 4] = [
+        RwPolicy::ReadPreferring,
+        RwPolicy::WritePreferring,
+        RwPolicy::TaskFair,
+        RwPolicy::PhaseFair,
+    ];
+
+    #[test]
+    fn mutual_exclusion() {
+        for policy in POLICIES {
+            let data = Arc::new(RwFair::new(Vec::new()).with_policy(policy));
+            let ths = (0..N_THREADS)
+                .map(|n| {
+                    let data = data.clone();
+                    std::thread::spawn(move || {
+                        for _ in 0..WORK / 10 {
+                            // Not atomic, so interleaving would lose pushes
+                            let mut guard = data.write();
+                            let len = guard.len();
+                            std::thread::yield_now();
+                            guard.push(n);
+                            assert_eq!(guard.len(), len + 1);
+                            drop(guard);
+                            let guard = data.read();
+                            let len = guard.len();
+                            std::thread::yield_now();
+                            assert_eq!(guard.len(), len);
+                        }
+                    })
+                })
+                .collect::<Vec<_>>();
+            ths.into_iter().for_each(|th| th.join().unwrap());
+            let data = Arc::into_inner(data).unwrap().into_inner();
+            assert_eq!(data.len(), WORK / 10 * N_THREADS, "{policy:?}");
+        }
+    }
+
+    #[test]
+    fn readers_share() {
+        for policy in POLICIES {
+            let data = Arc::new(RwFair::new(0).with_policy(policy));
+            let guard = data.read();
+            // Would wait forever if readers excluded each other
+            let th = std::thread::spawn({
+                let data = data.clone();
+                move || *data.read()
+            });
+            assert_eq!(th.join().unwrap(), 0);
+            drop(guard);
+        }
+    }
+
+    #[test]
+    fn writer_not_starved_by_readers() {
+        for policy in POLICIES {
+            if policy == RwPolicy::ReadPreferring {
+                continue;
+            }
+            let data = Arc::new(RwFair::new(0).with_policy(policy));
+            let wrote = Arc::new(AtomicBool::new(false));
+            let readers: Vec<_> = (0..N_THREADS - 1)
+                .map(|_| {
+                    let (data, wrote) = (data.clone(), wrote.clone());
+                    std::thread::spawn(move || {
+                        // Overlapping reads, so that some reader always holds the lock until the writer got in
+                        while !wrote.load(Ordering::SeqCst) {
+                            let _guard = data.read();
+                            std::thread::yield_now();
+                        }
+                    })
+                })
+                .collect();
+            std::thread::sleep(Duration::from_millis(10));
+            *data.write() += 1;
+            wrote.store(true, Ordering::SeqCst);
+            readers.into_iter().for_each(|th| th.join().unwrap());
+            assert_eq!(*data.read(), 1);
+        }
+    }
+
+    #[test]
+    fn writer_waits_for_readers() {
+        for policy in POLICIES {
+            let data = Arc::new(RwFair::new(0).with_policy(policy));
+            let wrote = Arc::new(AtomicBool::new(false));
+            let guard = data.read();
+            let th = std::thread::spawn({
+                let (data, wrote) = (data.clone(), wrote.clone());
+                move || {
+                    *data.write() += 1;
+                    wrote.store(true, Ordering::SeqCst);
+                }
+            });
+            std::thread::sleep(Duration::from_millis(50));
+            assert!(!wrote.load(Ordering::SeqCst), "{policy:?}");
+            drop(guard);
+            th.join().unwrap();
+            assert_eq!(*data.read(), 1);
+        }
+    }
+
+    /// Whether a reader arriving while a writer waits for another reader enters before the writer
+    fn reader_joins_readers(policy: RwPolicy) -> bool {
+        let data = Arc::new(RwFair::new(()).with_policy(policy));
+        let order = Arc::new(Mutex::new(Vec::new()));
+        let guard = data.read();
+        let writer = std::thread::spawn({
+            let (data, order) = (data.clone(), order.clone());
+            move || {
+                let _guard = data.write();
+                order.lock().unwrap().push("writer");
+            }
+        });
+        std::thread::sleep(Duration::from_millis(50));
+        let reader = std::thread::spawn({
+            let (data, order) = (data.clone(), order.clone());
+            move || {
+                let _guard = data.read();
+                order.lock().unwrap().push("reader");
+            }
+        });
+        std::thread::sleep(Duration::from_millis(50));
+        drop(guard);
+        writer.join().unwrap();
+        reader.join().unwrap();
+        let first = order.lock().unwrap()[0];
+        first == "reader"
+    }
+
+    #[test]
+    fn preferences() {
+        assert!(reader_joins_readers(RwPolicy::ReadPreferring));
+        assert!(!reader_joins_readers(RwPolicy::WritePreferring));
+        assert!(!reader_joins_readers(RwPolicy::TaskFair));
+        assert!(!reader_joins_readers(RwPolicy::PhaseFair));
+    }
+
+    /// Order in which a writer and then a reader, both arriving while another writer holds the lock, enter
+    fn after_writer(policy: RwPolicy) -> Vec<&'static str> {
+        let data = Arc::new(RwFair::new(()).with_policy(policy));
+        let order = Arc::new(Mutex::new(Vec::new()));
+        let guard = data.write();
+        let ths: Vec<_> = [true, false]
+            .into_iter()
+            .map(|writes| {
+                let th = std::thread::spawn({
+                    let (data, order) = (data.clone(), order.clone());
+                    move || {
+                        if writes {
+                            let _guard = data.write();
+                            std::thread::sleep(Duration::from_millis(10));
+                            order.lock().unwrap().push("writer");
+                        } else {
+                            let _guard = data.read();
+                            order.lock().unwrap().push("reader");
+                        }
+                    }
+                });
+                std::thread::sleep(Duration::from_millis(50));
+                th
+            })
+            .collect();
+        drop(guard);
+        ths.into_iter().for_each(|th| th.join().unwrap());
+        Arc::into_inner(order).unwrap().into_inner().unwrap()
+    }
+
+    #[test]
+    fn downgrade_keeps_writers_out() {
+        for policy in POLICIES {
+            for upgrades in [false, true] {
+                let data = RwFair::new(0).with_policy(policy);
+                let data = Arc::new(if upgrades { data.with_upgrades() } else { data });
+                let mut guard = data.write();
+                let writer = std::thread::spawn({
+                    let data = data.clone();
+                    move || *data.write() = 2
+                });
+                std::thread::sleep(Duration::from_millis(10));
+                *guard = 1;
+                let guard = guard.downgrade();
+                std::thread::sleep(Duration::from_millis(10));
+                assert_eq!(*guard, 1, "{policy:?}");
+                drop(guard);
+                writer.join().unwrap();
+                assert_eq!(*data.read(), 2);
+            }
+        }
+    }
+
+    #[test]
+    fn upgrade_keeps_writers_out() {
+        for policy in POLICIES {
+            let data = Arc::new(RwFair::new(0).with_policy(policy).with_upgrades());
+            let (wrote, upgraded) = (
+                Arc::new(AtomicBool::new(false)),
+                Arc::new(AtomicBool::new(false)),
+            );
+            let guard = data.upgradable_read();
+            let reader = data.read();
+            let writer = std::thread::spawn({
+                let (data, wrote) = (data.clone(), wrote.clone());
+                move || {
+                    *data.write() += 10;
+                    wrote.store(true, Ordering::SeqCst);
+                }
+            });
+            let upgrader = std::thread::spawn({
+                let (data, upgraded) = (data.clone(), upgraded.clone());
+                move || {
+                    // Waits in line for the lock to upgrade
+                    let guard = data.upgradable_read();
+                    let read = *guard;
+                    *guard.upgrade() = read + 100;
+                    upgraded.store(true, Ordering::SeqCst);
+                }
+            });
+            std::thread::sleep(Duration::from_millis(10));
+            assert_eq!(*guard, 0);
+            let mut guard = std::thread::scope(|s| {
+                let upgrading = s.spawn(|| guard.upgrade());
+                // Waits for the reader
+                std::thread::sleep(Duration::from_millis(10));
+                assert!(!upgrading.is_finished(), "{policy:?}");
+                drop(reader);
+                upgrading.join().unwrap()
+            });
+            assert!(
+                !wrote.load(Ordering::SeqCst) && !upgraded.load(Ordering::SeqCst),
+                "{policy:?}"
+            );
+            *guard += 1;
+            drop(guard);
+            writer.join().unwrap();
+            upgrader.join().unwrap();
+            // Both waited in line, in either order, and neither lost the other's write
+            assert_eq!(*data.read(), 111, "{policy:?}");
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "with_upgrades")]
+    fn upgradable_read_opt_in() {
+        drop(RwFair::new(0).upgradable_read());
+    }
+
+    #[test]
+    fn phase_fair_alternates() {
+        assert_eq!(after_writer(RwPolicy::TaskFair), ["writer", "reader"]);
+        // Readers that waited for a writer enter before the next one
+        assert_eq!(after_writer(RwPolicy::PhaseFair), ["reader", "writer"]);
+    }
+}
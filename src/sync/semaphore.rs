@@ -0,0 +1,233 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Counting semaphore that hands a released permit to whichever waiter checks first (weak semaphore).
+///
+/// Nothing orders the waiters, so a thread that releases and acquires again in a loop may take the permit every time
+/// while another waits forever, as with a test-and-set spinlock. Use `StrongSemaphore` when every waiter must acquire
+/// eventually.
+///
+/// # Examples
+/// ```
+/// use rads::sync::semaphore::WeakSemaphore;
+///
+/// let sem = WeakSemaphore::new(2);
+/// let a = sem.acquire();
+/// let b = sem.acquire();
+/// assert!(sem.try_acquire().is_none());
+/// drop(a);
+/// assert_eq!(sem.available(), 1);
+/// # drop(b);
+/// ```
+pub struct WeakSemaphore {
+    permits: AtomicUsize,
+}
+pub struct WeakPermit<'a>(&'a WeakSemaphore);
+impl WeakSemaphore {
+    pub fn new(permits: usize) -> Self {
+        Self {
+            permits: AtomicUsize::new(permits),
+        }
+    }
+    pub fn acquire(&self) -> WeakPermit<'_> {
+        loop {
+            if let Some(permit) = self.try_acquire() {
+                return permit;
+            }
+            std::thread::yield_now();
+        }
+    }
+    pub fn try_acquire(&self) -> Option<WeakPermit<'_>> {
+        self.permits
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1))
+            .ok()
+            .map(|_| WeakPermit(self))
+    }
+    /// Number of permits no thread holds
+    pub fn available(&self) -> usize {
+        self.permits.load(Ordering::SeqCst)
+    }
+}
+impl Drop for WeakPermit<'_> {
+    fn drop(&mut self) {
+        self.0.permits.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+/// Counting semaphore that hands permits to waiters in the order they started waiting (strong or FIFO semaphore).
+///
+/// Each waiter takes the next ticket, and only the one whose ticket is served may take a free permit, so a thread that
+/// releases and acquires again queues up behind those already waiting. Every waiter thus acquires once `n` permits
+/// were released, where `n` is the number of waiters ahead of it.
+///
+/// # Examples
+/// ```
+/// use rads::sync::semaphore::StrongSemaphore;
+///
+/// let sem = std::sync::Arc::new(StrongSemaphore::new(1));
+/// let permit = sem.acquire();
+/// let th = std::thread::spawn({
+///     let sem = sem.clone();
+///     move || drop(sem.acquire())
+/// });
+/// // Waits in line until the permit is released
+/// while sem.waiting() == 0 {
+///     std::thread::yield_now();
+/// }
+/// drop(permit);
+/// th.join().unwrap();
+/// ```
+pub struct StrongSemaphore {
+    permits: AtomicUsize,
+    next: AtomicUsize,
+    serving: AtomicUsize,
+}
+pub struct StrongPermit<'a>(&'a StrongSemaphore);
+impl StrongSemaphore {
+    pub fn new(permits: usize) -> Self {
+        Self {
+            permits: AtomicUsize::new(permits),
+            next: AtomicUsize::new(0),
+            serving: AtomicUsize::new(0),
+        }
+    }
+    pub fn acquire(&self) -> StrongPermit<'_> {
+        let ticket = self.next.fetch_add(1, Ordering::SeqCst);
+        while self.serving.load(Ordering::SeqCst) != ticket {
+            std::thread::yield_now();
+        }
+        // First in line, so no other thread takes a permit before it
+        while !self.take() {
+            std::thread::yield_now();
+        }
+        self.serving.fetch_add(1, Ordering::SeqCst);
+        StrongPermit(self)
+    }
+    /// Acquires only if no thread waits and a permit is free
+    pub fn try_acquire(&self) -> Option<StrongPermit<'_>> {
+        let serving = self.serving.load(Ordering::SeqCst);
+        // Takes a ticket only if it is served right away
+        self.next
+            .compare_exchange(serving, serving + 1, Ordering::SeqCst, Ordering::SeqCst)
+            .ok()?;
+        let taken = self.take();
+        self.serving.fetch_add(1, Ordering::SeqCst);
+        taken.then(|| StrongPermit(self))
+    }
+    /// Number of permits no thread holds
+    pub fn available(&self) -> usize {
+        self.permits.load(Ordering::SeqCst)
+    }
+    /// Number of threads in line for a permit
+    pub fn waiting(&self) -> usize {
+        // Loads `serving` first, since it never overtakes `next`
+        let serving = self.serving.load(Ordering::SeqCst);
+        self.next.load(Ordering::SeqCst) - serving
+    }
+    fn take(&self) -> bool {
+        self.permits
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1))
+            .is_ok()
+    }
+}
+impl Drop for StrongPermit<'_> {
+    fn drop(&mut self) {
+        self.0.permits.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::sync::semaphore::{StrongSemaphore, WeakSemaphore};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+    const N_THREADS: usize = 4;
+    const PERMITS: usize = 2;
+    const WORK: usize = 1_000;
+
+    /// Most threads inside at the same time, each running `acquire` around its critical section
+    fn max_inside(acquire: impl Fn(&mut dyn FnMut()) + Send + Sync + 'static) -> usize {
+        let acquire = Arc::new(acquire);
+        let (inside, max) = (Arc::new(AtomicUsize::new(0)), Arc::new(AtomicUsize::new(0)));
+        let ths: Vec<_> = (0..N_THREADS)
+            .map(|_| {
+                let (acquire, inside, max) = (acquire.clone(), inside.clone(), max.clone());
+                std::thread::spawn(move || {
+                    for _ in 0..WORK {
+                        acquire(&mut || {
+                            let n = inside.fetch_add(1, Ordering::SeqCst) + 1;
+                            max.fetch_max(n, Ordering::SeqCst);
+                            std::thread::yield_now();
+                            inside.fetch_sub(1, Ordering::SeqCst);
+                        });
+                    }
+                })
+            })
+            .collect();
+        ths.into_iter().for_each(|th| th.join().unwrap());
+        max.load(Ordering::SeqCst)
+    }
+
+    #[test]
+    fn bounds_concurrency() {
+        let sem = WeakSemaphore::new(PERMITS);
+        assert!(
+            max_inside(move |f| {
+                let _permit = sem.acquire();
+                f()
+            }) <= PERMITS
+        );
+        let sem = StrongSemaphore::new(PERMITS);
+        assert!(
+            max_inside(move |f| {
+                let _permit = sem.acquire();
+                f()
+            }) <= PERMITS
+        );
+    }
+
+    #[test]
+    fn try_acquire() {
+        let sem = WeakSemaphore::new(1);
+        let permit = sem.try_acquire().unwrap();
+        assert!(sem.try_acquire().is_none());
+        drop(permit);
+        assert_eq!(sem.available(), 1);
+
+        let sem = StrongSemaphore::new(1);
+        let permit = sem.try_acquire().unwrap();
+        assert!(sem.try_acquire().is_none());
+        // Gave up its ticket
+        assert_eq!(sem.waiting(), 0);
+        drop(permit);
+        assert!(sem.try_acquire().is_some());
+        assert_eq!(sem.available(), 1);
+    }
+
+    #[test]
+    fn strong_first_come_first_served() {
+        let sem = Arc::new(StrongSemaphore::new(1));
+        let order = Arc::new(Mutex::new(Vec::new()));
+        let permit = sem.acquire();
+        let ths: Vec<_> = (0..N_THREADS)
+            .map(|n| {
+                let th = std::thread::spawn({
+                    let (sem, order) = (sem.clone(), order.clone());
+                    move || {
+                        let _permit = sem.acquire();
+                        order.lock().unwrap().push(n);
+                    }
+                });
+                // Queues up before the next thread
+                while sem.waiting() <= n {
+                    std::thread::yield_now();
+                }
+                th
+            })
+            .collect();
+        // A free permit goes to the first in line
+        assert!(sem.try_acquire().is_none());
+        drop(permit);
+        ths.into_iter().for_each(|th| th.join().unwrap());
+        assert_eq!(*order.lock().unwrap(), (0..N_THREADS).collect::<Vec<_>>());
+    }
+}
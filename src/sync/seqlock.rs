@@ -0,0 +1,200 @@
+use std::cell::UnsafeCell;
+
+use super::shim::{
+    atomic::{fence, AtomicUsize, Ordering},
+    thread::yield_now,
+};
+
+/// Lock for small `Copy` data that readers never block, since they copy it optimistically and retry if a writer
+/// changed it meanwhile (sequence lock).
+///
+/// A writer makes the sequence number odd while it writes, and even again once done. A reader copies the data between
+/// two loads of the sequence number, and keeps the copy only if both are the same even number. Readers thus never
+/// write to shared memory, which suits frequently read data like clocks or statistics, though a continuous stream of
+/// writers may starve a reader.
+///
+/// A reader may copy the data while a writer writes it, and only discards the torn copy afterwards. Rust has no stable
+/// way to copy memory atomically byte by byte, so the copy is volatile like in other seqlocks, and Miri reports it as a
+/// data race.
+///
+/// # Examples
+/// ```
+/// use rads::sync::SeqLock;
+///
+/// let clock = std::sync::Arc::new(SeqLock::new((0, 0)));
+/// let th = std::thread::spawn({
+///     let clock = clock.clone();
+///     move || {
+///         for n in 1..=1_000 {
+///             clock.write((n, n));
+///         }
+///     }
+/// });
+/// let (a, b) = clock.read();
+/// assert_eq!(a, b);
+/// th.join().unwrap();
+/// assert_eq!(clock.read(), (1_000, 1_000));
+/// ```
+#[derive(Default)]
+pub struct SeqLock<T: Copy> {
+    /// Odd while a writer writes, and bumped by 2 per write
+    seq: AtomicUsize,
+    data: UnsafeCell<T>,
+}
+// Readers only copy the data, which they discard if a writer wrote it meanwhile
+unsafe impl<T: Copy + Send> Send for SeqLock<T> {}
+unsafe impl<T: Copy + Send> Sync for SeqLock<T> {}
+
+impl<T: Copy> SeqLock<T> {
+    pub fn new(value: T) -> Self {
+        Self {
+            seq: AtomicUsize::new(0),
+            data: UnsafeCell::new(value),
+        }
+    }
+    pub fn into_inner(self) -> T {
+        self.data.into_inner()
+    }
+    /// Copies the data once no writer wrote it during the copy
+    pub fn read(&self) -> T {
+        loop {
+            if let Some(value) = self.try_read() {
+                return value;
+            }
+            yield_now();
+        }
+    }
+    /// Copies the data, unless a writer wrote it during the copy
+    pub fn try_read(&self) -> Option<T> {
+        // Acquires the data of the write that made it even
+        let seq = self.seq.load(Ordering::Acquire);
+        if seq & 1 == 1 {
+            return None;
+        }
+        // SAFETY: T is Copy, and the copy is discarded if it raced with a writer
+        let value = unsafe { std::ptr::read_volatile(self.data.get()) };
+        // Keeps the copy before the second load, which sees any write that the copy may have seen
+        fence(Ordering::Acquire);
+        (self.seq.load(Ordering::Relaxed) == seq).then_some(value)
+    }
+    /// Waits for other writers, then replaces the data
+    pub fn write(&self, value: T) {
+        let seq = loop {
+            let seq = self.seq.load(Ordering::Relaxed);
+            let exclusive = seq & 1 == 0
+                && (self.seq)
+                    .compare_exchange_weak(seq, seq + 1, Ordering::Acquire, Ordering::Relaxed)
+                    .is_ok();
+            if exclusive {
+                break seq;
+            }
+            yield_now();
+        };
+        // Keeps the write after the odd number, so that a reader that copies any of it sees the number change
+        fence(Ordering::Release);
+        // SAFETY: the odd number excludes other writers, and readers discard what they copy meanwhile
+        unsafe { std::ptr::write_volatile(self.data.get(), value) };
+        // Releases the data to readers that see the even number
+        self.seq.store(seq + 2, Ordering::Release);
+    }
+}
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+    use crate::sync::SeqLock;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+    const N_THREADS: usize = 4;
+    // Miri is slow
+    const WORK: usize = if cfg!(miri) { 10 } else { 10_000 };
+
+    #[test]
+    fn read_write() {
+        let lock = SeqLock::new([0; 4]);
+        assert_eq!(lock.read(), [0; 4]);
+        lock.write([1; 4]);
+        assert_eq!(lock.try_read(), Some([1; 4]));
+        assert_eq!(lock.into_inner(), [1; 4]);
+    }
+
+    #[test]
+    fn write_excludes_readers() {
+        let lock = SeqLock::new(0);
+        lock.seq.fetch_add(1, Ordering::SeqCst);
+        assert_eq!(lock.try_read(), None);
+        lock.seq.fetch_add(1, Ordering::SeqCst);
+        assert_eq!(lock.try_read(), Some(0));
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore = "Reports the volatile copy that races with a writer")]
+    fn never_torn() {
+        let lock = Arc::new(SeqLock::new([0; 16]));
+        let done = Arc::new(AtomicBool::new(false));
+        let readers: Vec<_> = (0..N_THREADS - 1)
+            .map(|_| {
+                let (lock, done) = (lock.clone(), done.clone());
+                std::thread::spawn(move || {
+                    while !done.load(Ordering::SeqCst) {
+                        let data = lock.read();
+                        assert!(data.iter().all(|&n| n == data[0]), "Torn {data:?}");
+                    }
+                })
+            })
+            .collect();
+        let writers: Vec<_> = (0..2)
+            .map(|_| {
+                let lock = lock.clone();
+                std::thread::spawn(move || {
+                    for n in 1..=WORK {
+                        lock.write([n; 16]);
+                    }
+                })
+            })
+            .collect();
+        writers.into_iter().for_each(|th| th.join().unwrap());
+        done.store(true, Ordering::SeqCst);
+        readers.into_iter().for_each(|th| th.join().unwrap());
+        assert_eq!(lock.read(), [WORK; 16]);
+    }
+}
+
+#[cfg(all(test, loom))]
+mod loom_tests {
+    use crate::sync::SeqLock;
+    use loom::sync::Arc;
+
+    #[test]
+    fn never_torn() {
+        loom::model(|| {
+            let lock = Arc::new(SeqLock::new((0, 0)));
+            let writer = loom::thread::spawn({
+                let lock = lock.clone();
+                move || {
+                    lock.write((1, 1));
+                    lock.write((2, 2));
+                }
+            });
+            let (a, b) = lock.read();
+            assert_eq!(a, b);
+            writer.join().unwrap();
+            assert_eq!(lock.read(), (2, 2));
+        });
+    }
+
+    #[test]
+    fn writers_exclude_each_other() {
+        loom::model(|| {
+            let lock = Arc::new(SeqLock::new(0));
+            let ths: Vec<_> = (1..=2)
+                .map(|n| {
+                    let lock = lock.clone();
+                    loom::thread::spawn(move || lock.write(n))
+                })
+                .collect();
+            ths.into_iter().for_each(|th| th.join().unwrap());
+            assert_eq!(lock.seq.load(loom::sync::atomic::Ordering::SeqCst), 4);
+            assert!([1, 2].contains(&lock.read()));
+        });
+    }
+}
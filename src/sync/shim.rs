@@ -0,0 +1,23 @@
+//! Atomics, mutexes and threads of `std`, or of [loom](https://docs.rs/loom) with `--cfg loom`, so that loom model
+//! checks every interleaving and memory ordering the locks allow.
+//!
+//! Locks import these instead of `std`'s to be model checked, and their loom tests live in a `loom_tests` module.
+#[cfg(loom)]
+pub(super) use loom::{
+    sync::{atomic, Mutex},
+    thread,
+};
+#[cfg(not(loom))]
+pub(super) use std::{
+    sync::{atomic, Mutex},
+    thread,
+};
+
+/// Orders the SeqCst stores before it with the SeqCst loads after it, as SeqCst already does, except that loom models
+/// SeqCst accesses as AcqRel, which would let it report false races for every lock that stores its flag then loads the
+/// others'
+#[inline(always)]
+pub(super) fn store_load_fence() {
+    #[cfg(loom)]
+    atomic::fence(atomic::Ordering::SeqCst);
+}
@@ -0,0 +1,167 @@
+/// How a thread passes the time between checks while it waits for its turn on a lock, chosen when constructing it.
+///
+/// # Examples
+/// ```
+/// use rads::sync::lamports_bakery::{Bakery, BakeryN};
+/// use rads::sync::{Backoff, NoStarveMutex};
+///
+/// let mu = std::sync::Arc::new(Bakery::new(2).with_strategy(Backoff::default()));
+/// let th = std::thread::spawn({
+///     let mut mu = BakeryN::new(1, &mu);
+///     move || drop(mu.lock())
+/// });
+/// drop(BakeryN::new(0, &mu).lock());
+/// th.join().unwrap();
+/// ```
+pub trait SpinStrategy: Send + Sync {
+    /// Pauses after the `n_failed`-th check in a row found it is not the thread's turn yet, counting from 0
+    fn pause(&self, n_failed: u32);
+    /// Whether `pause` parks the thread, so that the lock must unpark it once it may be its turn
+    fn parks(&self) -> bool {
+        false
+    }
+}
+
+impl<S: SpinStrategy + ?Sized> SpinStrategy for Box<S> {
+    fn pause(&self, n_failed: u32) {
+        (**self).pause(n_failed)
+    }
+    fn parks(&self) -> bool {
+        (**self).parks()
+    }
+}
+
+/// Busy-waits with `std::hint::spin_loop()`, which enters soonest if the lock holder runs on another core
+#[derive(Clone, Copy, Default, Debug)]
+pub struct SpinLoop;
+impl SpinStrategy for SpinLoop {
+    fn pause(&self, _: u32) {
+        std::hint::spin_loop();
+    }
+}
+
+/// Gives up the rest of its time slice with `std::thread::yield_now()`, e.g. when there are more threads than cores
+#[derive(Clone, Copy, Default, Debug)]
+pub struct Yield;
+impl SpinStrategy for Yield {
+    fn pause(&self, _: u32) {
+        super::shim::thread::yield_now();
+    }
+}
+
+/// Busy-waits twice as long after each failed check, up to `2^max_shift` spins, then yields, so that contended
+/// threads stop hammering the shared variables
+#[derive(Clone, Copy, Debug)]
+pub struct Backoff {
+    pub max_shift: u32,
+}
+impl Default for Backoff {
+    fn default() -> Self {
+        Self { max_shift: 6 }
+    }
+}
+impl SpinStrategy for Backoff {
+    fn pause(&self, n_failed: u32) {
+        if n_failed > self.max_shift {
+            return super::shim::thread::yield_now();
+        }
+        for _ in 0..1 << n_failed {
+            std::hint::spin_loop();
+        }
+    }
+}
+
+/// Parks until the thread releasing the lock unparks it, which saves CPU during long critical sections
+#[derive(Clone, Copy, Default, Debug)]
+pub struct Park;
+impl SpinStrategy for Park {
+    fn pause(&self, _: u32) {
+        super::shim::thread::park();
+    }
+    fn parks(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::sync::filter::{FilterLock, FilterLockN};
+    use crate::sync::lamports_bakery::{Bakery, BakeryN};
+    use crate::sync::peterson::Peterson;
+    use crate::sync::{Backoff, NoStarveMutex, Park, SpinLoop, SpinStrategy, Yield};
+    use std::sync::atomic::{AtomicI32, Ordering};
+    use std::sync::Arc;
+    const N_THREADS: usize = 4;
+    const WORK: i32 = 100;
+    /// Runs the critical section under a thread's lock
+    type Lock = Box<dyn FnMut(&mut dyn FnMut()) + Send>;
+
+    /// Strategies to test every lock with, built anew for each lock
+    fn strategies() -> Vec<fn() -> Box<dyn SpinStrategy>> {
+        vec![
+            || Box::new(SpinLoop),
+            || Box::new(Yield),
+            || Box::new(Backoff::default()),
+            || Box::new(Park),
+        ]
+    }
+
+    /// Increments a counter non-atomically under the lock from every thread
+    fn count(locks: Vec<Lock>) -> i32 {
+        let data = Arc::new(AtomicI32::new(0));
+        let ths: Vec<_> = locks
+            .into_iter()
+            .map(|mut lock| {
+                let data = data.clone();
+                std::thread::spawn(move || {
+                    for _ in 0..WORK {
+                        lock(&mut || {
+                            let i = data.load(Ordering::Relaxed);
+                            std::thread::yield_now();
+                            data.store(i + 1, Ordering::Relaxed);
+                        });
+                    }
+                })
+            })
+            .collect();
+        ths.into_iter().for_each(|th| th.join().unwrap());
+        data.load(Ordering::Relaxed)
+    }
+
+    #[test]
+    fn mutual_exclusion() {
+        for strategy in strategies() {
+            let (mut a, mut b) = Peterson::binary_mutex_with(strategy());
+            let peterson: Vec<Lock> = vec![
+                Box::new(move |f| {
+                    let _guard = a.lock();
+                    f()
+                }),
+                Box::new(move |f| {
+                    let _guard = b.lock();
+                    f()
+                }),
+            ];
+            assert_eq!(count(peterson), 2 * WORK);
+
+            let bakery = Arc::new(Bakery::new(N_THREADS).with_strategy(strategy()));
+            let filter = Arc::new(FilterLock::new(N_THREADS).with_strategy(strategy()));
+            let locks = (0..N_THREADS).map(|n| -> Lock {
+                let mut mu = BakeryN::new(n, &bakery);
+                Box::new(move |f| {
+                    let _guard = mu.lock();
+                    f()
+                })
+            });
+            assert_eq!(count(locks.collect()), N_THREADS as i32 * WORK);
+            let locks = (0..N_THREADS).map(|n| -> Lock {
+                let mut mu = FilterLockN::new(n, &filter);
+                Box::new(move |f| {
+                    let _guard = mu.lock();
+                    f()
+                })
+            });
+            assert_eq!(count(locks.collect()), N_THREADS as i32 * WORK);
+        }
+    }
+}
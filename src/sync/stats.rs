@@ -0,0 +1,160 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// How long threads waited for a lock and how often each held it, to verify its fairness empirically.
+///
+/// # Examples
+/// ```
+/// use rads::sync::lamports_bakery::{Bakery, BakeryN};
+/// use rads::sync::NoStarveMutex;
+///
+/// let mu = std::sync::Arc::new(Bakery::new(2).with_stats());
+/// let ths = (0..2)
+///     .map(|n| {
+///         let mut mu = BakeryN::new(n, &mu);
+///         std::thread::spawn(move || (0..100).for_each(|_| drop(mu.lock())))
+///     })
+///     .collect::<Vec<_>>();
+/// ths.into_iter().for_each(|th| th.join().unwrap());
+/// let stats = mu.stats().unwrap();
+/// assert!(stats.holders.iter().all(|h| h.acquisitions == 100));
+/// assert!(stats.handoffs <= 200);
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct LockStats {
+    /// Stats of each thread's handle, by index
+    pub holders: Vec<HolderStats>,
+    /// Number of times the lock passed straight to a thread that waited for it when it was released
+    pub handoffs: u64,
+    pub total_handoff: Duration,
+    /// Longest time from a release to the next waiting thread holding the lock
+    pub max_handoff: Duration,
+}
+impl LockStats {
+    pub fn average_handoff(&self) -> Duration {
+        average(self.total_handoff, self.handoffs)
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct HolderStats {
+    pub acquisitions: u64,
+    /// Time from wanting the lock to holding it, summed over every acquisition
+    pub total_wait: Duration,
+    pub max_wait: Duration,
+}
+impl HolderStats {
+    pub fn average_wait(&self) -> Duration {
+        average(self.total_wait, self.acquisitions)
+    }
+}
+
+fn average(total: Duration, n: u64) -> Duration {
+    match n {
+        0 => Duration::ZERO,
+        // In nanoseconds, since `Duration` only divides by `u32`, which `n` may exceed
+        n => Duration::from_nanos((total.as_nanos() / n as u128) as u64),
+    }
+}
+
+/// Records `LockStats` for the locks constructed with stats, which serializes their bookkeeping behind a `Mutex`
+#[derive(Default)]
+pub(super) struct Recorder(Mutex<Recording>);
+#[derive(Default)]
+struct Recording {
+    stats: LockStats,
+    /// When each holder started wanting the lock, while it waits
+    wanted: Vec<Option<Instant>>,
+    /// When the lock was last released, until the next thread holds it
+    released: Option<Instant>,
+}
+impl Recorder {
+    pub(super) fn new(size: usize) -> Self {
+        Self(Mutex::new(Recording {
+            stats: LockStats {
+                holders: vec![HolderStats::default(); size],
+                ..LockStats::default()
+            },
+            wanted: vec![None; size],
+            released: None,
+        }))
+    }
+    pub(super) fn want(&self, n: usize) {
+        let mut rec = self.0.lock().unwrap();
+        if rec.wanted.len() <= n {
+            rec.wanted.resize(n + 1, None);
+            rec.stats.holders.resize(n + 1, HolderStats::default());
+        }
+        rec.wanted[n] = Some(Instant::now());
+    }
+    pub(super) fn acquire(&self, n: usize) {
+        let now = Instant::now();
+        let rec = &mut *self.0.lock().unwrap();
+        let Some(wanted) = rec.wanted[n].take() else {
+            return;
+        };
+        let holder = &mut rec.stats.holders[n];
+        holder.acquisitions += 1;
+        holder.total_wait += now - wanted;
+        holder.max_wait = holder.max_wait.max(now - wanted);
+        if let Some(released) = rec.released.take().filter(|&r| wanted <= r) {
+            let stats = &mut rec.stats;
+            stats.handoffs += 1;
+            stats.total_handoff += now - released;
+            stats.max_handoff = stats.max_handoff.max(now - released);
+        }
+    }
+    pub(super) fn release(&self) {
+        self.0.lock().unwrap().released = Some(Instant::now());
+    }
+    pub(super) fn stats(&self) -> LockStats {
+        self.0.lock().unwrap().stats.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::sync::stats::{HolderStats, Recorder};
+    use std::time::Duration;
+
+    #[test]
+    fn records_waits_and_handoffs() {
+        let rec = Recorder::new(2);
+        rec.want(0);
+        rec.acquire(0);
+        rec.want(1);
+        std::thread::sleep(Duration::from_millis(10));
+        rec.release();
+        rec.acquire(1);
+        rec.release();
+        // Did not wait when it was released
+        rec.want(0);
+        rec.acquire(0);
+        let stats = rec.stats();
+        assert_eq!(stats.holders[0].acquisitions, 2);
+        assert!(stats.holders[1].max_wait >= Duration::from_millis(10));
+        assert_eq!(stats.handoffs, 1);
+        assert!(stats.max_handoff < stats.holders[1].max_wait);
+        assert_eq!(HolderStats::default().average_wait(), Duration::ZERO);
+    }
+
+    #[test]
+    fn average_past_u32() {
+        let stats = HolderStats {
+            acquisitions: 1 << 32,
+            total_wait: Duration::from_secs(3 << 32),
+            max_wait: Duration::from_secs(5),
+        };
+        assert_eq!(stats.average_wait(), Duration::from_secs(3));
+    }
+
+    #[test]
+    fn grows_with_holders() {
+        let rec = Recorder::new(0);
+        rec.want(2);
+        rec.acquire(2);
+        let stats = rec.stats();
+        assert_eq!(stats.holders.len(), 3);
+        assert_eq!(stats.holders[2].acquisitions, 1);
+    }
+}
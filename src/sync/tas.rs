@@ -0,0 +1,74 @@
+use super::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Test-and-set spinlock, which is simple and fast when uncontended, but unfair.
+///
+/// Every waiter races to swap the flag as soon as it is released, so a thread that releases and immediately locks
+/// again usually wins before the waiters even notice, starving them. It is a baseline to compare the `NoStarveMutex`
+/// locks against, rather than one to use.
+///
+/// # Examples
+/// ```
+/// use crate::rads::sync::tas::TasLock;
+/// use std::sync::atomic::Ordering;
+/// use rads::sync::Mutex;
+///
+/// let data = std::sync::Arc::new(std::sync::atomic::AtomicI32::new(0));
+/// let mu = TasLock::new();
+/// let ths = (0..4)
+///     .map(|n| {
+///         let data = data.clone();
+///         let mut mu = mu.clone();
+///         std::thread::spawn(move || {
+///             for _ in 0..10_000 {
+///                 let _guard = mu.lock();
+///                 let i = data.load(Ordering::Relaxed);
+///                 data.store(i + n, Ordering::Relaxed);
+///             }
+///         })
+///     })
+///     .collect::<Vec<_>>();
+/// ths.into_iter().for_each(|th| th.join().unwrap());
+/// assert_eq!(data.load(Ordering::Relaxed), 10_000 * (0 + 1 + 2 + 3));
+/// ```
+#[derive(Clone, Default)]
+pub struct TasLock(std::sync::Arc<AtomicBool>);
+pub struct TasGuard<'a>(&'a TasLock);
+
+impl TasLock {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl<'a> Mutex<'a, TasGuard<'a>> for TasLock {
+    fn lock(&'a mut self) -> TasGuard<'a> {
+        while self.0.swap(true, Ordering::Acquire) {
+            std::thread::yield_now();
+        }
+        TasGuard(self)
+    }
+}
+
+impl Drop for TasGuard<'_> {
+    fn drop(&mut self) {
+        self.0 .0.store(false, Ordering::Release);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::sync::{tas::TasLock, Mutex};
+
+    #[test]
+    fn mutual_exclusion() {
+        let mu = TasLock::new();
+        crate::sync::tests::mutual_exclusion(|_| {
+            let mut mu = mu.clone();
+            move |f| {
+                let _guard = mu.lock();
+                f()
+            }
+        });
+    }
+}
@@ -0,0 +1,297 @@
+use super::{BinaryMutex, NoStarveMutex, Slots, WantGuard};
+
+/// N-ary mutex composed of binary mutexes, e.g. Peterson's, arranged as a tournament tree (Peterson-Fischer).
+///
+/// Each thread starts at a leaf of the tree and climbs to the root, locking each node on the side it came from, so
+/// that each node decides between the winners of its 2 subtrees. Whoever holds the root holds the lock, and releases
+/// the nodes from the root down. Any `BinaryMutex` thus gets an N-ary form, which inherits its starvation freedom,
+/// though not first-come-first-served order across subtrees, with `O(log n)` nodes to lock instead of `O(n)` flags to
+/// check.
+///
+/// # Examples
+/// ```
+/// use rads::sync::peterson::Peterson;
+/// use rads::sync::tournament::{NAry, NAryN};
+/// use rads::sync::NoStarveMutex;
+/// use std::sync::atomic::Ordering;
+///
+/// let data = std::sync::Arc::new(std::sync::atomic::AtomicI32::new(0));
+/// let mu = std::sync::Arc::new(NAry::<Peterson>::new(4));
+/// let ths = (0..4)
+///     .map(|n| {
+///         let data = data.clone();
+///         let mut mu = NAryN::new(n, &mu);
+///         std::thread::spawn(move || {
+///             for _ in 0..1_000 {
+///                 let _guard = mu.lock();
+///                 let i = data.load(Ordering::Relaxed);
+///                 data.store(i + 1, Ordering::Relaxed);
+///             }
+///         })
+///     })
+///     .collect::<Vec<_>>();
+/// ths.into_iter().for_each(|th| th.join().unwrap());
+/// assert_eq!(data.load(Ordering::Relaxed), 4_000);
+/// ```
+pub struct NAry<M> {
+    /// Heap-ordered, so that node `i` counting from 1 has children `2i` and `2i + 1`, and thread `n` starts below node
+    /// `(leaves + n) / 2`
+    nodes: Vec<M>,
+    size: usize,
+    /// Whether each leaf is claimed by an `NAryHandle`
+    taken: Slots,
+}
+impl<M: BinaryMutex> NAry<M> {
+    pub fn new(size: usize) -> Self {
+        assert!(size > 1, "Do you really need a mutex of size {size}?");
+        let leaves = size.next_power_of_two();
+        Self {
+            nodes: (1..leaves).map(|_| M::new()).collect(),
+            size,
+            taken: Slots::new(size),
+        }
+    }
+    /// Sized for as many threads as can run in parallel on this host, to `register` when the thread count isn't known
+    /// statically
+    pub fn for_current_host() -> Self {
+        Self::new(super::host_parallelism())
+    }
+    /// Claims a free leaf, which is released once the handle drops, or None if every leaf is taken.
+    ///
+    /// Leaves of `NAryN::new` aren't claimed, so use either one or the other for the same tournament.
+    pub fn register(self: &std::sync::Arc<Self>) -> Option<NAryHandle<M>> {
+        let n = self.taken.claim()?;
+        Some(NAryHandle(NAryN::new(n, self)))
+    }
+    /// Nodes that thread `n` locks, as indices into `nodes`, and on which side, from its leaf up to the root
+    fn path(&self, n: usize) -> impl DoubleEndedIterator<Item = (usize, usize)> {
+        let (leaves, depth) = (self.nodes.len() + 1, self.nodes.len().count_ones());
+        (0..depth as usize).map(move |level| {
+            let below = (leaves + n) >> level;
+            ((below >> 1) - 1, below & 1)
+        })
+    }
+}
+
+pub struct NAryN<M> {
+    n: usize,
+    tournament: std::sync::Arc<NAry<M>>,
+}
+pub struct NAryWant<'a, M: BinaryMutex>(Option<&'a NAryN<M>>);
+pub struct NAryGuard<'a, M: BinaryMutex>(&'a NAryN<M>);
+impl<M: BinaryMutex> NAryN<M> {
+    // Does not check if index is taken.
+    pub fn new(n: usize, tournament: &std::sync::Arc<NAry<M>>) -> Self {
+        let size = tournament.size;
+        assert!(
+            n < size,
+            "0-based user index {n} >= tournament of size={size}"
+        );
+        Self {
+            n,
+            tournament: tournament.clone(),
+        }
+    }
+    fn leaf(&self) -> (&M, usize) {
+        let (node, side) = self.tournament.path(self.n).next().unwrap();
+        (&self.tournament.nodes[node], side)
+    }
+    /// Releases the given nodes from the root down
+    fn release(&self, nodes: impl DoubleEndedIterator<Item = (usize, usize)>) {
+        for (node, side) in nodes.rev() {
+            self.tournament.nodes[node].release(side);
+        }
+    }
+}
+
+/// Leaf claimed by `NAry::register`, which no other handle shares.
+pub struct NAryHandle<M: BinaryMutex>(NAryN<M>);
+impl<M: BinaryMutex> NAryHandle<M> {
+    /// 0-based index of the claimed leaf
+    pub fn index(&self) -> usize {
+        self.0.n
+    }
+}
+impl<'a, M: BinaryMutex + 'a> NoStarveMutex<'a, NAryGuard<'a, M>, NAryWant<'a, M>>
+    for NAryHandle<M>
+{
+    fn want_lock(&'a mut self) -> NAryWant<'a, M> {
+        self.0.want_lock()
+    }
+}
+impl<M: BinaryMutex> Drop for NAryHandle<M> {
+    fn drop(&mut self) {
+        self.0.tournament.taken.release(self.0.n);
+    }
+}
+
+impl<'a, M: BinaryMutex + 'a> NoStarveMutex<'a, NAryGuard<'a, M>, NAryWant<'a, M>> for NAryN<M> {
+    // Wanting the leaf is the doorway, since the tournament above it decides between subtrees only
+    fn want_lock(&'a mut self) -> NAryWant<'a, M> {
+        let (leaf, side) = self.leaf();
+        leaf.want(side);
+        NAryWant(Some(self))
+    }
+}
+
+impl<'a, M: BinaryMutex + 'a> WantGuard<'a, NAryGuard<'a, M>> for NAryWant<'a, M> {
+    fn wait(mut self) -> NAryGuard<'a, M> {
+        let mu = self.0.take().unwrap();
+        for (level, (node, side)) in mu.tournament.path(mu.n).enumerate() {
+            let node = &mu.tournament.nodes[node];
+            if level > 0 {
+                node.want(side);
+            }
+            node.wait(side);
+        }
+        NAryGuard(mu)
+    }
+    // Climbs back down and wants the leaf again if it must wait at any node above it, like the Filter lock
+    fn try_wait(mut self) -> Result<NAryGuard<'a, M>, Self> {
+        let mu = self.0.unwrap();
+        let path: Vec<_> = mu.tournament.path(mu.n).collect();
+        for (level, &(node, side)) in path.iter().enumerate() {
+            let node = &mu.tournament.nodes[node];
+            if level > 0 {
+                node.want(side);
+            }
+            if !node.is_ready(side) {
+                if level > 0 {
+                    mu.release(path[..=level].iter().copied());
+                    let (leaf, side) = mu.leaf();
+                    leaf.want(side);
+                }
+                return Err(self);
+            }
+            node.wait(side);
+        }
+        self.0 = None;
+        Ok(NAryGuard(mu))
+    }
+    fn is_ready(&self) -> bool {
+        let mu = self.0.unwrap();
+        let mut path = mu.tournament.path(mu.n);
+        let (leaf, side) = path.next().unwrap();
+        mu.tournament.nodes[leaf].is_ready(side)
+            && path.all(|(node, side)| !mu.tournament.nodes[node].is_contended(side))
+    }
+}
+impl<M: BinaryMutex> Drop for NAryWant<'_, M> {
+    fn drop(&mut self) {
+        if let Some(mu) = self.0 {
+            super::abandon();
+            let (leaf, side) = mu.leaf();
+            leaf.release(side);
+        }
+    }
+}
+
+impl<M: BinaryMutex> Drop for NAryGuard<'_, M> {
+    fn drop(&mut self) {
+        self.0.release(self.0.tournament.path(self.0.n));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::sync::peterson::Peterson;
+    use crate::sync::tournament::{NAry, NAryN};
+    use crate::sync::{NoStarveMutex, WantGuard};
+    use std::sync::atomic::{AtomicI32, Ordering};
+    use std::sync::Arc;
+    const WORK: usize = 1_000;
+
+    #[test]
+    fn path() {
+        // Leaves below nodes 4 to 7, i.e. indices 3 to 6
+        let mu = NAry::<Peterson>::new(5);
+        assert_eq!(mu.nodes.len(), 7);
+        assert_eq!(mu.path(0).collect::<Vec<_>>(), [(3, 0), (1, 0), (0, 0)]);
+        assert_eq!(mu.path(3).collect::<Vec<_>>(), [(4, 1), (1, 1), (0, 0)]);
+        assert_eq!(mu.path(4).collect::<Vec<_>>(), [(5, 0), (2, 0), (0, 1)]);
+        assert_eq!(
+            NAry::<Peterson>::new(2).path(1).collect::<Vec<_>>(),
+            [(0, 1)]
+        );
+    }
+
+    #[test]
+    fn mutual_exclusion() {
+        for size in [2, 3, 4, 5] {
+            let data = Arc::new(AtomicI32::new(0));
+            let mu = Arc::new(NAry::<Peterson>::new(size));
+            let ths: Vec<_> = (0..size)
+                .map(|n| {
+                    let data = data.clone();
+                    let mut mu = NAryN::new(n, &mu);
+                    std::thread::spawn(move || {
+                        for _ in 0..WORK {
+                            // Not atomic, so interleaving would lose increments
+                            let _guard = mu.lock();
+                            let i = data.load(Ordering::Relaxed);
+                            std::thread::yield_now();
+                            data.store(i + 1, Ordering::Relaxed);
+                        }
+                    })
+                })
+                .collect();
+            ths.into_iter().for_each(|th| th.join().unwrap());
+            assert_eq!(data.load(Ordering::Relaxed), (WORK * size) as i32);
+        }
+    }
+
+    #[test]
+    fn try_lock() {
+        let mu = Arc::new(NAry::<Peterson>::new(4));
+        let (mut a, mut b, mut c) = (NAryN::new(0, &mu), NAryN::new(1, &mu), NAryN::new(3, &mu));
+        let guard = a.try_lock().unwrap();
+        // Loses at the leaf, and at the root
+        assert!(b.try_lock().is_none());
+        assert!(c.try_lock().is_none());
+
+        let want = c.want_lock();
+        assert!(!want.is_ready());
+        // Gave back the root, so keeps only its leaf
+        let want = want.try_wait().err().unwrap();
+        drop(guard);
+        assert!(want.is_ready());
+        let guard = want.try_wait().ok().unwrap();
+        assert!(a.try_lock().is_none());
+        drop(guard);
+        assert!(a.try_lock().is_some());
+    }
+
+    #[test]
+    fn register() {
+        let data = Arc::new(AtomicI32::new(0));
+        let mu = Arc::new(NAry::<Peterson>::for_current_host());
+        let size = mu.size;
+        assert!(size >= 2);
+        let ths: Vec<_> = (0..size * 2)
+            .map(|_| {
+                let (data, mu) = (data.clone(), mu.clone());
+                std::thread::spawn(move || {
+                    // Half the threads wait for a leaf that another releases
+                    let mut mu = loop {
+                        match mu.register() {
+                            Some(handle) => break handle,
+                            None => std::thread::yield_now(),
+                        }
+                    };
+                    for _ in 0..WORK / 10 {
+                        let _guard = mu.lock();
+                        let i = data.load(Ordering::Relaxed);
+                        std::thread::yield_now();
+                        data.store(i + 1, Ordering::Relaxed);
+                    }
+                    mu.index()
+                })
+            })
+            .collect();
+        for th in ths {
+            assert!(th.join().unwrap() < size);
+        }
+        assert_eq!(data.load(Ordering::Relaxed), (WORK / 10 * size * 2) as i32);
+        assert!(mu.taken.all_free());
+    }
+}
@@ -0,0 +1,348 @@
+use std::sync::atomic::{AtomicPtr, AtomicUsize, Ordering};
+
+/// Object on which threads agree on one of the values they propose, each in a bounded number of its own steps
+/// (wait-free consensus).
+pub trait Consensus<T> {
+    // Proposes `value`, and returns the value that every thread's `decide()` returns, which one of them proposed
+    fn decide(&self, value: T) -> &T;
+}
+
+/// Consensus for any number of threads by compare-and-swap, which decides the first value proposed.
+///
+/// # Examples
+/// ```
+/// use rads::sync::universal::{CasConsensus, Consensus};
+///
+/// let consensus = std::sync::Arc::new(CasConsensus::new());
+/// let ths = (0..4)
+///     .map(|n| {
+///         let consensus = consensus.clone();
+///         std::thread::spawn(move || *consensus.decide(n))
+///     })
+///     .collect::<Vec<_>>();
+/// let decided: Vec<_> = ths.into_iter().map(|th| th.join().unwrap()).collect();
+/// assert!(decided.iter().all(|&d| d == decided[0]));
+/// ```
+pub struct CasConsensus<T> {
+    decided: AtomicPtr<T>,
+}
+// Every thread reads the decided value, which whichever thread proposed it gave up
+unsafe impl<T: Send + Sync> Send for CasConsensus<T> {}
+unsafe impl<T: Send + Sync> Sync for CasConsensus<T> {}
+
+impl<T> CasConsensus<T> {
+    pub fn new() -> Self {
+        Self {
+            decided: AtomicPtr::new(std::ptr::null_mut()),
+        }
+    }
+    /// Value decided so far, if any thread proposed one
+    pub fn decided(&self) -> Option<&T> {
+        // SAFETY: once decided, the value lives as long as the consensus object
+        unsafe { self.decided.load(Ordering::SeqCst).as_ref() }
+    }
+}
+impl<T> Default for CasConsensus<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl<T> Consensus<T> for CasConsensus<T> {
+    fn decide(&self, value: T) -> &T {
+        let proposed = Box::into_raw(Box::new(value));
+        let decided = match self.decided.compare_exchange(
+            std::ptr::null_mut(),
+            proposed,
+            Ordering::SeqCst,
+            Ordering::SeqCst,
+        ) {
+            Ok(_) => proposed,
+            Err(decided) => {
+                // SAFETY: lost, so no other thread saw it
+                drop(unsafe { Box::from_raw(proposed) });
+                decided
+            }
+        };
+        // SAFETY: once decided, the value lives as long as the consensus object
+        unsafe { &*decided }
+    }
+}
+impl<T> Drop for CasConsensus<T> {
+    fn drop(&mut self) {
+        let decided = *self.decided.get_mut();
+        if !decided.is_null() {
+            // SAFETY: no thread borrows it anymore
+            drop(unsafe { Box::from_raw(decided) });
+        }
+    }
+}
+
+/// Object whose operations run one at a time, e.g. a queue or a counter, which `Universal` shares between threads.
+///
+/// `apply` must be deterministic, since each thread replays the same invocations on its own replica.
+pub trait Sequential: Clone {
+    type Invocation;
+    type Response;
+    fn apply(&mut self, invocation: &Self::Invocation) -> Self::Response;
+}
+
+/// Wait-free concurrent form of any `Sequential` object, which threads agree on the order of invocations for through
+/// consensus (Herlihy's universal construction).
+///
+/// Invocations form a log, whose every node decides its successor by `CasConsensus`. Each thread announces its
+/// invocation, then threads nodes onto the log until its own is on it, helping the thread whose turn it is by the
+/// length of the log before its own, so that every invocation is threaded after at most `n` others. Each thread then
+/// replays the log on its own replica of the object up to its invocation, whose response that gives.
+///
+/// The log grows with every invocation until the object drops, trading memory for the guarantee that no thread waits
+/// on another, unlike with a lock.
+///
+/// # Examples
+/// ```
+/// use rads::sync::universal::{Sequential, Universal, UniversalN};
+///
+/// #[derive(Clone, Default)]
+/// struct Counter(u32);
+/// impl Sequential for Counter {
+///     type Invocation = u32;
+///     type Response = u32;
+///     fn apply(&mut self, add: &u32) -> u32 {
+///         self.0 += add;
+///         self.0
+///     }
+/// }
+///
+/// let counter = std::sync::Arc::new(Universal::new(4, Counter::default()));
+/// let ths = (0..4)
+///     .map(|n| {
+///         let mut counter = UniversalN::new(n, &counter);
+///         std::thread::spawn(move || (0..100).map(|_| counter.apply(1)).max().unwrap())
+///     })
+///     .collect::<Vec<_>>();
+/// let max = ths.into_iter().map(|th| th.join().unwrap()).max();
+/// assert_eq!(max, Some(400));
+/// ```
+pub struct Universal<S: Sequential> {
+    init: S,
+    /// Node of each thread's latest invocation
+    announce: Vec<AtomicPtr<Node<S::Invocation>>>,
+    /// Latest node each thread saw on the log
+    head: Vec<AtomicPtr<Node<S::Invocation>>>,
+    /// Sentinel that starts the log
+    tail: *mut Node<S::Invocation>,
+}
+// Every thread reads the invocations on the log, and replays them on its own replica
+unsafe impl<S: Sequential + Send + Sync> Send for Universal<S> where S::Invocation: Send + Sync {}
+unsafe impl<S: Sequential + Send + Sync> Sync for Universal<S> where S::Invocation: Send + Sync {}
+
+struct Node<I> {
+    /// None for the tail
+    invocation: Option<I>,
+    next: CasConsensus<NodePtr<I>>,
+    /// 1-based position on the log, or 0 until threaded onto it
+    seq: AtomicUsize,
+}
+struct NodePtr<I>(*mut Node<I>);
+impl<I> Node<I> {
+    fn new(invocation: Option<I>, seq: usize) -> *mut Self {
+        Box::into_raw(Box::new(Self {
+            invocation,
+            next: CasConsensus::new(),
+            seq: AtomicUsize::new(seq),
+        }))
+    }
+    fn seq(&self) -> usize {
+        self.seq.load(Ordering::SeqCst)
+    }
+}
+
+impl<S: Sequential> Universal<S> {
+    pub fn new(size: usize, init: S) -> Self {
+        assert!(size > 0, "Expect at least 1 thread");
+        let tail = Node::new(None, 1);
+        Self {
+            init,
+            announce: (0..size).map(|_| AtomicPtr::new(tail)).collect(),
+            head: (0..size).map(|_| AtomicPtr::new(tail)).collect(),
+            tail,
+        }
+    }
+    /// Node furthest along the log that any thread saw
+    fn max_head(&self) -> *mut Node<S::Invocation> {
+        (self.head.iter())
+            .map(|head| head.load(Ordering::SeqCst))
+            // SAFETY: nodes live as long as the object
+            .max_by_key(|&node| unsafe { &*node }.seq())
+            .unwrap()
+    }
+}
+impl<S: Sequential> Drop for Universal<S> {
+    fn drop(&mut self) {
+        // SAFETY: no thread borrows them anymore, and each node is either on the log or only announced, which must be
+        // checked before freeing the log
+        for announced in &mut self.announce {
+            let announced = *announced.get_mut();
+            if unsafe { &*announced }.seq() == 0 {
+                drop(unsafe { Box::from_raw(announced) });
+            }
+        }
+        let mut node = self.tail;
+        while !node.is_null() {
+            let next = unsafe { &*node }
+                .next
+                .decided()
+                .map_or(std::ptr::null_mut(), |next| next.0);
+            drop(unsafe { Box::from_raw(node) });
+            node = next;
+        }
+    }
+}
+
+pub struct UniversalN<S: Sequential> {
+    n: usize,
+    universal: std::sync::Arc<Universal<S>>,
+    /// Object after replaying the log up to `applied`
+    replica: S,
+    applied: *const Node<S::Invocation>,
+}
+// Only borrows nodes of the log, which the shared object owns
+unsafe impl<S: Sequential + Send + Sync> Send for UniversalN<S> where S::Invocation: Send + Sync {}
+
+impl<S: Sequential> UniversalN<S> {
+    // Does not check if index is taken.
+    pub fn new(n: usize, universal: &std::sync::Arc<Universal<S>>) -> Self {
+        let size = universal.head.len();
+        assert!(
+            n < size,
+            "0-based user index {n} >= universal object of size={size}"
+        );
+        Self {
+            n,
+            universal: universal.clone(),
+            replica: universal.init.clone(),
+            applied: universal.tail,
+        }
+    }
+    /// Applies `invocation` to the shared object, after every invocation threaded onto the log before it
+    pub fn apply(&mut self, invocation: S::Invocation) -> S::Response {
+        let (u, n) = (&*self.universal, self.n);
+        let size = u.head.len();
+        let node = Node::new(Some(invocation), 0);
+        u.announce[n].store(node, Ordering::SeqCst);
+        u.head[n].store(u.max_head(), Ordering::SeqCst);
+        // SAFETY: nodes live as long as the object
+        while unsafe { &*node }.seq() == 0 {
+            let before = unsafe { &*u.head[n].load(Ordering::SeqCst) };
+            // Helps whoever's turn it is next, so that no thread keeps losing
+            let help = u.announce[(before.seq() + 1) % size].load(Ordering::SeqCst);
+            let prefer = match unsafe { &*help }.seq() {
+                0 => help,
+                _ => node,
+            };
+            let after = before.next.decide(NodePtr(prefer)).0;
+            unsafe { &*after }
+                .seq
+                .store(before.seq() + 1, Ordering::SeqCst);
+            u.head[n].store(after, Ordering::SeqCst);
+        }
+        u.head[n].store(node, Ordering::SeqCst);
+        loop {
+            let applied = unsafe { &*self.applied };
+            // Threaded, since it comes before this thread's node
+            let next = applied.next.decided().unwrap().0;
+            self.applied = next;
+            let next = unsafe { &*next };
+            let response = self.replica.apply(next.invocation.as_ref().unwrap());
+            if std::ptr::eq(next, node) {
+                return response;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::sync::universal::{CasConsensus, Consensus, Sequential, Universal, UniversalN};
+    use std::sync::Arc;
+    const N_THREADS: usize = 4;
+    const WORK: usize = 500;
+
+    #[derive(Clone, Default)]
+    struct FetchAdd(usize);
+    impl Sequential for FetchAdd {
+        type Invocation = ();
+        type Response = usize;
+        fn apply(&mut self, _: &()) -> usize {
+            self.0 += 1;
+            self.0 - 1
+        }
+    }
+
+    #[derive(Clone, Default)]
+    struct Stack(Vec<usize>);
+    enum StackOp {
+        Push(usize),
+        Pop,
+    }
+    impl Sequential for Stack {
+        type Invocation = StackOp;
+        type Response = Option<usize>;
+        fn apply(&mut self, op: &StackOp) -> Option<usize> {
+            match op {
+                StackOp::Push(i) => {
+                    self.0.push(*i);
+                    None
+                }
+                StackOp::Pop => self.0.pop(),
+            }
+        }
+    }
+
+    #[test]
+    fn consensus() {
+        let consensus = CasConsensus::new();
+        assert!(consensus.decided().is_none());
+        assert_eq!(*consensus.decide("a"), "a");
+        assert_eq!(*consensus.decide("b"), "a");
+        assert_eq!(consensus.decided(), Some(&"a"));
+    }
+
+    #[test]
+    fn linearizable() {
+        let counter = Arc::new(Universal::new(N_THREADS, FetchAdd::default()));
+        let ths: Vec<_> = (0..N_THREADS)
+            .map(|n| {
+                let mut counter = UniversalN::new(n, &counter);
+                std::thread::spawn(move || (0..WORK).map(|_| counter.apply(())).collect::<Vec<_>>())
+            })
+            .collect();
+        let mut responses: Vec<_> = ths.into_iter().flat_map(|th| th.join().unwrap()).collect();
+        // Each invocation saw a distinct count, as if they ran one at a time
+        responses.sort_unstable();
+        assert_eq!(responses, (0..WORK * N_THREADS).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn stack() {
+        let stack = Arc::new(Universal::new(N_THREADS, Stack::default()));
+        let ths: Vec<_> = (0..N_THREADS)
+            .map(|n| {
+                let mut stack = UniversalN::new(n, &stack);
+                std::thread::spawn(move || {
+                    (0..WORK)
+                        .map(|i| {
+                            stack.apply(StackOp::Push(n * WORK + i));
+                            stack.apply(StackOp::Pop).unwrap()
+                        })
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+        let mut popped: Vec<_> = ths.into_iter().flat_map(|th| th.join().unwrap()).collect();
+        // Every push popped exactly once
+        popped.sort_unstable();
+        assert_eq!(popped, (0..WORK * N_THREADS).collect::<Vec<_>>());
+        let mut stack = UniversalN::new(0, &stack);
+        assert_eq!(stack.apply(StackOp::Pop), None);
+    }
+}